@@ -0,0 +1,38 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use alloc::vec::Vec;
+use parity_scale_codec::{Decode, Encode};
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
+
+pub const INTERFACE: InterfaceHash =
+    redshirt_syscalls::interface_hash!("console", ConsoleMessage, ReadResponse);
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub enum ConsoleMessage {
+    /// Ask to read text typed on the console. Replied with a [`ReadResponse`] once at least one
+    /// byte is available.
+    Read {
+        /// Maximum number of bytes to return.
+        max_len: u32,
+    },
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub struct ReadResponse {
+    /// UTF-8 text typed on the console. Never longer than the requested maximum length. Empty
+    /// if the console has been closed, in which case no more data will ever be available.
+    pub data: Vec<u8>,
+}
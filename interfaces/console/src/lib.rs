@@ -0,0 +1,45 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Console.
+//!
+//! This interface allows reading the text typed by the user on a console, such as a keyboard
+//! or a serial port. The handler is responsible for echoing and line editing, and only makes
+//! the text of a line available once it has been validated.
+//!
+//! Programs that use WASI don't need to use this interface directly. Reading from the standard
+//! input is automatically performed through it.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+pub mod ffi;
+
+/// Reads at most `max_len` bytes of text typed on the console.
+///
+/// Waits until at least one byte is available. Returns an empty `Vec` if the console has been
+/// closed.
+pub async fn read(max_len: u32) -> Vec<u8> {
+    let msg = ffi::ConsoleMessage::Read { max_len };
+    let response: ffi::ReadResponse = unsafe {
+        redshirt_syscalls::emit_message_with_response(&ffi::INTERFACE, msg)
+            .unwrap()
+            .await
+    };
+    response.data
+}
@@ -14,15 +14,12 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use parity_scale_codec::{Decode, Encode};
-use redshirt_syscalls::InterfaceHash;
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0x99, 0x94, 0xca, 0x60, 0xcf, 0x73, 0x7b, 0x59, 0xf7, 0xdc, 0x0c, 0xc4, 0xf0, 0x57, 0x42, 0x2e,
-    0x79, 0xa7, 0xb6, 0x81, 0xbb, 0xf8, 0x4e, 0x24, 0x8e, 0xbf, 0x1a, 0x8f, 0x2c, 0xf6, 0xea, 0xc8,
-]);
+pub const INTERFACE: InterfaceHash =
+    redshirt_syscalls::interface_hash!("disk", DiskMessage, DiskCommand);
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum DiskMessage {
     /// Notify of the existence of a new disk.
     // TODO: what if this id was already registered?
@@ -56,7 +53,7 @@ pub enum DiskMessage {
     WriteFinished(WriteId),
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum DiskCommand {
     StartRead {
         id: ReadId,
@@ -70,8 +67,12 @@ pub enum DiskCommand {
     },
 }
 
-#[derive(Debug, Encode, Decode, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(
+    Debug, Encode, Decode, InterfaceSchema, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
 pub struct ReadId(pub u64);
 
-#[derive(Debug, Encode, Decode, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(
+    Debug, Encode, Decode, InterfaceSchema, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
 pub struct WriteId(pub u64);
@@ -14,15 +14,12 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use parity_scale_codec::{Decode, Encode};
-use redshirt_syscalls::InterfaceHash;
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0x56, 0xf0, 0xad, 0x54, 0x6c, 0x6d, 0x91, 0xce, 0xc2, 0x10, 0x88, 0xf6, 0x32, 0x2b, 0x66, 0x45,
-    0xd4, 0xcf, 0xbe, 0xa3, 0xf7, 0x03, 0x13, 0xcd, 0x04, 0x65, 0xfd, 0x7f, 0x06, 0xd4, 0x24, 0xa1,
-]);
+pub const INTERFACE: InterfaceHash =
+    redshirt_syscalls::interface_hash!("ethernet", NetworkMessage, Vec<u8>, ());
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum NetworkMessage {
     /// Notify of the existence of a new Ethernet interface.
     // TODO: what if this id was already registered?
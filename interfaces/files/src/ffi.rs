@@ -0,0 +1,114 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use alloc::{string::String, vec::Vec};
+use parity_scale_codec::{Decode, Encode};
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
+
+pub const INTERFACE: InterfaceHash = redshirt_syscalls::interface_hash!(
+    "files",
+    FilesMessage,
+    OpenResponse,
+    ReadResponse,
+    WriteResponse
+);
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub enum FilesMessage {
+    /// Ask to open a file. Replied with a [`OpenResponse`].
+    Open(Open),
+    /// Ask to read data from a file. Replied with a [`ReadResponse`].
+    Read(Read),
+    /// Ask to write data to a file. Replied with a [`WriteResponse`].
+    Write(Write),
+    /// Close the given file. Doesn't expect any response. The given file ID will no longer be
+    /// valid.
+    Close(u64),
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub struct Open {
+    /// Name of the volume containing the file.
+    pub volume: String,
+    /// Path of the file within the volume, with `/` as separator.
+    pub path: String,
+    /// If true, the file is created if it doesn't exist.
+    pub create: bool,
+    /// If true and `create` is true, opening fails if the file already exists.
+    pub exclusive: bool,
+    /// If true, the content of the file is erased.
+    pub truncate: bool,
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub struct OpenResponse {
+    pub result: Result<OpenedFile, FilesError>,
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub struct OpenedFile {
+    /// Identifier of the file, to pass to the other messages.
+    pub file_id: u64,
+    /// Size of the file, in bytes.
+    pub size: u64,
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub struct Read {
+    pub file_id: u64,
+    /// Offset, in bytes, within the file of the data to read.
+    pub offset: u64,
+    /// Number of bytes to read.
+    pub len: u32,
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub struct ReadResponse {
+    /// Data read from the file. Shorter than the requested length if the end of the file has
+    /// been reached.
+    pub result: Result<Vec<u8>, FilesError>,
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub struct Write {
+    pub file_id: u64,
+    /// Offset, in bytes, within the file where to write the data. If the offset is past the end
+    /// of the file, the gap is filled with zeroes.
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub struct WriteResponse {
+    pub result: Result<(), FilesError>,
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema, Clone, PartialEq, Eq)]
+pub enum FilesError {
+    /// No volume with this name is available.
+    NoSuchVolume,
+    /// The file doesn't exist.
+    NotFound,
+    /// The file already exists.
+    AlreadyExists,
+    /// The path designates a directory.
+    IsDirectory,
+    /// The volume doesn't accept writes.
+    ReadOnly,
+    /// The file ID is invalid.
+    InvalidFile,
+    /// Error while accessing the volume.
+    Io,
+}
@@ -0,0 +1,131 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Files.
+//!
+//! This interface allows opening, reading, and writing files stored on a volume. A volume is
+//! typically a file system found on one of the disks registered through the `disk` interface,
+//! and is designated by a name chosen by the handler of this interface.
+//!
+//! Files are designated by their path within their volume. Directories can't be opened.
+//!
+//! Programs that use WASI don't need to use this interface directly. The directories listed in
+//! the `mounts` field of the kernel's `LaunchConfig` are automatically accessed through it.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+
+pub use ffi::FilesError;
+
+pub mod ffi;
+
+/// Options for opening a file. See [`open`].
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    /// If true, the file is created if it doesn't exist.
+    pub create: bool,
+    /// If true and `create` is true, opening fails if the file already exists.
+    pub exclusive: bool,
+    /// If true, the content of the file is erased.
+    pub truncate: bool,
+}
+
+/// Opens the file at the given path within the given volume.
+pub async fn open(volume: &str, path: &str, options: OpenOptions) -> Result<File, FilesError> {
+    let msg = ffi::FilesMessage::Open(ffi::Open {
+        volume: String::from(volume),
+        path: String::from(path),
+        create: options.create,
+        exclusive: options.exclusive,
+        truncate: options.truncate,
+    });
+
+    let response: ffi::OpenResponse = unsafe {
+        redshirt_syscalls::emit_message_with_response(&ffi::INTERFACE, msg)
+            .unwrap()
+            .await
+    };
+
+    let opened = response.result?;
+    Ok(File {
+        id: opened.file_id,
+        size: opened.size,
+    })
+}
+
+/// Open file. Closed when dropped.
+#[derive(Debug)]
+pub struct File {
+    id: u64,
+    size: u64,
+}
+
+impl File {
+    /// Returns the size of the file when it has been opened, updated with the writes performed
+    /// through this object.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Reads up to `len` bytes starting at the given offset. Returns fewer bytes if the end of
+    /// the file has been reached.
+    pub async fn read_at(&self, offset: u64, len: u32) -> Result<Vec<u8>, FilesError> {
+        let msg = ffi::FilesMessage::Read(ffi::Read {
+            file_id: self.id,
+            offset,
+            len,
+        });
+
+        let response: ffi::ReadResponse = unsafe {
+            redshirt_syscalls::emit_message_with_response(&ffi::INTERFACE, msg)
+                .unwrap()
+                .await
+        };
+
+        response.result
+    }
+
+    /// Writes `data` starting at the given offset.
+    pub async fn write_at(&mut self, offset: u64, data: Vec<u8>) -> Result<(), FilesError> {
+        let end = offset.saturating_add(data.len() as u64);
+        let msg = ffi::FilesMessage::Write(ffi::Write {
+            file_id: self.id,
+            offset,
+            data,
+        });
+
+        let response: ffi::WriteResponse = unsafe {
+            redshirt_syscalls::emit_message_with_response(&ffi::INTERFACE, msg)
+                .unwrap()
+                .await
+        };
+
+        response.result?;
+        self.size = core::cmp::max(self.size, end);
+        Ok(())
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        unsafe {
+            let msg = ffi::FilesMessage::Close(self.id);
+            let _ = redshirt_syscalls::emit_message_without_response(&ffi::INTERFACE, &msg);
+        }
+    }
+}
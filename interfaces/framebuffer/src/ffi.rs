@@ -31,25 +31,23 @@
 //! There actually exists two interfaces that use the same messages format: with events, or without
 //! events. Messages whose first byte is `3` are invalid in the "without events" interface.
 
-use redshirt_syscalls::InterfaceHash;
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE_WITH_EVENTS: InterfaceHash = InterfaceHash::from_raw_hash([
-    0xfc, 0x60, 0x2e, 0x6e, 0xf2, 0x43, 0x9c, 0xa0, 0x40, 0x88, 0x81, 0x7d, 0xe5, 0xaf, 0xb6, 0x90,
-    0x9e, 0x57, 0xc6, 0xc2, 0x5e, 0xbf, 0x02, 0x5b, 0x87, 0x7f, 0xaa, 0xae, 0xbe, 0xd5, 0x19, 0x9c,
-]);
+// Only the [`Event`] answers of this interface are SCALE-encoded. The name must be changed
+// whenever the format of the messages changes.
+pub const INTERFACE_WITH_EVENTS: InterfaceHash =
+    redshirt_syscalls::interface_hash!("framebuffer-with-events", Event);
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE_WITHOUT_EVENTS: InterfaceHash = InterfaceHash::from_raw_hash([
-    0xdf, 0x67, 0x74, 0x34, 0xd8, 0x0d, 0xc5, 0x9e, 0xf0, 0x6e, 0xb9, 0x44, 0xce, 0xaa, 0xc4, 0xde,
-    0x8d, 0x2f, 0xdf, 0x39, 0x0a, 0xe6, 0xa8, 0x29, 0x3c, 0x8f, 0x88, 0x76, 0x5b, 0xe9, 0x1c, 0x70,
-]);
+// The messages of this interface aren't SCALE-encoded. The name must be changed whenever their
+// format changes.
+pub const INTERFACE_WITHOUT_EVENTS: InterfaceHash =
+    redshirt_syscalls::interface_hash!("framebuffer-without-events");
 
 /// Event that can be reported by a framebuffer.
 ///
 /// > **Note**: These events are designed to take into account the possibility that some events are
 /// >           lost. This can happen if the recipient queues messages too slowly.
-#[derive(Debug, Clone, parity_scale_codec::Encode, parity_scale_codec::Decode)]
+#[derive(Debug, Clone, parity_scale_codec::Encode, parity_scale_codec::Decode, InterfaceSchema)]
 pub enum Event {
     /// A keyboard key has been pressed or released.
     KeyboardChange {
@@ -83,7 +81,7 @@ pub enum Event {
     },
 }
 
-#[derive(Debug, Clone, parity_scale_codec::Encode, parity_scale_codec::Decode)]
+#[derive(Debug, Clone, parity_scale_codec::Encode, parity_scale_codec::Decode, InterfaceSchema)]
 pub enum MouseButton {
     /// Typically but not necessarily the left mouse button.
     Main,
@@ -91,7 +89,7 @@ pub enum MouseButton {
     Secondary,
 }
 
-#[derive(Debug, Clone, parity_scale_codec::Encode, parity_scale_codec::Decode)]
+#[derive(Debug, Clone, parity_scale_codec::Encode, parity_scale_codec::Decode, InterfaceSchema)]
 pub enum ElementState {
     Pressed,
     Released,
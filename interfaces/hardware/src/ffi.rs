@@ -15,16 +15,18 @@
 
 use alloc::vec::Vec;
 use parity_scale_codec::{Decode, Encode};
-use redshirt_syscalls::InterfaceHash;
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0x24, 0x5d, 0x25, 0x5e, 0x37, 0xf1, 0x8a, 0xce, 0x23, 0xd6, 0x68, 0xe9, 0xe2, 0xd8, 0xd1, 0xbc,
-    0x37, 0xf3, 0xd3, 0x3c, 0xad, 0x55, 0xf8, 0xd9, 0x22, 0x3a, 0x57, 0xd1, 0x54, 0x46, 0x7b, 0x78,
-]);
+pub const INTERFACE: InterfaceHash = redshirt_syscalls::interface_hash!(
+    "hardware",
+    HardwareMessage,
+    u64,
+    Vec<HardwareAccessResponse>,
+    ()
+);
 
 /// Message in destination to the hardware interface handler.
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum HardwareMessage {
     /// Allocate RAM. Must answer with a `u64`. The value `0` is returned if the allocation is
     /// too large.
@@ -62,7 +64,7 @@ pub enum HardwareMessage {
 }
 
 /// Request to perform accesses to physical memory or to ports.
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum Operation {
     PhysicalMemoryMemset {
         address: u64,
@@ -139,7 +141,7 @@ pub enum Operation {
 }
 
 /// Response to a [`HardwareMessage::HardwareAccess`].
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum HardwareAccessResponse {
     /// Sent back in response to a [`Operation::PhysicalMemoryReadU8`].
     PhysicalMemoryReadU8(Vec<u8>),
@@ -15,30 +15,69 @@
 
 use alloc::vec::Vec;
 use core::{convert::TryFrom as _, num::NonZeroU64};
-use redshirt_syscalls::{EncodedMessage, InterfaceHash, MessageId, Pid};
+use redshirt_syscalls::{EncodedMessage, InterfaceHash, InterfaceSchema, MessageId, Pid};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0x49, 0x6e, 0x56, 0x14, 0x8c, 0xd4, 0x2b, 0xc3, 0x9b, 0x4e, 0xbf, 0x5e, 0xb6, 0x2c, 0x60, 0x4d,
-    0x7d, 0xd5, 0x70, 0x92, 0x4d, 0x4f, 0x70, 0xdf, 0xb3, 0xda, 0xf6, 0xfe, 0xdc, 0x65, 0x93, 0x8a,
-]);
+pub const INTERFACE: InterfaceHash =
+    redshirt_syscalls::interface_hash!("interface", InterfaceMessage, InterfaceRegisterResponse);
 
-#[derive(Debug, parity_scale_codec::Encode, parity_scale_codec::Decode)]
+#[derive(Debug, parity_scale_codec::Encode, parity_scale_codec::Decode, InterfaceSchema)]
 pub enum InterfaceMessage {
-    Register(InterfaceHash),
+    /// Registers the emitter as the handler of the given interfaces. Must be answered with an
+    /// [`InterfaceRegisterResponse`].
+    ///
+    /// A single registration covers all the interfaces of the list. This makes it possible, for
+    /// example, to handle both the old and the new version of an interface. The hash of the
+    /// interface each message has been emitted on is found in
+    /// [`DecodedInterfaceNotification::interface`].
+    Register(Vec<InterfaceHash>),
     NextMessage(NonZeroU64),
     Answer(MessageId, Result<Vec<u8>, ()>),
+    /// Unregisters the given registration. The interfaces become available for registration
+    /// again. Messages that haven't been delivered yet are kept and will be delivered to the next
+    /// handler of their interface.
+    ///
+    /// Like [`InterfaceMessage::Handover`], [`InterfaceMessage::Grant`] and
+    /// [`InterfaceMessage::Revoke`], can be emitted without expecting an answer. If an answer is
+    /// expected, it is a `Result<(), ()>` that is an error if the registration doesn't exist or
+    /// doesn't belong to the emitter.
+    Unregister(NonZeroU64),
+    /// Allows the given process to take over the given registration. When that process sends a
+    /// [`InterfaceMessage::Register`] message containing one of the interfaces of the
+    /// registration, the registration is transferred to it, and messages that haven't been
+    /// delivered yet are redirected to it if they concern one of the interfaces it registers.
+    ///
+    /// Until then, the current handler continues to receive messages.
+    ///
+    /// See [`InterfaceMessage::Unregister`] for the answer.
+    Handover(NonZeroU64, Pid),
+    /// Allows the given process to emit messages on the given interface, which must be one of
+    /// the interfaces of the given registration.
+    ///
+    /// This only has an effect if the interface has been restricted by the kernel, in which case
+    /// processes that haven't been granted this right fail to emit messages on it. Grants are
+    /// kept if the registration is later removed.
+    ///
+    /// See [`InterfaceMessage::Unregister`] for the answer, which is also an error if the
+    /// interface isn't covered by the registration.
+    Grant(NonZeroU64, InterfaceHash, Pid),
+    /// Cancels a previous [`InterfaceMessage::Grant`]. Messages already emitted by the process
+    /// are still delivered.
+    ///
+    /// Answered in the same way as [`InterfaceMessage::Grant`].
+    Revoke(NonZeroU64, InterfaceHash, Pid),
 }
 
-#[derive(Debug, parity_scale_codec::Encode, parity_scale_codec::Decode)]
+#[derive(Debug, parity_scale_codec::Encode, parity_scale_codec::Decode, InterfaceSchema)]
 pub struct InterfaceRegisterResponse {
     pub result: Result<NonZeroU64, InterfaceRegisterError>,
 }
 
-#[derive(Debug, Clone, parity_scale_codec::Encode, parity_scale_codec::Decode)]
+#[derive(Debug, Clone, parity_scale_codec::Encode, parity_scale_codec::Decode, InterfaceSchema)]
 pub enum InterfaceRegisterError {
-    /// There already exists a process registered for this interface.
+    /// There already exists a process registered for one of the interfaces.
     AlreadyRegistered,
+    /// The list of interfaces to register is empty.
+    EmptyList,
 }
 
 /// Either a decoded interface notification or a decoded process destroyed notification.
@@ -147,8 +186,8 @@ pub fn decode_interface_notification(buffer: &[u8]) -> Result<DecodedInterfaceNo
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DecodedInterfaceNotification {
-    /// Interface the message concerns.
-    // TODO: remove
+    /// Interface the message has been emitted on. If the registration covers multiple
+    /// interfaces, this indicates how the message must be interpreted.
     pub interface: InterfaceHash,
     /// Id of the message. Can be used for answering. `None` if no answer is expected.
     pub message_id: Option<MessageId>,
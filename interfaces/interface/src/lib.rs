@@ -19,24 +19,45 @@
 
 extern crate alloc;
 
-use core::{mem, num::NonZeroU64};
+use alloc::vec::Vec;
+use core::{iter, mem, num::NonZeroU64};
 use futures::prelude::*;
-use redshirt_syscalls::{Encode, EncodedMessage, InterfaceHash, MessageId};
+use redshirt_syscalls::{Encode, EncodedMessage, InterfaceHash, MessageId, Pid};
 
 pub use ffi::{DecodedInterfaceOrDestroyed, InterfaceRegisterError};
 
 pub mod ffi;
+pub mod shim;
 
 /// Registers the current program as the provider for the given interface hash.
 ///
 /// > **Note**: Interface hashes can be found in the various `ffi` modules of the crates in the
 /// >           `interfaces` directory, although that is subject to change.
 ///
-/// Returns an error if there was already a program registered for that interface.
+/// Returns an error if there was already a program registered for that interface, unless that
+/// program has handed over its registration to the current process by calling
+/// [`Registration::handover`].
 pub async fn register_interface(
     hash: InterfaceHash,
 ) -> Result<Registration, InterfaceRegisterError> {
-    let msg = ffi::InterfaceMessage::Register(hash);
+    register_interfaces(iter::once(hash)).await
+}
+
+/// Registers the current program as the provider for all the given interface hashes, using a
+/// single [`Registration`].
+///
+/// This is typically used in order to handle multiple versions of the same interface. The
+/// interface each message has been emitted on can be found in the
+/// [`interface`](ffi::DecodedInterfaceNotification::interface) field of the notification.
+///
+/// Returns an error if the list is empty, or if there was already a program registered for one
+/// of these interfaces, unless that program has handed over its registration to the current
+/// process by calling [`Registration::handover`]. On error, none of the interfaces is
+/// registered.
+pub async fn register_interfaces(
+    hashes: impl IntoIterator<Item = InterfaceHash>,
+) -> Result<Registration, InterfaceRegisterError> {
+    let msg = ffi::InterfaceMessage::Register(hashes.into_iter().collect::<Vec<_>>());
     // Unwrapping is ok because there's always something that handles interface registration.
     let id = {
         let msg: ffi::InterfaceRegisterResponse =
@@ -63,15 +84,73 @@ pub struct Registration {
     /// Identifier of the interface registration.
     id: NonZeroU64,
     /// Futures that will resolve when a message is received on the interface.
-    messages: stream::FuturesOrdered<redshirt_syscalls::MessageResponseFuture<EncodedMessage>>,
+    messages: stream::FuturesOrdered<
+        redshirt_syscalls::MessageResponseFuture<Result<EncodedMessage, ()>>,
+    >,
 }
 
 impl Registration {
     /// Returns the next message received on this interface.
+    ///
+    /// Never returns if the registration has been taken over by a different process. See
+    /// [`Registration::handover`].
     pub async fn next_message_raw(&mut self) -> DecodedInterfaceOrDestroyed {
-        let message = self.messages.next().await.unwrap();
-        self.add_message();
-        ffi::decode_notification(&message.0).unwrap()
+        match self.messages.next().await.unwrap() {
+            Ok(message) => {
+                self.add_message();
+                ffi::decode_notification(&message.0).unwrap()
+            }
+            // The kernel answers our requests for messages with an error once the registration
+            // no longer exists. No message will ever be received again.
+            Err(()) => future::pending().await,
+        }
+    }
+
+    /// Allows the process with the given [`Pid`] to take over this registration by calling
+    /// [`register_interface`] or [`register_interfaces`] with one of the interface hashes of this
+    /// registration.
+    ///
+    /// This registration continues to receive messages until the other process has registered.
+    /// Afterwards, the messages not delivered yet are redirected to the new handler, and
+    /// [`Registration::next_message_raw`] no longer returns any message, but doesn't panic
+    /// either. It is therefore safe to continue calling it, for example in a `select!` alongside
+    /// with other futures.
+    ///
+    /// > **Note**: Messages already received on this registration must still be answered.
+    pub fn handover(&self, new_handler: Pid) {
+        unsafe {
+            redshirt_syscalls::emit_message_without_response(
+                &ffi::INTERFACE,
+                ffi::InterfaceMessage::Handover(self.id, new_handler),
+            )
+            .unwrap();
+        }
+    }
+
+    /// Allows the process with the given [`Pid`] to emit messages on the given interface, which
+    /// must be one of the interfaces of this registration.
+    ///
+    /// This only has an effect on interfaces that the kernel has been configured to restrict.
+    /// Other interfaces accept messages from all processes.
+    pub fn grant(&self, interface: &InterfaceHash, pid: Pid) {
+        unsafe {
+            redshirt_syscalls::emit_message_without_response(
+                &ffi::INTERFACE,
+                ffi::InterfaceMessage::Grant(self.id, interface.clone(), pid),
+            )
+            .unwrap();
+        }
+    }
+
+    /// Cancels a previous call to [`Registration::grant`].
+    pub fn revoke(&self, interface: &InterfaceHash, pid: Pid) {
+        unsafe {
+            redshirt_syscalls::emit_message_without_response(
+                &ffi::INTERFACE,
+                ffi::InterfaceMessage::Revoke(self.id, interface.clone(), pid),
+            )
+            .unwrap();
+        }
     }
 
     fn add_message(&mut self) {
@@ -81,7 +160,7 @@ impl Registration {
                 .add_data(&EncodedMessage(message.0))
                 .emit_with_response_raw(&ffi::INTERFACE)
                 .unwrap();
-            redshirt_syscalls::message_response(msg_id)
+            redshirt_syscalls::message_response_or_error(msg_id)
         });
     }
 }
@@ -91,7 +170,12 @@ impl Drop for Registration {
         // Before dropping the registration, cancel all existing messages.
         let _ = mem::take(&mut self.messages);
 
-        // TODO: unregister; unregistrations aren't supported at the moment
+        unsafe {
+            let _ = redshirt_syscalls::emit_message_without_response(
+                &ffi::INTERFACE,
+                ffi::InterfaceMessage::Unregister(self.id),
+            );
+        }
     }
 }
 
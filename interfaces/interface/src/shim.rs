@@ -0,0 +1,325 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Translation shims between versions of an interface.
+//!
+//! Modifying an interface leads to a different interface hash. In order for the programs that
+//! use the old version to continue working, a program, called a *shim*, can register itself as
+//! the handler of the old version, translate each message into a message of the new version,
+//! emit it, then translate the answer back.
+//!
+//! Writing a shim consists in implementing the [`Translator`] trait and passing it to [`run`].
+//!
+//! ```no_run
+//! use redshirt_interface_interface::shim::{self, Translator};
+//! use redshirt_syscalls::{EncodedMessage, InterfaceHash, MessageId};
+//!
+//! struct Identity;
+//!
+//! impl Translator for Identity {
+//!     type Pending = ();
+//!
+//!     fn translate_message(
+//!         &mut self,
+//!         _: &InterfaceHash,
+//!         message: EncodedMessage,
+//!     ) -> Result<(EncodedMessage, ()), ()> {
+//!         Ok((message, ()))
+//!     }
+//!
+//!     fn translate_answer(
+//!         &mut self,
+//!         _: (),
+//!         answer: EncodedMessage,
+//!     ) -> Result<EncodedMessage, ()> {
+//!         Ok(answer)
+//!     }
+//! }
+//!
+//! # let old = InterfaceHash::from_raw_hash([1; 32]);
+//! # let new = InterfaceHash::from_raw_hash([2; 32]);
+//! let _ = redshirt_syscalls::block_on(shim::run(vec![old], new, Identity));
+//! ```
+//!
+//! > **Note**: The handler of the new interface sees the shim as the emitter of all the messages.
+//! >           Interfaces that rely on the [`Pid`](redshirt_syscalls::Pid) of the emitter, for
+//! >           example in order to track resources, can't be translated this way.
+
+use crate::{emit_answer, emit_message_error, ffi, register_interfaces, InterfaceRegisterError};
+
+use core::convert::Infallible;
+use futures::prelude::*;
+use redshirt_syscalls::{EncodedMessage, InterfaceHash, MessageId};
+
+/// Translates messages and answers from old versions of an interface to a new version.
+pub trait Translator {
+    /// Information about a message kept while its answer is being waited for, and passed to
+    /// [`Translator::translate_answer`].
+    type Pending;
+
+    /// Translates a message emitted on the interface `interface`, which is one of the old
+    /// versions passed to [`run`], into a message for the new version.
+    ///
+    /// Returns an error if the message can't be translated, in which case the emitter is
+    /// notified of an error in its message, if it expects an answer.
+    fn translate_message(
+        &mut self,
+        interface: &InterfaceHash,
+        message: EncodedMessage,
+    ) -> Result<(EncodedMessage, Self::Pending), ()>;
+
+    /// Translates an answer of the new version back into an answer for the old version.
+    ///
+    /// Returns an error if the answer can't be translated, in which case the emitter of the
+    /// original message is notified of an error in its message.
+    fn translate_answer(
+        &mut self,
+        pending: Self::Pending,
+        answer: EncodedMessage,
+    ) -> Result<EncodedMessage, ()>;
+}
+
+/// Registers the current program as the handler of all the interfaces of `old_interfaces`, then
+/// translates the messages received on them with `translator` and emits them on `new_interface`.
+///
+/// Multiple messages can be waiting for an answer at the same time. If the handler of
+/// `new_interface` answers a message with an error, the emitter of the original message is
+/// notified of an error as well.
+///
+/// Only returns if the registration fails.
+pub async fn run<T: Translator>(
+    old_interfaces: impl IntoIterator<Item = InterfaceHash>,
+    new_interface: InterfaceHash,
+    mut translator: T,
+) -> Result<Infallible, InterfaceRegisterError> {
+    let mut registration = register_interfaces(old_interfaces).await?;
+    let mut pending_answers = stream::FuturesUnordered::new();
+
+    loop {
+        // `FuturesUnordered` immediately yields `None` when it is empty.
+        let notification = if pending_answers.is_empty() {
+            registration.next_message_raw().await
+        } else {
+            let next_message = registration.next_message_raw();
+            futures::pin_mut!(next_message);
+            match future::select(next_message, pending_answers.next()).await {
+                future::Either::Left((notification, _)) => notification,
+                future::Either::Right((Some((message_id, pending, answer)), _)) => {
+                    match translate_answer(&mut translator, pending, answer) {
+                        Ok(answer) => emit_answer(message_id, answer),
+                        Err(()) => emit_message_error(message_id),
+                    }
+                    continue;
+                }
+                future::Either::Right((None, _)) => unreachable!(),
+            }
+        };
+
+        let notification = match notification {
+            ffi::DecodedInterfaceOrDestroyed::Interface(notification) => notification,
+            ffi::DecodedInterfaceOrDestroyed::ProcessDestroyed(_) => continue,
+        };
+
+        match translate_message(&mut translator, notification) {
+            Translated::Emit {
+                message,
+                pending,
+                answer_to: Some(message_id),
+            } => {
+                let emitted = unsafe {
+                    redshirt_syscalls::MessageBuilder::new()
+                        .add_data(&message)
+                        .emit_with_response_raw(&new_interface)
+                };
+                match emitted {
+                    Ok(msg_id) => pending_answers.push(
+                        redshirt_syscalls::message_response_or_error::<EncodedMessage>(msg_id)
+                            .map(move |answer| (message_id, pending, answer)),
+                    ),
+                    Err(_) => emit_message_error(message_id),
+                }
+            }
+            Translated::Emit {
+                message,
+                answer_to: None,
+                ..
+            } => unsafe {
+                let _ = redshirt_syscalls::emit_message_without_response(&new_interface, message);
+            },
+            Translated::Error(message_id) => emit_message_error(message_id),
+            Translated::Ignore => {}
+        }
+    }
+}
+
+/// What to do with a message received on one of the old interfaces.
+#[derive(Debug, PartialEq, Eq)]
+enum Translated<TPending> {
+    /// Emit the message on the new interface. If `answer_to` is `Some`, the answer must be
+    /// translated using `pending`, then used to answer the given message.
+    Emit {
+        message: EncodedMessage,
+        pending: TPending,
+        answer_to: Option<MessageId>,
+    },
+    /// Answer the given message with an error.
+    Error(MessageId),
+    /// Discard the message.
+    Ignore,
+}
+
+/// Translates a message received on one of the old interfaces.
+fn translate_message<T: Translator>(
+    translator: &mut T,
+    notification: ffi::DecodedInterfaceNotification,
+) -> Translated<T::Pending> {
+    match (
+        translator.translate_message(&notification.interface, notification.actual_data),
+        notification.message_id,
+    ) {
+        (Ok((message, pending)), answer_to) => Translated::Emit {
+            message,
+            pending,
+            answer_to,
+        },
+        (Err(()), Some(message_id)) => Translated::Error(message_id),
+        (Err(()), None) => Translated::Ignore,
+    }
+}
+
+/// Translates the answer of the handler of the new interface. Error answers are forwarded as is.
+fn translate_answer<T: Translator>(
+    translator: &mut T,
+    pending: T::Pending,
+    answer: Result<EncodedMessage, ()>,
+) -> Result<EncodedMessage, ()> {
+    translator.translate_answer(pending, answer?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{translate_answer, translate_message, Translated, Translator};
+    use crate::ffi::DecodedInterfaceNotification;
+    use alloc::vec;
+    use core::convert::TryFrom as _;
+    use redshirt_syscalls::{EncodedMessage, InterfaceHash, MessageId, Pid};
+
+    /// Translator that prefixes messages with the first byte of their interface, and strips the
+    /// first byte of answers. Messages and answers that are empty can't be translated.
+    struct Prefix;
+
+    impl Translator for Prefix {
+        type Pending = u8;
+
+        fn translate_message(
+            &mut self,
+            interface: &InterfaceHash,
+            message: EncodedMessage,
+        ) -> Result<(EncodedMessage, u8), ()> {
+            if message.0.is_empty() {
+                return Err(());
+            }
+            let prefix = interface.as_ref()[0];
+            let mut translated = vec![prefix];
+            translated.extend_from_slice(&message.0);
+            Ok((EncodedMessage(translated), prefix))
+        }
+
+        fn translate_answer(
+            &mut self,
+            pending: u8,
+            answer: EncodedMessage,
+        ) -> Result<EncodedMessage, ()> {
+            match answer.0.split_first() {
+                Some((first, rest)) if *first == pending => Ok(EncodedMessage(rest.to_vec())),
+                _ => Err(()),
+            }
+        }
+    }
+
+    fn notification(
+        interface: u8,
+        message_id: Option<MessageId>,
+        data: &[u8],
+    ) -> DecodedInterfaceNotification {
+        DecodedInterfaceNotification {
+            interface: InterfaceHash::from_raw_hash([interface; 32]),
+            message_id,
+            emitter_pid: Pid::from(1),
+            actual_data: EncodedMessage(data.to_vec()),
+        }
+    }
+
+    #[test]
+    fn translates_messages_of_each_interface() {
+        let message_id = MessageId::try_from(5).unwrap();
+        assert_eq!(
+            translate_message(&mut Prefix, notification(1, Some(message_id), &[7])),
+            Translated::Emit {
+                message: EncodedMessage(vec![1, 7]),
+                pending: 1,
+                answer_to: Some(message_id),
+            }
+        );
+        assert_eq!(
+            translate_message(&mut Prefix, notification(2, Some(message_id), &[7])),
+            Translated::Emit {
+                message: EncodedMessage(vec![2, 7]),
+                pending: 2,
+                answer_to: Some(message_id),
+            }
+        );
+    }
+
+    #[test]
+    fn message_without_answer() {
+        assert_eq!(
+            translate_message(&mut Prefix, notification(1, None, &[7])),
+            Translated::Emit {
+                message: EncodedMessage(vec![1, 7]),
+                pending: 1,
+                answer_to: None,
+            }
+        );
+    }
+
+    #[test]
+    fn translation_failure() {
+        let message_id = MessageId::try_from(5).unwrap();
+        assert_eq!(
+            translate_message(&mut Prefix, notification(1, Some(message_id), &[])),
+            Translated::Error(message_id)
+        );
+        assert_eq!(
+            translate_message(&mut Prefix, notification(1, None, &[])),
+            Translated::Ignore
+        );
+    }
+
+    #[test]
+    fn answers() {
+        assert_eq!(
+            translate_answer(&mut Prefix, 1, Ok(EncodedMessage(vec![1, 8]))),
+            Ok(EncodedMessage(vec![8]))
+        );
+        // The answer can't be translated.
+        assert_eq!(
+            translate_answer(&mut Prefix, 1, Ok(EncodedMessage(vec![2, 8]))),
+            Err(())
+        );
+        // Error answers are forwarded.
+        assert_eq!(translate_answer(&mut Prefix, 1, Err(())), Err(()));
+    }
+}
@@ -23,6 +23,18 @@
 //! - Sender sends a message with an empty body.
 //! - Handler sends back a Prometheus-compatible UTF-8 message.
 //!
+//! Alternatively:
+//!
+//! - Sender sends a message whose body is [`STUCK_EMITTERS_REQUEST`].
+//! - Handler sends back a Prometheus-compatible UTF-8 message containing, for each process
+//! waiting for a handler of an interface to be registered, how long it has been waiting.
+//!
+//! Or:
+//!
+//! - Sender sends a message whose body is [`GRANTS_REQUEST`].
+//! - Handler sends back a Prometheus-compatible UTF-8 message containing the list of restricted
+//! interfaces, and the processes that have been granted the right to emit messages on them.
+//!
 //! See [this page](https://prometheus.io/docs/instrumenting/exposition_formats/#text-format-details)
 //! for more information about the format.
 //!
@@ -34,11 +46,16 @@ extern crate alloc;
 use alloc::{string::String, vec::Vec};
 use redshirt_syscalls::InterfaceHash;
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0x8c, 0xb4, 0xc6, 0xee, 0xc9, 0x29, 0xc5, 0xce, 0xaf, 0x46, 0x28, 0x74, 0x9e, 0x96, 0x72, 0x58,
-    0xea, 0xd2, 0xa2, 0xa2, 0xd6, 0xeb, 0x7d, 0xc8, 0xe3, 0x95, 0x0c, 0x0e, 0x53, 0xde, 0x8c, 0xba,
-]);
+// The messages of this interface aren't SCALE-encoded. The name must be changed whenever their
+// format changes.
+pub const INTERFACE: InterfaceHash = redshirt_syscalls::interface_hash!("kernel-debug");
+
+/// Body of a message requesting the list of processes waiting for an interface handler.
+pub const STUCK_EMITTERS_REQUEST: &[u8] = &[1];
+
+/// Body of a message requesting the list of processes allowed to emit messages on restricted
+/// interfaces.
+pub const GRANTS_REQUEST: &[u8] = &[2];
 
 /// Loads metrics from the kernel, as a Prometheus-compatible UTF-8 string.
 pub async fn get_prometheus_metrics() -> String {
@@ -54,3 +71,35 @@ pub async fn get_prometheus_metrics() -> String {
         String::from_utf8(response.0).unwrap()
     }
 }
+
+/// Loads from the kernel the list of processes waiting for a handler of an interface to be
+/// registered, as a Prometheus-compatible UTF-8 string.
+pub async fn get_stuck_emitters_metrics() -> String {
+    unsafe {
+        let response: redshirt_syscalls::EncodedMessage =
+            redshirt_syscalls::emit_message_with_response(
+                &INTERFACE,
+                redshirt_syscalls::EncodedMessage(STUCK_EMITTERS_REQUEST.to_vec()),
+            )
+            .unwrap()
+            .await;
+
+        String::from_utf8(response.0).unwrap()
+    }
+}
+
+/// Loads from the kernel the list of restricted interfaces and of the processes allowed to emit
+/// messages on them, as a Prometheus-compatible UTF-8 string.
+pub async fn get_grants_metrics() -> String {
+    unsafe {
+        let response: redshirt_syscalls::EncodedMessage =
+            redshirt_syscalls::emit_message_with_response(
+                &INTERFACE,
+                redshirt_syscalls::EncodedMessage(GRANTS_REQUEST.to_vec()),
+            )
+            .unwrap()
+            .await;
+
+        String::from_utf8(response.0).unwrap()
+    }
+}
@@ -21,16 +21,15 @@
 /// - A `1` byte followed with a SCALE-codec-encoded [`KernelLogMethod`].
 ///
 use parity_scale_codec::{Decode, Encode};
-use redshirt_syscalls::InterfaceHash;
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0xcd, 0xba, 0x59, 0xb1, 0xb6, 0x5e, 0xd4, 0x9a, 0xfe, 0x25, 0xd0, 0x7c, 0x04, 0x1f, 0xae, 0x82,
-    0x5b, 0xf3, 0xc9, 0xca, 0x89, 0x48, 0x81, 0xe0, 0x3b, 0x3a, 0xd2, 0x76, 0x29, 0x04, 0x21, 0x1b,
-]);
+// Only the [`KernelLogMethod`] part of the messages is SCALE-encoded. The name must be changed
+// whenever the rest of the format changes.
+pub const INTERFACE: InterfaceHash =
+    redshirt_syscalls::interface_hash!("kernel-log", KernelLogMethod);
 
 /// How the kernel should log messages.
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, InterfaceSchema)]
 pub struct KernelLogMethod {
     /// If `true`, log messages should be shown. If `false`, they should be buffered up (to a
     /// certain limit) and will be shown as soon as `enabled` is true.
@@ -48,7 +47,7 @@ pub struct KernelLogMethod {
 /// In order to write, the kernel should repeatidly read a value from `wait_address` until
 /// its value, when AND-ed with `wait_mask`, is equal to `wait_compare_equal_if_ready`. Then it
 /// should write a value to `write_address`.
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, InterfaceSchema)]
 pub struct UartInfo {
     /// Where to read the value to compare.
     pub wait_address: UartAccess,
@@ -62,7 +61,7 @@ pub struct UartInfo {
 }
 
 /// How to access either the value to compare or where to write the output.
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, InterfaceSchema)]
 pub enum UartAccess {
     /// 32bits at a specific memory location.
     MemoryMappedU32(u64),
@@ -71,7 +70,7 @@ pub enum UartAccess {
 }
 
 /// Information about how the kernel should print on the framebuffer.
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, InterfaceSchema)]
 pub struct FramebufferInfo {
     /// Location in physical memory where the framebuffer starts.
     pub address: u64,
@@ -88,7 +87,7 @@ pub struct FramebufferInfo {
 }
 
 /// Format of the framebuffer's data.
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, InterfaceSchema)]
 pub enum FramebufferFormat {
     /// One ASCII character followed with one byte of characteristics.
     Text,
@@ -15,21 +15,18 @@
 
 use alloc::vec::Vec;
 use parity_scale_codec::{Decode, Encode};
-use redshirt_syscalls::InterfaceHash;
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0x1c, 0x72, 0xb5, 0x18, 0x7f, 0x73, 0x52, 0xfd, 0xf7, 0xa7, 0x81, 0xe2, 0xa8, 0x46, 0x51, 0xd7,
-    0xb3, 0xc6, 0x2d, 0x24, 0x31, 0x88, 0x96, 0x95, 0x6e, 0xfc, 0x7d, 0x4d, 0x86, 0x3f, 0xff, 0xa6,
-]);
+pub const INTERFACE: InterfaceHash =
+    redshirt_syscalls::interface_hash!("loader", LoaderMessage, LoadResponse);
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum LoaderMessage {
     /// Load the data corresponding to the blake3 hash passed as parameter.
     Load([u8; 32]),
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct LoadResponse {
     pub result: Result<Vec<u8>, ()>,
 }
@@ -30,11 +30,9 @@
 use core::{convert::TryFrom, fmt, str};
 use redshirt_syscalls::{Decode, EncodedMessage, InterfaceHash};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0xa6, 0xbc, 0x8d, 0xc3, 0x43, 0xbd, 0xdd, 0x3b, 0x44, 0x2f, 0x06, 0x40, 0xa8, 0x40, 0xad, 0x4f,
-    0x25, 0x57, 0x23, 0x91, 0x79, 0xc8, 0x16, 0x07, 0x6f, 0xab, 0xa9, 0xd6, 0x38, 0xca, 0x01, 0x8b,
-]);
+// The messages of this interface aren't SCALE-encoded. The name must be changed whenever their
+// format changes.
+pub const INTERFACE: InterfaceHash = redshirt_syscalls::interface_hash!("log");
 
 /// Log level of a message.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -15,16 +15,20 @@
 
 use alloc::vec::Vec;
 use parity_scale_codec::{Decode, Encode};
-use redshirt_syscalls::InterfaceHash;
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0x2d, 0x93, 0xd0, 0x48, 0xab, 0x88, 0x1c, 0x95, 0x87, 0xf5, 0x5c, 0x3b, 0xe6, 0x4d, 0x8f, 0x65,
-    0x3f, 0x37, 0x4c, 0x4e, 0xad, 0xea, 0x15, 0xcc, 0xf0, 0x17, 0x44, 0x0f, 0x6d, 0x6e, 0x5d, 0xc8,
-]);
+pub const INTERFACE: InterfaceHash = redshirt_syscalls::interface_hash!(
+    "pci",
+    PciMessage,
+    GetDevicesListResponse,
+    Result<(), ()>,
+    NextInterruptResponse,
+    Vec<MemoryAccessResponse>,
+    Vec<IoAccessResponse>,
+);
 
 /// Message in destination to the PCI interface handler.
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum PciMessage {
     /// Request list of PCI devices. Answered with a [`GetDevicesListResponse`].
     GetDevicesList,
@@ -102,14 +106,14 @@ pub enum PciMessage {
 }
 
 /// Response to [`PciMessage::GetDevicesList`].
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct GetDevicesListResponse {
     /// List of PCI devices available on the system.
     pub devices: Vec<PciDeviceInfo>,
 }
 
 /// Response to [`PciMessage::NextInterrupt`].
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum NextInterruptResponse {
     /// Success. We got an interrupt.
     Interrupt,
@@ -124,7 +128,7 @@ pub enum NextInterruptResponse {
 /// Location of a PCI device according to the controller.
 ///
 /// > **Note**: The acronym BDF stands for "Bus, Device, Function".
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Encode, Decode)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Encode, Decode, InterfaceSchema)]
 pub struct PciDeviceBdf {
     pub bus: u8,
     pub device: u8,
@@ -132,7 +136,7 @@ pub struct PciDeviceBdf {
 }
 
 /// Description of a single PCI device.
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, InterfaceSchema)]
 pub struct PciDeviceInfo {
     /// Location of the device on the machine. Uniquely identifies each device.
     pub location: PciDeviceBdf,
@@ -151,14 +155,14 @@ pub struct PciDeviceInfo {
 
 /// Description of a single PCI device.
 // TODO: actually figure out PCI and adjust this
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, InterfaceSchema)]
 pub enum PciBaseAddressRegister {
     Memory { base_address: u64 },
     Io { base_address: u32 },
 }
 
 /// Request to perform accesses to memory-mapped memory.
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum MemoryOperation {
     Memset {
         offset: u64,
@@ -196,7 +200,7 @@ pub enum MemoryOperation {
 }
 
 /// Request to perform accesses to I/O ports.
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum IoOperation {
     /// Write data to a port.
     WriteU8 {
@@ -237,7 +241,7 @@ pub enum IoOperation {
 }
 
 /// Response to a [`PciMessage::BarMemoryOperations`].
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum MemoryAccessResponse {
     /// Sent back in response to a [`MemoryOperation::ReadU8`].
     ReadU8(Vec<u8>),
@@ -248,7 +252,7 @@ pub enum MemoryAccessResponse {
 }
 
 /// Response to a [`PciMessage::BarIoOperations`].
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum IoAccessResponse {
     /// Sent back in response to a [`IoOperation::ReadU8`].
     ReadU8(u8),
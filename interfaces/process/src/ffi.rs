@@ -0,0 +1,96 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use alloc::{string::String, vec::Vec};
+use parity_scale_codec::{Decode, Encode};
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema, Pid};
+
+pub const INTERFACE: InterfaceHash = redshirt_syscalls::interface_hash!(
+    "process",
+    ProcessMessage,
+    Result<Pid, SpawnError>,
+    Result<ExitStatus, ()>,
+    Result<(), ()>,
+    Vec<Pid>,
+);
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub enum ProcessMessage {
+    /// Start a new process, whose parent is the process that emits the message. Must respond
+    /// with a `Result<Pid, SpawnError>`.
+    Spawn(Spawn),
+    /// Send response when the given process, which must have been spawned by the process that
+    /// emits the message, has ended. Responds with a `Result<ExitStatus, ()>`. An error is
+    /// returned if the process doesn't exist, has a different parent, or has already been
+    /// waited for.
+    Wait(Pid),
+    /// Kill the given process, which must have been spawned by the process that emits the
+    /// message. Responds with a `Result<(), ()>`. An error is returned if the process doesn't
+    /// exist, has a different parent, or has already ended.
+    Kill(Pid),
+    /// Responds with a `Vec<Pid>` containing the processes spawned by the process that emits the
+    /// message and that haven't ended yet.
+    List,
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub struct Spawn {
+    /// Program to execute.
+    pub module: SpawnModule,
+    /// Arguments passed to the program. By convention, the first argument is the name of the
+    /// program.
+    pub args: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub enum SpawnModule {
+    /// Hash of the module. The module is loaded through the `loader` interface.
+    Hash([u8; 32]),
+    /// Binary content of the module.
+    Bytes(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, InterfaceSchema)]
+pub enum SpawnError {
+    /// The `loader` interface couldn't provide the module.
+    LoadFailed,
+    /// The module isn't a valid Wasm module, or doesn't follow the conventions expected by the
+    /// kernel.
+    InvalidModule,
+    /// The module doesn't export a `_start` function without parameters.
+    BadEntryPoint,
+    /// The module imports a function that the kernel doesn't provide, or with a different
+    /// signature.
+    UnresolvedImport {
+        /// Name of the module the function is imported from.
+        module_name: String,
+        /// Name of the function.
+        function: String,
+    },
+    /// The initial memory or table of the module is above the limits of the process.
+    LimitExceeded,
+}
+
+/// How a process has ended.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, InterfaceSchema)]
+pub enum ExitStatus {
+    /// The main thread of the process has returned, or the process has voluntarily exited, with
+    /// the given exit code.
+    Exited(i32),
+    /// The process has been killed.
+    Killed,
+    /// The process has crashed.
+    Crashed,
+}
@@ -0,0 +1,80 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Processes.
+//!
+//! This interface is handled by the kernel. It makes it possible for a process to start other
+//! programs, to wait for them to end, and to kill them.
+//!
+//! The process that starts a program becomes its parent. Only the parent of a process can wait
+//! for it or kill it.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use redshirt_syscalls::Pid;
+
+pub mod ffi;
+pub mod restart;
+
+pub use ffi::{ExitStatus, SpawnError, SpawnModule};
+
+/// Starts a new process executing the given module and passes it the given arguments.
+pub async fn spawn(module: SpawnModule, args: Vec<Vec<u8>>) -> Result<Pid, SpawnError> {
+    let msg = ffi::ProcessMessage::Spawn(ffi::Spawn { module, args });
+    unsafe {
+        redshirt_syscalls::emit_message_with_response(&ffi::INTERFACE, msg)
+            .unwrap()
+            .await
+    }
+}
+
+/// Waits until the given process, previously started with [`spawn`], has ended.
+///
+/// Returns an error if the process wasn't started by the current process, or if it has already
+/// been waited for.
+pub async fn wait(pid: Pid) -> Result<ExitStatus, ()> {
+    let msg = ffi::ProcessMessage::Wait(pid);
+    unsafe {
+        redshirt_syscalls::emit_message_with_response(&ffi::INTERFACE, msg)
+            .unwrap()
+            .await
+    }
+}
+
+/// Kills the given process, previously started with [`spawn`].
+///
+/// Returns an error if the process wasn't started by the current process, or if it has already
+/// ended.
+pub async fn kill(pid: Pid) -> Result<(), ()> {
+    let msg = ffi::ProcessMessage::Kill(pid);
+    unsafe {
+        redshirt_syscalls::emit_message_with_response(&ffi::INTERFACE, msg)
+            .unwrap()
+            .await
+    }
+}
+
+/// Returns the list of the processes started with [`spawn`] that haven't ended yet.
+pub async fn list() -> Vec<Pid> {
+    let msg = ffi::ProcessMessage::List;
+    unsafe {
+        redshirt_syscalls::emit_message_with_response(&ffi::INTERFACE, msg)
+            .unwrap()
+            .await
+    }
+}
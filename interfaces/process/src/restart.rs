@@ -0,0 +1,63 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Delay between the restarts of a process that ends repeatedly.
+//!
+//! Used both by the kernel, which restarts its startup processes, and by the programs that
+//! supervise the processes they have started, so that all of them behave the same way.
+
+use core::{cmp, time::Duration};
+
+/// Delay before restarting a process that has ended for the first time.
+pub const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Maximum delay before restarting a process. A process that has been running for longer than
+/// this is considered healthy, and the delay is reset to [`INITIAL_BACKOFF`].
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Delay to wait before restarting a process. Doubles after each restart, in order to not waste
+/// resources restarting a program that can't work.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    /// Delay to wait before the next restart.
+    delay: Duration,
+}
+
+impl Backoff {
+    /// Initializes a new [`Backoff`] for a process that has never ended.
+    pub fn new() -> Self {
+        Backoff {
+            delay: INITIAL_BACKOFF,
+        }
+    }
+
+    /// Indicates that the process has ended after running for `ran_for`. Returns the delay to
+    /// wait before restarting it.
+    pub fn next_delay(&mut self, ran_for: Duration) -> Duration {
+        if ran_for >= MAX_BACKOFF {
+            self.delay = INITIAL_BACKOFF;
+        }
+
+        let delay = self.delay;
+        self.delay = cmp::min(self.delay * 2, MAX_BACKOFF);
+        delay
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new()
+    }
+}
@@ -15,15 +15,12 @@
 
 use alloc::vec::Vec;
 use parity_scale_codec::{Decode, Encode};
-use redshirt_syscalls::InterfaceHash;
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0xfb, 0x83, 0xa5, 0x46, 0xfd, 0xf1, 0x50, 0x7a, 0xef, 0x8c, 0xb6, 0x8f, 0xa5, 0x44, 0x49, 0x21,
-    0x53, 0xe5, 0x83, 0xda, 0xf0, 0x66, 0xbc, 0x1a, 0xd2, 0x18, 0xfd, 0x00, 0x54, 0x7f, 0xdb, 0x25,
-]);
+pub const INTERFACE: InterfaceHash =
+    redshirt_syscalls::interface_hash!("random", RandomMessage, GenerateResponse);
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum RandomMessage {
     /// Ask to generate cryptographically-secure list of random numbers of the given length.
     ///
@@ -33,7 +30,7 @@ pub enum RandomMessage {
     Generate { len: u16 },
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct GenerateResponse {
     /// Random bytes. Must be of the requested length.
     pub result: Vec<u8>,
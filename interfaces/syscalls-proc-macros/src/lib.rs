@@ -0,0 +1,190 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Procedural macros of the `redshirt-syscalls` crate.
+//!
+//! Shouldn't be used directly. Use the re-exports in `redshirt-syscalls` instead.
+
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned as _;
+
+/// Implements the `InterfaceSchema` trait on a struct or enum.
+///
+/// The hash covers the kind of the type (struct or enum), the names and order of the fields and
+/// variants, the `#[codec(...)]` attributes and explicit discriminants, and the schema hash of
+/// the type of each field. Fields marked with `#[codec(skip)]` aren't encoded, and are therefore
+/// ignored. The name of the type itself isn't part of the hash.
+///
+/// The type of each field must implement `InterfaceSchema` as well.
+#[proc_macro_derive(InterfaceSchema, attributes(codec))]
+pub fn derive_interface_schema(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    derive(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+fn derive(mut input: syn::DeriveInput) -> Result<TokenStream, syn::Error> {
+    let hasher_updates = match &input.data {
+        syn::Data::Struct(data) => {
+            let fields = fields_updates(&data.fields)?;
+            quote! { .update_str("struct") #fields }
+        }
+        syn::Data::Enum(data) => {
+            let num_variants = data.variants.len() as u64;
+            let mut variants = TokenStream::new();
+            for variant in &data.variants {
+                let name = variant.ident.to_string();
+                let attributes = codec_attributes(&variant.attrs)?;
+                let discriminant = variant
+                    .discriminant
+                    .as_ref()
+                    .map(|(_, expr)| quote!(#expr).to_string())
+                    .unwrap_or_default();
+                let fields = fields_updates(&variant.fields)?;
+                variants.extend(quote! {
+                    .update_str(#name)
+                    .update_str(#attributes)
+                    .update_str(#discriminant)
+                    #fields
+                });
+            }
+            quote! {
+                .update_str("enum")
+                .update(&#num_variants.to_le_bytes())
+                #variants
+            }
+        }
+        syn::Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span(),
+                "InterfaceSchema can't be derived on unions",
+            ))
+        }
+    };
+
+    // Every type parameter must itself implement `InterfaceSchema`.
+    let type_params = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    let where_clause = input.generics.make_where_clause();
+    for param in type_params {
+        where_clause
+            .predicates
+            .push(syn::parse_quote!(#param: ::redshirt_syscalls::InterfaceSchema));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::redshirt_syscalls::InterfaceSchema for #name #ty_generics #where_clause {
+            const SCHEMA_HASH: [u8; 32] = ::redshirt_syscalls::schema::SchemaHasher::new()
+                #hasher_updates
+                .finish();
+        }
+    })
+}
+
+/// Generates the calls to the methods of `SchemaHasher` corresponding to the given fields.
+fn fields_updates(fields: &syn::Fields) -> Result<TokenStream, syn::Error> {
+    let kind = match fields {
+        syn::Fields::Named(_) => "named",
+        syn::Fields::Unnamed(_) => "unnamed",
+        syn::Fields::Unit => "unit",
+    };
+
+    let mut num_fields = 0u64;
+    let mut out = TokenStream::new();
+    for (index, field) in fields.iter().enumerate() {
+        let attributes = codec_attributes(&field.attrs)?;
+        if attributes.split(',').any(|attr| attr == "skip") {
+            continue;
+        }
+
+        num_fields += 1;
+        let name = field
+            .ident
+            .as_ref()
+            .map(|ident| ident.to_string())
+            .unwrap_or_else(|| index.to_string());
+        let ty = &field.ty;
+        out.extend(quote_spanned! {ty.span()=>
+            .update_str(#name)
+            .update_str(#attributes)
+            .update(&<#ty as ::redshirt_syscalls::InterfaceSchema>::SCHEMA_HASH)
+        });
+    }
+
+    Ok(quote! {
+        .update_str(#kind)
+        .update(&#num_fields.to_le_bytes())
+        #out
+    })
+}
+
+/// Turns the `#[codec(...)]` attributes of an item into a canonical string.
+///
+/// For example `#[codec(index = "3")]` becomes `index=3`. Attributes are separated with commas.
+fn codec_attributes(attrs: &[syn::Attribute]) -> Result<String, syn::Error> {
+    let mut out = Vec::new();
+
+    for attr in attrs {
+        if !attr.path.is_ident("codec") {
+            continue;
+        }
+
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            other => return Err(syn::Error::new(other.span(), "expected `#[codec(...)]`")),
+        };
+
+        for nested in list.nested {
+            let meta = match nested {
+                syn::NestedMeta::Meta(meta) => meta,
+                syn::NestedMeta::Lit(lit) => {
+                    return Err(syn::Error::new(lit.span(), "unexpected literal"))
+                }
+            };
+
+            match meta {
+                syn::Meta::Path(path) => out.push(path_to_string(&path)),
+                syn::Meta::NameValue(name_value) => {
+                    let value = match &name_value.lit {
+                        syn::Lit::Str(lit) => lit.value(),
+                        syn::Lit::Int(lit) => lit.base10_digits().to_owned(),
+                        other => return Err(syn::Error::new(other.span(), "unsupported value")),
+                    };
+                    out.push(format!("{}={}", path_to_string(&name_value.path), value));
+                }
+                syn::Meta::List(list) => {
+                    return Err(syn::Error::new(list.span(), "unsupported codec attribute"))
+                }
+            }
+        }
+    }
+
+    Ok(out.join(","))
+}
+
+fn path_to_string(path: &syn::Path) -> String {
+    path.segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
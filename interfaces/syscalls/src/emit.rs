@@ -171,8 +171,12 @@ where
             message_id_out.as_mut_ptr(),
         );
 
-        if ret != 0 {
-            return Err(EmitErr::BadInterface);
+        match ret {
+            0 => {}
+            2 => return Err(EmitErr::TooManyPendingMessages),
+            3 => return Err(EmitErr::NotificationsQueueFull),
+            4 => return Err(EmitErr::Denied),
+            _ => return Err(EmitErr::BadInterface),
         }
 
         if needs_answer {
@@ -275,12 +279,24 @@ pub fn cancel_message(message_id: MessageId) {
 pub enum EmitErr {
     /// The given interface has no handler.
     BadInterface,
+    /// Too many messages emitted by this process are waiting for an answer.
+    TooManyPendingMessages,
+    /// The queue of notifications of this process is full, and the emission didn't allow
+    /// delays.
+    NotificationsQueueFull,
+    /// This process isn't allowed to emit messages on the given interface.
+    Denied,
 }
 
 impl fmt::Display for EmitErr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             EmitErr::BadInterface => write!(f, "The given interface has no handler"),
+            EmitErr::TooManyPendingMessages => {
+                write!(f, "Too many messages are waiting for an answer")
+            }
+            EmitErr::NotificationsQueueFull => write!(f, "The notifications queue is full"),
+            EmitErr::Denied => write!(f, "Not allowed to emit on the given interface"),
         }
     }
 }
@@ -81,7 +81,14 @@ extern "C" {
     /// order to lazily-load a handler for that interface if necessary. If this flag is not set,
     /// and no interface handler is available, then the function fails immediately.
     ///
-    /// Returns `0` on success, and `1` in case of error.
+    /// Returns `0` on success, or an error code:
+    ///
+    /// - `1` if the interface has no handler and `allow_delay` isn't set.
+    /// - `2` if `needs_answer` is set and the process has too many messages waiting for an
+    /// answer.
+    /// - `3` if `needs_answer` is set, the queue of notifications of the process is full, and
+    /// `allow_delay` isn't set. If `allow_delay` is set, the function blocks instead.
+    /// - `4` if the process isn't allowed to emit messages on this interface.
     ///
     /// On success, if `needs_answer` is true, will write the ID of new event into the memory
     /// pointed by `message_id_out`.
@@ -90,7 +97,6 @@ extern "C" {
     /// `interface_hash`, `msg_bufs_ptrs`, `message_id_out`, and all the sub-buffers referred to
     /// within `msg_bufs_ptrs`. In particular, it is invalid to modify these buffers while the
     /// function is running.
-    // TODO: document error that can happen
     pub(crate) fn emit_message(
         interface_hash: *const u8,
         msg_bufs_ptrs: *const u32,
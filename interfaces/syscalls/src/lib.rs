@@ -81,7 +81,10 @@ pub use emit::{
     cancel_message, emit_message_with_response, emit_message_without_response, MessageBuilder,
 };
 pub use ffi::DecodedNotificationRef;
-pub use response::{message_response, message_response_sync_raw, MessageResponseFuture};
+pub use response::{
+    message_response, message_response_or_error, message_response_sync_raw, MessageResponseFuture,
+};
+pub use schema::InterfaceSchema;
 pub use traits::{Decode, Encode, EncodedMessage, EncodedMessageRef};
 
 use core::{cmp::PartialEq, convert::TryFrom, fmt, num::NonZeroU64};
@@ -92,6 +95,7 @@ mod response;
 mod traits;
 
 pub mod ffi;
+pub mod schema;
 
 /// Identifier of a running process within a core.
 // TODO: move to a Pid module?
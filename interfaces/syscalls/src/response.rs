@@ -16,7 +16,6 @@
 use crate::{ffi, Decode, EncodedMessage, MessageId};
 
 use core::{
-    marker::PhantomData,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -38,12 +37,34 @@ pub fn message_response_sync_raw(msg_id: MessageId) -> EncodedMessage {
 /// Returns a future that is ready when a response to the given message comes back.
 ///
 /// The return value is the type the message decodes to.
+///
+/// # Panic
+///
+/// The future panics if the message is answered with an error. Use
+/// [`message_response_or_error`] if that is a possibility.
 pub fn message_response<T: Decode>(msg_id: MessageId) -> MessageResponseFuture<T> {
     MessageResponseFuture {
         finished: false,
         msg_id,
         registration: None,
-        marker: PhantomData,
+        // TODO: don't unwrap here?
+        decode: |response| Decode::decode(response.unwrap()).unwrap(),
+    }
+}
+
+/// Returns a future that is ready when a response to the given message comes back.
+///
+/// Contrary to [`message_response`], the future yields `Err` if the message has been answered
+/// with an error, for example because its handler has crashed.
+pub fn message_response_or_error<T: Decode>(
+    msg_id: MessageId,
+) -> MessageResponseFuture<Result<T, ()>> {
+    MessageResponseFuture {
+        finished: false,
+        msg_id,
+        registration: None,
+        // TODO: don't unwrap here?
+        decode: |response| response.map(|response| Decode::decode(response).unwrap()),
     }
 }
 
@@ -60,13 +81,11 @@ pub struct MessageResponseFuture<T> {
     msg_id: MessageId,
     finished: bool,
     registration: Option<crate::block_on::WakerRegistration>,
-    marker: PhantomData<T>,
+    /// Turns the response, or the error answer, into the output of the future.
+    decode: fn(Result<EncodedMessage, ()>) -> T,
 }
 
-impl<T> Future for MessageResponseFuture<T>
-where
-    T: Decode,
-{
+impl<T> Future for MessageResponseFuture<T> {
     type Output = T;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
@@ -75,8 +94,8 @@ where
         if let Some(response) = crate::block_on::peek_response(self.msg_id) {
             self.finished = true;
             let decoded = ffi::decode_notification(&response).unwrap();
-            return Poll::Ready(Decode::decode(decoded.actual_data.unwrap().into()).unwrap());
-            // TODO: don't unwrap here?
+            let response = decoded.actual_data.map(EncodedMessage::from);
+            return Poll::Ready((self.decode)(response));
         }
 
         if let Some(r) = &mut self.registration {
@@ -93,8 +112,8 @@ where
             debug_assert_eq!(decoded.message_id, self.msg_id);
 
             self.finished = true;
-            return Poll::Ready(Decode::decode(decoded.actual_data.unwrap().into()).unwrap());
-            // TODO: don't unwrap here?
+            let response = decoded.actual_data.map(EncodedMessage::from);
+            return Poll::Ready((self.decode)(response));
         }
 
         self.registration = Some(crate::block_on::register_message_waker(
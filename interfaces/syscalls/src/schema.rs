@@ -0,0 +1,347 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Deriving interface hashes from the types of the messages.
+//!
+//! An interface is identified by an [`InterfaceHash`]. Rather than choosing this hash at random,
+//! it can be computed from the name of the interface and a description of the types of its
+//! messages and answers. This way, modifying these types in an incompatible way automatically
+//! leads to a different interface, and the old and new versions of an interface can coexist.
+//!
+//! Each type that is part of an interface implements the [`InterfaceSchema`] trait, whose
+//! [`InterfaceSchema::SCHEMA_HASH`] is a hash of the layout of the type. For structs and enums,
+//! this trait is implemented using `#[derive(InterfaceSchema)]`. The [`interface_hash!`] macro
+//! then computes the hash of the interface from its name and the list of the types of its
+//! messages and answers.
+//!
+//! ```
+//! use parity_scale_codec::{Decode, Encode};
+//! use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
+//!
+//! #[derive(Encode, Decode, InterfaceSchema)]
+//! pub enum Message {
+//!     Ping(u32),
+//! }
+//!
+//! #[derive(Encode, Decode, InterfaceSchema)]
+//! pub struct Pong {
+//!     pub value: u32,
+//! }
+//!
+//! pub const INTERFACE: InterfaceHash = redshirt_syscalls::interface_hash!("ping", Message, Pong);
+//! ```
+//!
+//! The hash of a struct or enum covers the names of its fields and variants, their order, the
+//! `#[codec(...)]` attributes, and the hash of the type of each field. It doesn't cover the name
+//! of the type itself, meaning that renaming a type doesn't change the interface.
+//!
+//! Interfaces whose messages aren't SCALE-encoded can only pass the types they use, if any. The
+//! name of these interfaces must be modified whenever the format of their messages changes.
+//!
+//! > **Note**: Recursive types, such as an enum containing a `Vec` of itself, aren't supported.
+
+use crate::{InterfaceHash, MessageId, Pid, ThreadId};
+
+use alloc::{string::String, vec::Vec};
+use core::num::{NonZeroU128, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8};
+
+pub use redshirt_syscalls_proc_macros::InterfaceSchema;
+
+/// Type that can be part of the messages or answers of an interface.
+pub trait InterfaceSchema {
+    /// Hash of the layout of the type.
+    const SCHEMA_HASH: [u8; 32];
+}
+
+/// Computes the [`InterfaceHash`] of an interface from its name and the list of the types of its
+/// messages and answers.
+///
+/// The name distinguishes interfaces whose messages happen to have the same types. The order of
+/// the types matters.
+#[macro_export]
+macro_rules! interface_hash {
+    ($name:expr $(, $ty:ty)* $(,)?) => {
+        $crate::InterfaceHash::from_schema_hashes($name, &[
+            $(<$ty as $crate::InterfaceSchema>::SCHEMA_HASH),*
+        ])
+    };
+}
+
+impl InterfaceHash {
+    /// Builds the [`InterfaceHash`] of an interface from its name and the schema hashes of the
+    /// types of its messages and answers.
+    ///
+    /// Prefer using the [`interface_hash!`] macro.
+    pub const fn from_schema_hashes(name: &str, hashes: &[[u8; 32]]) -> Self {
+        let mut hasher = SchemaHasher::new().update_str("interface").update_str(name);
+        let mut n = 0;
+        while n < hashes.len() {
+            hasher = hasher.update(&hashes[n]);
+            n += 1;
+        }
+        InterfaceHash::from_raw_hash(hasher.finish())
+    }
+}
+
+/// SHA-256 hasher usable in constant contexts.
+///
+/// Used by `#[derive(InterfaceSchema)]`, and by the implementations of [`InterfaceSchema`] of
+/// this module.
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct SchemaHasher {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl SchemaHasher {
+    pub const fn new() -> Self {
+        SchemaHasher {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            block: [0; 64],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    /// Appends the given data to the hashed data.
+    pub const fn update(mut self, data: &[u8]) -> Self {
+        let mut n = 0;
+        while n < data.len() {
+            self.block[self.block_len] = data[n];
+            self.block_len += 1;
+            if self.block_len == 64 {
+                self.state = compress(self.state, &self.block);
+                self.block_len = 0;
+            }
+            n += 1;
+        }
+        self.total_len += data.len() as u64;
+        self
+    }
+
+    /// Appends the given string to the hashed data, prefixed with its length.
+    pub const fn update_str(self, data: &str) -> Self {
+        self.update(&(data.len() as u64).to_le_bytes())
+            .update(data.as_bytes())
+    }
+
+    /// Returns the hash of the data.
+    pub const fn finish(self) -> [u8; 32] {
+        let bits_len = self.total_len.wrapping_mul(8);
+        let mut hasher = self.update(&[0x80]);
+        while hasher.block_len != 56 {
+            hasher = hasher.update(&[0]);
+        }
+        hasher = hasher.update(&bits_len.to_be_bytes());
+        debug_assert!(hasher.block_len == 0);
+
+        let mut out = [0; 32];
+        let mut n = 0;
+        while n < 32 {
+            out[n] = hasher.state[n / 4].to_be_bytes()[n % 4];
+            n += 1;
+        }
+        out
+    }
+}
+
+impl Default for SchemaHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Round constants of SHA-256.
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Applies the SHA-256 compression function on the given block.
+const fn compress(state: [u32; 8], block: &[u8; 64]) -> [u32; 8] {
+    let mut w = [0u32; 64];
+    let mut n = 0;
+    while n < 16 {
+        w[n] = u32::from_be_bytes([
+            block[n * 4],
+            block[n * 4 + 1],
+            block[n * 4 + 2],
+            block[n * 4 + 3],
+        ]);
+        n += 1;
+    }
+    while n < 64 {
+        let s0 = w[n - 15].rotate_right(7) ^ w[n - 15].rotate_right(18) ^ (w[n - 15] >> 3);
+        let s1 = w[n - 2].rotate_right(17) ^ w[n - 2].rotate_right(19) ^ (w[n - 2] >> 10);
+        w[n] = w[n - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[n - 7])
+            .wrapping_add(s1);
+        n += 1;
+    }
+
+    let mut v = state;
+    let mut n = 0;
+    while n < 64 {
+        let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+        let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+        let temp1 = v[7]
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[n])
+            .wrapping_add(w[n]);
+        let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+        let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+        let temp2 = s0.wrapping_add(maj);
+
+        v[7] = v[6];
+        v[6] = v[5];
+        v[5] = v[4];
+        v[4] = v[3].wrapping_add(temp1);
+        v[3] = v[2];
+        v[2] = v[1];
+        v[1] = v[0];
+        v[0] = temp1.wrapping_add(temp2);
+        n += 1;
+    }
+
+    let mut out = state;
+    let mut n = 0;
+    while n < 8 {
+        out[n] = out[n].wrapping_add(v[n]);
+        n += 1;
+    }
+    out
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty),*) => {
+        $(
+            impl InterfaceSchema for $ty {
+                const SCHEMA_HASH: [u8; 32] =
+                    SchemaHasher::new().update_str(stringify!($ty)).finish();
+            }
+        )*
+    };
+}
+
+impl_primitive!(bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, ());
+
+// `String` is encoded the same way as a `Vec<u8>`, but carries a different meaning.
+impl_primitive!(String);
+
+// The `NonZero` types are encoded the same way as their non-zero counterpart, but fail to
+// decode if the value is zero.
+impl_primitive!(NonZeroU8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU128);
+
+impl_primitive!(InterfaceHash, MessageId, Pid, ThreadId);
+
+impl<T: InterfaceSchema> InterfaceSchema for Vec<T> {
+    const SCHEMA_HASH: [u8; 32] = SchemaHasher::new()
+        .update_str("Vec")
+        .update(&T::SCHEMA_HASH)
+        .finish();
+}
+
+impl<T: InterfaceSchema> InterfaceSchema for Option<T> {
+    const SCHEMA_HASH: [u8; 32] = SchemaHasher::new()
+        .update_str("Option")
+        .update(&T::SCHEMA_HASH)
+        .finish();
+}
+
+impl<T: InterfaceSchema, E: InterfaceSchema> InterfaceSchema for Result<T, E> {
+    const SCHEMA_HASH: [u8; 32] = SchemaHasher::new()
+        .update_str("Result")
+        .update(&T::SCHEMA_HASH)
+        .update(&E::SCHEMA_HASH)
+        .finish();
+}
+
+impl<T: InterfaceSchema, const N: usize> InterfaceSchema for [T; N] {
+    const SCHEMA_HASH: [u8; 32] = SchemaHasher::new()
+        .update_str("array")
+        .update(&(N as u64).to_le_bytes())
+        .update(&T::SCHEMA_HASH)
+        .finish();
+}
+
+macro_rules! impl_tuple {
+    ($($ty:ident),+) => {
+        impl<$($ty: InterfaceSchema),+> InterfaceSchema for ($($ty,)+) {
+            const SCHEMA_HASH: [u8; 32] = SchemaHasher::new()
+                .update_str("tuple")
+                $(.update(&$ty::SCHEMA_HASH))+
+                .finish();
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+
+#[cfg(test)]
+mod tests {
+    use super::SchemaHasher;
+
+    #[test]
+    fn sha256_known_values() {
+        // Test vectors from FIPS 180-2.
+        assert_eq!(
+            SchemaHasher::new().update(b"abc").finish(),
+            [
+                0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+                0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+                0xf2, 0x00, 0x15, 0xad
+            ]
+        );
+
+        assert_eq!(
+            SchemaHasher::new()
+                .update(b"abcdbcdecdefdefgefghfghighijhijk")
+                .update(b"ijkljklmklmnlmnomnopnopq")
+                .finish(),
+            [
+                0x24, 0x8d, 0x6a, 0x61, 0xd2, 0x06, 0x38, 0xb8, 0xe5, 0xc0, 0x26, 0x93, 0x0c, 0x3e,
+                0x60, 0x39, 0xa3, 0x3c, 0xe4, 0x59, 0x64, 0xff, 0x21, 0x67, 0xf6, 0xec, 0xed, 0xd4,
+                0x19, 0xdb, 0x06, 0xc1
+            ]
+        );
+    }
+
+    #[test]
+    fn schema_hash_changes() {
+        use crate::InterfaceSchema;
+        use alloc::vec::Vec;
+
+        assert_ne!(<Vec<u8>>::SCHEMA_HASH, <Vec<u16>>::SCHEMA_HASH);
+        assert_ne!(<[u8; 4]>::SCHEMA_HASH, <[u8; 5]>::SCHEMA_HASH);
+        assert_ne!(<Result<u8, ()>>::SCHEMA_HASH, <Result<(), u8>>::SCHEMA_HASH);
+    }
+}
@@ -13,16 +13,20 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use alloc::vec::Vec;
 use parity_scale_codec::{Decode, Encode};
-use redshirt_syscalls::InterfaceHash;
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0x10, 0x19, 0x16, 0x2a, 0x2b, 0x0c, 0x41, 0x36, 0x4a, 0x20, 0x01, 0x51, 0x47, 0x38, 0x27, 0x08,
-    0x4a, 0x3c, 0x1e, 0x07, 0x18, 0x1c, 0x27, 0x11, 0x55, 0x15, 0x1d, 0x5f, 0x22, 0x5b, 0x16, 0x20,
-]);
+pub const INTERFACE: InterfaceHash = redshirt_syscalls::interface_hash!(
+    "tcp",
+    TcpMessage,
+    TcpOpenResponse,
+    TcpCloseResponse,
+    TcpReadResponse,
+    TcpWriteResponse
+);
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum TcpMessage {
     Open(TcpOpen),
     /// Ask to close the socket. Replied with a [`TcpCloseResponse`].
@@ -37,7 +41,7 @@ pub enum TcpMessage {
     Destroy(u32),
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct TcpOpen {
     /// If true, then `ip` and `port` designate a local IP and port that the socket must listen
     /// on. A response will arrive when a remote connects to this IP and port.
@@ -51,13 +55,13 @@ pub struct TcpOpen {
     pub port: u16,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct TcpOpenResponse {
     // TODO: proper error type
     pub result: Result<TcpSocketOpen, ()>,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct TcpSocketOpen {
     pub socket_id: u32,
     pub local_ip: [u16; 8],
@@ -66,17 +70,17 @@ pub struct TcpSocketOpen {
     pub remote_port: u16,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct TcpClose {
     pub socket_id: u32,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct TcpCloseResponse {
     pub result: Result<(), TcpCloseError>,
 }
 
-#[derive(Debug, Encode, Decode, derive_more::Display)]
+#[derive(Debug, Encode, Decode, InterfaceSchema, derive_more::Display)]
 pub enum TcpCloseError {
     /// We have already sent a FIN to the remote. It is invalid to send another one.
     /// This happens if the connection is in the "Fin wait", "Fin wait 2", or "Last ACK" states.
@@ -87,12 +91,12 @@ pub enum TcpCloseError {
     InvalidSocket,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct TcpRead {
     pub socket_id: u32,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct TcpReadResponse {
     /// If the connection is in the "Closed" wait or "Last ACK" state, it is known that no more
     /// data will be received and an empty `Vec` is returned. If the connection is in the
@@ -100,7 +104,7 @@ pub struct TcpReadResponse {
     pub result: Result<Vec<u8>, TcpReadError>,
 }
 
-#[derive(Debug, Encode, Decode, derive_more::Display)]
+#[derive(Debug, Encode, Decode, InterfaceSchema, derive_more::Display)]
 pub enum TcpReadError {
     /// Connection is in the "Finished" state.
     ConnectionFinished,
@@ -108,18 +112,18 @@ pub enum TcpReadError {
     InvalidSocket,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct TcpWrite {
     pub socket_id: u32,
     pub data: Vec<u8>,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub struct TcpWriteResponse {
     pub result: Result<(), TcpWriteError>,
 }
 
-#[derive(Debug, Encode, Decode, derive_more::Display)]
+#[derive(Debug, Encode, Decode, InterfaceSchema, derive_more::Display)]
 pub enum TcpWriteError {
     /// We have sent a FIN to the remote, and thus are not allowed to send any more data.
     /// This happens if the connection is in the "Fin wait", "Fin wait 2", or "Last ACK" states.
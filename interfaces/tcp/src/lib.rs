@@ -79,8 +79,15 @@
 //! state and is now considered connected to that the remote.
 //!
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 use futures::{lock::Mutex, prelude::*, ready};
+#[cfg(feature = "std")]
 use redshirt_syscalls::{Encode as _, MessageResponseFuture};
+#[cfg(feature = "std")]
 use std::{
     cmp, io, mem,
     net::{IpAddr, Ipv6Addr, SocketAddr},
@@ -93,6 +100,7 @@ pub mod ffi;
 /// Active TCP connection to a remote.
 ///
 /// This type is similar to [`std::net::TcpStream`].
+#[cfg(feature = "std")]
 pub struct TcpStream {
     handle: u32,
     /// Buffer of data that has been read from the socket but not transmitted to the user yet.
@@ -110,6 +118,7 @@ pub struct TcpStream {
 /// Active TCP listening socket.
 ///
 /// This type is similar to [`std::net::TcpListener`].
+#[cfg(feature = "std")]
 pub struct TcpListener {
     local_addr: SocketAddr,
     next_incoming: Mutex<
@@ -119,6 +128,7 @@ pub struct TcpListener {
     >,
 }
 
+#[cfg(feature = "std")]
 impl TcpStream {
     /// Start connecting to the given address. Returns a `TcpStream` if the connection is
     /// successful. The returned `TcpStream` is in the "Established" state (but might quickly
@@ -179,6 +189,7 @@ impl TcpStream {
     }
 }
 
+#[cfg(feature = "std")]
 impl AsyncRead for TcpStream {
     fn poll_read(
         mut self: Pin<&mut Self>,
@@ -236,6 +247,7 @@ impl AsyncRead for TcpStream {
     // TODO: unsafe fn initializer(&self) -> Initializer { ... }
 }
 
+#[cfg(feature = "std")]
 impl AsyncWrite for TcpStream {
     fn poll_write(
         mut self: Pin<&mut Self>,
@@ -340,6 +352,7 @@ impl AsyncWrite for TcpStream {
     }
 }
 
+#[cfg(feature = "std")]
 impl tokio::io::AsyncRead for TcpStream {
     fn poll_read(
         self: Pin<&mut Self>,
@@ -354,6 +367,7 @@ impl tokio::io::AsyncRead for TcpStream {
     }
 }
 
+#[cfg(feature = "std")]
 impl tokio::io::AsyncWrite for TcpStream {
     fn poll_write(
         self: Pin<&mut Self>,
@@ -372,6 +386,7 @@ impl tokio::io::AsyncWrite for TcpStream {
     }
 }
 
+#[cfg(feature = "std")]
 impl Drop for TcpStream {
     fn drop(&mut self) {
         unsafe {
@@ -381,6 +396,7 @@ impl Drop for TcpStream {
     }
 }
 
+#[cfg(feature = "std")]
 impl TcpListener {
     /// Create a new [`TcpListener`] listening on the given address and port.
     pub fn bind(socket_addr: &SocketAddr) -> impl Future<Output = Result<TcpListener, ()>> {
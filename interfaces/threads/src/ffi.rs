@@ -0,0 +1,51 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use parity_scale_codec::{Decode, Encode};
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema, ThreadId};
+
+pub const INTERFACE: InterfaceHash = redshirt_syscalls::interface_hash!(
+    "threads",
+    ThreadsMessage,
+    Result<ThreadId, ()>,
+    Result<(), ()>,
+);
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub enum ThreadsMessage {
+    /// Start a new thread in the process that emits the message. Must respond with a
+    /// `Result<ThreadId, ()>`.
+    New(ThreadNew),
+    /// Send response when the given thread, which must belong to the process that emits the
+    /// message, has finished. Responds with a `Result<(), ()>`. An error is returned if the
+    /// thread doesn't exist or has already been joined.
+    Join(ThreadId),
+}
+
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
+pub struct ThreadNew {
+    /// Index within the indirect function table of the function to execute. This function must
+    /// have the signature `(i32) -> ()`.
+    pub fn_ptr: u32,
+    /// Value passed as parameter to the function.
+    pub user_data: u32,
+    /// Initial value of the stack pointer of the new thread. Must point to the end of a memory
+    /// area reserved for the stack of this thread.
+    ///
+    /// The kernel considers the first global defined by the module to be the stack pointer if it
+    /// is a mutable `i32`, like LLVM generates. This value is ignored if the module doesn't have
+    /// such a global.
+    pub stack_pointer: u32,
+}
@@ -0,0 +1,180 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Multithreading.
+//!
+//! This interface is handled by the kernel. It makes it possible for a process to start
+//! secondary threads that share its memory, and to wait for these threads to finish.
+//!
+//! Each thread has its own stack, allocated by [`spawn`] and freed when the thread is joined.
+//! The process ends as soon as its main thread finishes, no matter whether secondary threads
+//! are still running.
+//!
+//! > **Important**: WebAssembly compilers assume that only one thread exists. In particular,
+//! >                memory allocators and other global variables aren't protected against
+//! >                concurrent accesses. Since threads can be paused at any point, closures
+//! >                executed by secondary threads must be careful to not access such state while
+//! >                another thread might be doing the same. This is why [`spawn`] is `unsafe`.
+//!
+//! # Stack pointer
+//!
+//! WebAssembly doesn't have any notion of stack pointer. Compilers such as LLVM instead store
+//! the current position of the stack in a global variable, which all the threads share. The
+//! kernel saves and restores the value of this global whenever it switches between threads.
+//!
+//! The kernel assumes, like LLVM does, that the stack pointer is the first global defined by the
+//! module, provided that this global is a mutable `i32`. If the module doesn't have such a
+//! global, the stack pointer passed when starting a thread is ignored, and all the threads use
+//! the same stack.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{boxed::Box, vec};
+use core::mem;
+use futures::prelude::*;
+use redshirt_syscalls::ThreadId;
+
+pub mod ffi;
+
+/// Size, in bytes, of the stack allocated for each new thread.
+pub const STACK_SIZE: usize = 64 * 1024;
+
+/// Starts a new thread that executes the given closure.
+///
+/// Blocks until the kernel has started the thread. Returns an error if the kernel has refused to
+/// start it.
+///
+/// The memory used to start the thread is allocated by the calling thread, and freed by the
+/// thread that calls [`JoinHandle::join`]. The new thread itself doesn't allocate or free any
+/// memory, unless `function` does.
+///
+/// # Safety
+///
+/// The memory allocator and the other global variables of the program aren't protected against
+/// concurrent accesses. `function`, including the destructors of the variables it captures,
+/// must not access them while another thread might be doing the same. In particular, it must not
+/// allocate or free memory if other threads can do so at the same time.
+///
+/// The module must have a stack pointer that the kernel can identify. See the
+/// [module-level documentation](self#stack-pointer).
+pub unsafe fn spawn<F>(function: F) -> Result<JoinHandle, ()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let stack = vec![0u8; STACK_SIZE].into_boxed_slice();
+    // The stack grows downwards and must be aligned to 16 bytes.
+    let stack_pointer = (stack.as_ptr() as usize + stack.len()) & !15;
+
+    // The new thread moves the closure out of this slot, but the slot itself is only freed
+    // by the thread that joins the new thread.
+    let mut closure = Box::new(Some(function));
+    let closure_ptr: *mut Option<F> = &mut *closure;
+
+    let msg = ffi::ThreadsMessage::New(ffi::ThreadNew {
+        // In WebAssembly, function pointers are indices within the indirect function table.
+        fn_ptr: thread_entry_point::<F> as extern "C" fn(u32) as usize as u32,
+        user_data: closure_ptr as usize as u32,
+        stack_pointer: stack_pointer as u32,
+    });
+
+    let result: Result<ThreadId, ()> = redshirt_syscalls::block_on(
+        redshirt_syscalls::emit_message_with_response(&ffi::INTERFACE, msg).unwrap(),
+    );
+
+    // If the thread hasn't started, the closure is simply destroyed here.
+    let thread_id = result?;
+    Ok(JoinHandle {
+        thread_id,
+        stack,
+        closure,
+    })
+}
+
+/// Function executed by the kernel when a thread starts.
+///
+/// `user_data` is a pointer to the slot containing the closure, as created by [`spawn`].
+extern "C" fn thread_entry_point<F: FnOnce()>(user_data: u32) {
+    // Moving the closure out of its slot doesn't free any memory.
+    let closure = unsafe { &mut *(user_data as usize as *mut Option<F>) };
+    let closure = closure.take().unwrap();
+    closure();
+}
+
+/// Memory containing the closure passed to [`spawn`]. Its content is irrelevant once the thread
+/// has started, and it only needs to be freed.
+trait ClosureSlot: Send {}
+impl<T: Send> ClosureSlot for T {}
+
+/// Owned permission to wait for a thread to finish.
+///
+/// Dropping this object without calling [`JoinHandle::join`] leaks the stack of the thread and
+/// the memory used to start it, as it isn't possible to know when the thread stops using them.
+#[must_use]
+pub struct JoinHandle {
+    /// Identifier of the thread, as assigned by the kernel.
+    thread_id: ThreadId,
+    /// Memory used by the thread for its stack. Must not be freed before the thread has
+    /// finished.
+    stack: Box<[u8]>,
+    /// Slot that contained the closure passed to [`spawn`]. Must not be freed before the thread
+    /// has finished, as the thread might not have taken the closure out of it yet.
+    closure: Box<dyn ClosureSlot>,
+}
+
+impl JoinHandle {
+    /// Returns the identifier of the thread.
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    /// Returns a `Future` that is ready when the thread has finished.
+    ///
+    /// If the `Future` is dropped before it is ready, the stack of the thread is leaked.
+    pub fn join(mut self) -> impl Future<Output = ()> {
+        // The stack is only freed once the kernel has confirmed that the thread has finished.
+        let stack = Box::into_raw(mem::take(&mut self.stack));
+        let closure = Box::into_raw(mem::replace(
+            &mut self.closure,
+            Box::new(()) as Box<dyn ClosureSlot>,
+        ));
+
+        let msg = ffi::ThreadsMessage::Join(self.thread_id);
+        let response =
+            unsafe { redshirt_syscalls::emit_message_with_response(&ffi::INTERFACE, msg).unwrap() };
+
+        response.map(move |result: Result<(), ()>| {
+            // In case of error, it's not possible to know whether the thread is still using its
+            // stack, and we leak it.
+            if result.is_ok() {
+                unsafe {
+                    drop(Box::from_raw(stack));
+                    drop(Box::from_raw(closure));
+                }
+            }
+        })
+    }
+}
+
+impl Drop for JoinHandle {
+    fn drop(&mut self) {
+        // See the documentation of `JoinHandle`.
+        let stack = mem::take(&mut self.stack);
+        Box::leak(stack);
+        let closure = mem::replace(&mut self.closure, Box::new(()) as Box<dyn ClosureSlot>);
+        Box::leak(closure);
+    }
+}
@@ -14,15 +14,12 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use parity_scale_codec::{Decode, Encode};
-use redshirt_syscalls::InterfaceHash;
+use redshirt_syscalls::{InterfaceHash, InterfaceSchema};
 
-// TODO: this has been randomly generated; instead should be a hash or something
-pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
-    0x87, 0x9d, 0xe0, 0xda, 0x61, 0x13, 0x26, 0x1d, 0x1f, 0x3e, 0xfa, 0x79, 0x4c, 0x9e, 0xa4, 0x67,
-    0xf2, 0x81, 0xe8, 0x00, 0x39, 0x5e, 0xbe, 0x94, 0x1e, 0x49, 0xb8, 0xf8, 0xd4, 0x3b, 0x07, 0xce,
-]);
+pub const INTERFACE: InterfaceHash =
+    redshirt_syscalls::interface_hash!("video-output", VideoOutputMessage, NextImage);
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Encode, Decode, InterfaceSchema)]
 pub enum VideoOutputMessage {
     /// Notify of the existence of a new video output.
     // TODO: what if this id was already registered?
@@ -44,12 +41,12 @@ pub enum VideoOutputMessage {
     NextImage(u64),
 }
 
-#[derive(Debug, Encode, Decode, Clone)]
+#[derive(Debug, Encode, Decode, InterfaceSchema, Clone)]
 pub struct NextImage {
     pub changes: Vec<NextImageChange>,
 }
 
-#[derive(Debug, Encode, Decode, Clone)]
+#[derive(Debug, Encode, Decode, InterfaceSchema, Clone)]
 pub struct NextImageChange {
     pub screen_x_start: u32,
     // TODO: not necessary?
@@ -59,7 +56,7 @@ pub struct NextImageChange {
     pub pixels: Vec<Vec<u8>>,
 }
 
-#[derive(Debug, Encode, Decode, Clone)]
+#[derive(Debug, Encode, Decode, InterfaceSchema, Clone)]
 pub enum Format {
     R8G8B8X8,
 }
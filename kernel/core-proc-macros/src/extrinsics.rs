@@ -0,0 +1,373 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Implementation of the `extrinsics` attribute.
+//!
+//! See the documentation of the `extrinsics` function in the root of the crate.
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    spanned::Spanned as _,
+};
+
+/// Parameters passed to the `extrinsics` attribute.
+struct Params {
+    /// Name of the Wasm interface all the functions belong to.
+    wasm_interface: syn::LitStr,
+    /// Path to the `redshirt_core` crate.
+    krate: syn::Path,
+}
+
+impl Parse for Params {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut wasm_interface = None;
+        let mut krate = None;
+
+        for param in Punctuated::<syn::MetaNameValue, syn::Token![,]>::parse_terminated(input)? {
+            let value = match &param.lit {
+                syn::Lit::Str(value) => value,
+                lit => return Err(syn::Error::new(lit.span(), "Expected a string literal")),
+            };
+
+            if param.path.is_ident("wasm_interface") {
+                wasm_interface = Some(value.clone());
+            } else if param.path.is_ident("crate") {
+                krate = Some(value.parse()?);
+            } else {
+                return Err(syn::Error::new(param.path.span(), "Unknown parameter"));
+            }
+        }
+
+        Ok(Params {
+            wasm_interface: wasm_interface.ok_or_else(|| {
+                syn::Error::new(Span::call_site(), "Missing `wasm_interface` parameter")
+            })?,
+            krate: krate.unwrap_or_else(|| syn::parse_quote!(::redshirt_core)),
+        })
+    }
+}
+
+/// Parameters passed to an `extrinsic` attribute, in other words the return type of the
+/// function as seen by the Wasm code.
+struct ExtrinsicParams {
+    return_type: Option<WasmType>,
+}
+
+impl Parse for ExtrinsicParams {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.is_empty() {
+            return Ok(ExtrinsicParams { return_type: None });
+        }
+
+        let _: syn::Token![->] = input.parse()?;
+        let ty: syn::Type = input.parse()?;
+        Ok(ExtrinsicParams {
+            return_type: Some(WasmType::from_type(&ty)?),
+        })
+    }
+}
+
+/// Rust type of a parameter or return value, and the Wasm type it corresponds to.
+#[derive(Copy, Clone)]
+enum WasmType {
+    I32,
+    U32,
+    I64,
+    U64,
+}
+
+impl WasmType {
+    fn from_type(ty: &syn::Type) -> syn::Result<Self> {
+        if let syn::Type::Path(path) = ty {
+            if path.qself.is_none() {
+                if let Some(ident) = path.path.get_ident() {
+                    match ident.to_string().as_str() {
+                        "i32" => return Ok(WasmType::I32),
+                        "u32" => return Ok(WasmType::U32),
+                        "i64" => return Ok(WasmType::I64),
+                        "u64" => return Ok(WasmType::U64),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        Err(syn::Error::new(
+            ty.span(),
+            "Unsupported type; must be one of `i32`, `u32`, `i64` or `u64`",
+        ))
+    }
+
+    /// Returns the name of the variant of `ValueType` corresponding to this type.
+    fn value_type(&self) -> syn::Ident {
+        match self {
+            WasmType::I32 | WasmType::U32 => format_ident!("I32"),
+            WasmType::I64 | WasmType::U64 => format_ident!("I64"),
+        }
+    }
+
+    /// Returns an expression that extracts a value of this type from the given `WasmValue`.
+    ///
+    /// Unsigned values are obtained by reinterpreting the bits of the signed value.
+    fn decode(&self, value: &TokenStream) -> TokenStream {
+        match self {
+            WasmType::I32 => quote!(#value.into_i32().unwrap()),
+            WasmType::U32 => quote!(#value.into_i32().unwrap() as u32),
+            WasmType::I64 => quote!(#value.into_i64().unwrap()),
+            WasmType::U64 => quote!(#value.into_i64().unwrap() as u64),
+        }
+    }
+}
+
+/// Function annotated with `#[extrinsic]`.
+struct Extrinsic {
+    /// Name of the Rust function, which is also the name of the Wasm function.
+    name: syn::Ident,
+    /// Name of the variant of the generated enum.
+    variant: syn::Ident,
+    /// Type of the first parameter of the function.
+    state_ty: syn::Type,
+    /// Type of the second parameter of the function.
+    mem_access_ty: syn::Type,
+    /// Return type of the Rust function.
+    output: syn::ReturnType,
+    /// Types of the parameters passed by the Wasm code.
+    params: Vec<WasmType>,
+    /// Type of the value returned to the Wasm code.
+    return_type: Option<WasmType>,
+}
+
+impl Extrinsic {
+    fn from_fn(function: &syn::ItemFn, attr_params: ExtrinsicParams) -> syn::Result<Self> {
+        let mut inputs = function.sig.inputs.iter().map(|input| match input {
+            syn::FnArg::Typed(pat) => Ok(&*pat.ty),
+            syn::FnArg::Receiver(_) => Err(syn::Error::new(
+                input.span(),
+                "Extrinsics can't have a `self` parameter",
+            )),
+        });
+
+        let missing_param = || {
+            syn::Error::new(
+                function.sig.span(),
+                "Extrinsics must accept the state and the memory access as their first two \
+                parameters",
+            )
+        };
+        let state_ty = inputs.next().ok_or_else(missing_param)??.clone();
+        let mem_access_ty = inputs.next().ok_or_else(missing_param)??.clone();
+        let params = inputs
+            .map(|ty| WasmType::from_type(ty?))
+            .collect::<syn::Result<Vec<_>>>()?;
+
+        let variant = {
+            let name = function.sig.ident.to_string();
+            let camel_case = name
+                .split('_')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    chars
+                        .next()
+                        .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                        .unwrap_or_default()
+                })
+                .collect::<String>();
+            syn::Ident::new(&camel_case, function.sig.ident.span())
+        };
+
+        Ok(Extrinsic {
+            name: function.sig.ident.clone(),
+            variant,
+            state_ty,
+            mem_access_ty,
+            output: function.sig.output.clone(),
+            params,
+            return_type: attr_params.return_type,
+        })
+    }
+}
+
+pub(crate) fn extrinsics(params: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+    let Params {
+        wasm_interface,
+        krate,
+    } = syn::parse2(params)?;
+    let mut module: syn::ItemMod = syn::parse2(item)?;
+    let module_span = module.span();
+
+    let items = match &mut module.content {
+        Some((_, items)) => items,
+        None => {
+            return Err(syn::Error::new(
+                module_span,
+                "`extrinsics` must be applied on an inline module",
+            ))
+        }
+    };
+
+    // Find the functions annotated with `#[extrinsic]`, and remove said attribute.
+    let mut extrinsics = Vec::new();
+    for item in items.iter_mut() {
+        let function = match item {
+            syn::Item::Fn(f) => f,
+            _ => continue,
+        };
+
+        let attr_pos = match function
+            .attrs
+            .iter()
+            .position(|attr| attr.path.is_ident("extrinsic"))
+        {
+            Some(p) => p,
+            None => continue,
+        };
+
+        let attr = function.attrs.remove(attr_pos);
+        let attr_params = if attr.tokens.is_empty() {
+            ExtrinsicParams { return_type: None }
+        } else {
+            attr.parse_args()?
+        };
+
+        extrinsics.push(Extrinsic::from_fn(function, attr_params)?);
+    }
+
+    // All the functions must accept the same state and memory access, and return the same type,
+    // as they are all called from the same generated function.
+    let (state_ty, mem_access_ty, output) = match extrinsics.first() {
+        Some(e) => (&e.state_ty, &e.mem_access_ty, &e.output),
+        None => {
+            return Err(syn::Error::new(
+                module_span,
+                "No function annotated with `#[extrinsic]`",
+            ))
+        }
+    };
+    for extrinsic in &extrinsics[1..] {
+        if !same_tokens(state_ty, &extrinsic.state_ty) {
+            return Err(syn::Error::new(
+                extrinsic.state_ty.span(),
+                "All extrinsics must have the same state type",
+            ));
+        }
+        if !same_tokens(mem_access_ty, &extrinsic.mem_access_ty) {
+            return Err(syn::Error::new(
+                extrinsic.mem_access_ty.span(),
+                "All extrinsics must have the same memory access type",
+            ));
+        }
+        if !same_tokens(output, &extrinsic.output) {
+            return Err(syn::Error::new(
+                extrinsic.output.span(),
+                "All extrinsics must have the same return type",
+            ));
+        }
+    }
+
+    let variants = extrinsics.iter().map(|e| &e.variant).collect::<Vec<_>>();
+    let num_extrinsics = extrinsics.len();
+
+    let supported_extrinsics = extrinsics.iter().map(|extrinsic| {
+        let variant = &extrinsic.variant;
+        let function_name = extrinsic.name.to_string();
+        let params = extrinsic.params.iter().map(|ty| ty.value_type());
+        let return_type = match extrinsic.return_type {
+            Some(ty) => {
+                let ty = ty.value_type();
+                quote!(::core::option::Option::Some(#krate::ValueType::#ty))
+            }
+            None => quote!(::core::option::Option::None),
+        };
+
+        quote! {
+            #krate::extrinsics::SupportedExtrinsic {
+                id: wrap(Extrinsic::#variant),
+                wasm_interface: ::core::convert::From::from(#wasm_interface),
+                function_name: ::core::convert::From::from(#function_name),
+                signature: #krate::primitives::Signature::new(
+                    ::core::iter::empty()
+                        #(.chain(::core::iter::once(#krate::ValueType::#params)))*,
+                    #return_type,
+                ),
+            }
+        }
+    });
+
+    let calls = extrinsics.iter().map(|extrinsic| {
+        let variant = &extrinsic.variant;
+        let name = &extrinsic.name;
+        let param_names = (0..extrinsic.params.len())
+            .map(|n| format_ident!("param{}", n))
+            .collect::<Vec<_>>();
+        let decoded_params = extrinsic
+            .params
+            .iter()
+            .map(|ty| ty.decode(&quote!(params.next().unwrap())));
+
+        quote! {
+            Extrinsic::#variant => {
+                #(let #param_names = #decoded_params;)*
+                assert!(params.next().is_none());
+                #name(state, mem_access, #(#param_names),*)
+            }
+        }
+    });
+
+    let generated: Vec<syn::Item> = vec![
+        syn::parse_quote! {
+            /// Identifier of one of the extrinsics of this module.
+            #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+            pub(super) enum Extrinsic {
+                #(#variants,)*
+            }
+        },
+        syn::parse_quote! {
+            /// Returns the list of the extrinsics of this module. The identifiers are passed
+            /// through `wrap`.
+            pub(super) fn supported_extrinsics<T>(
+                mut wrap: impl FnMut(Extrinsic) -> T,
+            ) -> [#krate::extrinsics::SupportedExtrinsic<T>; #num_extrinsics] {
+                [#(#supported_extrinsics),*]
+            }
+        },
+        syn::parse_quote! {
+            /// Decodes the parameters of the given extrinsic and calls the corresponding function.
+            ///
+            /// The parameters must match the signature of the extrinsic, as found in the list
+            /// returned by `supported_extrinsics`.
+            pub(super) fn call(
+                id: Extrinsic,
+                state: #state_ty,
+                mut params: impl ::core::iter::ExactSizeIterator<Item = #krate::WasmValue>,
+                mem_access: #mem_access_ty,
+            ) #output {
+                match id {
+                    #(#calls)*
+                }
+            }
+        },
+    ];
+
+    items.extend(generated);
+    Ok(quote!(#module))
+}
+
+/// Returns true if `a` and `b` consist of the same tokens.
+fn same_tokens(a: &impl ToTokens, b: &impl ToTokens) -> bool {
+    a.to_token_stream().to_string() == b.to_token_stream().to_string()
+}
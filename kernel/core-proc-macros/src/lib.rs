@@ -17,6 +17,42 @@
 
 use std::{env, fs, path::Path, process::Command};
 
+mod extrinsics;
+
+/// Generates the list of extrinsics, the decoding of their parameters, and their dispatch, from
+/// the functions of a module.
+///
+/// Must be applied on an inline module, and passed the name of the Wasm interface the functions
+/// belong to with `wasm_interface = "..."`. The path to the `redshirt_core` crate can optionally
+/// be passed with `crate = "..."`.
+///
+/// Each function of the module annotated with `#[extrinsic]` is an extrinsic whose name is the
+/// name of the Rust function. Its first two parameters are the state and the memory access, and
+/// are passed through as is. The remaining parameters are the parameters passed by the Wasm code,
+/// and must be of type `i32`, `u32`, `i64` or `u64`. The type of the value returned to the Wasm
+/// code, if any, is indicated with `#[extrinsic(-> i32)]`. All the extrinsics must have the same
+/// types for the state and the memory access, and the same return type.
+///
+/// The following items are added to the module:
+///
+/// - An `Extrinsic` enum with one variant per extrinsic, named after the function in camel case.
+/// - `fn supported_extrinsics<T>(wrap: impl FnMut(Extrinsic) -> T) -> [SupportedExtrinsic<T>; N]`,
+/// returning the list of extrinsics with their signature.
+/// - `fn call(id: Extrinsic, state, params: impl ExactSizeIterator<Item = WasmValue>,
+/// mem_access)`, decoding the parameters and calling the function corresponding to `id`.
+///
+/// Since the signatures are derived from the Rust functions, they can't mismatch the way the
+/// parameters are decoded.
+#[proc_macro_attribute]
+pub fn extrinsics(
+    params: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    extrinsics::extrinsics(params.into(), item.into())
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
 /// Turns a string of WebAssembly text representation into a binary representation.
 #[proc_macro]
 pub fn wat_to_bin(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -14,7 +14,11 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use criterion::{criterion_group, criterion_main, Criterion};
-use redshirt_core::{extrinsics::wasi::WasiExtrinsics, Module, SystemBuilder, SystemRunOutcome};
+use redshirt_core::{
+    extrinsics::wasi::WasiExtrinsics,
+    scheduler::{Engine, WasmiEngine},
+    ExecuteOut, Module, SystemBuilder, SystemRunOutcome,
+};
 
 fn bench(c: &mut Criterion) {
     /* Original code:
@@ -56,23 +60,8 @@ fn bench(c: &mut Criterion) {
     */
     let module = Module::from_bytes(&include_bytes!("keccak.wasm")[..]).unwrap();
 
-    c.bench_function("keccak-wasm-4096-bytes", |b| {
-        let system = SystemBuilder::<WasiExtrinsics>::new([0; 64])
-            .build()
-            .unwrap();
-        b.iter(|| {
-            system.execute(&module).unwrap();
-            futures::executor::block_on(async {
-                loop {
-                    match system.run().await {
-                        SystemRunOutcome::ProgramFinished { outcome, .. } => break outcome,
-                        _ => panic!(),
-                    }
-                }
-            })
-            .unwrap();
-        })
-    });
+    // Other engines are meant to be added here.
+    bench_engine::<WasmiEngine>(c, &module, "keccak-wasm-4096-bytes");
 
     c.bench_function("keccak-native-4096-bytes", |b| {
         b.iter(|| {
@@ -90,5 +79,34 @@ fn bench(c: &mut Criterion) {
     });
 }
 
+/// Measures the time it takes for the given engine to run `module` to completion.
+fn bench_engine<TEng: Engine>(c: &mut Criterion, module: &Module, name: &str) {
+    c.bench_function(name, |b| {
+        let system = SystemBuilder::<WasiExtrinsics, TEng>::new([0; 64])
+            .build()
+            .unwrap();
+        b.iter(|| {
+            system.execute(module).unwrap();
+            futures::executor::block_on(async {
+                loop {
+                    let outcome = match system.run().await {
+                        ExecuteOut::Direct(outcome) => Some(outcome),
+                        ExecuteOut::ReadyToRun(to_run) => to_run.run(),
+                    };
+
+                    match outcome {
+                        Some(SystemRunOutcome::ProgramFinished { outcome, .. }) => {
+                            assert!(outcome.is_success());
+                            break;
+                        }
+                        Some(_) => panic!(),
+                        None => {}
+                    }
+                }
+            });
+        })
+    });
+}
+
 criterion_group!(benches, bench);
 criterion_main!(benches);
@@ -23,21 +23,19 @@
 //! TODO: write doc on how to implement this trait
 
 use crate::primitives::{Signature, WasmValue};
-use crate::{EncodedMessage, EncodedMessageRef, InterfaceHash, ThreadId};
+use crate::{EncodedMessage, EncodedMessageRef, InterfaceHash, MessageId, ThreadId};
 
-use alloc::{borrow::Cow, vec::Vec};
-use core::{fmt, iter, ops::Range};
+use alloc::{borrow::Cow, string::String, vec, vec::Vec};
+use core::{fmt, iter, ops::Range, time::Duration};
 
 pub mod log_calls;
 pub mod wasi;
 
 /// Trait implemented on types that can handle extrinsics.
 ///
-/// The `Default` trait is used to instantiate structs that implement this trait. One instance is
-/// created for each WASM process.
-// TODO: in this API one can only emit one message at the time; this is fine in terms of logic, but
-// is sub-optimal
-pub trait Extrinsics: Default {
+/// One instance is created for each WASM process, using
+/// [`with_launch_config`](Extrinsics::with_launch_config).
+pub trait Extrinsics: Sized {
     /// Identifier for an extrinsic function.
     ///
     /// Instead of passing around function names, we pass around identifiers.
@@ -54,12 +52,16 @@ pub trait Extrinsics: Default {
     /// Returns an iterator to the list of extrinsics that this struct supports.
     fn supported_extrinsics() -> Self::Iterator;
 
+    /// Creates the instance dedicated to a process that is being started with the given
+    /// configuration.
+    fn with_launch_config(config: LaunchConfig) -> Self;
+
     /// Called when a WASM module calls an extrinsic.
     ///
     /// Returns what to do next on this context.
     ///
-    /// Returning [`ExtrinsicsAction::Resume`] or [`ExtrinsicsAction::ProgramCrash`] finishes the
-    /// extrinsic call and destroys the context.
+    /// Returning [`ExtrinsicsAction::Resume`], [`ExtrinsicsAction::ProgramCrash`] or
+    /// [`ExtrinsicsAction::ProgramExit`] finishes the extrinsic call and destroys the context.
     fn new_context(
         &self,
         tid: ThreadId,
@@ -71,21 +73,177 @@ pub trait Extrinsics: Default {
     /// If [`ExtrinsicsAction::EmitMessage`] has been emitted, this function is later called in
     /// order to notify of the response.
     ///
-    /// The response is `None` if no response is expected.
+    /// The response is `None` if no response is expected, and `Some(Err(()))` if the message has
+    /// been answered with an error, for example because its handler has crashed. An error answer
+    /// is a normal situation that must not lead to a panic.
     ///
     /// Returns what to do next on this context.
     ///
-    /// Returning [`ExtrinsicsAction::Resume`] or [`ExtrinsicsAction::ProgramCrash`] finishes the
-    /// extrinsic call and destroys the context.
+    /// Returning [`ExtrinsicsAction::Resume`], [`ExtrinsicsAction::ProgramCrash`] or
+    /// [`ExtrinsicsAction::ProgramExit`] finishes the extrinsic call and destroys the context.
     fn inject_message_response(
         &self,
         ctxt: &mut Self::Context,
-        response: Option<EncodedMessageRef>,
+        response: Option<Result<EncodedMessageRef, ()>>,
+        proc_access: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction;
+
+    /// If [`ExtrinsicsAction::EmitMessages`] has been emitted, this function is later called,
+    /// once all the messages have been emitted and all the expected responses have arrived.
+    ///
+    /// `responses` contains one entry per message, in the same order as the messages. An entry
+    /// is `None` if no response is expected for the corresponding message, and `Some(Err(()))`
+    /// if the message has been answered with an error, as for
+    /// [`Extrinsics::inject_message_response`].
+    ///
+    /// Returns what to do next on this context.
+    ///
+    /// Returning [`ExtrinsicsAction::Resume`], [`ExtrinsicsAction::ProgramCrash`] or
+    /// [`ExtrinsicsAction::ProgramExit`] finishes the extrinsic call and destroys the context.
+    fn inject_message_responses(
+        &self,
+        ctxt: &mut Self::Context,
+        responses: Vec<Option<Result<EncodedMessage, ()>>>,
+        proc_access: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction;
+
+    /// If [`ExtrinsicsAction::EmitMessagesWaitFirst`] has been emitted, this function is later
+    /// called, once all the messages have been emitted and the first response has arrived.
+    ///
+    /// `emitted` contains the identifiers attributed to the emitted messages, in the same order
+    /// as the messages. An entry is `None` if no response is expected for the corresponding
+    /// message. `message_id` is the message whose response has arrived, and is either one of
+    /// `emitted` or one of the messages passed in `wait_also`. `response` is `Err` if the
+    /// message has been answered with an error, as for [`Extrinsics::inject_message_response`].
+    ///
+    /// Returns what to do next on this context.
+    ///
+    /// Returning [`ExtrinsicsAction::Resume`], [`ExtrinsicsAction::ProgramCrash`] or
+    /// [`ExtrinsicsAction::ProgramExit`] finishes the extrinsic call and destroys the context.
+    fn inject_first_response(
+        &self,
+        ctxt: &mut Self::Context,
+        emitted: Vec<Option<MessageId>>,
+        message_id: MessageId,
+        response: Result<EncodedMessage, ()>,
         proc_access: &mut impl ExtrinsicsMemoryAccess,
     ) -> ExtrinsicsAction;
+
+    /// Called when a WASM module calls one of the functions that are handled directly by the
+    /// scheduler, before the call is processed.
+    ///
+    /// Returns `None` if nothing has to be done once the call has finished, which is the default.
+    /// Otherwise, [`Extrinsics::finish_scheduler_call`] is later called with the returned context.
+    fn start_scheduler_call(
+        &self,
+        tid: ThreadId,
+        call: SchedulerCall,
+        params: impl ExactSizeIterator<Item = WasmValue>,
+        proc_access: &mut impl ExtrinsicsMemoryAccess,
+    ) -> Option<Self::Context> {
+        let _ = (tid, call, params, proc_access);
+        None
+    }
+
+    /// Called when a call for which [`Extrinsics::start_scheduler_call`] has returned a context
+    /// has finished. `result` is the value that the function returns to the program, and the
+    /// memory of the process already contains what the function has written.
+    ///
+    /// Returns what to do next on this context, similar to [`Extrinsics::new_context`]. The
+    /// returned [`ExtrinsicsAction::Resume`] should normally contain `result`.
+    fn finish_scheduler_call(
+        &self,
+        ctxt: &mut Self::Context,
+        result: Option<WasmValue>,
+        proc_access: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        let _ = (ctxt, proc_access);
+        ExtrinsicsAction::Resume(result)
+    }
+}
+
+/// Function handled directly by the scheduler rather than by the implementation of
+/// [`Extrinsics`]. See [`Extrinsics::start_scheduler_call`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SchedulerCall {
+    /// `redshirt::next_notification`.
+    NextNotification,
+    /// `redshirt::emit_message`.
+    EmitMessage,
+    /// `redshirt::cancel_message`.
+    CancelMessage,
+}
+
+/// Configuration of a process, passed to [`Extrinsics::with_launch_config`] when the process is
+/// started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchConfig {
+    /// Arguments passed to the program. By convention, the first argument is the name of the
+    /// program.
+    pub args: Vec<Vec<u8>>,
+
+    /// Environment variables passed to the program, in the `KEY=value` format.
+    pub env_vars: Vec<Vec<u8>>,
+
+    /// Paths of the directories of the file system that are opened before the program starts,
+    /// and that the program can access. The paths are reported as is to the program.
+    ///
+    /// An empty path designates the root of the file system.
+    pub preopens: Vec<String>,
+
+    /// Directories that are opened before the program starts, like [`LaunchConfig::preopens`],
+    /// but whose content is accessed through the `files` interface instead of being kept in
+    /// memory.
+    pub mounts: Vec<Mount>,
+
+    /// If true, reading from the standard input of the program is performed through the
+    /// `console` interface. If false, the standard input is always empty.
+    ///
+    /// Defaults to `false`. Only the programs meant to interact with the user, such as a shell,
+    /// should have this enabled, as all the programs reading from the console compete for the
+    /// text being typed.
+    pub console_stdin: bool,
+
+    /// Addresses on which the program accepts TCP connections through the `tcp` interface.
+    /// A listening socket is opened for each of them before the program starts.
+    pub tcp_listeners: Vec<TcpListener>,
 }
 
-/// Access to a process's memory.
+/// Directory whose content is accessed through the `files` interface. See
+/// [`LaunchConfig::mounts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    /// Path reported to the program.
+    pub path: String,
+
+    /// Name of the volume, as known by the handler of the `files` interface.
+    pub volume: String,
+}
+
+/// Address on which a program accepts TCP connections. See [`LaunchConfig::tcp_listeners`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpListener {
+    /// IPv6 address to listen on. IPv4 addresses must be IPv4-mapped.
+    pub ip: [u16; 8],
+
+    /// TCP port to listen on.
+    pub port: u16,
+}
+
+impl Default for LaunchConfig {
+    fn default() -> Self {
+        LaunchConfig {
+            args: Vec::new(),
+            env_vars: Vec::new(),
+            preopens: vec![String::new()],
+            mounts: Vec::new(),
+            console_stdin: false,
+            tcp_listeners: Vec::new(),
+        }
+    }
+}
+
+/// Access to a process's memory and execution statistics.
 pub trait ExtrinsicsMemoryAccess {
     /// Reads the process' memory in the given range and returns a copy of it.
     ///
@@ -98,6 +256,17 @@ pub trait ExtrinsicsMemoryAccess {
 
     /// Writes the given data in the process's memory at the given offset.
     fn write_memory(&mut self, offset: u32, data: &[u8]) -> Result<(), ExtrinsicsMemoryAccessErr>;
+
+    /// Returns the total time spent executing the threads of the process, including threads that
+    /// have finished.
+    fn process_cpu_time(&self) -> Duration;
+
+    /// Returns the total time spent executing the thread that is calling the extrinsic.
+    fn thread_cpu_time(&self) -> Duration;
+
+    /// Returns the current value of the monotonic clock of the scheduler. Only the difference
+    /// between two values is meaningful.
+    fn monotonic_clock(&self) -> Duration;
 }
 
 /// Error that can happen when reading or writing the memory of a process.
@@ -146,6 +315,10 @@ pub enum ExtrinsicsAction {
     /// Crash the program that called the extrinsic.
     ProgramCrash,
 
+    /// Terminate the program that called the extrinsic, with the given exit code. All the
+    /// threads of the program are stopped.
+    ProgramExit(i32),
+
     /// Successfully finish the call and return with the given value.
     Resume(Option<WasmValue>),
 
@@ -158,6 +331,50 @@ pub enum ExtrinsicsAction {
         message: EncodedMessage,
         response_expected: bool,
     },
+
+    /// Emit several messages at once.
+    ///
+    /// The messages are emitted in order, and their responses are waited upon simultaneously.
+    /// Once all the responses have arrived, they are injected back using
+    /// [`Extrinsics::inject_message_responses`].
+    ///
+    /// If one of the messages can't be emitted, the program is crashed, even if some of the
+    /// messages before it have already been emitted.
+    EmitMessages(Vec<MessageToEmit>),
+
+    /// Emit several messages at once, then wait until the response to one of them, or to one of
+    /// the messages of `wait_also`, arrives.
+    ///
+    /// The messages are emitted in order. Once a response has arrived, it is injected back using
+    /// [`Extrinsics::inject_first_response`]. The responses to the other messages are kept in
+    /// the queue of the process, and can be waited upon by a later call of any thread of the
+    /// same process by passing their identifier in `wait_also`.
+    ///
+    /// `wait_also` must only contain identifiers previously reported by
+    /// [`Extrinsics::inject_first_response`] and whose response hasn't been injected yet.
+    ///
+    /// If one of the messages can't be emitted, or if there isn't any response to wait for, the
+    /// program is crashed.
+    EmitMessagesWaitFirst {
+        /// Messages to emit.
+        messages: Vec<MessageToEmit>,
+        /// Messages already emitted whose response must also be waited for.
+        wait_also: Vec<MessageId>,
+    },
+}
+
+/// Message emitted as part of [`ExtrinsicsAction::EmitMessages`] or
+/// [`ExtrinsicsAction::EmitMessagesWaitFirst`].
+#[derive(Debug, Clone)]
+pub struct MessageToEmit {
+    /// Interface to emit the message on.
+    pub interface: InterfaceHash,
+
+    /// Body of the message.
+    pub message: EncodedMessage,
+
+    /// True if a response to this message is expected.
+    pub response_expected: bool,
 }
 
 /// Dummy implementation of the [`Extrinsics`] trait.
@@ -173,6 +390,10 @@ impl Extrinsics for NoExtrinsics {
         iter::empty()
     }
 
+    fn with_launch_config(_: LaunchConfig) -> Self {
+        NoExtrinsics
+    }
+
     fn new_context(
         &self,
         _: ThreadId,
@@ -186,7 +407,27 @@ impl Extrinsics for NoExtrinsics {
     fn inject_message_response(
         &self,
         ctxt: &mut Self::Context,
-        _: Option<EncodedMessageRef>,
+        _: Option<Result<EncodedMessageRef, ()>>,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        match *ctxt {} // unreachable
+    }
+
+    fn inject_message_responses(
+        &self,
+        ctxt: &mut Self::Context,
+        _: Vec<Option<Result<EncodedMessage, ()>>>,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        match *ctxt {} // unreachable
+    }
+
+    fn inject_first_response(
+        &self,
+        ctxt: &mut Self::Context,
+        _: Vec<Option<MessageId>>,
+        _: MessageId,
+        _: Result<EncodedMessage, ()>,
         _: &mut impl ExtrinsicsMemoryAccess,
     ) -> ExtrinsicsAction {
         match *ctxt {} // unreachable
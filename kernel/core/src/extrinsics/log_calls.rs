@@ -14,36 +14,145 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 //! Implementation of the [`Extrinsics`] trait that wraps around another implementation and sends
-//! all the calls to the `log` interface for debugging.
+//! the calls to the `log` interface for debugging, similar to `strace`.
 
-use crate::extrinsics::{Extrinsics, ExtrinsicsAction, ExtrinsicsMemoryAccess, SupportedExtrinsic};
-use crate::{EncodedMessage, EncodedMessageRef, ThreadId, WasmValue};
+use crate::extrinsics::{
+    Extrinsics, ExtrinsicsAction, ExtrinsicsMemoryAccess, LaunchConfig, SchedulerCall,
+    SupportedExtrinsic,
+};
+use crate::{EncodedMessage, EncodedMessageRef, InterfaceHash, MessageId, ThreadId, WasmValue};
 
-use alloc::{borrow::Cow, format, string::String, vec, vec::Vec};
-use core::mem;
+use alloc::{
+    format,
+    string::{String, ToString as _},
+    vec,
+    vec::Vec,
+};
+use core::{
+    cmp,
+    convert::TryFrom as _,
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use redshirt_syscalls::Decode;
 
-/// Implementation of the [`Extrinsics`] trait that logs all calls to the underlying handler.
+/// Maximum number of bytes of a buffer or message that are included in a record. The rest is
+/// omitted.
+const MAX_DISPLAYED_BYTES: usize = 64;
+
+/// Maximum number of characters of a decoded message that are included in a record. The rest is
+/// omitted.
+const MAX_DISPLAYED_MESSAGE_CHARS: usize = 256;
+
+/// Implementation of the [`Extrinsics`] trait that logs calls to the underlying handler.
+///
+/// Logging is initially disabled, and can be enabled or disabled at any time with
+/// [`LogExtrinsics::set_enabled`]. While it is enabled, a record is sent to the `log` interface
+/// every time a call has finished. A record looks like this:
+///
+/// ```text
+/// call=wasi_snapshot_preview1::fd_tell args=(fd=3, offset=12) result=0 duration=5µs
+/// ```
+///
+/// The calls to the `next_notification`, `emit_message` and `cancel_message` functions, which
+/// are handled by the scheduler, are logged as well, through
+/// [`Extrinsics::start_scheduler_call`].
+///
+/// The parameters of these functions and of the WASI functions are decoded: the strings and
+/// buffers that the function reads are printed as they are when the call starts, and the values
+/// that the function writes are printed as they are after the call has successfully finished.
+/// The parameters of other functions are printed as raw values.
 ///
-/// Every time a call has finished, a message is sent to the `log` interface containing the
-/// parameters and return value.
+/// The bodies of the messages emitted on the interfaces known by the kernel, such as `console`
+/// or `files`, are decoded and printed using their `Debug` implementation. The other messages,
+/// and the answers, are printed as raw bytes.
 ///
-/// This struct only has access to the low-level signatures of the functions it wraps around, and
-/// therefore cannot, for example, print the content of memory that the inner function reads from
-/// or writes to.
+/// `emitted` contains the interface and body of the messages emitted by the underlying handler
+/// during the call, and is omitted if there isn't any. `duration` is measured using
+/// [`ExtrinsicsMemoryAccess::monotonic_clock`] and includes the time spent waiting for the
+/// responses to these messages.
 #[derive(Debug)]
 pub struct LogExtrinsics<TInner> {
     /// Actual implementation.
     inner: TInner,
     /// Log level for the messages.
     log_level: redshirt_log_interface::Level,
+    /// If true, calls are logged.
+    enabled: AtomicBool,
 }
 
 impl<TInner> LogExtrinsics<TInner> {
-    /// Builds a new [`LogExtrinsics`].
+    /// Builds a new [`LogExtrinsics`]. Logging is initially disabled.
     pub fn new(inner: TInner) -> Self {
         LogExtrinsics {
             inner,
             log_level: redshirt_log_interface::Level::Trace,
+            enabled: AtomicBool::new(false),
+        }
+    }
+
+    /// Enables or disables the logging of calls.
+    ///
+    /// Calls that are in progress are logged if and only if logging was enabled when they
+    /// started.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns true if calls are being logged.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Called after the inner implementation has returned an action. Updates the record and, if
+    /// the call is finished, returns the action that sends it to the `log` interface.
+    fn after_inner_action<TInnerCtxt>(
+        &self,
+        ctxt: &mut Context<TInnerCtxt>,
+        action: ExtrinsicsAction,
+        mem_access: &impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        let record = match &mut ctxt.record {
+            Some(r) => r,
+            None => return action,
+        };
+
+        match &action {
+            ExtrinsicsAction::EmitMessage {
+                interface, message, ..
+            } => {
+                record.emitted.push(format!(
+                    "{:?}: {}",
+                    interface,
+                    format_message(interface, &message.0)
+                ));
+                return action;
+            }
+            ExtrinsicsAction::EmitMessages(messages)
+            | ExtrinsicsAction::EmitMessagesWaitFirst { messages, .. } => {
+                for m in messages {
+                    record.emitted.push(format!(
+                        "{:?}: {}",
+                        m.interface,
+                        format_message(&m.interface, &m.message.0)
+                    ));
+                }
+                return action;
+            }
+            _ => {}
+        }
+
+        let mut record = ctxt.record.take().unwrap();
+        record.finish(&action, mem_access);
+        ctxt.waiting_for_log_message = Some(action);
+
+        let mut message = vec![u8::from(self.log_level)];
+        message.extend(record.to_string().as_bytes());
+        ExtrinsicsAction::EmitMessage {
+            interface: redshirt_log_interface::ffi::INTERFACE,
+            message: EncodedMessage(message),
+            response_expected: false,
         }
     }
 }
@@ -62,17 +171,20 @@ where
 pub struct ExtrinsicId<TInner> {
     /// The function name prefixed with its module name.
     f_name: String,
+    /// Names and kinds of the parameters of the function, if known.
+    params: Option<&'static [(&'static str, ParamKind)]>,
     /// Actual identifier.
     inner: TInner,
 }
 
 /// Context for a logging call.
 pub struct Context<TInner> {
-    /// The inner context.
-    inner: TInner,
+    /// The inner context. `None` for a call handled by the scheduler for which the inner
+    /// implementation hasn't returned any context.
+    inner: Option<TInner>,
 
-    /// Start of the encoded log message. Only the return value is missing.
-    message_start: Vec<u8>,
+    /// Record of the call being built. `None` if logging was disabled when the call started.
+    record: Option<CallRecord>,
 
     /// If `Some`, the inner context has finished and we have sent a log message. When the
     /// confirmation comes back, we send back the content in this `Option`.
@@ -95,6 +207,10 @@ where
         LogIterator(TInner::supported_extrinsics())
     }
 
+    fn with_launch_config(config: LaunchConfig) -> Self {
+        Self::new(TInner::with_launch_config(config))
+    }
+
     fn new_context(
         &self,
         thread_id: ThreadId,
@@ -104,91 +220,143 @@ where
     ) -> (Self::Context, ExtrinsicsAction) {
         let params = params.collect::<Vec<_>>();
 
-        let message_start = {
-            let mut msg = vec![u8::from(self.log_level)];
-            msg.extend(id.f_name.as_bytes());
-            msg.extend(b"(");
-            for (n, param) in params.iter().enumerate() {
-                if n != 0 {
-                    msg.extend(b", ");
-                }
-                msg.extend(format!("{:?}", param).as_bytes());
-            }
-            msg.extend(b") -> ");
-            msg
+        // The record must be started before calling the inner implementation, as it might
+        // modify the memory that the parameters point to.
+        let record = if self.is_enabled() {
+            Some(CallRecord::start(
+                id.f_name.clone(),
+                id.params,
+                params.clone(),
+                &*mem_access,
+            ))
+        } else {
+            None
         };
 
         let (inner_ctxt, action) =
             self.inner
                 .new_context(thread_id, &id.inner, params.into_iter(), mem_access);
         let mut ctxt = Context {
-            inner: inner_ctxt,
-            message_start,
+            inner: Some(inner_ctxt),
+            record,
             waiting_for_log_message: None,
         };
 
-        let ret_value_str = match action {
-            ExtrinsicsAction::Resume(val) => {
-                ctxt.waiting_for_log_message = Some(ExtrinsicsAction::Resume(val));
-                Cow::Owned(format!("{:?}", val).into_bytes())
-            }
-            a @ ExtrinsicsAction::ProgramCrash => {
-                ctxt.waiting_for_log_message = Some(a);
-                Cow::Borrowed(&b"<crash>"[..])
-            }
-            a @ ExtrinsicsAction::EmitMessage { .. } => return (ctxt, a),
-        };
-
-        let mut message = mem::replace(&mut ctxt.message_start, Vec::new());
-        message.extend(&ret_value_str[..]);
-
-        debug_assert!(ctxt.waiting_for_log_message.is_some());
-        let action = ExtrinsicsAction::EmitMessage {
-            interface: redshirt_log_interface::ffi::INTERFACE,
-            message: EncodedMessage(message),
-            response_expected: false,
-        };
-
+        let action = self.after_inner_action(&mut ctxt, action, &*mem_access);
         (ctxt, action)
     }
 
     fn inject_message_response(
         &self,
         ctxt: &mut Self::Context,
-        response: Option<EncodedMessageRef>,
+        response: Option<Result<EncodedMessageRef, ()>>,
         mem_access: &mut impl ExtrinsicsMemoryAccess,
     ) -> ExtrinsicsAction {
         if let Some(waiting_for_log_message) = ctxt.waiting_for_log_message.take() {
             debug_assert!(response.is_none());
-            debug_assert!(ctxt.message_start.is_empty());
+            debug_assert!(ctxt.record.is_none());
             return waiting_for_log_message;
         }
 
-        let ret_value_str =
-            match self
-                .inner
-                .inject_message_response(&mut ctxt.inner, response, mem_access)
-            {
-                ExtrinsicsAction::Resume(val) => {
-                    ctxt.waiting_for_log_message = Some(ExtrinsicsAction::Resume(val));
-                    Cow::Owned(format!("{:?}", val).into_bytes())
-                }
-                a @ ExtrinsicsAction::ProgramCrash => {
-                    ctxt.waiting_for_log_message = Some(a);
-                    Cow::Borrowed(&b"<crash>"[..])
-                }
-                a @ ExtrinsicsAction::EmitMessage { .. } => return a,
-            };
+        let inner_ctxt = ctxt.inner.as_mut().unwrap();
+        let action = self
+            .inner
+            .inject_message_response(inner_ctxt, response, mem_access);
+        self.after_inner_action(ctxt, action, &*mem_access)
+    }
 
-        let mut message = mem::replace(&mut ctxt.message_start, Vec::new());
-        message.extend(&ret_value_str[..]);
+    fn inject_message_responses(
+        &self,
+        ctxt: &mut Self::Context,
+        responses: Vec<Option<Result<EncodedMessage, ()>>>,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        // The record is sent using `EmitMessage`, and its confirmation is therefore never
+        // injected here.
+        debug_assert!(ctxt.waiting_for_log_message.is_none());
 
-        debug_assert!(ctxt.waiting_for_log_message.is_some());
-        ExtrinsicsAction::EmitMessage {
-            interface: redshirt_log_interface::ffi::INTERFACE,
-            message: EncodedMessage(message),
-            response_expected: false,
+        let inner_ctxt = ctxt.inner.as_mut().unwrap();
+        let action = self
+            .inner
+            .inject_message_responses(inner_ctxt, responses, mem_access);
+        self.after_inner_action(ctxt, action, &*mem_access)
+    }
+
+    fn inject_first_response(
+        &self,
+        ctxt: &mut Self::Context,
+        emitted: Vec<Option<MessageId>>,
+        message_id: MessageId,
+        response: Result<EncodedMessage, ()>,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        // Same remark as in `inject_message_responses`.
+        debug_assert!(ctxt.waiting_for_log_message.is_none());
+
+        let action = self.inner.inject_first_response(
+            ctxt.inner.as_mut().unwrap(),
+            emitted,
+            message_id,
+            response,
+            mem_access,
+        );
+        self.after_inner_action(ctxt, action, &*mem_access)
+    }
+
+    fn start_scheduler_call(
+        &self,
+        thread_id: ThreadId,
+        call: SchedulerCall,
+        params: impl ExactSizeIterator<Item = WasmValue>,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+    ) -> Option<Self::Context> {
+        let params = params.collect::<Vec<_>>();
+
+        let record = if self.is_enabled() {
+            let function_name = match call {
+                SchedulerCall::NextNotification => "next_notification",
+                SchedulerCall::EmitMessage => "emit_message",
+                SchedulerCall::CancelMessage => "cancel_message",
+            };
+            let kinds =
+                known_params("redshirt", function_name).filter(|kinds| kinds.len() == params.len());
+            Some(CallRecord::start(
+                format!("redshirt::{}", function_name),
+                kinds,
+                params.clone(),
+                &*mem_access,
+            ))
+        } else {
+            None
+        };
+
+        let inner_ctxt =
+            self.inner
+                .start_scheduler_call(thread_id, call, params.into_iter(), mem_access);
+        if record.is_none() && inner_ctxt.is_none() {
+            return None;
         }
+
+        Some(Context {
+            inner: inner_ctxt,
+            record,
+            waiting_for_log_message: None,
+        })
+    }
+
+    fn finish_scheduler_call(
+        &self,
+        ctxt: &mut Self::Context,
+        result: Option<WasmValue>,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        let action = match &mut ctxt.inner {
+            Some(inner_ctxt) => self
+                .inner
+                .finish_scheduler_call(inner_ctxt, result, mem_access),
+            None => ExtrinsicsAction::Resume(result),
+        };
+        self.after_inner_action(ctxt, action, &*mem_access)
     }
 }
 
@@ -203,6 +371,8 @@ where
 
         let id = ExtrinsicId {
             f_name: format!("{}::{}", item.wasm_interface, item.function_name),
+            params: known_params(&item.wasm_interface, &item.function_name)
+                .filter(|params| params.len() == item.signature.parameters().len()),
             inner: item.id,
         };
 
@@ -223,3 +393,650 @@ impl<TInner, TExtId> ExactSizeIterator for LogIterator<TInner> where
     TInner: ExactSizeIterator<Item = SupportedExtrinsic<TExtId>>
 {
 }
+
+/// How to print a parameter of an extrinsic.
+#[derive(Debug, Copy, Clone)]
+enum ParamKind {
+    /// Integer printed in decimal.
+    Int,
+    /// Integer printed in hexadecimal, such as flags.
+    Hex,
+    /// Pointer to memory whose content isn't printed.
+    Ptr,
+    /// Pointer to a buffer read by the function. The length of the buffer is the parameter at
+    /// the given index.
+    BufferIn { len: usize },
+    /// Pointer to a buffer written by the function. The length of the buffer is the parameter at
+    /// the given index.
+    BufferOut { len: usize },
+    /// Pointer to a list of `iovec`s pointing to buffers read by the function. The number of
+    /// `iovec`s is the parameter at the given index.
+    IoVecsIn { len: usize },
+    /// Pointer to a list of `iovec`s pointing to buffers written by the function. The number of
+    /// `iovec`s is the parameter at index `len`, and the total number of bytes written is a
+    /// `u32` found at the pointer passed as the parameter at index `written`.
+    IoVecsOut { len: usize, written: usize },
+    /// Pointer to a `u16` written by the function.
+    OutU16,
+    /// Pointer to a `u32` written by the function.
+    OutU32,
+    /// Pointer to a `u64` written by the function.
+    OutU64,
+    /// Pointer to a `u64` read by the function, such as a message identifier.
+    InU64,
+    /// Pointer to an interface hash read by the function.
+    Interface,
+    /// Pointer to a list of `iovec`s pointing to the body of a message emitted on the interface
+    /// whose hash is pointed to by the parameter at index `interface`. The number of `iovec`s is
+    /// the parameter at index `len`.
+    Message { interface: usize, len: usize },
+    /// Pointer to a list of message identifiers read by the function. The number of identifiers
+    /// is the parameter at the given index.
+    MessageIds { len: usize },
+    /// Pointer to a message identifier written by the function if the first bit of the
+    /// parameter at the given index is set.
+    OutMessageId { flags: usize },
+    /// Pointer to a buffer where the function writes a notification, and whose length is the
+    /// parameter at the given index. The function returns the size of the notification.
+    OutNotification { len: usize },
+}
+
+/// Returns the names and kinds of the parameters of the given function, if known.
+fn known_params(
+    wasm_interface: &str,
+    function_name: &str,
+) -> Option<&'static [(&'static str, ParamKind)]> {
+    use ParamKind::*;
+
+    if wasm_interface == "redshirt" {
+        return Some(match function_name {
+            "cancel_message" => &[("message_id", InU64)],
+            "emit_message" => &[
+                ("interface", Interface),
+                (
+                    "message",
+                    Message {
+                        interface: 0,
+                        len: 2,
+                    },
+                ),
+                ("message_len", Int),
+                ("flags", Hex),
+                ("message_id", OutMessageId { flags: 3 }),
+            ],
+            "next_notification" => &[
+                ("to_poll", MessageIds { len: 1 }),
+                ("to_poll_len", Int),
+                ("out", OutNotification { len: 3 }),
+                ("out_len", Int),
+                ("flags", Hex),
+            ],
+            _ => return None,
+        });
+    }
+
+    if wasm_interface != "wasi_snapshot_preview1" {
+        return None;
+    }
+
+    Some(match function_name {
+        "args_get" => &[("argv", Ptr), ("argv_buf", Ptr)],
+        "args_sizes_get" => &[("argc", OutU32), ("argv_buf_size", OutU32)],
+        "clock_time_get" => &[("id", Int), ("precision", Int), ("time", OutU64)],
+        "environ_get" => &[("environ", Ptr), ("environ_buf", Ptr)],
+        "environ_sizes_get" => &[("environc", OutU32), ("environ_buf_size", OutU32)],
+        "fd_close" => &[("fd", Int)],
+        "fd_fdstat_get" => &[("fd", Int), ("stat", Ptr)],
+        "fd_fdstat_set_flags" => &[("fd", Int), ("flags", Hex)],
+        "fd_filestat_get" => &[("fd", Int), ("buf", Ptr)],
+        "fd_prestat_dir_name" => &[
+            ("fd", Int),
+            ("path", BufferOut { len: 2 }),
+            ("path_len", Int),
+        ],
+        "fd_prestat_get" => &[("fd", Int), ("buf", Ptr)],
+        "fd_read" => &[
+            ("fd", Int),
+            ("iovs", IoVecsOut { len: 2, written: 3 }),
+            ("iovs_len", Int),
+            ("nread", OutU32),
+        ],
+        "fd_readdir" => &[
+            ("fd", Int),
+            ("buf", Ptr),
+            ("buf_len", Int),
+            ("cookie", Int),
+            ("bufused", OutU32),
+        ],
+        "fd_seek" => &[
+            ("fd", Int),
+            ("offset", Int),
+            ("whence", Int),
+            ("newoffset", OutU64),
+        ],
+        "fd_tell" => &[("fd", Int), ("offset", OutU64)],
+        "fd_write" => &[
+            ("fd", Int),
+            ("iovs", IoVecsIn { len: 2 }),
+            ("iovs_len", Int),
+            ("nwritten", OutU32),
+        ],
+        "path_create_directory" | "path_remove_directory" | "path_unlink_file" => &[
+            ("fd", Int),
+            ("path", BufferIn { len: 2 }),
+            ("path_len", Int),
+        ],
+        "path_filestat_get" => &[
+            ("fd", Int),
+            ("flags", Hex),
+            ("path", BufferIn { len: 3 }),
+            ("path_len", Int),
+            ("buf", Ptr),
+        ],
+        "path_open" => &[
+            ("fd", Int),
+            ("dirflags", Hex),
+            ("path", BufferIn { len: 3 }),
+            ("path_len", Int),
+            ("oflags", Hex),
+            ("fs_rights_base", Hex),
+            ("fs_rights_inheriting", Hex),
+            ("fdflags", Hex),
+            ("opened_fd", OutU32),
+        ],
+        "path_rename" => &[
+            ("fd", Int),
+            ("old_path", BufferIn { len: 2 }),
+            ("old_path_len", Int),
+            ("new_fd", Int),
+            ("new_path", BufferIn { len: 5 }),
+            ("new_path_len", Int),
+        ],
+        "poll_oneoff" => &[
+            ("in", Ptr),
+            ("out", Ptr),
+            ("nsubscriptions", Int),
+            ("nevents", OutU32),
+        ],
+        "proc_exit" => &[("rval", Int)],
+        "random_get" => &[("buf", BufferOut { len: 1 }), ("buf_len", Int)],
+        "sched_yield" => &[],
+        "sock_accept" => &[("fd", Int), ("flags", Hex), ("opened_fd", OutU32)],
+        "sock_recv" => &[
+            ("fd", Int),
+            ("ri_data", IoVecsOut { len: 2, written: 4 }),
+            ("ri_data_len", Int),
+            ("ri_flags", Hex),
+            ("ro_datalen", OutU32),
+            ("ro_flags", OutU16),
+        ],
+        "sock_send" => &[
+            ("fd", Int),
+            ("si_data", IoVecsIn { len: 2 }),
+            ("si_data_len", Int),
+            ("si_flags", Hex),
+            ("so_datalen", OutU32),
+        ],
+        "sock_shutdown" => &[("fd", Int), ("how", Hex)],
+        _ => return None,
+    })
+}
+
+/// Record of a call, sent to the `log` interface once the call has finished.
+struct CallRecord {
+    /// The function name prefixed with its module name.
+    f_name: String,
+    /// Parameters passed by the program.
+    params: Vec<WasmValue>,
+    /// Names and kinds of the parameters, if known.
+    kinds: Option<&'static [(&'static str, ParamKind)]>,
+    /// Printed value of each parameter. Updated when the call finishes.
+    args: Vec<String>,
+    /// Interface and body of each message emitted during the call.
+    emitted: Vec<String>,
+    /// Printed outcome of the call. Empty until the call has finished.
+    result: String,
+    /// Value of the monotonic clock when the call started.
+    start: Duration,
+    /// Duration of the call. Zero until the call has finished.
+    duration: Duration,
+}
+
+impl CallRecord {
+    /// Starts a record for a call to the given function, whose parameters have the given names
+    /// and kinds if known.
+    fn start(
+        f_name: String,
+        kinds: Option<&'static [(&'static str, ParamKind)]>,
+        params: Vec<WasmValue>,
+        mem_access: &impl ExtrinsicsMemoryAccess,
+    ) -> Self {
+        let args = match kinds {
+            Some(kinds) => kinds
+                .iter()
+                .zip(params.iter())
+                .map(|((name, kind), value)| {
+                    format!(
+                        "{}={}",
+                        name,
+                        format_input(*kind, value, &params, mem_access)
+                    )
+                })
+                .collect(),
+            None => params.iter().map(|param| format!("{:?}", param)).collect(),
+        };
+
+        CallRecord {
+            f_name,
+            params,
+            kinds,
+            args,
+            emitted: Vec::new(),
+            result: String::new(),
+            start: mem_access.monotonic_clock(),
+            duration: Duration::new(0, 0),
+        }
+    }
+
+    /// Fills the outcome of the call.
+    fn finish(&mut self, action: &ExtrinsicsAction, mem_access: &impl ExtrinsicsMemoryAccess) {
+        self.duration = mem_access
+            .monotonic_clock()
+            .checked_sub(self.start)
+            .unwrap_or_else(|| Duration::new(0, 0));
+
+        self.result = match action {
+            ExtrinsicsAction::Resume(Some(value)) => format_int(value),
+            ExtrinsicsAction::Resume(None) => String::from("()"),
+            ExtrinsicsAction::ProgramCrash => String::from("<crash>"),
+            ExtrinsicsAction::ProgramExit(code) => format!("<exit {}>", code),
+            ExtrinsicsAction::EmitMessage { .. }
+            | ExtrinsicsAction::EmitMessages(_)
+            | ExtrinsicsAction::EmitMessagesWaitFirst { .. } => unreachable!(),
+        };
+
+        if let (Some(kinds), ExtrinsicsAction::Resume(Some(result))) = (self.kinds, action) {
+            for (n, (name, kind)) in kinds.iter().enumerate() {
+                if let Some(output) =
+                    format_output(*kind, &self.params[n], &self.params, result, mem_access)
+                {
+                    self.args[n] = format!("{}={}", name, output);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for CallRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "call={} args=({})", self.f_name, self.args.join(", "))?;
+        if !self.emitted.is_empty() {
+            write!(f, " emitted=[{}]", self.emitted.join(", "))?;
+        }
+        write!(f, " result={} duration={:?}", self.result, self.duration)
+    }
+}
+
+/// Prints a parameter when the call starts.
+fn format_input(
+    kind: ParamKind,
+    value: &WasmValue,
+    params: &[WasmValue],
+    mem_access: &impl ExtrinsicsMemoryAccess,
+) -> String {
+    let decoded = match kind {
+        ParamKind::Int => return format_int(value),
+        ParamKind::Hex => return format!("{:#x}", as_u64(value)),
+        ParamKind::BufferIn { len } => read_buffer(mem_access, as_u32(value), as_u32(&params[len])),
+        ParamKind::IoVecsIn { len } => read_io_vectors(
+            mem_access,
+            as_u32(value),
+            as_u32(&params[len]),
+            u32::max_value(),
+        ),
+        ParamKind::InU64 => read_integer(mem_access, as_u32(value), 8).map(|v| v.to_string()),
+        ParamKind::Interface => {
+            read_interface(mem_access, as_u32(value)).map(|interface| format!("{:?}", interface))
+        }
+        ParamKind::Message { interface, len } => {
+            read_interface(mem_access, as_u32(&params[interface])).and_then(|interface| {
+                let (body, _) = read_io_vectors_raw(
+                    mem_access,
+                    as_u32(value),
+                    as_u32(&params[len]),
+                    u32::max_value(),
+                    usize::max_value(),
+                )?;
+                Some(format_message(&interface, &body))
+            })
+        }
+        ParamKind::MessageIds { len } => {
+            read_message_ids(mem_access, as_u32(value), as_u32(&params[len]))
+        }
+        ParamKind::Ptr
+        | ParamKind::BufferOut { .. }
+        | ParamKind::IoVecsOut { .. }
+        | ParamKind::OutU16
+        | ParamKind::OutU32
+        | ParamKind::OutU64
+        | ParamKind::OutMessageId { .. }
+        | ParamKind::OutNotification { .. } => return format!("{:#x}", as_u32(value)),
+    };
+
+    decoded.unwrap_or_else(|| format!("{:#x} <out of range>", as_u32(value)))
+}
+
+/// Prints a parameter written by the function after the call has finished and returned
+/// `result`.
+///
+/// Returns `None` if the parameter isn't written by the function or can't be decoded.
+fn format_output(
+    kind: ParamKind,
+    value: &WasmValue,
+    params: &[WasmValue],
+    result: &WasmValue,
+    mem_access: &impl ExtrinsicsMemoryAccess,
+) -> Option<String> {
+    let ptr = as_u32(value);
+
+    // `next_notification` returns the size of the notification, which has only been written if
+    // it isn't zero and fits in the buffer.
+    if let ParamKind::OutNotification { len } = kind {
+        let size = as_u32(result);
+        if size == 0 || size > as_u32(&params[len]) {
+            return None;
+        }
+        let notification = mem_access.read_memory(ptr..ptr.checked_add(size)?).ok()?;
+        return Some(format_notification(&notification));
+    }
+
+    // The values written by the other functions are only meaningful if they have returned 0,
+    // which indicates a success.
+    if !matches!(result, WasmValue::I32(0)) {
+        return None;
+    }
+
+    match kind {
+        ParamKind::BufferOut { len } => read_buffer(mem_access, ptr, as_u32(&params[len])),
+        ParamKind::IoVecsOut { len, written } => {
+            let written = read_integer(mem_access, as_u32(&params[written]), 4)?;
+            let written = u32::try_from(written).unwrap();
+            read_io_vectors(mem_access, ptr, as_u32(&params[len]), written)
+        }
+        ParamKind::OutU16 => Some(read_integer(mem_access, ptr, 2)?.to_string()),
+        ParamKind::OutU32 => Some(read_integer(mem_access, ptr, 4)?.to_string()),
+        ParamKind::OutU64 => Some(read_integer(mem_access, ptr, 8)?.to_string()),
+        ParamKind::OutMessageId { flags } if as_u64(&params[flags]) & 0x1 != 0 => {
+            Some(read_integer(mem_access, ptr, 8)?.to_string())
+        }
+        ParamKind::Int
+        | ParamKind::Hex
+        | ParamKind::Ptr
+        | ParamKind::BufferIn { .. }
+        | ParamKind::IoVecsIn { .. }
+        | ParamKind::InU64
+        | ParamKind::Interface
+        | ParamKind::Message { .. }
+        | ParamKind::MessageIds { .. }
+        | ParamKind::OutMessageId { .. }
+        | ParamKind::OutNotification { .. } => None,
+    }
+}
+
+/// Prints a notification written by `next_notification`. The bodies of the messages emitted on
+/// the interfaces known by the kernel are decoded.
+fn format_notification(notification: &[u8]) -> String {
+    use redshirt_interface_interface::ffi::{decode_notification, DecodedInterfaceOrDestroyed};
+
+    // Message identifiers are printed as integers, like the parameters of the functions.
+    if let Ok(answer) = redshirt_syscalls::ffi::decode_notification(notification) {
+        let body = match answer.actual_data {
+            Ok(body) => escape(body.as_ref(), false),
+            Err(()) => String::from("<error>"),
+        };
+        return format!(
+            "Answer {{ message_id: {}, body: {} }}",
+            u64::from(answer.message_id),
+            body
+        );
+    }
+
+    match decode_notification(notification) {
+        Ok(DecodedInterfaceOrDestroyed::Interface(notif)) => format!(
+            "Interface {{ interface: {:?}, message_id: {:?}, emitter: {:?}, message: {} }}",
+            notif.interface,
+            notif.message_id.map(u64::from),
+            notif.emitter_pid,
+            format_message(&notif.interface, &notif.actual_data.0)
+        ),
+        Ok(DecodedInterfaceOrDestroyed::ProcessDestroyed(notif)) => {
+            format!("ProcessDestroyed {{ pid: {:?} }}", notif.pid)
+        }
+        Err(()) => escape(notification, false),
+    }
+}
+
+/// Prints the body of a message emitted on the given interface.
+///
+/// If the interface is known by the kernel, the message is decoded and printed using its `Debug`
+/// implementation. Otherwise, or if it fails to decode, it is printed as raw bytes.
+fn format_message(interface: &InterfaceHash, body: &[u8]) -> String {
+    let decoders: &[(InterfaceHash, fn(EncodedMessage) -> Option<String>)] = &[
+        (
+            redshirt_console_interface::ffi::INTERFACE,
+            decode_debug::<redshirt_console_interface::ffi::ConsoleMessage>,
+        ),
+        (
+            redshirt_files_interface::ffi::INTERFACE,
+            decode_debug::<redshirt_files_interface::ffi::FilesMessage>,
+        ),
+        (
+            redshirt_interface_interface::ffi::INTERFACE,
+            decode_debug::<redshirt_interface_interface::ffi::InterfaceMessage>,
+        ),
+        (
+            redshirt_loader_interface::ffi::INTERFACE,
+            decode_debug::<redshirt_loader_interface::ffi::LoaderMessage>,
+        ),
+        (redshirt_log_interface::ffi::INTERFACE, decode_log),
+        (
+            redshirt_process_interface::ffi::INTERFACE,
+            decode_debug::<redshirt_process_interface::ffi::ProcessMessage>,
+        ),
+        (
+            redshirt_random_interface::ffi::INTERFACE,
+            decode_debug::<redshirt_random_interface::ffi::RandomMessage>,
+        ),
+        (
+            redshirt_system_time_interface::ffi::INTERFACE,
+            decode_debug::<redshirt_system_time_interface::ffi::TimeMessage>,
+        ),
+        (
+            redshirt_tcp_interface::ffi::INTERFACE,
+            decode_debug::<redshirt_tcp_interface::ffi::TcpMessage>,
+        ),
+        (
+            redshirt_threads_interface::ffi::INTERFACE,
+            decode_debug::<redshirt_threads_interface::ffi::ThreadsMessage>,
+        ),
+        (
+            redshirt_time_interface::ffi::INTERFACE,
+            decode_debug::<redshirt_time_interface::ffi::TimeMessage>,
+        ),
+    ];
+
+    let decoded = decoders
+        .iter()
+        .find(|(hash, _)| hash == interface)
+        .and_then(|(_, decode)| decode(EncodedMessage(body.to_vec())));
+
+    match decoded {
+        Some(decoded) if decoded.chars().count() > MAX_DISPLAYED_MESSAGE_CHARS => {
+            let mut out = decoded
+                .chars()
+                .take(MAX_DISPLAYED_MESSAGE_CHARS)
+                .collect::<String>();
+            out.push_str("...");
+            out
+        }
+        Some(decoded) => decoded,
+        None => escape(body, false),
+    }
+}
+
+/// Decodes a message and prints it using its `Debug` implementation.
+fn decode_debug<T: Decode + fmt::Debug>(message: EncodedMessage) -> Option<String> {
+    Some(format!("{:?}", T::decode(message).ok()?))
+}
+
+/// Decodes a message emitted on the `log` interface and prints it.
+fn decode_log(message: EncodedMessage) -> Option<String> {
+    let decoded = redshirt_log_interface::ffi::DecodedLogMessage::decode(message).ok()?;
+    Some(format!("{:?} {:?}", decoded.level(), decoded.message()))
+}
+
+/// Prints an integer value in decimal.
+fn format_int(value: &WasmValue) -> String {
+    match value {
+        WasmValue::I32(v) => v.to_string(),
+        WasmValue::I64(v) => v.to_string(),
+        v => format!("{:?}", v),
+    }
+}
+
+/// Returns the bits of a value, reinterpreted as an unsigned integer.
+fn as_u64(value: &WasmValue) -> u64 {
+    match *value {
+        WasmValue::I32(v) => u64::from(v as u32),
+        WasmValue::I64(v) => v as u64,
+        WasmValue::F32(v) => u64::from(v),
+        WasmValue::F64(v) => v,
+    }
+}
+
+/// Returns the value of a pointer or length.
+fn as_u32(value: &WasmValue) -> u32 {
+    as_u64(value) as u32
+}
+
+/// Reads a little-endian unsigned integer of `num_bytes` bytes from memory. `num_bytes` must be
+/// at most 8.
+fn read_integer(mem_access: &impl ExtrinsicsMemoryAccess, ptr: u32, num_bytes: u32) -> Option<u64> {
+    let bytes = mem_access
+        .read_memory(ptr..ptr.checked_add(num_bytes)?)
+        .ok()?;
+    let mut buffer = [0; 8];
+    buffer[..bytes.len()].copy_from_slice(&bytes);
+    Some(u64::from_le_bytes(buffer))
+}
+
+/// Reads an interface hash from memory.
+fn read_interface(mem_access: &impl ExtrinsicsMemoryAccess, ptr: u32) -> Option<InterfaceHash> {
+    let bytes = mem_access.read_memory(ptr..ptr.checked_add(32)?).ok()?;
+    Some(InterfaceHash::from(
+        <[u8; 32]>::try_from(&bytes[..]).unwrap(),
+    ))
+}
+
+/// Reads a list of `num` message identifiers from memory and prints it. Empty entries are
+/// printed as `0`.
+fn read_message_ids(
+    mem_access: &impl ExtrinsicsMemoryAccess,
+    ptr: u32,
+    num: u32,
+) -> Option<String> {
+    let mut ids = Vec::new();
+    for n in 0..num {
+        if ids.len() >= MAX_DISPLAYED_BYTES / 8 {
+            ids.push(String::from("..."));
+            break;
+        }
+
+        let id = read_integer(mem_access, ptr.checked_add(n.checked_mul(8)?)?, 8)?;
+        ids.push(id.to_string());
+    }
+    Some(format!("[{}]", ids.join(", ")))
+}
+
+/// Reads a buffer from memory and prints it.
+fn read_buffer(mem_access: &impl ExtrinsicsMemoryAccess, ptr: u32, len: u32) -> Option<String> {
+    let to_read = cmp::min(len, u32::try_from(MAX_DISPLAYED_BYTES).unwrap());
+    let bytes = mem_access
+        .read_memory(ptr..ptr.checked_add(to_read)?)
+        .ok()?;
+    Some(escape(&bytes, to_read < len))
+}
+
+/// Reads the buffers pointed to by a list of `iovec`s and prints their concatenation, stopping
+/// after `limit` bytes.
+fn read_io_vectors(
+    mem_access: &impl ExtrinsicsMemoryAccess,
+    ptr: u32,
+    num: u32,
+    limit: u32,
+) -> Option<String> {
+    let (bytes, truncated) = read_io_vectors_raw(mem_access, ptr, num, limit, MAX_DISPLAYED_BYTES)?;
+    Some(escape(&bytes, truncated))
+}
+
+/// Reads the buffers pointed to by a list of `iovec`s and returns their concatenation, stopping
+/// after `limit` bytes. At most `max_bytes` bytes are read, in which case the returned boolean
+/// is true if the rest has been omitted.
+fn read_io_vectors_raw(
+    mem_access: &impl ExtrinsicsMemoryAccess,
+    ptr: u32,
+    num: u32,
+    limit: u32,
+    max_bytes: usize,
+) -> Option<(Vec<u8>, bool)> {
+    let mut bytes = Vec::new();
+    let mut remaining = limit;
+    let mut truncated = false;
+
+    for n in 0..num {
+        if remaining == 0 {
+            break;
+        }
+
+        if bytes.len() >= max_bytes {
+            truncated = true;
+            break;
+        }
+
+        let iovec_ptr = ptr.checked_add(n.checked_mul(8)?)?;
+        let buf = u32::try_from(read_integer(mem_access, iovec_ptr, 4)?).unwrap();
+        let buf_len =
+            u32::try_from(read_integer(mem_access, iovec_ptr.checked_add(4)?, 4)?).unwrap();
+        let buf_len = cmp::min(buf_len, remaining);
+        remaining -= buf_len;
+
+        let to_read = cmp::min(
+            buf_len,
+            u32::try_from(max_bytes - bytes.len()).unwrap_or(u32::max_value()),
+        );
+        truncated |= to_read < buf_len;
+        bytes.extend(
+            mem_access
+                .read_memory(buf..buf.checked_add(to_read)?)
+                .ok()?,
+        );
+    }
+
+    Some((bytes, truncated))
+}
+
+/// Prints bytes as a string literal, escaping the non-printable characters.
+///
+/// At most [`MAX_DISPLAYED_BYTES`] bytes are printed. If `truncated` is true, or if `bytes` is
+/// longer than that, the string is followed with `...`.
+fn escape(bytes: &[u8], truncated: bool) -> String {
+    let mut out = String::from("\"");
+    for byte in bytes.iter().take(MAX_DISPLAYED_BYTES) {
+        out.extend(core::ascii::escape_default(*byte).map(char::from));
+    }
+    out.push('"');
+    if truncated || bytes.len() > MAX_DISPLAYED_BYTES {
+        out.push_str("...");
+    }
+    out
+}
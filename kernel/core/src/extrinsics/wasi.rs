@@ -18,18 +18,25 @@
 // Reference for function signatures:
 // https://github.com/WebAssembly/wasi-libc/blob/e1149ab0677317c6c981bcbb5e4c159e4d2b9669/libc-bottom-half/headers/public/wasi/api.h
 
-use crate::extrinsics::{Extrinsics, ExtrinsicsAction, ExtrinsicsMemoryAccess, SupportedExtrinsic};
-use crate::{sig, Encode as _, EncodedMessage, EncodedMessageRef, ThreadId, WasmValue};
+use crate::extrinsics::{
+    Extrinsics, ExtrinsicsAction, ExtrinsicsMemoryAccess, LaunchConfig, MessageToEmit,
+    SupportedExtrinsic,
+};
+use crate::{Encode as _, EncodedMessage, EncodedMessageRef, MessageId, ThreadId, WasmValue};
 
 use alloc::{
-    borrow::Cow,
+    collections::VecDeque,
     string::String,
     sync::Arc,
     vec,
     vec::{IntoIter, Vec},
 };
-use core::{cmp, convert::TryFrom as _, fmt};
+use core::{cmp, convert::TryFrom as _, fmt, mem};
+use either::Either;
 use hashbrown::HashMap;
+use redshirt_console_interface::ffi as console_ffi;
+use redshirt_files_interface::ffi as files_ffi;
+use redshirt_tcp_interface::ffi as tcp_ffi;
 use spinning_top::Spinlock;
 
 /// Implementation of the [`Extrinsics`] trait for WASI.
@@ -47,14 +54,43 @@ pub struct WasiExtrinsics {
     /// closing.
     file_descriptors: Spinlock<Vec<Option<FileDescriptor>>>,
 
-    /// Virtual file system accessible to the program.
+    /// Virtual file system accessible to the program. Lives entirely in memory, and is
+    /// destroyed alongside the program.
     file_system: Arc<Inode>,
+
+    /// `WaitMonotonic` messages emitted by `poll_oneoff` and not answered yet, with the value of
+    /// the monotonic clock they wait for. Calls to `poll_oneoff` reuse them if possible, and wait
+    /// for them in order to pick up their responses.
+    timers: Spinlock<Vec<(MessageId, u128)>>,
 }
 
+/// Number of connections that a listening socket accepts ahead of the calls to `sock_accept`.
+///
+/// The `tcp` interface has no concept of a socket that accepts connections. Instead, we open
+/// sockets listening on the same address, each of which becomes connected when a remote connects
+/// to it. Connection attempts are refused if none of them is listening.
+const LISTEN_BACKLOG: usize = 2;
+
+/// Maximum number of bytes requested from the `console` interface when waiting for the console
+/// to be ready to be read from.
+const CONSOLE_POLL_READ_LEN: u32 = 4096;
+
 #[derive(Debug)]
 enum FileDescriptor {
     /// Valid file descriptor but that points to nothing.
     Empty,
+    /// Reading is performed through the `console` interface.
+    ConsoleIn {
+        /// Data received from the `console` interface that must be returned by the next read.
+        read_buffer: Vec<u8>,
+        /// `Read` message sent to the `console` interface and not answered yet.
+        pending_read: Option<MessageId>,
+        /// Error to return by the next read.
+        read_error: Option<wasi::Errno>,
+        /// True if the console has been closed. Reads then return no data once `read_buffer` is
+        /// empty.
+        closed: bool,
+    },
     LogOut {
         /// We buffer data and emit a log message only on line splits.
         buffer: Vec<u8>,
@@ -64,7 +100,162 @@ enum FileDescriptor {
         inode: Arc<Inode>,
         /// Position of the cursor within the file. Always 0 for directories.
         file_cursor_pos: u64,
+        /// Flags of the file descriptor, as set by `path_open` or `fd_fdstat_set_flags`.
+        flags: wasi::Fdflags,
+        /// If the file descriptor has been opened before the program started, contains the path
+        /// reported to the program.
+        preopen_name: Option<String>,
+    },
+    /// Directory whose content is accessed through the `files` interface. Always opened before
+    /// the program started.
+    Mount {
+        /// Name of the volume, as known by the handler of the `files` interface.
+        volume: String,
+        /// Path reported to the program.
+        preopen_name: String,
+    },
+    /// File opened through the `files` interface.
+    MountedFile {
+        /// Identifier attributed to the file by the handler of the `files` interface.
+        file_id: u64,
+        /// Position of the cursor within the file.
+        file_cursor_pos: u64,
+        /// Size of the file when it was opened, updated with the writes performed through this
+        /// file descriptor.
+        size: u64,
+        /// Flags of the file descriptor, as set by `path_open` or `fd_fdstat_set_flags`.
+        flags: wasi::Fdflags,
     },
+    /// Socket that accepts TCP connections through the `tcp` interface. Always opened before
+    /// the program started.
+    TcpListener {
+        /// IPv6 address to listen on.
+        ip: [u16; 8],
+        /// TCP port to listen on.
+        port: u16,
+        /// `Open` messages sent to the `tcp` interface in order to accept connections, and not
+        /// answered yet.
+        pending_accepts: Vec<MessageId>,
+        /// Identifiers of the sockets that have been accepted, or `Err` if accepting has failed,
+        /// that must be returned by the next calls to `sock_accept`.
+        accepted: VecDeque<Result<u32, ()>>,
+    },
+    /// TCP connection opened through the `tcp` interface.
+    TcpSocket {
+        /// Identifier attributed to the socket by the handler of the `tcp` interface.
+        socket_id: u32,
+        /// Data received from the `tcp` interface that must be returned by the next read.
+        read_buffer: Vec<u8>,
+        /// `Read` message sent to the `tcp` interface and not answered yet.
+        pending_read: Option<MessageId>,
+        /// Error to return by the next read.
+        read_error: Option<wasi::Errno>,
+        /// Flags of the file descriptor, as set by `sock_accept` or `fd_fdstat_set_flags`.
+        flags: wasi::Fdflags,
+    },
+}
+
+impl FileDescriptor {
+    /// Returns true if reading from this file descriptor, or accepting a connection in the case
+    /// of a listening socket, would block.
+    fn read_would_block(&self) -> bool {
+        match self {
+            FileDescriptor::ConsoleIn {
+                read_buffer,
+                read_error,
+                closed,
+                ..
+            } => read_buffer.is_empty() && read_error.is_none() && !closed,
+            FileDescriptor::TcpSocket {
+                read_buffer,
+                read_error,
+                ..
+            } => read_buffer.is_empty() && read_error.is_none(),
+            FileDescriptor::TcpListener { accepted, .. } => accepted.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Returns the messages to emit, in addition to the ones that are already pending, in order
+    /// for this file descriptor to become ready to be read from. The identifiers of the emitted
+    /// messages must then be passed to [`FileDescriptor::record_read_message`].
+    ///
+    /// `max_len` is the maximum number of bytes to request from the `console` interface.
+    fn read_messages_to_emit(&self, max_len: u32) -> Vec<MessageToEmit> {
+        match self {
+            FileDescriptor::ConsoleIn {
+                pending_read: None, ..
+            } => vec![MessageToEmit {
+                interface: console_ffi::INTERFACE,
+                message: console_ffi::ConsoleMessage::Read { max_len }.encode(),
+                response_expected: true,
+            }],
+            FileDescriptor::TcpSocket {
+                socket_id,
+                pending_read: None,
+                ..
+            } => vec![MessageToEmit {
+                interface: tcp_ffi::INTERFACE,
+                message: tcp_ffi::TcpMessage::Read(tcp_ffi::TcpRead {
+                    socket_id: *socket_id,
+                })
+                .encode(),
+                response_expected: true,
+            }],
+            FileDescriptor::TcpListener {
+                ip,
+                port,
+                pending_accepts,
+                ..
+            } => (pending_accepts.len()..LISTEN_BACKLOG)
+                .map(|_| MessageToEmit {
+                    interface: tcp_ffi::INTERFACE,
+                    message: tcp_ffi::TcpMessage::Open(tcp_ffi::TcpOpen {
+                        listen: true,
+                        ip: *ip,
+                        port: *port,
+                    })
+                    .encode(),
+                    response_expected: true,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the messages emitted on behalf of this file descriptor that haven't been
+    /// answered yet.
+    ///
+    /// The responses to these messages are waited for by whichever thread reads from, or polls,
+    /// this file descriptor.
+    // TODO: if multiple threads wait for the same message, only one of them is woken up
+    fn pending_read_messages(&self) -> Vec<MessageId> {
+        match self {
+            FileDescriptor::ConsoleIn { pending_read, .. }
+            | FileDescriptor::TcpSocket { pending_read, .. } => {
+                pending_read.iter().cloned().collect()
+            }
+            FileDescriptor::TcpListener {
+                pending_accepts, ..
+            } => pending_accepts.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Records that a message returned by [`FileDescriptor::read_messages_to_emit`] has been
+    /// emitted.
+    fn record_read_message(&mut self, message_id: MessageId) {
+        match self {
+            FileDescriptor::ConsoleIn { pending_read, .. }
+            | FileDescriptor::TcpSocket { pending_read, .. } => {
+                *pending_read = Some(message_id);
+            }
+            FileDescriptor::TcpListener {
+                pending_accepts, ..
+            } => pending_accepts.push(message_id),
+            _ => {}
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -73,235 +264,242 @@ enum Inode {
         entries: Spinlock<HashMap<String, Arc<Inode>, fnv::FnvBuildHasher>>,
     },
     File {
-        content: Vec<u8>,
+        /// Content of the file. Shared between all the file descriptors pointing to this file.
+        content: Spinlock<Vec<u8>>,
     },
 }
 
 impl Default for WasiExtrinsics {
     fn default() -> WasiExtrinsics {
-        let fs_root = Arc::new(Inode::Directory {
-            entries: Spinlock::new({
-                let mut hashmap = HashMap::default();
-                /*// TODO: hack to toy with DOOM
-                hashmap.insert(
-                    "doom1.wad".to_string(),
-                    Arc::new(Inode::File {
-                        content: include_bytes!("../../../../DOOM/doom1.wad").to_vec(),
-                    }),
-                );*/
-                hashmap
-            }),
-        });
-
-        WasiExtrinsics {
-            args: vec![b"foo".to_vec()], // TODO: "foo" is a dummy program name
-            env_vars: vec![b"HOME=/home".to_vec()], // TODO: dummy
-            file_descriptors: Spinlock::new(vec![
-                // stdin
-                Some(FileDescriptor::Empty),
-                // stdout
-                Some(FileDescriptor::LogOut {
-                    level: redshirt_log_interface::Level::Info,
-                    buffer: Vec::new(),
-                }),
-                // stderr
-                Some(FileDescriptor::LogOut {
-                    level: redshirt_log_interface::Level::Error,
-                    buffer: Vec::new(),
-                }),
-                // pre-opened access to filesystem
-                Some(FileDescriptor::FilesystemEntry {
-                    inode: fs_root.clone(),
-                    file_cursor_pos: 0,
-                }),
-            ]),
-            file_system: fs_root,
-        }
+        WasiExtrinsics::with_launch_config(Default::default())
     }
 }
 
 /// Identifier of a WASI extrinsic.
 #[derive(Debug, Clone)]
-pub struct ExtrinsicId(ExtrinsicIdInner);
-
-#[derive(Debug, Clone)]
-enum ExtrinsicIdInner {
-    ArgsGet,
-    ArgsSizesGet,
-    ClockTimeGet,
-    EnvironGet,
-    EnvironSizesGet,
-    FdClose,
-    FdFdstatGet,
-    FdFdstatSetFlags,
-    FdFilestatGet,
-    FdPrestatDirName,
-    FdPrestatGet,
-    FdRead,
-    FdSeek,
-    FdTell,
-    FdWrite,
-    PathCreateDirectory,
-    PathFilestatGet,
-    PathOpen,
-    PollOneOff,
-    ProcExit,
-    RandomGet,
-    SchedYield,
-}
+pub struct ExtrinsicId(calls::Extrinsic);
 
 /// Context for a call to a WASI external function.
 pub struct Context(ContextInner);
 
 enum ContextInner {
-    WaitClockVal { out_ptr: u32 },
-    WaitRandom { out_ptr: u32, remaining_len: u32 },
-    TryFlushLogOut(usize),
+    WaitClockVal {
+        out_ptr: u32,
+    },
+    WaitRandom {
+        out_ptr: u32,
+        remaining_len: u32,
+    },
+    /// Waiting for the lines written by `fd_write` on a file descriptor that outputs to the
+    /// `log` interface to have been emitted.
+    WaitLogOut,
+    /// Waiting for the value of the monotonic clock, in order to determine when the clock
+    /// subscriptions of a `poll_oneoff` call expire.
+    PollGetTime {
+        /// Subscriptions to file descriptors that aren't ready to be read from.
+        fds: Vec<PollFd>,
+        clocks: Vec<PollClock>,
+        events_out: u32,
+        num_events_out: u32,
+    },
+    /// Waiting for one of the file descriptors of a `poll_oneoff` call to be ready to be read
+    /// from, or for the monotonic clock to reach the earliest deadline of that call.
+    PollWait {
+        /// Subscriptions to file descriptors that aren't ready to be read from.
+        fds: Vec<PollFd>,
+        /// `None` if the call has no clock subscription.
+        timeout: Option<PollTimeout>,
+        /// What each of the messages emitted while entering this state is for.
+        emitted_for: Vec<PollMessage>,
+        events_out: u32,
+        num_events_out: u32,
+    },
+    /// Waiting for the response to the `Open` message sent to the `files` interface by
+    /// `path_open`.
+    WaitFilesOpen {
+        opened_fd_ptr: u32,
+        flags: wasi::Fdflags,
+    },
+    /// Waiting for the response to the `Read` message sent to the `files` interface by
+    /// `fd_read`.
+    WaitFilesRead {
+        fd: usize,
+        /// Pointers and lengths of the buffers to write the data to, alternating.
+        out_buffers: Vec<u32>,
+        num_read_out: u32,
+    },
+    /// Waiting for the response to the `Write` message sent to the `files` interface by
+    /// `fd_write`.
+    WaitFilesWrite {
+        fd: usize,
+        /// Position of the cursor once the write has succeeded.
+        end_offset: u64,
+        num_written: u32,
+        num_written_out: u32,
+    },
+    /// Waiting for a response to one of the `Open` messages sent to the `tcp` interface on
+    /// behalf of the listening socket `fd`, for `sock_accept`.
+    WaitTcpAccept {
+        fd: usize,
+        opened_fd_ptr: u32,
+        flags: wasi::Fdflags,
+    },
+    /// Waiting for the response to the `Read` message sent to the `console` or `tcp` interface
+    /// on behalf of `fd`, for `fd_read` or `sock_recv`.
+    WaitBufferedRead {
+        fd: usize,
+        /// Pointers and lengths of the buffers to write the data to, alternating.
+        out_buffers: Vec<u32>,
+        num_read_out: u32,
+    },
+    /// Waiting for the response to the `Write` message sent to the `tcp` interface by
+    /// `fd_write` or `sock_send`.
+    WaitTcpWrite {
+        num_written: u32,
+        num_written_out: u32,
+    },
+    /// Waiting for the response to the `Close` message sent to the `tcp` interface by
+    /// `sock_shutdown`.
+    WaitTcpShutdown,
+    /// Waiting for the messages emitted by `fd_close` to have been emitted, and for the response
+    /// to the message that was pending on the closed file descriptor, if any.
+    WaitClose,
     Resume(Option<WasmValue>),
     Finished,
 }
 
+/// Clock subscription of a call to `poll_oneoff`.
+struct PollClock {
+    userdata: u64,
+    /// Timeout in nanoseconds.
+    timeout: u64,
+    /// If true, `timeout` is a value of the monotonic clock. If false, it is relative to the
+    /// moment when `poll_oneoff` has been called.
+    absolute: bool,
+}
+
+impl PollClock {
+    /// Returns the value of the monotonic clock when this subscription expires.
+    fn deadline(&self, now: u128) -> u128 {
+        if self.absolute {
+            u128::from(self.timeout)
+        } else {
+            now.saturating_add(u128::from(self.timeout))
+        }
+    }
+}
+
+/// Subscription of a call to `poll_oneoff` to a file descriptor being ready to be read from.
+struct PollFd {
+    userdata: u64,
+    fd: usize,
+}
+
+/// Clock subscriptions of a call to `poll_oneoff`, once the current time is known.
+struct PollTimeout {
+    /// Value of the monotonic clock when the earliest subscriptions expire.
+    deadline: u128,
+    /// Events to report once `deadline` is reached.
+    events: Vec<PollEvent>,
+}
+
+/// Message emitted by a call to `poll_oneoff`.
+enum PollMessage {
+    /// Message emitted on behalf of the given file descriptor, so that it becomes ready to be
+    /// read from.
+    Fd(usize),
+    /// `WaitMonotonic` message of the `time` interface, with the value it waits for.
+    Timer(u128),
+}
+
+/// Event to write back as the result of a call to `poll_oneoff`.
+struct PollEvent {
+    userdata: u64,
+    error: wasi::Errno,
+    ty: wasi::Eventtype,
+    /// For `fd_read` and `fd_write` events, number of bytes available.
+    nbytes: u64,
+}
+
 impl Extrinsics for WasiExtrinsics {
     type ExtrinsicId = ExtrinsicId;
     type Context = Context;
     type Iterator = IntoIter<SupportedExtrinsic<Self::ExtrinsicId>>;
 
+    fn with_launch_config(config: LaunchConfig) -> Self {
+        let file_system = Arc::new(Inode::Directory {
+            entries: Spinlock::new(HashMap::default()),
+        });
+
+        let mut file_descriptors = vec![
+            // stdin
+            Some(if config.console_stdin {
+                FileDescriptor::ConsoleIn {
+                    read_buffer: Vec::new(),
+                    pending_read: None,
+                    read_error: None,
+                    closed: false,
+                }
+            } else {
+                FileDescriptor::Empty
+            }),
+            // stdout
+            Some(FileDescriptor::LogOut {
+                level: redshirt_log_interface::Level::Info,
+                buffer: Vec::new(),
+            }),
+            // stderr
+            Some(FileDescriptor::LogOut {
+                level: redshirt_log_interface::Level::Error,
+                buffer: Vec::new(),
+            }),
+        ];
+
+        // Pre-opened directories must immediately follow stdin, stdout, and stderr, as the libc
+        // discovers them by iterating over file descriptors until `fd_prestat_get` fails.
+        for preopen in config.preopens {
+            let inode = match create_directory_all(&file_system, &preopen) {
+                Some(inode) => inode,
+                None => continue,
+            };
+
+            file_descriptors.push(Some(FileDescriptor::FilesystemEntry {
+                inode,
+                file_cursor_pos: 0,
+                flags: 0,
+                preopen_name: Some(preopen),
+            }));
+        }
+
+        for mount in config.mounts {
+            file_descriptors.push(Some(FileDescriptor::Mount {
+                volume: mount.volume,
+                preopen_name: mount.path,
+            }));
+        }
+
+        // Listening sockets come after all the directories, so that `fd_prestat_get` failing on
+        // them stops the libc's discovery.
+        for listener in config.tcp_listeners {
+            file_descriptors.push(Some(FileDescriptor::TcpListener {
+                ip: listener.ip,
+                port: listener.port,
+                pending_accepts: Vec::new(),
+                accepted: VecDeque::new(),
+            }));
+        }
+
+        WasiExtrinsics {
+            args: config.args,
+            env_vars: config.env_vars,
+            file_descriptors: Spinlock::new(file_descriptors),
+            file_system,
+            timers: Spinlock::new(Vec::new()),
+        }
+    }
+
     fn supported_extrinsics() -> Self::Iterator {
-        vec![
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::ArgsGet),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("args_get"),
-                signature: sig!((I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::ArgsSizesGet),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("args_sizes_get"),
-                signature: sig!((I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::ClockTimeGet),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("clock_time_get"),
-                signature: sig!((I32, I64, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::EnvironGet),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("environ_get"),
-                signature: sig!((I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::EnvironSizesGet),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("environ_sizes_get"),
-                signature: sig!((I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::FdClose),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("fd_close"),
-                signature: sig!((I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::FdFdstatGet),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("fd_fdstat_get"),
-                signature: sig!((I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::FdFdstatSetFlags),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("fd_fdstat_set_flags"),
-                signature: sig!((I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::FdFilestatGet),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("fd_filestat_get"),
-                signature: sig!((I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::FdPrestatDirName),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("fd_prestat_dir_name"),
-                signature: sig!((I32, I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::FdPrestatGet),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("fd_prestat_get"),
-                signature: sig!((I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::FdRead),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("fd_read"),
-                signature: sig!((I32, I32, I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::FdSeek),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("fd_seek"),
-                signature: sig!((I32, I64, I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::FdTell),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("fd_tell"),
-                signature: sig!((I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::FdWrite),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("fd_write"),
-                signature: sig!((I32, I32, I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::PathCreateDirectory),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("path_create_directory"),
-                signature: sig!((I32, I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::PathFilestatGet),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("path_filestat_get"),
-                signature: sig!((I32, I32, I32, I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::PathOpen),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("path_open"),
-                signature: sig!((I32, I32, I32, I32, I32, I64, I64, I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::PollOneOff),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("poll_oneoff"),
-                signature: sig!((I32, I32, I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::ProcExit),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("proc_exit"),
-                signature: sig!((I32)),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::RandomGet),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("random_get"),
-                signature: sig!((I32, I32) -> I32),
-            },
-            SupportedExtrinsic {
-                id: ExtrinsicId(ExtrinsicIdInner::SchedYield),
-                wasm_interface: Cow::Borrowed("wasi_snapshot_preview1"),
-                function_name: Cow::Borrowed("sched_yield"),
-                signature: sig!(() -> I32),
-            },
-        ]
-        .into_iter()
+        Vec::from(calls::supported_extrinsics(ExtrinsicId)).into_iter()
     }
 
     fn new_context(
@@ -314,30 +512,7 @@ impl Extrinsics for WasiExtrinsics {
         // All these function calls have the same return type. They return an error if there is
         // something fundamentally wrong in the system call (for example: a pointer to
         // out-of-bounds memory) and we have to make the program crash.
-        let result = match id.0 {
-            ExtrinsicIdInner::ArgsGet => args_get(self, params, mem_access),
-            ExtrinsicIdInner::ArgsSizesGet => args_sizes_get(self, params, mem_access),
-            ExtrinsicIdInner::ClockTimeGet => clock_time_get(self, params, mem_access),
-            ExtrinsicIdInner::EnvironGet => environ_get(self, params, mem_access),
-            ExtrinsicIdInner::EnvironSizesGet => environ_sizes_get(self, params, mem_access),
-            ExtrinsicIdInner::FdClose => fd_close(self, params, mem_access),
-            ExtrinsicIdInner::FdFdstatGet => fd_fdstat_get(self, params, mem_access),
-            ExtrinsicIdInner::FdFdstatSetFlags => unimplemented!(),
-            ExtrinsicIdInner::FdFilestatGet => fd_filestat_get(self, params, mem_access),
-            ExtrinsicIdInner::FdPrestatDirName => fd_prestat_dir_name(self, params, mem_access),
-            ExtrinsicIdInner::FdPrestatGet => fd_prestat_get(self, params, mem_access),
-            ExtrinsicIdInner::FdRead => fd_read(self, params, mem_access),
-            ExtrinsicIdInner::FdSeek => fd_seek(self, params, mem_access),
-            ExtrinsicIdInner::FdTell => unimplemented!(),
-            ExtrinsicIdInner::FdWrite => fd_write(self, params, mem_access),
-            ExtrinsicIdInner::PathCreateDirectory => unimplemented!(),
-            ExtrinsicIdInner::PathFilestatGet => path_filestat_get(self, params, mem_access),
-            ExtrinsicIdInner::PathOpen => path_open(self, params, mem_access),
-            ExtrinsicIdInner::PollOneOff => poll_oneoff(self, params, mem_access),
-            ExtrinsicIdInner::ProcExit => proc_exit(self, params, mem_access),
-            ExtrinsicIdInner::RandomGet => random_get(self, params, mem_access),
-            ExtrinsicIdInner::SchedYield => sched_yield(self, params, mem_access),
-        };
+        let result = calls::call(id.0, self, params, mem_access);
 
         match result {
             Ok((context, action)) => (Context(context), action),
@@ -351,9 +526,21 @@ impl Extrinsics for WasiExtrinsics {
     fn inject_message_response(
         &self,
         ctxt: &mut Self::Context,
-        response: Option<EncodedMessageRef>,
+        response: Option<Result<EncodedMessageRef, ()>>,
         mem_access: &mut impl ExtrinsicsMemoryAccess,
     ) -> ExtrinsicsAction {
+        // An error answer means that the handler of the interface has failed to process the
+        // message, for example because it has crashed. This is reported to the program as an
+        // I/O error.
+        let response = match response {
+            Some(Ok(response)) => Some(response),
+            Some(Err(())) => {
+                ctxt.0 = ContextInner::Finished;
+                return ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(wasi::ERRNO_IO))));
+            }
+            None => None,
+        };
+
         match ctxt.0 {
             ContextInner::WaitClockVal { out_ptr } => {
                 let response = response.unwrap();
@@ -416,47 +603,374 @@ impl Extrinsics for WasiExtrinsics {
                     }
                 }
             }
-            ContextInner::TryFlushLogOut(fd) => {
-                let mut file_descriptors_lock = self.file_descriptors.lock();
-                let file_descriptor = {
-                    match file_descriptors_lock.get_mut(fd).and_then(|v| v.as_mut()) {
-                        Some(fd) => fd,
-                        None => {
-                            ctxt.0 = ContextInner::Finished;
-                            return ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
-                        }
-                    }
+            ContextInner::PollGetTime {
+                ref mut fds,
+                ref clocks,
+                events_out,
+                num_events_out,
+            } => {
+                let response = response.unwrap();
+                // TODO: extra copy
+                let now: u128 = match EncodedMessage::from(response).decode() {
+                    Ok(v) => v,
+                    Err(_) => return ExtrinsicsAction::ProgramCrash,
                 };
 
-                if let FileDescriptor::LogOut { level, buffer } = file_descriptor {
-                    if let Some(split_pos) = buffer.iter().position(|c| *c == b'\n') {
-                        let mut encoded_message = Vec::new();
-                        encoded_message.push(u8::from(*level));
-                        encoded_message.extend(buffer.drain(..split_pos));
-                        buffer.remove(0);
+                // `clocks` is never empty.
+                let deadline = clocks.iter().map(|c| c.deadline(now)).min().unwrap();
+                let events = clocks
+                    .iter()
+                    .filter(|c| c.deadline(now) <= deadline)
+                    .map(|c| PollEvent {
+                        userdata: c.userdata,
+                        error: wasi::ERRNO_SUCCESS,
+                        ty: wasi::EVENTTYPE_CLOCK,
+                        nbytes: 0,
+                    })
+                    .collect::<Vec<_>>();
+
+                let fds = mem::take(fds);
+                ctxt.0 = ContextInner::Finished;
 
-                        let action = ExtrinsicsAction::EmitMessage {
-                            interface: redshirt_log_interface::ffi::INTERFACE,
-                            message: EncodedMessage(encoded_message),
-                            response_expected: false,
-                        };
+                if deadline <= now {
+                    let mut ready_events = ready_poll_events(&self.file_descriptors.lock(), &fds);
+                    ready_events.extend(events);
+                    return match write_poll_events(
+                        mem_access,
+                        events_out,
+                        num_events_out,
+                        &ready_events,
+                    ) {
+                        Ok(()) => ExtrinsicsAction::Resume(Some(WasmValue::I32(0))),
+                        Err(_) => ExtrinsicsAction::ProgramCrash,
+                    };
+                }
 
-                        ctxt.0 = ContextInner::TryFlushLogOut(fd);
+                let timeout = PollTimeout { deadline, events };
+                match poll_wait(
+                    self,
+                    fds,
+                    Some(timeout),
+                    events_out,
+                    num_events_out,
+                    mem_access,
+                ) {
+                    Ok((context, action)) => {
+                        ctxt.0 = context;
                         action
-                    } else {
+                    }
+                    Err(_) => ExtrinsicsAction::ProgramCrash,
+                }
+            }
+            ContextInner::WaitFilesOpen {
+                opened_fd_ptr,
+                flags,
+            } => {
+                ctxt.0 = ContextInner::Finished;
+
+                let response = response.unwrap();
+                // TODO: extra copy
+                let response: files_ffi::OpenResponse =
+                    match EncodedMessage::from(response).decode() {
+                        Ok(v) => v,
+                        Err(_) => return ExtrinsicsAction::ProgramCrash,
+                    };
+
+                let opened = match response.result {
+                    Ok(o) => o,
+                    Err(err) => {
+                        let ret = Some(WasmValue::I32(From::from(files_error_to_errno(&err))));
+                        return ExtrinsicsAction::Resume(ret);
+                    }
+                };
+
+                let new_fd = allocate_file_descriptor(
+                    &mut self.file_descriptors.lock(),
+                    FileDescriptor::MountedFile {
+                        file_id: opened.file_id,
+                        file_cursor_pos: 0,
+                        size: opened.size,
+                        flags,
+                    },
+                );
+
+                match mem_access.write_memory(opened_fd_ptr, &new_fd.to_le_bytes()) {
+                    Ok(()) => ExtrinsicsAction::Resume(Some(WasmValue::I32(0))),
+                    Err(_) => ExtrinsicsAction::ProgramCrash,
+                }
+            }
+            ContextInner::WaitFilesRead {
+                fd,
+                ref out_buffers,
+                num_read_out,
+            } => {
+                let response = response.unwrap();
+                // TODO: extra copy
+                let response: files_ffi::ReadResponse =
+                    match EncodedMessage::from(response).decode() {
+                        Ok(v) => v,
+                        Err(_) => return ExtrinsicsAction::ProgramCrash,
+                    };
+
+                let result = match response.result {
+                    Ok(data) => {
+                        files_read_finished(self, fd, out_buffers, num_read_out, &data, mem_access)
+                    }
+                    Err(err) => {
                         ctxt.0 = ContextInner::Finished;
-                        ExtrinsicsAction::Resume(Some(WasmValue::I32(0)))
+                        let ret = Some(WasmValue::I32(From::from(files_error_to_errno(&err))));
+                        return ExtrinsicsAction::Resume(ret);
                     }
-                } else {
-                    ctxt.0 = ContextInner::Finished;
-                    ExtrinsicsAction::Resume(Some(WasmValue::I32(0)))
+                };
+
+                ctxt.0 = ContextInner::Finished;
+                match result {
+                    Ok(()) => ExtrinsicsAction::Resume(Some(WasmValue::I32(0))),
+                    Err(_) => ExtrinsicsAction::ProgramCrash,
+                }
+            }
+            ContextInner::WaitFilesWrite {
+                fd,
+                end_offset,
+                num_written,
+                num_written_out,
+            } => {
+                ctxt.0 = ContextInner::Finished;
+
+                let response = response.unwrap();
+                // TODO: extra copy
+                let response: files_ffi::WriteResponse =
+                    match EncodedMessage::from(response).decode() {
+                        Ok(v) => v,
+                        Err(_) => return ExtrinsicsAction::ProgramCrash,
+                    };
+
+                if let Err(err) = response.result {
+                    let ret = Some(WasmValue::I32(From::from(files_error_to_errno(&err))));
+                    return ExtrinsicsAction::Resume(ret);
+                }
+
+                if let Some(Some(FileDescriptor::MountedFile {
+                    file_cursor_pos,
+                    size,
+                    ..
+                })) = self.file_descriptors.lock().get_mut(fd)
+                {
+                    *file_cursor_pos = end_offset;
+                    *size = cmp::max(*size, end_offset);
+                }
+
+                match mem_access.write_memory(num_written_out, &num_written.to_le_bytes()) {
+                    Ok(()) => ExtrinsicsAction::Resume(Some(WasmValue::I32(0))),
+                    Err(_) => ExtrinsicsAction::ProgramCrash,
+                }
+            }
+            ContextInner::WaitTcpWrite {
+                num_written,
+                num_written_out,
+            } => {
+                ctxt.0 = ContextInner::Finished;
+
+                let response = response.unwrap();
+                // TODO: extra copy
+                let response: tcp_ffi::TcpWriteResponse =
+                    match EncodedMessage::from(response).decode() {
+                        Ok(v) => v,
+                        Err(_) => return ExtrinsicsAction::ProgramCrash,
+                    };
+
+                if let Err(err) = response.result {
+                    let errno = match err {
+                        tcp_ffi::TcpWriteError::FinAlreaySent => wasi::ERRNO_PIPE,
+                        tcp_ffi::TcpWriteError::ConnectionFinished => wasi::ERRNO_NOTCONN,
+                        tcp_ffi::TcpWriteError::InvalidSocket => wasi::ERRNO_BADF,
+                    };
+                    return ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(errno))));
                 }
+
+                match mem_access.write_memory(num_written_out, &num_written.to_le_bytes()) {
+                    Ok(()) => ExtrinsicsAction::Resume(Some(WasmValue::I32(0))),
+                    Err(_) => ExtrinsicsAction::ProgramCrash,
+                }
+            }
+            ContextInner::WaitTcpShutdown => {
+                ctxt.0 = ContextInner::Finished;
+
+                let response = response.unwrap();
+                // TODO: extra copy
+                let response: tcp_ffi::TcpCloseResponse =
+                    match EncodedMessage::from(response).decode() {
+                        Ok(v) => v,
+                        Err(_) => return ExtrinsicsAction::ProgramCrash,
+                    };
+
+                let errno = match response.result {
+                    // Shutting down the writing side multiple times isn't an error.
+                    Ok(()) | Err(tcp_ffi::TcpCloseError::FinAlreaySent) => wasi::ERRNO_SUCCESS,
+                    Err(tcp_ffi::TcpCloseError::ConnectionFinished) => wasi::ERRNO_NOTCONN,
+                    Err(tcp_ffi::TcpCloseError::InvalidSocket) => wasi::ERRNO_BADF,
+                };
+                ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(errno))))
             }
             ContextInner::Resume(value) => {
                 ctxt.0 = ContextInner::Finished;
                 ExtrinsicsAction::Resume(value)
             }
-            ContextInner::Finished => unreachable!(),
+            ContextInner::WaitLogOut
+            | ContextInner::PollWait { .. }
+            | ContextInner::WaitTcpAccept { .. }
+            | ContextInner::WaitBufferedRead { .. }
+            | ContextInner::WaitClose
+            | ContextInner::Finished => unreachable!(),
+        }
+    }
+
+    fn inject_message_responses(
+        &self,
+        ctxt: &mut Self::Context,
+        responses: Vec<Option<Result<EncodedMessage, ()>>>,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        match ctxt.0 {
+            ContextInner::WaitLogOut | ContextInner::WaitClose => {
+                debug_assert!(responses.iter().all(|r| r.is_none()));
+                ctxt.0 = ContextInner::Finished;
+                ExtrinsicsAction::Resume(Some(WasmValue::I32(0)))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn inject_first_response(
+        &self,
+        ctxt: &mut Self::Context,
+        emitted: Vec<Option<MessageId>>,
+        message_id: MessageId,
+        response: Result<EncodedMessage, ()>,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        let result = match ctxt.0 {
+            ContextInner::PollWait {
+                ref mut fds,
+                ref mut timeout,
+                ref emitted_for,
+                events_out,
+                num_events_out,
+            } => {
+                let fds = mem::take(fds);
+                let mut timeout = timeout.take();
+                let mut file_descriptors_lock = self.file_descriptors.lock();
+                let mut timers = self.timers.lock();
+
+                // All the messages emitted by `poll_wait` expect a response.
+                for (target, message_id) in emitted_for.iter().zip(emitted) {
+                    let message_id = message_id.unwrap();
+                    match *target {
+                        PollMessage::Fd(fd) => {
+                            if let Some(Some(file_descriptor)) = file_descriptors_lock.get_mut(fd) {
+                                file_descriptor.record_read_message(message_id);
+                            }
+                        }
+                        PollMessage::Timer(value) => timers.push((message_id, value)),
+                    }
+                }
+
+                // A timer that fires before the deadline of this call has been emitted by a
+                // previous call, and only means that we must continue waiting.
+                let mut timed_out = false;
+                if let Some(pos) = timers.iter().position(|(id, _)| *id == message_id) {
+                    let (_, value) = timers.remove(pos);
+                    if response.is_err() {
+                        ctxt.0 = ContextInner::Finished;
+                        let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_IO)));
+                        return ExtrinsicsAction::Resume(ret);
+                    }
+                    timed_out = timeout.as_ref().map_or(false, |t| value >= t.deadline);
+                } else if dispatch_read_response(&mut file_descriptors_lock, message_id, response)
+                    .is_err()
+                {
+                    ctxt.0 = ContextInner::Finished;
+                    return ExtrinsicsAction::ProgramCrash;
+                }
+
+                drop(timers);
+                let mut events = ready_poll_events(&file_descriptors_lock, &fds);
+                drop(file_descriptors_lock);
+                if timed_out {
+                    events.extend(timeout.take().unwrap().events);
+                }
+
+                if events.is_empty() {
+                    poll_wait(self, fds, timeout, events_out, num_events_out, mem_access)
+                } else {
+                    write_poll_events(mem_access, events_out, num_events_out, &events).map(|()| {
+                        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+                        (ContextInner::Finished, action)
+                    })
+                }
+            }
+            ContextInner::WaitBufferedRead {
+                fd,
+                ref mut out_buffers,
+                num_read_out,
+            } => {
+                let out_buffers = mem::take(out_buffers);
+                let mut file_descriptors_lock = self.file_descriptors.lock();
+                record_read_messages(&mut file_descriptors_lock, fd, emitted);
+                match dispatch_read_response(&mut file_descriptors_lock, message_id, response) {
+                    Ok(()) => match file_descriptors_lock.get_mut(fd).and_then(|f| f.as_mut()) {
+                        Some(file_descriptor) => buffered_read(
+                            fd,
+                            file_descriptor,
+                            out_buffers,
+                            num_read_out,
+                            mem_access,
+                        ),
+                        // The file descriptor has been closed in the meanwhile.
+                        None => Ok((
+                            ContextInner::Finished,
+                            ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(
+                                wasi::ERRNO_BADF,
+                            )))),
+                        )),
+                    },
+                    Err(err) => Err(err),
+                }
+            }
+            ContextInner::WaitTcpAccept {
+                fd,
+                opened_fd_ptr,
+                flags,
+            } => {
+                let mut file_descriptors_lock = self.file_descriptors.lock();
+                record_read_messages(&mut file_descriptors_lock, fd, emitted);
+                match dispatch_read_response(&mut file_descriptors_lock, message_id, response) {
+                    Ok(()) => tcp_accept(
+                        &mut file_descriptors_lock,
+                        fd,
+                        flags,
+                        opened_fd_ptr,
+                        mem_access,
+                    ),
+                    Err(err) => Err(err),
+                }
+            }
+            ContextInner::WaitClose => Ok((
+                ContextInner::Finished,
+                ExtrinsicsAction::Resume(Some(WasmValue::I32(0))),
+            )),
+            _ => unreachable!(),
+        };
+
+        match result {
+            Ok((context, action)) => {
+                ctxt.0 = context;
+                action
+            }
+            Err(_) => {
+                ctxt.0 = ContextInner::Finished;
+                ExtrinsicsAction::ProgramCrash
+            }
         }
     }
 }
@@ -465,12 +979,13 @@ impl Extrinsics for WasiExtrinsics {
 //
 // # About unwrapping and panics
 //
-// It is allowed and encouraged to panic in case of an anomaly in the number or the types of
-// arguments. This is because function signatures have normally been verified before the call is
-// made.
+// The parameters passed by the Wasm code are decoded by code generated by the `extrinsics`
+// attribute, which panics in case of an anomaly in their number or types. This is because
+// function signatures have been verified when the program was loaded. Parameters declared as
+// `u32` or `u64` are obtained by reinterpreting the bits of the corresponding `i32` or `i64`.
 //
-// Any other error condition, including for example converting `i32` parameters to `u32`, should
-// be handled by not panicking.
+// Any other error condition, including for example converting parameters to `usize`, should be
+// handled by not panicking.
 
 /// Dummy error type that "absorbs" all possible error types.
 struct WasiCallErr;
@@ -480,382 +995,1942 @@ impl<T: fmt::Debug> From<T> for WasiCallErr {
     }
 }
 
-fn args_get(
-    state: &WasiExtrinsics,
-    params: impl ExactSizeIterator<Item = WasmValue>,
-    mem_access: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    args_or_env_get(&state.args, params, mem_access)
-}
-
-fn args_sizes_get(
-    state: &WasiExtrinsics,
-    params: impl ExactSizeIterator<Item = WasmValue>,
-    mem_access: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    args_or_env_sizes_get(&state.args, params, mem_access)
-}
-
-fn clock_time_get(
-    _: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
-    _: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let clock_id = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    let _precision = params.next().unwrap().into_i64().unwrap();
+/// Functions that implement the WASI extrinsics.
+///
+/// The list of supported extrinsics, and the decoding of the parameters passed by the Wasm code,
+/// are generated from the signatures of the functions annotated with `#[extrinsic]`.
+#[redshirt_core_proc_macros::extrinsics(wasm_interface = "wasi_snapshot_preview1", crate = "crate")]
+mod calls {
+    use super::*;
+
+    #[extrinsic(-> i32)]
+    fn args_get(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        argv: u32,
+        argv_buf: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        args_or_env_get(&state.args, mem_access, argv, argv_buf)
+    }
 
-    let time_out = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
+    #[extrinsic(-> i32)]
+    fn args_sizes_get(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        argc_out: u32,
+        argv_buf_size_out: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        args_or_env_sizes_get(&state.args, mem_access, argc_out, argv_buf_size_out)
+    }
 
-    match clock_id {
-        wasi::CLOCKID_REALTIME => {
+    #[extrinsic(-> i32)]
+    fn clock_time_get(
+        _: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        clock_id: u32,
+        _precision: i64,
+        time_out: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        match clock_id {
+            wasi::CLOCKID_REALTIME => {
+                let action = ExtrinsicsAction::EmitMessage {
+                    interface: redshirt_system_time_interface::ffi::INTERFACE,
+                    message: redshirt_system_time_interface::ffi::TimeMessage::GetSystem.encode(),
+                    response_expected: true,
+                };
+
+                let context = ContextInner::WaitClockVal { out_ptr: time_out };
+                Ok((context, action))
+            }
+            wasi::CLOCKID_MONOTONIC => {
+                let action = ExtrinsicsAction::EmitMessage {
+                    interface: redshirt_time_interface::ffi::INTERFACE,
+                    message: redshirt_time_interface::ffi::TimeMessage::GetMonotonic.encode(),
+                    response_expected: true,
+                };
+
+                let context = ContextInner::WaitClockVal { out_ptr: time_out };
+                Ok((context, action))
+            }
+            wasi::CLOCKID_PROCESS_CPUTIME_ID | wasi::CLOCKID_THREAD_CPUTIME_ID => {
+                let cpu_time = if clock_id == wasi::CLOCKID_PROCESS_CPUTIME_ID {
+                    mem_access.process_cpu_time()
+                } else {
+                    mem_access.thread_cpu_time()
+                };
+
+                let converted_value: wasi::Timestamp =
+                    wasi::Timestamp::try_from(cpu_time.as_nanos()).unwrap_or(wasi::Timestamp::MAX);
+                mem_access.write_memory(time_out, &converted_value.to_le_bytes())?;
+
+                let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+                Ok((ContextInner::Finished, action))
+            }
+            _ => Err(WasiCallErr),
+        }
+    }
+
+    #[extrinsic(-> i32)]
+    fn environ_get(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        environ: u32,
+        environ_buf: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        args_or_env_get(&state.env_vars, mem_access, environ, environ_buf)
+    }
+
+    #[extrinsic(-> i32)]
+    fn environ_sizes_get(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        environc_out: u32,
+        environ_buf_size_out: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        args_or_env_sizes_get(
+            &state.env_vars,
+            mem_access,
+            environc_out,
+            environ_buf_size_out,
+        )
+    }
+
+    #[extrinsic(-> i32)]
+    fn fd_close(
+        state: &WasiExtrinsics,
+        _: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let mut file_descriptors_lock = state.file_descriptors.lock();
+
+        let fd = usize::try_from(fd)?;
+
+        // Check validity of the file descriptor.
+        if file_descriptors_lock
+            .get(fd)
+            .map(|f| f.is_none())
+            .unwrap_or(true)
+        {
+            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+            let action = ExtrinsicsAction::Resume(ret);
+            return Ok((ContextInner::Finished, action));
+        }
+
+        let closed = file_descriptors_lock[fd].take();
+
+        // Clean up the tail of `file_descriptors_lock`.
+        while file_descriptors_lock
+            .last()
+            .map(|f| f.is_none())
+            .unwrap_or(false)
+        {
+            file_descriptors_lock.pop();
+        }
+        file_descriptors_lock.shrink_to_fit();
+
+        // Files opened through the `files` interface must be closed on the handler's side as well.
+        if let Some(FileDescriptor::MountedFile { file_id, .. }) = closed {
             let action = ExtrinsicsAction::EmitMessage {
-                interface: redshirt_system_time_interface::ffi::INTERFACE,
-                message: redshirt_system_time_interface::ffi::TimeMessage::GetSystem.encode(),
-                response_expected: true,
+                interface: files_ffi::INTERFACE,
+                message: files_ffi::FilesMessage::Close(file_id).encode(),
+                response_expected: false,
+            };
+            let context = ContextInner::Resume(Some(WasmValue::I32(0)));
+            return Ok((context, action));
+        }
+
+        // Same for sockets opened through the `tcp` interface. Destroying the socket makes the
+        // handler answer the pending read, if any, whose response we pick up.
+        if let Some(FileDescriptor::TcpSocket {
+            socket_id,
+            pending_read,
+            ..
+        }) = closed
+        {
+            let message = MessageToEmit {
+                interface: tcp_ffi::INTERFACE,
+                message: tcp_ffi::TcpMessage::Destroy(socket_id).encode(),
+                response_expected: false,
             };
+            let action = match pending_read {
+                Some(pending_read) => ExtrinsicsAction::EmitMessagesWaitFirst {
+                    messages: vec![message],
+                    wait_also: vec![pending_read],
+                },
+                None => ExtrinsicsAction::EmitMessages(vec![message]),
+            };
+            return Ok((ContextInner::WaitClose, action));
+        }
+
+        // Sockets that have been accepted but not returned by `sock_accept` are destroyed as
+        // well.
+        // TODO: the sockets that are still listening on behalf of the listener are leaked
+        if let Some(FileDescriptor::TcpListener { accepted, .. }) = closed {
+            let messages = accepted
+                .into_iter()
+                .filter_map(|socket_id| socket_id.ok())
+                .map(|socket_id| MessageToEmit {
+                    interface: tcp_ffi::INTERFACE,
+                    message: tcp_ffi::TcpMessage::Destroy(socket_id).encode(),
+                    response_expected: false,
+                })
+                .collect::<Vec<_>>();
+            if !messages.is_empty() {
+                return Ok((
+                    ContextInner::WaitClose,
+                    ExtrinsicsAction::EmitMessages(messages),
+                ));
+            }
+        }
+
+        // TODO: the `Read` message that might be pending on the console stays pending, as there
+        // is no guarantee that it is ever answered
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn fd_fdstat_get(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        stat_out_buf: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let file_descriptors_lock = state.file_descriptors.lock();
+
+        // Find out which file descriptor the user wants to write to.
+        let file_descriptor = {
+            let fd = usize::try_from(fd)?;
+            match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        // TODO: we mimic what wasmtime does, but documentation about these rights is pretty sparse
+        let dirs_rights = wasi::RIGHTS_FD_FDSTAT_SET_FLAGS
+            | wasi::RIGHTS_FD_SYNC
+            | wasi::RIGHTS_FD_ADVISE
+            | wasi::RIGHTS_PATH_CREATE_DIRECTORY
+            | wasi::RIGHTS_PATH_CREATE_FILE
+            | wasi::RIGHTS_PATH_LINK_SOURCE
+            | wasi::RIGHTS_PATH_LINK_TARGET
+            | wasi::RIGHTS_PATH_OPEN
+            | wasi::RIGHTS_FD_READDIR
+            | wasi::RIGHTS_PATH_READLINK
+            | wasi::RIGHTS_PATH_RENAME_SOURCE
+            | wasi::RIGHTS_PATH_RENAME_TARGET
+            | wasi::RIGHTS_PATH_FILESTAT_GET
+            | wasi::RIGHTS_PATH_FILESTAT_SET_SIZE
+            | wasi::RIGHTS_PATH_FILESTAT_SET_TIMES
+            | wasi::RIGHTS_FD_FILESTAT_GET
+            | wasi::RIGHTS_FD_FILESTAT_SET_SIZE
+            | wasi::RIGHTS_FD_FILESTAT_SET_TIMES
+            | wasi::RIGHTS_PATH_SYMLINK
+            | wasi::RIGHTS_PATH_REMOVE_DIRECTORY
+            | wasi::RIGHTS_PATH_UNLINK_FILE
+            | wasi::RIGHTS_POLL_FD_READWRITE;
+        let files_rights = wasi::RIGHTS_FD_DATASYNC
+            | wasi::RIGHTS_FD_READ
+            | wasi::RIGHTS_FD_SEEK
+            | wasi::RIGHTS_FD_FDSTAT_SET_FLAGS
+            | wasi::RIGHTS_FD_SYNC
+            | wasi::RIGHTS_FD_TELL
+            | wasi::RIGHTS_FD_WRITE
+            | wasi::RIGHTS_FD_ADVISE
+            | wasi::RIGHTS_FD_ALLOCATE
+            | wasi::RIGHTS_FD_FILESTAT_GET
+            | wasi::RIGHTS_FD_FILESTAT_SET_SIZE
+            | wasi::RIGHTS_FD_FILESTAT_SET_TIMES
+            | wasi::RIGHTS_POLL_FD_READWRITE;
+
+        let sockets_rights = wasi::RIGHTS_FD_FDSTAT_SET_FLAGS
+            | wasi::RIGHTS_FD_READ
+            | wasi::RIGHTS_FD_WRITE
+            | wasi::RIGHTS_POLL_FD_READWRITE
+            | wasi::RIGHTS_SOCK_SHUTDOWN;
+
+        let stat = match file_descriptor {
+            FileDescriptor::Empty => wasi::Fdstat {
+                fs_filetype: wasi::FILETYPE_CHARACTER_DEVICE,
+                fs_flags: 0,
+                fs_rights_base: 0,
+                fs_rights_inheriting: 0,
+            },
+            FileDescriptor::ConsoleIn { .. } => wasi::Fdstat {
+                fs_filetype: wasi::FILETYPE_CHARACTER_DEVICE,
+                fs_flags: 0,
+                fs_rights_base: wasi::RIGHTS_FD_READ | wasi::RIGHTS_POLL_FD_READWRITE,
+                fs_rights_inheriting: 0,
+            },
+            FileDescriptor::LogOut { .. } => wasi::Fdstat {
+                fs_filetype: wasi::FILETYPE_CHARACTER_DEVICE,
+                fs_flags: wasi::FDFLAGS_APPEND,
+                fs_rights_base: 0x0820_004a, // TODO: that's what wasmtime returns, don't know what it means
+                fs_rights_inheriting: 0x0820_004a, // TODO: that's what wasmtime returns, don't know what it means
+            },
+            FileDescriptor::FilesystemEntry { inode, flags, .. } => match **inode {
+                Inode::Directory { .. } => wasi::Fdstat {
+                    fs_filetype: wasi::FILETYPE_DIRECTORY,
+                    fs_flags: *flags,
+                    fs_rights_base: dirs_rights,
+                    fs_rights_inheriting: files_rights | dirs_rights,
+                },
+                Inode::File { .. } => wasi::Fdstat {
+                    fs_filetype: wasi::FILETYPE_REGULAR_FILE,
+                    fs_flags: *flags,
+                    fs_rights_base: files_rights,
+                    fs_rights_inheriting: files_rights,
+                },
+            },
+            FileDescriptor::Mount { .. } => wasi::Fdstat {
+                fs_filetype: wasi::FILETYPE_DIRECTORY,
+                fs_flags: 0,
+                fs_rights_base: dirs_rights,
+                fs_rights_inheriting: files_rights | dirs_rights,
+            },
+            FileDescriptor::MountedFile { flags, .. } => wasi::Fdstat {
+                fs_filetype: wasi::FILETYPE_REGULAR_FILE,
+                fs_flags: *flags,
+                fs_rights_base: files_rights,
+                fs_rights_inheriting: files_rights,
+            },
+            FileDescriptor::TcpListener { .. } => wasi::Fdstat {
+                fs_filetype: wasi::FILETYPE_SOCKET_STREAM,
+                fs_flags: 0,
+                fs_rights_base: wasi::RIGHTS_FD_READ | wasi::RIGHTS_POLL_FD_READWRITE,
+                fs_rights_inheriting: sockets_rights,
+            },
+            FileDescriptor::TcpSocket { flags, .. } => wasi::Fdstat {
+                fs_filetype: wasi::FILETYPE_SOCKET_STREAM,
+                fs_flags: *flags,
+                fs_rights_base: sockets_rights,
+                fs_rights_inheriting: 0,
+            },
+        };
+
+        // Note: this is a bit of dark magic, but it is the only solution at the moment.
+        // Can be tested with the following snippet:
+        // ```c
+        // #include <stdio.h>
+        // #include <wasi/api.h>
+        // int main() {
+        //     __wasi_fdstat_t* ptr = (__wasi_fdstat_t*)0x1000;
+        //     printf("%p %p %p %p %p %d\n", ptr, &ptr->fs_filetype, &ptr->fs_flags, &ptr->fs_rights_base, &ptr->fs_rights_inheriting, sizeof(__wasi_fdstat_t));
+        //     return 0;
+        // }
+        // ```
+        // Which prints `0x1000 0x1000 0x1002 0x1008 0x1010 24`
+        mem_access.write_memory(stat_out_buf, &[0; 24])?;
+        mem_access.write_memory(stat_out_buf, &[stat.fs_filetype])?;
+        mem_access.write_memory(
+            stat_out_buf.checked_add(2).ok_or(WasiCallErr)?,
+            &stat.fs_flags.to_le_bytes(),
+        )?;
+        mem_access.write_memory(
+            stat_out_buf.checked_add(8).ok_or(WasiCallErr)?,
+            &stat.fs_rights_base.to_le_bytes(),
+        )?;
+        mem_access.write_memory(
+            stat_out_buf.checked_add(16).ok_or(WasiCallErr)?,
+            &stat.fs_rights_inheriting.to_le_bytes(),
+        )?;
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn fd_fdstat_set_flags(
+        state: &WasiExtrinsics,
+        _: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        new_flags: i32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let mut file_descriptors_lock = state.file_descriptors.lock();
+
+        let file_descriptor = {
+            let fd = usize::try_from(fd)?;
+            match file_descriptors_lock.get_mut(fd).and_then(|v| v.as_mut()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        let new_flags = wasi::Fdflags::try_from(new_flags)?;
+
+        match file_descriptor {
+            FileDescriptor::Empty
+            | FileDescriptor::ConsoleIn { .. }
+            | FileDescriptor::LogOut { .. }
+            | FileDescriptor::Mount { .. }
+            | FileDescriptor::TcpListener { .. } => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSUP)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::FilesystemEntry { flags, .. }
+            | FileDescriptor::MountedFile { flags, .. }
+            | FileDescriptor::TcpSocket { flags, .. } => *flags = new_flags,
+        }
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn fd_filestat_get(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        filestat_out_buf: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let file_descriptors_lock = state.file_descriptors.lock();
+
+        // Find out which file descriptor the user wants to write to.
+        let file_descriptor = {
+            let fd = usize::try_from(fd)?;
+            match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        let filestat = match file_descriptor {
+            FileDescriptor::Empty
+            | FileDescriptor::ConsoleIn { .. }
+            | FileDescriptor::LogOut { .. } => wasi::Filestat {
+                dev: 0,
+                ino: 0,
+                filetype: wasi::FILETYPE_CHARACTER_DEVICE,
+                nlink: 1,
+                size: 0,
+                atim: 0,
+                mtim: 0,
+                ctim: 0,
+            },
+            FileDescriptor::FilesystemEntry { inode, .. } => filestat_from_inode(inode),
+            FileDescriptor::Mount { .. } => wasi::Filestat {
+                dev: 0,
+                ino: 0,
+                filetype: wasi::FILETYPE_DIRECTORY,
+                nlink: 1,
+                size: 0,
+                atim: 0,
+                mtim: 0,
+                ctim: 0,
+            },
+            FileDescriptor::MountedFile { size, .. } => wasi::Filestat {
+                dev: 0,
+                ino: 0,
+                filetype: wasi::FILETYPE_REGULAR_FILE,
+                nlink: 1,
+                size: *size,
+                atim: 0,
+                mtim: 0,
+                ctim: 0,
+            },
+            FileDescriptor::TcpListener { .. } | FileDescriptor::TcpSocket { .. } => {
+                wasi::Filestat {
+                    dev: 0,
+                    ino: 0,
+                    filetype: wasi::FILETYPE_SOCKET_STREAM,
+                    nlink: 1,
+                    size: 0,
+                    atim: 0,
+                    mtim: 0,
+                    ctim: 0,
+                }
+            }
+        };
+
+        write_filestat(mem_access, filestat_out_buf, &filestat)?;
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn fd_prestat_dir_name(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        path_out: u32,
+        path_out_len: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let file_descriptors_lock = state.file_descriptors.lock();
+
+        // Find out which file descriptor the user wants to write to.
+        let file_descriptor = {
+            let fd = usize::try_from(fd)?;
+            match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        let name = match file_descriptor {
+            FileDescriptor::Empty
+            | FileDescriptor::ConsoleIn { .. }
+            | FileDescriptor::LogOut { .. }
+            | FileDescriptor::MountedFile { .. }
+            | FileDescriptor::TcpListener { .. }
+            | FileDescriptor::TcpSocket { .. } => {
+                // TODO: is that the correct return type?
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            // Note that no null terminator is needed.
+            // Also note that apparently any value other than an empty string will fail to match
+            // relative paths? it's weird
+            // cc https://github.com/CraneStation/wasi-libc/blob/9efc2f428358564fe64c374d762d0bfce1d92507/libc-bottom-half/libpreopen/libpreopen.c#L470
+            FileDescriptor::FilesystemEntry {
+                preopen_name: Some(name),
+                ..
+            }
+            | FileDescriptor::Mount {
+                preopen_name: name, ..
+            } => name.as_bytes(),
+            FileDescriptor::FilesystemEntry {
+                preopen_name: None, ..
+            } => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+        };
+
+        let path_out_len = usize::try_from(path_out_len).unwrap_or(usize::max_value());
+
+        // TODO: is it correct to truncate if the buffer is too small?
+        let to_write = cmp::min(path_out_len, name.len());
+        mem_access.write_memory(path_out, &name[..to_write])?;
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn fd_prestat_get(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        prestat_out_buf: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let file_descriptors_lock = state.file_descriptors.lock();
+
+        // Find out which file descriptor the user wants to write to.
+        let file_descriptor = {
+            let fd = usize::try_from(fd)?;
+            match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        let pr_name_len: u32 = match file_descriptor {
+            FileDescriptor::Empty
+            | FileDescriptor::ConsoleIn { .. }
+            | FileDescriptor::LogOut { .. } => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSUP)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::FilesystemEntry {
+                preopen_name: Some(name),
+                ..
+            }
+            | FileDescriptor::Mount {
+                preopen_name: name, ..
+            } => u32::try_from(name.len())?,
+            // Returning `EBADF` for listening sockets stops the discovery of the pre-opened
+            // directories by the libc.
+            FileDescriptor::FilesystemEntry {
+                preopen_name: None, ..
+            }
+            | FileDescriptor::MountedFile { .. }
+            | FileDescriptor::TcpListener { .. }
+            | FileDescriptor::TcpSocket { .. } => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+        };
+
+        // Note: this is a bit of dark magic, but it is the only solution at the moment.
+        // Can be tested with the following snippet:
+        // ```c
+        // #include <stdio.h>
+        // #include <wasi/api.h>
+        // int main() {
+        //     __wasi_prestat_t* ptr = (__wasi_prestat_t*)0x1000;
+        //     printf("%p %p %p %d\n", ptr, &ptr->tag, &ptr->u.dir, sizeof(__wasi_prestat_t));
+        //     return 0;
+        // }
+        // ```
+        // Which prints `0x1000 0x1000 0x1004 8`
+        mem_access.write_memory(prestat_out_buf, &[0; 8])?;
+        mem_access.write_memory(prestat_out_buf, &[wasi::PREOPENTYPE_DIR])?;
+        mem_access.write_memory(
+            prestat_out_buf.checked_add(4).ok_or(WasiCallErr)?,
+            &pr_name_len.to_le_bytes(),
+        )?;
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn fd_read(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        addr: u32,
+        num: u32,
+        num_read_out: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let mut file_descriptors_lock = state.file_descriptors.lock();
+
+        // Find out which file descriptor the user wants to read from.
+        let fd = usize::try_from(fd)?;
+        let mut file_descriptor = {
+            match file_descriptors_lock.get_mut(fd).and_then(|v| v.as_mut()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        // Get a list of pointers and lengths to read to.
+        let out_buffers_list = read_io_vectors(mem_access, addr, num)?;
+
+        let total_read: u32 = match &mut file_descriptor {
+            FileDescriptor::Empty | FileDescriptor::LogOut { .. } => 0,
+            FileDescriptor::ConsoleIn { .. } | FileDescriptor::TcpSocket { .. } => {
+                return buffered_read(
+                    fd,
+                    file_descriptor,
+                    out_buffers_list,
+                    num_read_out,
+                    mem_access,
+                );
+            }
+            FileDescriptor::TcpListener { .. } => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTCONN)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::Mount { .. } => {
+                // Same error as for directories below.
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::MountedFile {
+                file_id,
+                file_cursor_pos,
+                ..
+            } => {
+                let len = out_buffers_list
+                    .chunks(2)
+                    .fold(0u32, |len, buffer| len.saturating_add(buffer[1]));
+
+                let action = ExtrinsicsAction::EmitMessage {
+                    interface: files_ffi::INTERFACE,
+                    message: files_ffi::FilesMessage::Read(files_ffi::Read {
+                        file_id: *file_id,
+                        offset: *file_cursor_pos,
+                        len,
+                    })
+                    .encode(),
+                    response_expected: true,
+                };
+
+                let context = ContextInner::WaitFilesRead {
+                    fd,
+                    out_buffers: out_buffers_list,
+                    num_read_out,
+                };
+
+                return Ok((context, action));
+            }
+            FileDescriptor::FilesystemEntry {
+                inode,
+                file_cursor_pos,
+                ..
+            } => {
+                match &**inode {
+                    Inode::Directory { .. } => {
+                        // TODO: is that the correct error?
+                        let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                        let action = ExtrinsicsAction::Resume(ret);
+                        return Ok((ContextInner::Finished, action));
+                    }
+                    Inode::File { content, .. } => {
+                        let content = content.lock();
+                        let mut total_read = 0;
+                        for buffer in out_buffers_list.chunks(2) {
+                            let buffer_ptr = buffer[0];
+                            let buffer_len = usize::try_from(buffer[1])?;
+                            // The cursor can be past the end of the file if the file has been
+                            // truncated through another file descriptor.
+                            let file_cursor_pos_usize = usize::try_from(*file_cursor_pos)?;
+                            let to_copy = cmp::min(
+                                content.len().saturating_sub(file_cursor_pos_usize),
+                                buffer_len,
+                            );
+                            if to_copy == 0 {
+                                break;
+                            }
+                            mem_access.write_memory(
+                                buffer_ptr,
+                                &content[file_cursor_pos_usize..file_cursor_pos_usize + to_copy],
+                            )?;
+                            *file_cursor_pos = file_cursor_pos
+                                .checked_add(u64::try_from(to_copy)?)
+                                .ok_or(WasiCallErr)?;
+                            total_read += to_copy;
+                        }
+                        u32::try_from(total_read)?
+                    }
+                }
+            }
+        };
+
+        // Write to the last parameter the number of bytes that have been read in total.
+        mem_access.write_memory(num_read_out, &total_read.to_le_bytes())?;
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn fd_readdir(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        buf: u32,
+        buf_len: u32,
+        cookie: u64,
+        bufused_out: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let file_descriptors_lock = state.file_descriptors.lock();
+
+        let file_descriptor = {
+            let fd = usize::try_from(fd)?;
+            match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        // Entries are reported sorted by name, and the cookie of an entry is its position within
+        // this list, plus one. `.` and `..` are not reported.
+        let entries = match file_descriptor {
+            FileDescriptor::Empty
+            | FileDescriptor::ConsoleIn { .. }
+            | FileDescriptor::LogOut { .. } => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::Mount { .. } => {
+                // TODO: the `files` interface doesn't support listing directories
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSUP)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::MountedFile { .. }
+            | FileDescriptor::TcpListener { .. }
+            | FileDescriptor::TcpSocket { .. } => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTDIR)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::FilesystemEntry { inode, .. } => match &**inode {
+                Inode::File { .. } => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTDIR)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+                Inode::Directory { entries } => {
+                    let mut list = entries
+                        .lock()
+                        .iter()
+                        .map(|(name, inode)| (name.clone(), inode.clone()))
+                        .collect::<Vec<_>>();
+                    list.sort_by(|a, b| a.0.cmp(&b.0));
+                    list
+                }
+            },
+        };
+
+        // Note: this is a bit of dark magic, but it is the only solution at the moment.
+        // Can be tested with the following snippet:
+        // ```c
+        // #include <stdio.h>
+        // #include <wasi/api.h>
+        // int main() {
+        //     __wasi_dirent_t* ptr = (__wasi_dirent_t*)0x1000;
+        //     printf("%p %p %p %p %p %d\n", ptr, &ptr->d_next, &ptr->d_ino, &ptr->d_namlen, &ptr->d_type, sizeof(__wasi_dirent_t));
+        //     return 0;
+        // }
+        // ```
+        // Which prints `0x1000 0x1000 0x1008 0x1010 0x1014 24`
+        // Each entry is immediately followed with its name. If the buffer is too small, the last
+        // entry is truncated, and the program is expected to call `fd_readdir` again.
+        let buf_len_usize = usize::try_from(buf_len)?;
+        let mut out = Vec::new();
+        let start = usize::try_from(cookie).unwrap_or(usize::max_value());
+        for (index, (name, inode)) in entries.iter().enumerate().skip(start) {
+            if out.len() >= buf_len_usize {
+                break;
+            }
+
+            let filestat = filestat_from_inode(inode);
+            let d_next = wasi::Dircookie::try_from(index)?
+                .checked_add(1)
+                .ok_or(WasiCallErr)?;
+            out.extend_from_slice(&d_next.to_le_bytes());
+            out.extend_from_slice(&filestat.ino.to_le_bytes());
+            out.extend_from_slice(&u32::try_from(name.len())?.to_le_bytes());
+            out.extend_from_slice(&[filestat.filetype, 0, 0, 0]);
+            out.extend_from_slice(name.as_bytes());
+        }
+        out.truncate(buf_len_usize);
+
+        mem_access.write_memory(buf, &out)?;
+        mem_access.write_memory(bufused_out, &u32::try_from(out.len())?.to_le_bytes())?;
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn fd_seek(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        offset: i64,
+        whence: i32,
+        out_ptr: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let mut file_descriptors_lock = state.file_descriptors.lock();
+
+        // Find out which file descriptor the user wants to seek.
+        let mut file_descriptor = {
+            let fd = usize::try_from(fd)?;
+            match file_descriptors_lock.get_mut(fd).and_then(|v| v.as_mut()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        let whence = u8::try_from(whence)?;
+
+        let (file_cursor_pos, max_offset) = match &mut file_descriptor {
+            FileDescriptor::Empty
+            | FileDescriptor::ConsoleIn { .. }
+            | FileDescriptor::LogOut { .. }
+            | FileDescriptor::Mount { .. } => {
+                // TODO: is that the correct error?
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::TcpListener { .. } | FileDescriptor::TcpSocket { .. } => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_SPIPE)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::FilesystemEntry {
+                inode,
+                file_cursor_pos,
+                ..
+            } => match &**inode {
+                Inode::Directory { .. } => {
+                    // TODO: is that the correct error?
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+                Inode::File { content, .. } => {
+                    (file_cursor_pos, u64::try_from(content.lock().len())?)
+                }
+            },
+            FileDescriptor::MountedFile {
+                file_cursor_pos,
+                size,
+                ..
+            } => (file_cursor_pos, *size),
+        };
+
+        // TODO: do that properly
+        let new_offset: u64 = match whence {
+            wasi::WHENCE_SET => cmp::min(u64::try_from(cmp::max(offset, 0))?, max_offset),
+            wasi::WHENCE_CUR => cmp::min(file_cursor_pos.saturating_add(offset as u64), max_offset),
+            wasi::WHENCE_END => cmp::min(
+                u64::try_from(cmp::max(0, max_offset as i64 + offset))?,
+                max_offset,
+            ),
+            _ => panic!(), // TODO: no
+        };
+        *file_cursor_pos = new_offset;
+
+        // Write to the last parameter the new offset.
+        mem_access.write_memory(out_ptr, &new_offset.to_le_bytes())?;
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn fd_tell(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        out_ptr: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let file_descriptors_lock = state.file_descriptors.lock();
+
+        let file_descriptor = {
+            let fd = usize::try_from(fd)?;
+            match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        let offset = match file_descriptor {
+            FileDescriptor::Empty
+            | FileDescriptor::ConsoleIn { .. }
+            | FileDescriptor::LogOut { .. }
+            | FileDescriptor::TcpListener { .. }
+            | FileDescriptor::TcpSocket { .. } => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_SPIPE)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::Mount { .. } => {
+                // Same error as `fd_seek`.
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::MountedFile {
+                file_cursor_pos, ..
+            } => *file_cursor_pos,
+            FileDescriptor::FilesystemEntry {
+                inode,
+                file_cursor_pos,
+                ..
+            } => match **inode {
+                Inode::Directory { .. } => {
+                    // Same error as `fd_seek`.
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+                Inode::File { .. } => *file_cursor_pos,
+            },
+        };
+
+        mem_access.write_memory(out_ptr, &offset.to_le_bytes())?;
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn fd_write(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        addr: u32,
+        num: u32,
+        num_written_out: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let mut file_descriptors_lock = state.file_descriptors.lock();
+
+        // Find out which file descriptor the user wants to write to.
+        let fd = usize::try_from(fd)?;
+        let file_descriptor = {
+            match file_descriptors_lock.get_mut(fd).and_then(|v| v.as_mut()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        // Get a list of pointers and lengths to write.
+        let list_to_write = read_io_vectors(mem_access, addr, num)?;
+
+        match file_descriptor {
+            FileDescriptor::Empty | FileDescriptor::ConsoleIn { .. } => {
+                // TODO: is that the right error code?
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOSYS)));
+                let action = ExtrinsicsAction::Resume(ret);
+                Ok((ContextInner::Finished, action))
+            }
+            FileDescriptor::Mount { .. } => {
+                // Same error as for directories below.
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                let action = ExtrinsicsAction::Resume(ret);
+                Ok((ContextInner::Finished, action))
+            }
+            FileDescriptor::TcpListener { .. } => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTCONN)));
+                let action = ExtrinsicsAction::Resume(ret);
+                Ok((ContextInner::Finished, action))
+            }
+            FileDescriptor::TcpSocket { socket_id, .. } => {
+                tcp_socket_write(*socket_id, &list_to_write, num_written_out, mem_access)
+            }
+            FileDescriptor::MountedFile {
+                file_id,
+                file_cursor_pos,
+                size,
+                flags,
+            } => {
+                let mut data = Vec::new();
+                for ptr_and_len in list_to_write.chunks(2) {
+                    let ptr = ptr_and_len[0];
+                    let len = ptr_and_len[1];
+                    data.extend(mem_access.read_memory(ptr..ptr + len)?);
+                }
+
+                let offset = if (*flags & wasi::FDFLAGS_APPEND) != 0 {
+                    *size
+                } else {
+                    *file_cursor_pos
+                };
+                let num_written = u32::try_from(data.len())?;
+                let end_offset = offset
+                    .checked_add(u64::from(num_written))
+                    .ok_or(WasiCallErr)?;
+
+                let action = ExtrinsicsAction::EmitMessage {
+                    interface: files_ffi::INTERFACE,
+                    message: files_ffi::FilesMessage::Write(files_ffi::Write {
+                        file_id: *file_id,
+                        offset,
+                        data,
+                    })
+                    .encode(),
+                    response_expected: true,
+                };
+
+                let context = ContextInner::WaitFilesWrite {
+                    fd,
+                    end_offset,
+                    num_written,
+                    num_written_out,
+                };
+
+                Ok((context, action))
+            }
+            FileDescriptor::LogOut { level, buffer } => {
+                let mut total_written = 0usize;
+                for ptr_and_len in list_to_write.chunks(2) {
+                    let ptr = ptr_and_len[0];
+                    let len = ptr_and_len[1];
+
+                    buffer.extend(mem_access.read_memory(ptr..ptr + len)?);
+                    total_written = total_written
+                        .checked_add(usize::try_from(len)?)
+                        .ok_or(WasiCallErr)?;
+                }
+
+                // Write to the fourth parameter the number of bytes written to the file descriptor.
+                {
+                    let total_written = u32::try_from(total_written)?;
+                    mem_access.write_memory(num_written_out, &total_written.to_le_bytes())?;
+                }
+
+                // Flush each complete line of `buffer` into a log message. All the messages are
+                // emitted at once.
+                let mut messages = Vec::new();
+                while let Some(split_pos) = buffer.iter().position(|c| *c == b'\n') {
+                    let mut encoded_message = Vec::new();
+                    encoded_message.push(u8::from(*level));
+                    encoded_message.extend(buffer.drain(..split_pos));
+                    buffer.remove(0);
+
+                    messages.push(MessageToEmit {
+                        interface: redshirt_log_interface::ffi::INTERFACE,
+                        message: EncodedMessage(encoded_message),
+                        response_expected: false,
+                    });
+                }
+
+                if messages.is_empty() {
+                    let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+                    Ok((ContextInner::Finished, action))
+                } else {
+                    let action = ExtrinsicsAction::EmitMessages(messages);
+                    Ok((ContextInner::WaitLogOut, action))
+                }
+            }
+            FileDescriptor::FilesystemEntry {
+                inode,
+                file_cursor_pos,
+                flags,
+                ..
+            } => {
+                let mut content = match &**inode {
+                    Inode::Directory { .. } => {
+                        // TODO: is that the correct error?
+                        let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                        let action = ExtrinsicsAction::Resume(ret);
+                        return Ok((ContextInner::Finished, action));
+                    }
+                    Inode::File { content } => content.lock(),
+                };
+
+                if (*flags & wasi::FDFLAGS_APPEND) != 0 {
+                    *file_cursor_pos = u64::try_from(content.len())?;
+                }
+
+                let mut total_written = 0usize;
+                for ptr_and_len in list_to_write.chunks(2) {
+                    let ptr = ptr_and_len[0];
+                    let len = ptr_and_len[1];
+                    let data = mem_access.read_memory(ptr..ptr + len)?;
+
+                    // The cursor can be past the end of the file, in which case the gap is filled
+                    // with zeroes.
+                    let start = usize::try_from(*file_cursor_pos)?;
+                    let end = start.checked_add(data.len()).ok_or(WasiCallErr)?;
+                    if content.len() < end {
+                        content.resize(end, 0);
+                    }
+                    content[start..end].copy_from_slice(&data);
+
+                    *file_cursor_pos = u64::try_from(end)?;
+                    total_written = total_written.checked_add(data.len()).ok_or(WasiCallErr)?;
+                }
+
+                // Write to the fourth parameter the number of bytes written to the file descriptor.
+                mem_access.write_memory(
+                    num_written_out,
+                    &u32::try_from(total_written)?.to_le_bytes(),
+                )?;
+
+                let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+                Ok((ContextInner::Finished, action))
+            }
+        }
+    }
+
+    #[extrinsic(-> i32)]
+    fn path_create_directory(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        path_buf: u32,
+        path_buf_len: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let fd_inode = {
+            let file_descriptors_lock = state.file_descriptors.lock();
+            let fd = usize::try_from(fd)?;
+            match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+                Some(FileDescriptor::FilesystemEntry { inode, .. }) => inode.clone(),
+                Some(FileDescriptor::Mount { .. }) => {
+                    // TODO: the `files` interface doesn't support modifying directories
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSUP)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+                _ => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        let path = {
+            let path_utf8 = mem_access.read_memory(path_buf..path_buf + path_buf_len)?;
+            String::from_utf8(path_utf8)? // TODO: return error code?
+        };
+
+        let (parent, name) = match resolve_parent(&fd_inode, &path) {
+            Some(p) => p,
+            None => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOENT)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+        };
+
+        let errno = match &*parent {
+            Inode::Directory { entries } => {
+                let mut entries = entries.lock();
+                if entries.contains_key(name) {
+                    wasi::ERRNO_EXIST
+                } else {
+                    entries.insert(
+                        String::from(name),
+                        Arc::new(Inode::Directory {
+                            entries: Spinlock::new(HashMap::default()),
+                        }),
+                    );
+                    wasi::ERRNO_SUCCESS
+                }
+            }
+            // `resolve_parent` always returns a directory.
+            Inode::File { .. } => unreachable!(),
+        };
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(errno))));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn path_filestat_get(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        _lookup_flags: u32,
+        path_buf: u32,
+        path_buf_len: u32,
+        filestat_out_buf: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let file_descriptors_lock = state.file_descriptors.lock();
+
+        let file_descriptor = {
+            let fd = usize::try_from(fd)?;
+            match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        let fd_inode = match file_descriptor {
+            FileDescriptor::Empty
+            | FileDescriptor::ConsoleIn { .. }
+            | FileDescriptor::LogOut { .. }
+            | FileDescriptor::MountedFile { .. }
+            | FileDescriptor::TcpListener { .. }
+            | FileDescriptor::TcpSocket { .. } => {
+                // TODO: is that the correct return type?
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::Mount { .. } => {
+                // TODO: the `files` interface doesn't support querying information about files
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSUP)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::FilesystemEntry { inode, .. } => inode.clone(),
+        };
+
+        let path = {
+            let path_utf8 = mem_access.read_memory(path_buf..path_buf + path_buf_len)?;
+            String::from_utf8(path_utf8)? // TODO: return error code?
+        };
+
+        let resolved_path = match resolve_path(&fd_inode, &path) {
+            Some(p) => p,
+            None => {
+                let action =
+                    ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(wasi::ERRNO_NOENT))));
+                return Ok((ContextInner::Finished, action));
+            }
+        };
+
+        let filestat = filestat_from_inode(&resolved_path);
+
+        write_filestat(mem_access, filestat_out_buf, &filestat)?;
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn path_open(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        _lookup_flags: u32,
+        path_buf: u32,
+        path_buf_len: u32,
+        open_flags: i32,
+        _fs_rights_base: u64,
+        _fs_rights_inherting: u64,
+        fd_flags: i32,
+        opened_fd_ptr: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let mut file_descriptors_lock = state.file_descriptors.lock();
+
+        let file_descriptor = {
+            let fd = usize::try_from(fd)?;
+            match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+                Some(fd) => fd,
+                None => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            }
+        };
+
+        // Directory relative to which the path is resolved. Either a directory of the in-memory
+        // file system, or the name of a volume of the `files` interface.
+        let fd_inode = match file_descriptor {
+            FileDescriptor::Empty
+            | FileDescriptor::ConsoleIn { .. }
+            | FileDescriptor::LogOut { .. }
+            | FileDescriptor::MountedFile { .. }
+            | FileDescriptor::TcpListener { .. }
+            | FileDescriptor::TcpSocket { .. } => {
+                // TODO: is that the correct return type?
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            FileDescriptor::FilesystemEntry { inode, .. } => Either::Left(inode.clone()),
+            FileDescriptor::Mount { volume, .. } => Either::Right(volume.clone()),
+        };
+
+        let path = {
+            let path_utf8 = mem_access.read_memory(path_buf..path_buf + path_buf_len)?;
+            String::from_utf8(path_utf8)? // TODO: return error code?
+        };
+
+        let open_flags = wasi::Oflags::try_from(open_flags)?;
+        let fd_flags = wasi::Fdflags::try_from(fd_flags)?;
+
+        let fd_inode = match fd_inode {
+            Either::Left(inode) => inode,
+            Either::Right(volume) => {
+                if (open_flags & wasi::OFLAGS_DIRECTORY) != 0 {
+                    // TODO: the `files` interface doesn't support opening directories
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSUP)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+
+                let action = ExtrinsicsAction::EmitMessage {
+                    interface: files_ffi::INTERFACE,
+                    message: files_ffi::FilesMessage::Open(files_ffi::Open {
+                        volume,
+                        path,
+                        create: (open_flags & wasi::OFLAGS_CREAT) != 0,
+                        exclusive: (open_flags & wasi::OFLAGS_EXCL) != 0,
+                        truncate: (open_flags & wasi::OFLAGS_TRUNC) != 0,
+                    })
+                    .encode(),
+                    response_expected: true,
+                };
+
+                let context = ContextInner::WaitFilesOpen {
+                    opened_fd_ptr,
+                    flags: fd_flags,
+                };
+
+                return Ok((context, action));
+            }
+        };
+
+        let resolved_path = match resolve_path(&fd_inode, &path) {
+            Some(_)
+                if (open_flags & wasi::OFLAGS_CREAT) != 0
+                    && (open_flags & wasi::OFLAGS_EXCL) != 0 =>
+            {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_EXIST)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            Some(p) => p,
+            None if (open_flags & wasi::OFLAGS_CREAT) != 0 => {
+                let (parent, name) = match resolve_parent(&fd_inode, &path) {
+                    Some(p) => p,
+                    None => {
+                        let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOENT)));
+                        let action = ExtrinsicsAction::Resume(ret);
+                        return Ok((ContextInner::Finished, action));
+                    }
+                };
+
+                let new_file = match &*parent {
+                    Inode::Directory { entries } => entries
+                        .lock()
+                        .entry(String::from(name))
+                        .or_insert_with(|| {
+                            Arc::new(Inode::File {
+                                content: Spinlock::new(Vec::new()),
+                            })
+                        })
+                        .clone(),
+                    // `resolve_parent` always returns a directory.
+                    Inode::File { .. } => unreachable!(),
+                };
+                new_file
+            }
+            None => {
+                let action =
+                    ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(wasi::ERRNO_NOENT))));
+                return Ok((ContextInner::Finished, action));
+            }
+        };
+
+        match &*resolved_path {
+            Inode::File { .. } if (open_flags & wasi::OFLAGS_DIRECTORY) != 0 => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTDIR)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            Inode::File { content } if (open_flags & wasi::OFLAGS_TRUNC) != 0 => {
+                content.lock().clear();
+            }
+            Inode::Directory { .. } if (open_flags & wasi::OFLAGS_TRUNC) != 0 => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_ISDIR)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            _ => {}
+        }
+
+        let new_fd = allocate_file_descriptor(
+            &mut file_descriptors_lock,
+            FileDescriptor::FilesystemEntry {
+                inode: resolved_path,
+                file_cursor_pos: 0,
+                flags: fd_flags,
+                preopen_name: None,
+            },
+        );
+
+        mem_access.write_memory(opened_fd_ptr, &new_fd.to_le_bytes())?;
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn path_remove_directory(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        path_buf: u32,
+        path_buf_len: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        path_remove(state, mem_access, fd, path_buf, path_buf_len, true)
+    }
+
+    #[extrinsic(-> i32)]
+    fn path_rename(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        old_fd: u32,
+        old_path_buf: u32,
+        old_path_buf_len: u32,
+        new_fd: u32,
+        new_path_buf: u32,
+        new_path_buf_len: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let file_descriptors_lock = state.file_descriptors.lock();
+
+        let mut fd_and_paths = Vec::with_capacity(2);
+        for (fd, path_buf, path_buf_len) in [
+            (old_fd, old_path_buf, old_path_buf_len),
+            (new_fd, new_path_buf, new_path_buf_len),
+        ] {
+            let fd = usize::try_from(fd)?;
+            let fd_inode = match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+                Some(FileDescriptor::FilesystemEntry { inode, .. }) => inode.clone(),
+                Some(FileDescriptor::Mount { .. }) => {
+                    // TODO: the `files` interface doesn't support renaming files
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSUP)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+                _ => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                    let action = ExtrinsicsAction::Resume(ret);
+                    return Ok((ContextInner::Finished, action));
+                }
+            };
+
+            let path = {
+                let path_utf8 = mem_access.read_memory(path_buf..path_buf + path_buf_len)?;
+                String::from_utf8(path_utf8)? // TODO: return error code?
+            };
+
+            fd_and_paths.push((fd_inode, path));
+        }
+
+        let (old_parent, old_name) = match resolve_parent(&fd_and_paths[0].0, &fd_and_paths[0].1) {
+            Some(p) => p,
+            None => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOENT)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+        };
+        let (new_parent, new_name) = match resolve_parent(&fd_and_paths[1].0, &fd_and_paths[1].1) {
+            Some(p) => p,
+            None => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOENT)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+        };
+
+        // `resolve_parent` always returns directories.
+        let (old_entries, new_entries) = match (&*old_parent, &*new_parent) {
+            (Inode::Directory { entries: old }, Inode::Directory { entries: new }) => (old, new),
+            _ => unreachable!(),
+        };
+
+        // Note that the source and destination directories can be the same. The entries are
+        // therefore never locked at the same time.
+        let moved = match old_entries.lock().get(old_name) {
+            Some(inode) => inode.clone(),
+            None => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOENT)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+        };
+
+        // A directory can't be moved inside of itself.
+        if is_within(&new_parent, &moved) {
+            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_INVAL)));
+            let action = ExtrinsicsAction::Resume(ret);
+            return Ok((ContextInner::Finished, action));
+        }
+
+        // Check whether the destination can be overwritten.
+        let errno = match (&*moved, new_entries.lock().get(new_name).map(|i| &**i)) {
+            (_, None) => wasi::ERRNO_SUCCESS,
+            (Inode::File { .. }, Some(Inode::File { .. })) => wasi::ERRNO_SUCCESS,
+            (Inode::File { .. }, Some(Inode::Directory { .. })) => wasi::ERRNO_ISDIR,
+            (Inode::Directory { .. }, Some(Inode::File { .. })) => wasi::ERRNO_NOTDIR,
+            (Inode::Directory { .. }, Some(Inode::Directory { entries })) => {
+                if entries.lock().is_empty() {
+                    wasi::ERRNO_SUCCESS
+                } else {
+                    wasi::ERRNO_NOTEMPTY
+                }
+            }
+        };
+
+        if errno == wasi::ERRNO_SUCCESS {
+            old_entries.lock().remove(old_name);
+            new_entries.lock().insert(String::from(new_name), moved);
+        }
+
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(errno))));
+        Ok((ContextInner::Finished, action))
+    }
+
+    #[extrinsic(-> i32)]
+    fn path_unlink_file(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        path_buf: u32,
+        path_buf_len: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        path_remove(state, mem_access, fd, path_buf, path_buf_len, false)
+    }
+
+    #[extrinsic(-> i32)]
+    fn poll_oneoff(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        subscriptions_buf: u32,
+        events_out: u32,
+        num_subscriptions: u32,
+        num_events_out: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        if num_subscriptions == 0 {
+            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_INVAL)));
+            return Ok((ContextInner::Finished, ExtrinsicsAction::Resume(ret)));
+        }
+
+        // A subscription is 48 bytes long: `userdata` at offset 0, the tag of the union at offset 8,
+        // and the content of the union at offset 16.
+        let subscriptions = {
+            let len = num_subscriptions.checked_mul(48).ok_or(WasiCallErr)?;
+            let end = subscriptions_buf.checked_add(len).ok_or(WasiCallErr)?;
+            mem_access.read_memory(subscriptions_buf..end)?
+        };
+
+        let file_descriptors_lock = state.file_descriptors.lock();
+
+        let mut ready_events = Vec::new();
+        let mut fds = Vec::new();
+        let mut clocks = Vec::new();
+
+        for subscription in subscriptions.chunks(48) {
+            let userdata = u64::from_le_bytes(<[u8; 8]>::try_from(&subscription[0..8]).unwrap());
+
+            match subscription[8] {
+                wasi::EVENTTYPE_CLOCK => {
+                    let clock_id =
+                        u32::from_le_bytes(<[u8; 4]>::try_from(&subscription[16..20]).unwrap());
+                    let timeout =
+                        u64::from_le_bytes(<[u8; 8]>::try_from(&subscription[24..32]).unwrap());
+                    let flags =
+                        u16::from_le_bytes(<[u8; 2]>::try_from(&subscription[40..42]).unwrap());
+                    let absolute = (flags & wasi::SUBCLOCKFLAGS_SUBSCRIPTION_CLOCK_ABSTIME) != 0;
+
+                    // All the clocks are waited upon using the monotonic clock. A relative timeout
+                    // on the real time clock is equivalent to one on the monotonic clock.
+                    match (clock_id, absolute) {
+                        (wasi::CLOCKID_MONOTONIC, _) | (wasi::CLOCKID_REALTIME, false) => clocks
+                            .push(PollClock {
+                                userdata,
+                                timeout,
+                                absolute,
+                            }),
+                        _ => ready_events.push(PollEvent {
+                            userdata,
+                            error: wasi::ERRNO_NOTSUP,
+                            ty: wasi::EVENTTYPE_CLOCK,
+                            nbytes: 0,
+                        }),
+                    }
+                }
+                ty @ wasi::EVENTTYPE_FD_READ | ty @ wasi::EVENTTYPE_FD_WRITE => {
+                    let fd =
+                        u32::from_le_bytes(<[u8; 4]>::try_from(&subscription[16..20]).unwrap());
+                    let fd = usize::try_from(fd).unwrap_or(usize::max_value());
+                    let file_descriptor = file_descriptors_lock.get(fd).and_then(|v| v.as_ref());
+
+                    // Writing never blocks, and neither does reading from the file descriptors
+                    // other than the console and sockets.
+                    if ty == wasi::EVENTTYPE_FD_READ
+                        && file_descriptor.map_or(false, |f| f.read_would_block())
+                    {
+                        fds.push(PollFd { userdata, fd });
+                        continue;
+                    }
 
-            let context = ContextInner::WaitClockVal { out_ptr: time_out };
-            Ok((context, action))
+                    ready_events.push(poll_fd_event(file_descriptor, userdata, ty));
+                }
+                _ => {
+                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_INVAL)));
+                    return Ok((ContextInner::Finished, ExtrinsicsAction::Resume(ret)));
+                }
+            }
         }
-        wasi::CLOCKID_MONOTONIC => {
-            let action = ExtrinsicsAction::EmitMessage {
-                interface: redshirt_time_interface::ffi::INTERFACE,
-                message: redshirt_time_interface::ffi::TimeMessage::GetMonotonic.encode(),
-                response_expected: true,
-            };
 
-            let context = ContextInner::WaitClockVal { out_ptr: time_out };
-            Ok((context, action))
-        }
-        wasi::CLOCKID_PROCESS_CPUTIME_ID => unimplemented!(), // TODO:
-        wasi::CLOCKID_THREAD_CPUTIME_ID => unimplemented!(),  // TODO:
-        _ => Err(WasiCallErr),
-    }
-}
+        drop(file_descriptors_lock);
 
-fn environ_get(
-    state: &WasiExtrinsics,
-    params: impl ExactSizeIterator<Item = WasmValue>,
-    mem_access: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    args_or_env_get(&state.env_vars, params, mem_access)
-}
+        if !ready_events.is_empty() {
+            write_poll_events(mem_access, events_out, num_events_out, &ready_events)?;
+            let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+            return Ok((ContextInner::Finished, action));
+        }
 
-fn environ_sizes_get(
-    state: &WasiExtrinsics,
-    params: impl ExactSizeIterator<Item = WasmValue>,
-    mem_access: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    args_or_env_sizes_get(&state.env_vars, params, mem_access)
-}
+        if clocks.is_empty() {
+            return poll_wait(state, fds, None, events_out, num_events_out, mem_access);
+        }
 
-fn fd_close(
-    state: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
-    _: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let mut file_descriptors_lock = state.file_descriptors.lock();
+        // The current time is necessary in order to determine when the clock subscriptions
+        // expire.
+        let action = ExtrinsicsAction::EmitMessage {
+            interface: redshirt_time_interface::ffi::INTERFACE,
+            message: redshirt_time_interface::ffi::TimeMessage::GetMonotonic.encode(),
+            response_expected: true,
+        };
 
-    let fd = usize::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
+        let context = ContextInner::PollGetTime {
+            fds,
+            clocks,
+            events_out,
+            num_events_out,
+        };
 
-    // Check validity of the file descriptor.
-    if file_descriptors_lock
-        .get(fd)
-        .map(|f| f.is_none())
-        .unwrap_or(true)
-    {
-        let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
-        let action = ExtrinsicsAction::Resume(ret);
-        return Ok((ContextInner::Finished, action));
+        Ok((context, action))
     }
 
-    file_descriptors_lock[fd] = None;
-
-    // Clean up the tail of `file_descriptors_lock`.
-    while file_descriptors_lock
-        .last()
-        .map(|f| f.is_none())
-        .unwrap_or(false)
-    {
-        file_descriptors_lock.pop();
+    #[extrinsic]
+    fn proc_exit(
+        _: &WasiExtrinsics,
+        _: &mut impl ExtrinsicsMemoryAccess,
+        ret_val: i32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        // If the exit code is weird, it's probably one of these values:
+        // https://github.com/WebAssembly/wasi-libc/blob/320054e84f8f2440def3b1c8700cedb8fd697bf8/libc-top-half/musl/include/sysexits.h
+        Ok((
+            ContextInner::Finished,
+            ExtrinsicsAction::ProgramExit(ret_val),
+        ))
     }
-    file_descriptors_lock.shrink_to_fit();
 
-    let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
-    Ok((ContextInner::Finished, action))
-}
-
-fn fd_fdstat_get(
-    state: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
-    mem_access: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let file_descriptors_lock = state.file_descriptors.lock();
-
-    // Find out which file descriptor the user wants to write to.
-    let file_descriptor = {
-        let fd = usize::try_from(params.next().unwrap().into_i32().unwrap())?;
-        match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
-            Some(fd) => fd,
-            None => {
-                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
-                let action = ExtrinsicsAction::Resume(ret);
-                return Ok((ContextInner::Finished, action));
+    #[extrinsic(-> i32)]
+    fn random_get(
+        _: &WasiExtrinsics,
+        _: &mut impl ExtrinsicsMemoryAccess,
+        buf: u32,
+        len: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let len_to_request = u16::try_from(len).unwrap_or(u16::max_value());
+        debug_assert!(u32::from(len_to_request) <= len);
+        let action = ExtrinsicsAction::EmitMessage {
+            interface: redshirt_random_interface::ffi::INTERFACE,
+            message: redshirt_random_interface::ffi::RandomMessage::Generate {
+                len: len_to_request,
             }
-        }
-    };
+            .encode(),
+            response_expected: true,
+        };
 
-    // TODO: we mimic what wasmtime does, but documentation about these rights is pretty sparse
-    let dirs_rights = wasi::RIGHTS_FD_FDSTAT_SET_FLAGS
-        | wasi::RIGHTS_FD_SYNC
-        | wasi::RIGHTS_FD_ADVISE
-        | wasi::RIGHTS_PATH_CREATE_DIRECTORY
-        | wasi::RIGHTS_PATH_CREATE_FILE
-        | wasi::RIGHTS_PATH_LINK_SOURCE
-        | wasi::RIGHTS_PATH_LINK_TARGET
-        | wasi::RIGHTS_PATH_OPEN
-        | wasi::RIGHTS_FD_READDIR
-        | wasi::RIGHTS_PATH_READLINK
-        | wasi::RIGHTS_PATH_RENAME_SOURCE
-        | wasi::RIGHTS_PATH_RENAME_TARGET
-        | wasi::RIGHTS_PATH_FILESTAT_GET
-        | wasi::RIGHTS_PATH_FILESTAT_SET_SIZE
-        | wasi::RIGHTS_PATH_FILESTAT_SET_TIMES
-        | wasi::RIGHTS_FD_FILESTAT_GET
-        | wasi::RIGHTS_FD_FILESTAT_SET_SIZE
-        | wasi::RIGHTS_FD_FILESTAT_SET_TIMES
-        | wasi::RIGHTS_PATH_SYMLINK
-        | wasi::RIGHTS_PATH_REMOVE_DIRECTORY
-        | wasi::RIGHTS_PATH_UNLINK_FILE
-        | wasi::RIGHTS_POLL_FD_READWRITE;
-    let files_rights = wasi::RIGHTS_FD_DATASYNC
-        | wasi::RIGHTS_FD_READ
-        | wasi::RIGHTS_FD_SEEK
-        | wasi::RIGHTS_FD_FDSTAT_SET_FLAGS
-        | wasi::RIGHTS_FD_SYNC
-        | wasi::RIGHTS_FD_TELL
-        | wasi::RIGHTS_FD_WRITE
-        | wasi::RIGHTS_FD_ADVISE
-        | wasi::RIGHTS_FD_ALLOCATE
-        | wasi::RIGHTS_FD_FILESTAT_GET
-        | wasi::RIGHTS_FD_FILESTAT_SET_SIZE
-        | wasi::RIGHTS_FD_FILESTAT_SET_TIMES
-        | wasi::RIGHTS_POLL_FD_READWRITE;
-
-    let stat = match file_descriptor {
-        FileDescriptor::Empty => wasi::Fdstat {
-            fs_filetype: wasi::FILETYPE_CHARACTER_DEVICE,
-            fs_flags: 0,
-            fs_rights_base: 0,
-            fs_rights_inheriting: 0,
-        },
-        FileDescriptor::LogOut { .. } => wasi::Fdstat {
-            fs_filetype: wasi::FILETYPE_CHARACTER_DEVICE,
-            fs_flags: wasi::FDFLAGS_APPEND,
-            fs_rights_base: 0x0820_004a, // TODO: that's what wasmtime returns, don't know what it means
-            fs_rights_inheriting: 0x0820_004a, // TODO: that's what wasmtime returns, don't know what it means
-        },
-        FileDescriptor::FilesystemEntry { inode, .. } => match **inode {
-            Inode::Directory { .. } => wasi::Fdstat {
-                fs_filetype: wasi::FILETYPE_DIRECTORY,
-                fs_flags: 0,
-                fs_rights_base: dirs_rights,
-                fs_rights_inheriting: files_rights | dirs_rights,
-            },
-            Inode::File { .. } => wasi::Fdstat {
-                fs_filetype: wasi::FILETYPE_REGULAR_FILE,
-                fs_flags: 0,
-                fs_rights_base: files_rights,
-                fs_rights_inheriting: files_rights,
-            },
-        },
-    };
+        let context = ContextInner::WaitRandom {
+            out_ptr: buf,
+            remaining_len: len,
+        };
 
-    let stat_out_buf = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
+        Ok((context, action))
+    }
 
-    // Note: this is a bit of dark magic, but it is the only solution at the moment.
-    // Can be tested with the following snippet:
-    // ```c
-    // #include <stdio.h>
-    // #include <wasi/api.h>
-    // int main() {
-    //     __wasi_fdstat_t* ptr = (__wasi_fdstat_t*)0x1000;
-    //     printf("%p %p %p %p %p %d\n", ptr, &ptr->fs_filetype, &ptr->fs_flags, &ptr->fs_rights_base, &ptr->fs_rights_inheriting, sizeof(__wasi_fdstat_t));
-    //     return 0;
-    // }
-    // ```
-    // Which prints `0x1000 0x1000 0x1002 0x1008 0x1010 24`
-    mem_access.write_memory(stat_out_buf, &[0; 24])?;
-    mem_access.write_memory(stat_out_buf, &[stat.fs_filetype])?;
-    mem_access.write_memory(
-        stat_out_buf.checked_add(2).ok_or(WasiCallErr)?,
-        &stat.fs_flags.to_le_bytes(),
-    )?;
-    mem_access.write_memory(
-        stat_out_buf.checked_add(8).ok_or(WasiCallErr)?,
-        &stat.fs_rights_base.to_le_bytes(),
-    )?;
-    mem_access.write_memory(
-        stat_out_buf.checked_add(16).ok_or(WasiCallErr)?,
-        &stat.fs_rights_inheriting.to_le_bytes(),
-    )?;
+    #[extrinsic(-> i32)]
+    fn sched_yield(
+        _: &WasiExtrinsics,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        // TODO: implement in a better way?
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        Ok((ContextInner::Finished, action))
+    }
 
-    let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
-    Ok((ContextInner::Finished, action))
-}
+    #[extrinsic(-> i32)]
+    fn sock_accept(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        flags: i32,
+        opened_fd_ptr: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let mut file_descriptors_lock = state.file_descriptors.lock();
 
-fn fd_filestat_get(
-    state: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
-    _mem_access: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let file_descriptors_lock = state.file_descriptors.lock();
+        let fd = usize::try_from(fd)?;
+        let flags = wasi::Fdflags::try_from(flags)?;
 
-    // Find out which file descriptor the user wants to write to.
-    let file_descriptor = {
-        let fd = usize::try_from(params.next().unwrap().into_i32().unwrap())?;
         match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
-            Some(fd) => fd,
+            Some(FileDescriptor::TcpListener { .. }) => {}
+            Some(_) => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSOCK)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
             None => {
                 let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
                 let action = ExtrinsicsAction::Resume(ret);
                 return Ok((ContextInner::Finished, action));
             }
-        }
-    };
-
-    let _stat_out_buf = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
-
-    unimplemented!();
-}
+        };
 
-fn fd_prestat_dir_name(
-    state: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
-    mem_access: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let file_descriptors_lock = state.file_descriptors.lock();
+        tcp_accept(
+            &mut file_descriptors_lock,
+            fd,
+            flags,
+            opened_fd_ptr,
+            mem_access,
+        )
+    }
 
-    // Find out which file descriptor the user wants to write to.
-    let file_descriptor = {
-        let fd = usize::try_from(params.next().unwrap().into_i32().unwrap())?;
-        match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
-            Some(fd) => fd,
+    #[extrinsic(-> i32)]
+    fn sock_recv(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        addr: u32,
+        num: u32,
+        ri_flags: i32,
+        num_read_out: u32,
+        ro_flags_out: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let mut file_descriptors_lock = state.file_descriptors.lock();
+
+        let fd = usize::try_from(fd)?;
+        let out_buffers_list = read_io_vectors(mem_access, addr, num)?;
+        let ri_flags = wasi::Riflags::try_from(ri_flags)?;
+
+        let file_descriptor = match file_descriptors_lock.get_mut(fd).and_then(|v| v.as_mut()) {
+            Some(file_descriptor @ FileDescriptor::TcpSocket { .. }) => file_descriptor,
+            Some(_) => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSOCK)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
             None => {
                 let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
                 let action = ExtrinsicsAction::Resume(ret);
                 return Ok((ContextInner::Finished, action));
             }
-        }
-    };
+        };
 
-    let name = match file_descriptor {
-        FileDescriptor::Empty | FileDescriptor::LogOut { .. } => {
-            // TODO: is that the correct return type?
-            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+        // TODO: support `RIFLAGS_RECV_PEEK` and `RIFLAGS_RECV_WAITALL`
+        if ri_flags != 0 {
+            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSUP)));
             let action = ExtrinsicsAction::Resume(ret);
             return Ok((ContextInner::Finished, action));
         }
-        // TODO: correct name; note that no null terminator is needed
-        // note that apparently any value other than an empty string will fail to match relative paths? it's weird
-        // cc https://github.com/CraneStation/wasi-libc/blob/9efc2f428358564fe64c374d762d0bfce1d92507/libc-bottom-half/libpreopen/libpreopen.c#L470
-        FileDescriptor::FilesystemEntry { .. } => b"",
-    };
-
-    let path_out = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    let path_out_len =
-        usize::try_from(params.next().unwrap().into_i32().unwrap()).unwrap_or(usize::max_value());
-    assert!(params.next().is_none());
 
-    // TODO: is it correct to truncate if the buffer is too small?
-    let to_write = cmp::min(path_out_len, name.len());
-    mem_access.write_memory(path_out, &name[..to_write])?;
-
-    let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
-    Ok((ContextInner::Finished, action))
-}
+        // Data that doesn't fit in the buffers is kept for the next read, and is thus never
+        // truncated.
+        let ro_flags: wasi::Roflags = 0;
+        mem_access.write_memory(ro_flags_out, &ro_flags.to_le_bytes())?;
+
+        buffered_read(
+            fd,
+            file_descriptor,
+            out_buffers_list,
+            num_read_out,
+            mem_access,
+        )
+    }
 
-fn fd_prestat_get(
-    state: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
-    mem_access: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let file_descriptors_lock = state.file_descriptors.lock();
+    #[extrinsic(-> i32)]
+    fn sock_send(
+        state: &WasiExtrinsics,
+        mem_access: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        addr: u32,
+        num: u32,
+        _si_flags: i32,
+        num_written_out: u32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let file_descriptors_lock = state.file_descriptors.lock();
+
+        let fd = usize::try_from(fd)?;
+        let list_to_write = read_io_vectors(mem_access, addr, num)?;
+        // No flag is defined at the moment.
 
-    // Find out which file descriptor the user wants to write to.
-    let file_descriptor = {
-        let fd = usize::try_from(params.next().unwrap().into_i32().unwrap())?;
         match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
-            Some(fd) => fd,
+            Some(FileDescriptor::TcpSocket { socket_id, .. }) => {
+                tcp_socket_write(*socket_id, &list_to_write, num_written_out, mem_access)
+            }
+            Some(_) => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSOCK)));
+                let action = ExtrinsicsAction::Resume(ret);
+                Ok((ContextInner::Finished, action))
+            }
             None => {
                 let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
                 let action = ExtrinsicsAction::Resume(ret);
-                return Ok((ContextInner::Finished, action));
+                Ok((ContextInner::Finished, action))
             }
         }
-    };
+    }
 
-    let pr_name_len: u32 = match file_descriptor {
-        FileDescriptor::Empty | FileDescriptor::LogOut { .. } => {
-            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSUP)));
-            let action = ExtrinsicsAction::Resume(ret);
-            return Ok((ContextInner::Finished, action));
-        }
-        FileDescriptor::FilesystemEntry { inode, .. } => match **inode {
-            // TODO: we don't know for sure that it's been pre-open
-            Inode::Directory { .. } => 0, // TODO: must match the length of the return value of `fd_prestat_dir_name`
-            Inode::File { .. } => {
-                // TODO: is that the correct return type?
-                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSUP)));
+    #[extrinsic(-> i32)]
+    fn sock_shutdown(
+        state: &WasiExtrinsics,
+        _: &mut impl ExtrinsicsMemoryAccess,
+        fd: u32,
+        how: i32,
+    ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+        let file_descriptors_lock = state.file_descriptors.lock();
+
+        let fd = usize::try_from(fd)?;
+        let how = wasi::Sdflags::try_from(how)?;
+
+        let socket_id = match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+            Some(FileDescriptor::TcpSocket { socket_id, .. }) => *socket_id,
+            Some(_) => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSOCK)));
                 let action = ExtrinsicsAction::Resume(ret);
                 return Ok((ContextInner::Finished, action));
             }
-        },
-    };
+            None => {
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+        };
 
-    let prestat_out_buf = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
+        // TODO: the `tcp` interface has no way to stop receiving data, so `SDFLAGS_RD` is ignored
+        if (how & wasi::SDFLAGS_WR) == 0 {
+            let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+            return Ok((ContextInner::Finished, action));
+        }
 
-    // Note: this is a bit of dark magic, but it is the only solution at the moment.
-    // Can be tested with the following snippet:
-    // ```c
-    // #include <stdio.h>
-    // #include <wasi/api.h>
-    // int main() {
-    //     __wasi_prestat_t* ptr = (__wasi_prestat_t*)0x1000;
-    //     printf("%p %p %p %d\n", ptr, &ptr->tag, &ptr->u.dir, sizeof(__wasi_prestat_t));
-    //     return 0;
-    // }
-    // ```
-    // Which prints `0x1000 0x1000 0x1004 8`
-    mem_access.write_memory(prestat_out_buf, &[0; 8])?;
-    mem_access.write_memory(prestat_out_buf, &[wasi::PREOPENTYPE_DIR])?;
-    mem_access.write_memory(
-        prestat_out_buf.checked_add(4).ok_or(WasiCallErr)?,
-        &pr_name_len.to_le_bytes(),
-    )?;
+        let action = ExtrinsicsAction::EmitMessage {
+            interface: tcp_ffi::INTERFACE,
+            message: tcp_ffi::TcpMessage::Close(tcp_ffi::TcpClose { socket_id }).encode(),
+            response_expected: true,
+        };
 
-    let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
-    Ok((ContextInner::Finished, action))
+        Ok((ContextInner::WaitTcpShutdown, action))
+    }
 }
 
-fn fd_read(
+// Utility functions below.
+
+/// Common implementation of `path_remove_directory` and `path_unlink_file`.
+fn path_remove(
     state: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
     mem_access: &mut impl ExtrinsicsMemoryAccess,
+    fd: u32,
+    path_buf: u32,
+    path_buf_len: u32,
+    directory: bool,
 ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let mut file_descriptors_lock = state.file_descriptors.lock();
-
-    // Find out which file descriptor the user wants to read from.
-    let mut file_descriptor = {
-        let fd = usize::try_from(params.next().unwrap().into_i32().unwrap())?;
-        match file_descriptors_lock.get_mut(fd).and_then(|v| v.as_mut()) {
-            Some(fd) => fd,
-            None => {
+    let fd_inode = {
+        let file_descriptors_lock = state.file_descriptors.lock();
+        let fd = usize::try_from(fd)?;
+        match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
+            Some(FileDescriptor::FilesystemEntry { inode, .. }) => inode.clone(),
+            Some(FileDescriptor::Mount { .. }) => {
+                // TODO: the `files` interface doesn't support modifying directories
+                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOTSUP)));
+                let action = ExtrinsicsAction::Resume(ret);
+                return Ok((ContextInner::Finished, action));
+            }
+            _ => {
                 let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
                 let action = ExtrinsicsAction::Resume(ret);
                 return Ok((ContextInner::Finished, action));
@@ -863,494 +2938,596 @@ fn fd_read(
         }
     };
 
-    // Get a list of pointers and lengths to read to.
-    // Elements 0, 2, 4, 6, ... in that list are pointers, and elements 1, 3, 5, 7, ... are
-    // lengths.
-    let out_buffers_list = {
-        let addr = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-        let num = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-        let list_buf = mem_access.read_memory(addr..addr + 4 * num * 2)?;
-        // TODO: don't panic if allocation size is too large
-        let mut list_out = Vec::with_capacity(usize::try_from(num)?);
-        for elem in list_buf.chunks(4) {
-            list_out.push(u32::from_le_bytes(<[u8; 4]>::try_from(elem).unwrap()));
-        }
-        list_out
+    let path = {
+        let path_utf8 = mem_access.read_memory(path_buf..path_buf + path_buf_len)?;
+        String::from_utf8(path_utf8)? // TODO: return error code?
     };
 
-    let total_read: u32 = match &mut file_descriptor {
-        FileDescriptor::Empty | FileDescriptor::LogOut { .. } => 0,
-        FileDescriptor::FilesystemEntry {
-            inode,
-            file_cursor_pos,
-        } => {
-            match &**inode {
-                Inode::Directory { .. } => {
-                    // TODO: is that the correct error?
-                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
-                    let action = ExtrinsicsAction::Resume(ret);
-                    return Ok((ContextInner::Finished, action));
-                }
-                Inode::File { content, .. } => {
-                    let mut total_read = 0;
-                    for buffer in out_buffers_list.chunks(2) {
-                        let buffer_ptr = buffer[0];
-                        let buffer_len = usize::try_from(buffer[1])?;
-                        // The cursor position cannot go past `content.len()`, and thus always
-                        // fits in a `usize`.
-                        let file_cursor_pos_usize = usize::try_from(*file_cursor_pos).unwrap();
-                        debug_assert!(file_cursor_pos_usize <= content.len());
-                        let to_copy = cmp::min(content.len() - file_cursor_pos_usize, buffer_len);
-                        if to_copy == 0 {
-                            break;
-                        }
-                        mem_access.write_memory(
-                            buffer_ptr,
-                            &content[file_cursor_pos_usize..file_cursor_pos_usize + to_copy],
-                        )?;
-                        *file_cursor_pos = file_cursor_pos
-                            .checked_add(u64::try_from(to_copy)?)
-                            .ok_or(WasiCallErr)?;
-                        debug_assert!(
-                            *file_cursor_pos
-                                <= u64::try_from(content.len()).unwrap_or(u64::max_value())
-                        );
-                        total_read += to_copy;
-                    }
-                    u32::try_from(total_read)?
-                }
-            }
+    let (parent, name) = match resolve_parent(&fd_inode, &path) {
+        Some(p) => p,
+        None => {
+            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOENT)));
+            let action = ExtrinsicsAction::Resume(ret);
+            return Ok((ContextInner::Finished, action));
         }
     };
 
-    // Write to the last parameter the number of bytes that have been read in total.
-    let out_ptr = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
-    mem_access.write_memory(out_ptr, &total_read.to_le_bytes())?;
+    let mut entries = match &*parent {
+        Inode::Directory { entries } => entries.lock(),
+        // `resolve_parent` always returns a directory.
+        Inode::File { .. } => unreachable!(),
+    };
+
+    // Open file descriptors keep a reference to the inode, and thus continue to work after the
+    // entry has been removed.
+    let errno = match entries.get(name).map(|i| &**i) {
+        None => wasi::ERRNO_NOENT,
+        Some(Inode::File { .. }) if directory => wasi::ERRNO_NOTDIR,
+        Some(Inode::Directory { .. }) if !directory => wasi::ERRNO_ISDIR,
+        Some(Inode::Directory { entries }) if !entries.lock().is_empty() => wasi::ERRNO_NOTEMPTY,
+        Some(_) => wasi::ERRNO_SUCCESS,
+    };
 
-    let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+    if errno == wasi::ERRNO_SUCCESS {
+        entries.remove(name);
+    }
+
+    let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(errno))));
     Ok((ContextInner::Finished, action))
 }
 
-fn fd_seek(
+/// Inserts the given file descriptor in the first available slot, and returns its value.
+fn allocate_file_descriptor(
+    file_descriptors: &mut Vec<Option<FileDescriptor>>,
+    file_descriptor: FileDescriptor,
+) -> u32 {
+    let fd_val = if let Some(fd_val) = file_descriptors.iter().position(|fd| fd.is_none()) {
+        file_descriptors[fd_val] = Some(file_descriptor);
+        fd_val
+    } else {
+        file_descriptors.push(Some(file_descriptor));
+        file_descriptors.len() - 1
+    };
+
+    // TODO: return error code with "too many fds"
+    u32::try_from(fd_val).unwrap()
+}
+
+/// Called when the `files` interface answers a read issued by `fd_read`. Copies `data` to the
+/// buffers of the program.
+fn files_read_finished(
     state: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
+    fd: usize,
+    out_buffers: &[u32],
+    num_read_out: u32,
+    data: &[u8],
     mem_access: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let mut file_descriptors_lock = state.file_descriptors.lock();
+) -> Result<(), WasiCallErr> {
+    let total_read = copy_to_buffers(out_buffers, num_read_out, data, mem_access)?;
 
-    // Find out which file descriptor the user wants to seek.
-    let mut file_descriptor = {
-        let fd = usize::try_from(params.next().unwrap().into_i32().unwrap())?;
-        match file_descriptors_lock.get_mut(fd).and_then(|v| v.as_mut()) {
-            Some(fd) => fd,
-            None => {
-                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
-                let action = ExtrinsicsAction::Resume(ret);
-                return Ok((ContextInner::Finished, action));
-            }
-        }
-    };
+    if let Some(Some(FileDescriptor::MountedFile {
+        file_cursor_pos, ..
+    })) = state.file_descriptors.lock().get_mut(fd)
+    {
+        *file_cursor_pos = file_cursor_pos.saturating_add(u64::try_from(total_read)?);
+    }
 
-    let offset: i64 = params.next().unwrap().into_i64().unwrap();
-    let whence = u8::try_from(params.next().unwrap().into_i32().unwrap())?;
+    Ok(())
+}
 
-    let new_offset: u64 = match &mut file_descriptor {
-        FileDescriptor::Empty | FileDescriptor::LogOut { .. } => {
-            // TODO: is that the correct error?
-            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
-            let action = ExtrinsicsAction::Resume(ret);
-            return Ok((ContextInner::Finished, action));
-        }
-        FileDescriptor::FilesystemEntry {
-            inode,
-            file_cursor_pos,
-        } => {
-            match &**inode {
-                Inode::Directory { .. } => {
-                    // TODO: is that the correct error?
-                    let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
-                    let action = ExtrinsicsAction::Resume(ret);
-                    return Ok((ContextInner::Finished, action));
-                }
-                Inode::File { content, .. } => {
-                    let max_offset = u64::try_from(content.len())?;
-                    // TODO: do that properly
-                    let new_offset = match whence {
-                        wasi::WHENCE_SET => {
-                            cmp::min(u64::try_from(cmp::max(offset, 0))?, max_offset)
-                        }
-                        wasi::WHENCE_CUR => {
-                            cmp::min(file_cursor_pos.saturating_add(offset as u64), max_offset)
-                        }
-                        wasi::WHENCE_END => cmp::min(
-                            u64::try_from(cmp::max(0, max_offset as i64 + offset))?,
-                            max_offset,
-                        ),
-                        _ => panic!(), // TODO: no
-                    };
-                    *file_cursor_pos = new_offset;
-                    new_offset
-                }
-            }
+/// Copies `data` to the buffers passed to `fd_read`, and writes the number of bytes copied at
+/// `num_read_out`. Returns the number of bytes copied.
+///
+/// If `data` is larger than the buffers, the excess is discarded.
+fn copy_to_buffers(
+    out_buffers: &[u32],
+    num_read_out: u32,
+    data: &[u8],
+    mem_access: &mut impl ExtrinsicsMemoryAccess,
+) -> Result<usize, WasiCallErr> {
+    let mut remaining = data;
+    for buffer in out_buffers.chunks(2) {
+        if remaining.is_empty() {
+            break;
         }
-    };
 
-    // Write to the last parameter the new offset.
-    let out_ptr = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
-    mem_access.write_memory(out_ptr, &new_offset.to_le_bytes())?;
+        let to_copy = cmp::min(remaining.len(), usize::try_from(buffer[1])?);
+        mem_access.write_memory(buffer[0], &remaining[..to_copy])?;
+        remaining = &remaining[to_copy..];
+    }
 
-    let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
-    Ok((ContextInner::Finished, action))
+    let total_read = data.len() - remaining.len();
+    mem_access.write_memory(num_read_out, &u32::try_from(total_read)?.to_le_bytes())?;
+    Ok(total_read)
 }
 
-fn fd_write(
-    state: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
+/// Writes the result of a call to `poll_oneoff` to the memory of the process.
+fn write_poll_events(
     mem_access: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let mut file_descriptors_lock = state.file_descriptors.lock();
+    events_out: u32,
+    num_events_out: u32,
+    events: &[PollEvent],
+) -> Result<(), WasiCallErr> {
+    // An event is 32 bytes long: `userdata` at offset 0, `error` at offset 8, `type` at
+    // offset 10, and the `nbytes` and `flags` of `fd_read` and `fd_write` events at offsets 16
+    // and 24.
+    let mut buffer = Vec::with_capacity(events.len() * 32);
+    for event in events {
+        let mut out = [0; 32];
+        out[0..8].copy_from_slice(&event.userdata.to_le_bytes());
+        out[8..10].copy_from_slice(&event.error.to_le_bytes());
+        out[10] = event.ty;
+        out[16..24].copy_from_slice(&event.nbytes.to_le_bytes());
+        buffer.extend_from_slice(&out);
+    }
 
-    // Find out which file descriptor the user wants to write to.
-    let fd = usize::try_from(params.next().unwrap().into_i32().unwrap())?;
-    let file_descriptor = {
-        match file_descriptors_lock.get_mut(fd).and_then(|v| v.as_mut()) {
-            Some(fd) => fd,
-            None => {
-                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
-                let action = ExtrinsicsAction::Resume(ret);
-                return Ok((ContextInner::Finished, action));
-            }
-        }
-    };
+    mem_access.write_memory(events_out, &buffer)?;
+    mem_access.write_memory(num_events_out, &u32::try_from(events.len())?.to_le_bytes())?;
+    Ok(())
+}
 
-    // Get a list of pointers and lengths to write.
-    // Elements 0, 2, 4, 6, ... in that list are pointers, and elements 1, 3, 5, 7, ... are
-    // lengths.
-    let list_to_write = {
-        let addr = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-        let num = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-        let list_buf = mem_access.read_memory(addr..addr + 4 * num * 2)?;
-        // TODO: don't panic if allocation size is too large
-        let mut list_out = Vec::with_capacity(usize::try_from(num)?);
-        for elem in list_buf.chunks(4) {
-            list_out.push(u32::from_le_bytes(<[u8; 4]>::try_from(elem).unwrap()));
-        }
-        list_out
-    };
+/// Reads a list of `iovec`s or `ciovec`s from the memory of the program.
+///
+/// Elements 0, 2, 4, 6, ... in the returned list are pointers, and elements 1, 3, 5, 7, ... are
+/// lengths.
+fn read_io_vectors(
+    mem_access: &mut impl ExtrinsicsMemoryAccess,
+    addr: u32,
+    num: u32,
+) -> Result<Vec<u32>, WasiCallErr> {
+    let list_buf = mem_access.read_memory(addr..addr + 4 * num * 2)?;
+    // TODO: don't panic if allocation size is too large
+    let mut list_out = Vec::with_capacity(usize::try_from(num)?);
+    for elem in list_buf.chunks(4) {
+        list_out.push(u32::from_le_bytes(<[u8; 4]>::try_from(elem).unwrap()));
+    }
+    Ok(list_out)
+}
 
-    match file_descriptor {
-        FileDescriptor::Empty => {
-            // TODO: is that the right error code?
-            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_NOSYS)));
-            let action = ExtrinsicsAction::Resume(ret);
-            Ok((ContextInner::Finished, action))
+/// Common implementation of `fd_read` and `sock_recv` for the console and TCP sockets.
+///
+/// Returns the data or the error received on behalf of `file_descriptor`, or waits for some to
+/// arrive.
+fn buffered_read(
+    fd: usize,
+    file_descriptor: &mut FileDescriptor,
+    out_buffers: Vec<u32>,
+    num_read_out: u32,
+    mem_access: &mut impl ExtrinsicsMemoryAccess,
+) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
+    let (read_buffer, read_error, closed) = match file_descriptor {
+        FileDescriptor::ConsoleIn {
+            read_buffer,
+            read_error,
+            closed,
+            ..
+        } => (read_buffer, read_error, *closed),
+        FileDescriptor::TcpSocket {
+            read_buffer,
+            read_error,
+            ..
+        } => (read_buffer, read_error, false),
+        // The file descriptor has been closed and replaced while waiting.
+        _ => {
+            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
+            return Ok((ContextInner::Finished, ExtrinsicsAction::Resume(ret)));
         }
-        FileDescriptor::LogOut { level, buffer } => {
-            let mut total_written = 0usize;
-            for ptr_and_len in list_to_write.chunks(2) {
-                let ptr = ptr_and_len[0];
-                let len = ptr_and_len[1];
-
-                buffer.extend(mem_access.read_memory(ptr..ptr + len)?);
-                total_written = total_written
-                    .checked_add(usize::try_from(len)?)
-                    .ok_or(WasiCallErr)?;
-            }
+    };
 
-            // Write to the fourth parameter the number of bytes written to the file descriptor.
-            {
-                let out_ptr = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-                let total_written = u32::try_from(total_written)?;
-                mem_access.write_memory(out_ptr, &total_written.to_le_bytes())?;
-            }
+    // Data that doesn't fit in the buffers is kept for the next read.
+    if !read_buffer.is_empty() || closed {
+        let total_read = copy_to_buffers(&out_buffers, num_read_out, read_buffer, mem_access)?;
+        read_buffer.drain(..total_read);
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        return Ok((ContextInner::Finished, action));
+    }
 
-            assert!(params.next().is_none());
+    if let Some(errno) = read_error.take() {
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(errno))));
+        return Ok((ContextInner::Finished, action));
+    }
 
-            // Flush `buffer` into a log message if possible.
-            if let Some(split_pos) = buffer.iter().position(|c| *c == b'\n') {
-                let mut encoded_message = Vec::new();
-                encoded_message.push(u8::from(*level));
-                encoded_message.extend(buffer.drain(..split_pos));
-                buffer.remove(0);
+    let max_len = out_buffers
+        .chunks(2)
+        .fold(0u32, |len, buffer| len.saturating_add(buffer[1]));
 
-                let action = ExtrinsicsAction::EmitMessage {
-                    interface: redshirt_log_interface::ffi::INTERFACE,
-                    message: EncodedMessage(encoded_message),
-                    response_expected: false,
-                };
+    let action = ExtrinsicsAction::EmitMessagesWaitFirst {
+        messages: file_descriptor.read_messages_to_emit(max_len),
+        wait_also: file_descriptor.pending_read_messages(),
+    };
 
-                let context = ContextInner::TryFlushLogOut(fd);
-                Ok((context, action))
-            } else {
-                let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
-                Ok((ContextInner::Finished, action))
-            }
-        }
-        FileDescriptor::FilesystemEntry { .. } => unimplemented!(), // TODO:
-    }
+    let context = ContextInner::WaitBufferedRead {
+        fd,
+        out_buffers,
+        num_read_out,
+    };
+
+    Ok((context, action))
 }
 
-fn path_filestat_get(
-    state: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
+/// Common implementation of `sock_accept` and of the subsequent attempts once a connection
+/// has been accepted on behalf of the listening socket `fd`.
+fn tcp_accept(
+    file_descriptors: &mut Vec<Option<FileDescriptor>>,
+    fd: usize,
+    flags: wasi::Fdflags,
+    opened_fd_ptr: u32,
     mem_access: &mut impl ExtrinsicsMemoryAccess,
 ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let file_descriptors_lock = state.file_descriptors.lock();
-
-    let file_descriptor = {
-        let fd = usize::try_from(params.next().unwrap().into_i32().unwrap())?;
-        match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
-            Some(fd) => fd,
-            None => {
-                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
-                let action = ExtrinsicsAction::Resume(ret);
-                return Ok((ContextInner::Finished, action));
-            }
-        }
-    };
-
-    let fd_inode = match file_descriptor {
-        FileDescriptor::Empty | FileDescriptor::LogOut { .. } => {
-            // TODO: is that the correct return type?
+    let listener = file_descriptors.get_mut(fd).and_then(|v| v.as_mut());
+    let accepted = match listener {
+        Some(FileDescriptor::TcpListener { accepted, .. }) => accepted.pop_front(),
+        // The file descriptor has been closed and replaced while waiting.
+        _ => {
             let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
-            let action = ExtrinsicsAction::Resume(ret);
-            return Ok((ContextInner::Finished, action));
+            return Ok((ContextInner::Finished, ExtrinsicsAction::Resume(ret)));
         }
-        FileDescriptor::FilesystemEntry { inode, .. } => inode.clone(),
     };
 
-    let _lookup_flags = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
+    match accepted {
+        Some(Ok(socket_id)) => {
+            let new_fd = allocate_file_descriptor(
+                file_descriptors,
+                FileDescriptor::TcpSocket {
+                    socket_id,
+                    read_buffer: Vec::new(),
+                    pending_read: None,
+                    read_error: None,
+                    flags,
+                },
+            );
+
+            mem_access.write_memory(opened_fd_ptr, &new_fd.to_le_bytes())?;
+            let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+            Ok((ContextInner::Finished, action))
+        }
+        Some(Err(())) => {
+            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_CONNABORTED)));
+            Ok((ContextInner::Finished, ExtrinsicsAction::Resume(ret)))
+        }
+        None => {
+            // Checked above.
+            let listener = file_descriptors[fd].as_ref().unwrap();
+            let action = ExtrinsicsAction::EmitMessagesWaitFirst {
+                messages: listener.read_messages_to_emit(0),
+                wait_also: listener.pending_read_messages(),
+            };
 
-    let path = {
-        let path_buf = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-        let path_buf_len = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-        let path_utf8 = mem_access.read_memory(path_buf..path_buf + path_buf_len)?;
-        String::from_utf8(path_utf8)? // TODO: return error code?
-    };
+            let context = ContextInner::WaitTcpAccept {
+                fd,
+                opened_fd_ptr,
+                flags,
+            };
 
-    let resolved_path = match resolve_path(&fd_inode, &path) {
-        Some(p) => p,
-        None => {
-            let action =
-                ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(wasi::ERRNO_NOENT))));
-            return Ok((ContextInner::Finished, action));
+            Ok((context, action))
         }
-    };
+    }
+}
 
-    let filestat = filestat_from_inode(&resolved_path);
+/// Records that the messages returned by [`FileDescriptor::read_messages_to_emit`] for `fd`
+/// have been emitted. Does nothing if `fd` has been closed in the meanwhile.
+fn record_read_messages(
+    file_descriptors: &mut [Option<FileDescriptor>],
+    fd: usize,
+    emitted: Vec<Option<MessageId>>,
+) {
+    if let Some(Some(file_descriptor)) = file_descriptors.get_mut(fd) {
+        // All these messages expect a response.
+        for message_id in emitted {
+            file_descriptor.record_read_message(message_id.unwrap());
+        }
+    }
+}
 
-    let filestat_out_buf = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
+/// Updates the state of the file descriptor that `message_id` has been emitted on behalf of
+/// with the response to this message. Does nothing if that file descriptor has been closed.
+///
+/// Returns an error if the response can't be decoded.
+fn dispatch_read_response(
+    file_descriptors: &mut [Option<FileDescriptor>],
+    message_id: MessageId,
+    response: Result<EncodedMessage, ()>,
+) -> Result<(), WasiCallErr> {
+    // An error answer means that the handler of the interface has failed to process the
+    // message, for example because it has crashed. This is reported to the program as an
+    // I/O error.
+    for file_descriptor in file_descriptors.iter_mut().filter_map(|f| f.as_mut()) {
+        match file_descriptor {
+            FileDescriptor::ConsoleIn {
+                read_buffer,
+                pending_read,
+                read_error,
+                closed,
+            } if *pending_read == Some(message_id) => {
+                *pending_read = None;
+                match response {
+                    Ok(response) => {
+                        let response: console_ffi::ReadResponse =
+                            response.decode().map_err(|_| WasiCallErr)?;
+                        // An empty response means that the console has been closed.
+                        *closed = response.data.is_empty();
+                        read_buffer.extend_from_slice(&response.data);
+                    }
+                    Err(()) => *read_error = Some(wasi::ERRNO_IO),
+                }
+            }
+            FileDescriptor::TcpSocket {
+                read_buffer,
+                pending_read,
+                read_error,
+                ..
+            } if *pending_read == Some(message_id) => {
+                *pending_read = None;
+                let result = match response {
+                    Ok(response) => {
+                        let response: tcp_ffi::TcpReadResponse =
+                            response.decode().map_err(|_| WasiCallErr)?;
+                        response.result.map_err(|err| match err {
+                            tcp_ffi::TcpReadError::ConnectionFinished => wasi::ERRNO_NOTCONN,
+                            tcp_ffi::TcpReadError::InvalidSocket => wasi::ERRNO_BADF,
+                        })
+                    }
+                    Err(()) => Err(wasi::ERRNO_IO),
+                };
+                match result {
+                    Ok(data) => read_buffer.extend_from_slice(&data),
+                    Err(errno) => *read_error = Some(errno),
+                }
+            }
+            FileDescriptor::TcpListener {
+                pending_accepts,
+                accepted,
+                ..
+            } if pending_accepts.contains(&message_id) => {
+                pending_accepts.retain(|id| *id != message_id);
+                let result = match response {
+                    Ok(response) => {
+                        let response: tcp_ffi::TcpOpenResponse =
+                            response.decode().map_err(|_| WasiCallErr)?;
+                        response.result.map(|opened| opened.socket_id)
+                    }
+                    Err(()) => Err(()),
+                };
+                accepted.push_back(result);
+            }
+            _ => continue,
+        }
 
-    // Note: this is a bit of dark magic, but it is the only solution at the moment.
-    // Can be tested with the following snippet:
-    // ```c
-    // #include <stdio.h>
-    // #include <wasi/api.h>
-    // int main() {
-    //     __wasi_filestat_t* ptr = (__wasi_filestat_t*)0x1000;
-    //     printf("%p %p %p %p %p %p %p %p %p %d\n", ptr, &ptr->dev, &ptr->ino, &ptr->filetype, &ptr->nlink, &ptr->size, &ptr->atim, &ptr->mtim, &ptr->ctim, sizeof(__wasi_filestat_t));
-    //     return 0;
-    // }
-    // ```
-    // Which prints `0x1000 0x1000 0x1008 0x1010 0x1018 0x1020 0x1028 0x1030 0x1038 64`
-    mem_access.write_memory(filestat_out_buf, &[0; 64])?;
-    mem_access.write_memory(filestat_out_buf, &filestat.dev.to_le_bytes())?;
-    mem_access.write_memory(
-        filestat_out_buf.checked_add(8).ok_or(WasiCallErr)?,
-        &filestat.ino.to_le_bytes(),
-    )?;
-    mem_access.write_memory(
-        filestat_out_buf.checked_add(16).ok_or(WasiCallErr)?,
-        &filestat.filetype.to_le_bytes(),
-    )?;
-    mem_access.write_memory(
-        filestat_out_buf.checked_add(24).ok_or(WasiCallErr)?,
-        &filestat.nlink.to_le_bytes(),
-    )?;
-    mem_access.write_memory(
-        filestat_out_buf.checked_add(32).ok_or(WasiCallErr)?,
-        &filestat.size.to_le_bytes(),
-    )?;
-    mem_access.write_memory(
-        filestat_out_buf.checked_add(40).ok_or(WasiCallErr)?,
-        &filestat.atim.to_le_bytes(),
-    )?;
-    mem_access.write_memory(
-        filestat_out_buf.checked_add(48).ok_or(WasiCallErr)?,
-        &filestat.mtim.to_le_bytes(),
-    )?;
-    mem_access.write_memory(
-        filestat_out_buf.checked_add(56).ok_or(WasiCallErr)?,
-        &filestat.ctim.to_le_bytes(),
-    )?;
+        return Ok(());
+    }
 
-    let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
-    Ok((ContextInner::Finished, action))
+    Ok(())
 }
 
-fn path_open(
+/// Common implementation of `poll_oneoff`, once the current time is known if necessary, and of
+/// the subsequent attempts if a response doesn't make any subscription ready.
+///
+/// Reports the file descriptors of `fds` that are ready, if any. Otherwise, waits for one of
+/// them to become ready or for `timeout` to be reached.
+fn poll_wait(
     state: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
+    fds: Vec<PollFd>,
+    timeout: Option<PollTimeout>,
+    events_out: u32,
+    num_events_out: u32,
     mem_access: &mut impl ExtrinsicsMemoryAccess,
 ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let mut file_descriptors_lock = state.file_descriptors.lock();
+    let file_descriptors_lock = state.file_descriptors.lock();
 
-    let file_descriptor = {
-        let fd = usize::try_from(params.next().unwrap().into_i32().unwrap())?;
-        match file_descriptors_lock.get(fd).and_then(|v| v.as_ref()) {
-            Some(fd) => fd,
-            None => {
-                let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
-                let action = ExtrinsicsAction::Resume(ret);
-                return Ok((ContextInner::Finished, action));
-            }
-        }
-    };
+    let ready_events = ready_poll_events(&file_descriptors_lock, &fds);
+    if !ready_events.is_empty() {
+        write_poll_events(mem_access, events_out, num_events_out, &ready_events)?;
+        let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
+        return Ok((ContextInner::Finished, action));
+    }
 
-    let fd_inode = match file_descriptor {
-        FileDescriptor::Empty | FileDescriptor::LogOut { .. } => {
-            // TODO: is that the correct return type?
-            let ret = Some(WasmValue::I32(From::from(wasi::ERRNO_BADF)));
-            let action = ExtrinsicsAction::Resume(ret);
-            return Ok((ContextInner::Finished, action));
-        }
-        FileDescriptor::FilesystemEntry { inode, .. } => inode.clone(),
-    };
+    let mut messages = Vec::new();
+    let mut emitted_for = Vec::new();
+    let mut wait_also = Vec::new();
 
-    let _lookup_flags = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
+    for poll_fd in &fds {
+        // The same file descriptor can appear multiple times.
+        if emitted_for.iter().any(|m| match m {
+            PollMessage::Fd(fd) => *fd == poll_fd.fd,
+            PollMessage::Timer(_) => false,
+        }) {
+            continue;
+        }
 
-    let path = {
-        let path_buf = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-        let path_buf_len = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-        let path_utf8 = mem_access.read_memory(path_buf..path_buf + path_buf_len)?;
-        String::from_utf8(path_utf8)? // TODO: return error code?
-    };
+        // `ready_poll_events` reports the file descriptors that have been closed.
+        let file_descriptor = file_descriptors_lock[poll_fd.fd].as_ref().unwrap();
+        for message in file_descriptor.read_messages_to_emit(CONSOLE_POLL_READ_LEN) {
+            messages.push(message);
+            emitted_for.push(PollMessage::Fd(poll_fd.fd));
+        }
+        for message_id in file_descriptor.pending_read_messages() {
+            if !wait_also.contains(&message_id) {
+                wait_also.push(message_id);
+            }
+        }
+    }
 
-    let resolved_path = match resolve_path(&fd_inode, &path) {
-        Some(p) => p,
-        None => {
-            let action =
-                ExtrinsicsAction::Resume(Some(WasmValue::I32(From::from(wasi::ERRNO_NOENT))));
-            return Ok((ContextInner::Finished, action));
+    // Timers emitted by previous calls are waited for as well, so that their responses don't
+    // stay in the queue of the process.
+    let timers = state.timers.lock();
+    if let Some(timeout) = &timeout {
+        if timers.iter().all(|(_, value)| *value > timeout.deadline) {
+            messages.push(MessageToEmit {
+                interface: redshirt_time_interface::ffi::INTERFACE,
+                message: redshirt_time_interface::ffi::TimeMessage::WaitMonotonic(timeout.deadline)
+                    .encode(),
+                response_expected: true,
+            });
+            emitted_for.push(PollMessage::Timer(timeout.deadline));
         }
+    }
+    wait_also.extend(timers.iter().map(|(message_id, _)| *message_id));
+
+    let context = ContextInner::PollWait {
+        fds,
+        timeout,
+        emitted_for,
+        events_out,
+        num_events_out,
     };
 
-    let _open_flags = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    let _fs_rights_base = u64::try_from(params.next().unwrap().into_i64().unwrap())?;
-    let _fs_rights_inherting = u64::try_from(params.next().unwrap().into_i64().unwrap())?;
-    let _fd_flags = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-
-    let new_fd = if let Some(fd_val) = file_descriptors_lock.iter().position(|fd| fd.is_none()) {
-        file_descriptors_lock[fd_val] = Some(FileDescriptor::FilesystemEntry {
-            inode: resolved_path,
-            file_cursor_pos: 0,
-        });
-        // TODO: return error code with "too many fds"
-        u32::try_from(fd_val).unwrap()
-    } else {
-        let fd_val = file_descriptors_lock.len();
-        file_descriptors_lock.push(Some(FileDescriptor::FilesystemEntry {
-            inode: resolved_path,
-            file_cursor_pos: 0,
-        }));
-        // TODO: return error code with "too many fds"
-        u32::try_from(fd_val).unwrap()
+    let action = ExtrinsicsAction::EmitMessagesWaitFirst {
+        messages,
+        wait_also,
     };
 
-    let opened_fd_ptr = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
-
-    mem_access.write_memory(opened_fd_ptr, &new_fd.to_le_bytes())?;
-
-    let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
-    Ok((ContextInner::Finished, action))
+    Ok((context, action))
 }
 
-fn poll_oneoff(
-    _: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
-    _: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let _subscriptions_buf = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    let _events_out_buf = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    let _buf_size = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    let _num_events_out = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
-
-    unimplemented!()
+/// Returns the events to report for the subscriptions of `fds` whose file descriptor is ready
+/// to be read from.
+fn ready_poll_events(
+    file_descriptors: &[Option<FileDescriptor>],
+    fds: &[PollFd],
+) -> Vec<PollEvent> {
+    fds.iter()
+        .filter_map(|poll_fd| {
+            let file_descriptor = file_descriptors.get(poll_fd.fd).and_then(|v| v.as_ref());
+            if file_descriptor.map_or(false, |f| f.read_would_block()) {
+                return None;
+            }
+            Some(poll_fd_event(
+                file_descriptor,
+                poll_fd.userdata,
+                wasi::EVENTTYPE_FD_READ,
+            ))
+        })
+        .collect()
 }
 
-fn proc_exit(
-    _: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
-    _: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let _ret_val = params.next().unwrap().into_i32().unwrap();
-    assert!(params.next().is_none());
-
-    // TODO: returning `ProgramCrash` leads to `unimplemented!()`, so we panic
-    // beforehand for a more explicit message
-    // If the exit code is weird, it's probably one of these values:
-    // https://github.com/WebAssembly/wasi-libc/blob/320054e84f8f2440def3b1c8700cedb8fd697bf8/libc-top-half/musl/include/sysexits.h
-    panic!("proc_exit called with {:?}", _ret_val);
+/// Returns the event to report for a subscription of `poll_oneoff` to `file_descriptor`, which
+/// must be ready.
+fn poll_fd_event(
+    file_descriptor: Option<&FileDescriptor>,
+    userdata: u64,
+    ty: wasi::Eventtype,
+) -> PollEvent {
+    let (error, nbytes) = match (file_descriptor, ty) {
+        (None, _) => (wasi::ERRNO_BADF, 0),
+        (
+            Some(FileDescriptor::FilesystemEntry {
+                inode,
+                file_cursor_pos,
+                ..
+            }),
+            wasi::EVENTTYPE_FD_READ,
+        ) => match &**inode {
+            // Same error as `fd_read`.
+            Inode::Directory { .. } => (wasi::ERRNO_BADF, 0),
+            Inode::File { content } => {
+                let len = u64::try_from(content.lock().len()).unwrap_or(u64::max_value());
+                (wasi::ERRNO_SUCCESS, len.saturating_sub(*file_cursor_pos))
+            }
+        },
+        (
+            Some(FileDescriptor::MountedFile {
+                file_cursor_pos,
+                size,
+                ..
+            }),
+            wasi::EVENTTYPE_FD_READ,
+        ) => (wasi::ERRNO_SUCCESS, size.saturating_sub(*file_cursor_pos)),
+        (Some(FileDescriptor::ConsoleIn { read_buffer, .. }), wasi::EVENTTYPE_FD_READ)
+        | (Some(FileDescriptor::TcpSocket { read_buffer, .. }), wasi::EVENTTYPE_FD_READ) => (
+            wasi::ERRNO_SUCCESS,
+            u64::try_from(read_buffer.len()).unwrap_or(u64::max_value()),
+        ),
+        (Some(FileDescriptor::TcpListener { accepted, .. }), wasi::EVENTTYPE_FD_READ) => (
+            wasi::ERRNO_SUCCESS,
+            u64::try_from(accepted.len()).unwrap_or(u64::max_value()),
+        ),
+        (Some(_), _) => (wasi::ERRNO_SUCCESS, 0),
+    };
 
-    // TODO: implement in a better way than crashing?
-    Ok((ContextInner::Finished, ExtrinsicsAction::ProgramCrash))
+    PollEvent {
+        userdata,
+        error,
+        ty,
+        nbytes,
+    }
 }
 
-fn random_get(
-    _: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
-    _: &mut impl ExtrinsicsMemoryAccess,
+/// Common implementation of `fd_write` and `sock_send` for TCP sockets.
+fn tcp_socket_write(
+    socket_id: u32,
+    list_to_write: &[u32],
+    num_written_out: u32,
+    mem_access: &mut impl ExtrinsicsMemoryAccess,
 ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let buf = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    let len = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
+    let mut data = Vec::new();
+    for ptr_and_len in list_to_write.chunks(2) {
+        let ptr = ptr_and_len[0];
+        let len = ptr_and_len[1];
+        data.extend(mem_access.read_memory(ptr..ptr + len)?);
+    }
 
-    let len_to_request = u16::try_from(len).unwrap_or(u16::max_value());
-    debug_assert!(u32::from(len_to_request) <= len);
+    // TODO: the `tcp` interface only allows one write at a time per socket, which multiple
+    // threads writing simultaneously would violate
+    let num_written = u32::try_from(data.len())?;
     let action = ExtrinsicsAction::EmitMessage {
-        interface: redshirt_random_interface::ffi::INTERFACE,
-        message: redshirt_random_interface::ffi::RandomMessage::Generate {
-            len: len_to_request,
-        }
-        .encode(),
+        interface: tcp_ffi::INTERFACE,
+        message: tcp_ffi::TcpMessage::Write(tcp_ffi::TcpWrite { socket_id, data }).encode(),
         response_expected: true,
     };
 
-    let context = ContextInner::WaitRandom {
-        out_ptr: buf,
-        remaining_len: len,
+    let context = ContextInner::WaitTcpWrite {
+        num_written,
+        num_written_out,
     };
 
     Ok((context, action))
 }
 
-fn sched_yield(
-    _: &WasiExtrinsics,
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
-    _: &mut impl ExtrinsicsMemoryAccess,
-) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    // TODO: implement in a better way?
-    assert!(params.next().is_none());
-    let action = ExtrinsicsAction::Resume(Some(WasmValue::I32(0)));
-    Ok((ContextInner::Finished, action))
+/// Converts an error reported by the `files` interface into a WASI error code.
+fn files_error_to_errno(error: &files_ffi::FilesError) -> wasi::Errno {
+    match error {
+        files_ffi::FilesError::NoSuchVolume => wasi::ERRNO_NOENT,
+        files_ffi::FilesError::NotFound => wasi::ERRNO_NOENT,
+        files_ffi::FilesError::AlreadyExists => wasi::ERRNO_EXIST,
+        files_ffi::FilesError::IsDirectory => wasi::ERRNO_ISDIR,
+        files_ffi::FilesError::ReadOnly => wasi::ERRNO_ROFS,
+        files_ffi::FilesError::InvalidFile => wasi::ERRNO_BADF,
+        files_ffi::FilesError::Io => wasi::ERRNO_IO,
+    }
 }
 
-// Utility functions below.
+/// Returns the directory at the given path, creating it and its parents if necessary.
+///
+/// Returns `None` if one of the components of the path is a file.
+fn create_directory_all(root: &Arc<Inode>, path: &str) -> Option<Arc<Inode>> {
+    let mut current = root.clone();
+
+    for component in path.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        let next = match &*current {
+            Inode::Directory { entries } => entries
+                .lock()
+                .entry(String::from(component))
+                .or_insert_with(|| {
+                    Arc::new(Inode::Directory {
+                        entries: Spinlock::new(HashMap::default()),
+                    })
+                })
+                .clone(),
+            Inode::File { .. } => return None,
+        };
+
+        current = next;
+    }
+
+    Some(current)
+}
 
 fn args_or_env_get(
     list: &[Vec<u8>],
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
     mem_access: &mut impl ExtrinsicsMemoryAccess,
+    argv: u32,
+    argv_buf: u32,
 ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let argv = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    let argv_buf = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
-
     let mut argv_pos = 0;
     let mut argv_buf_pos = 0;
 
@@ -1374,13 +3551,10 @@ fn args_or_env_get(
 
 fn args_or_env_sizes_get(
     list: &[Vec<u8>],
-    mut params: impl ExactSizeIterator<Item = WasmValue>,
     mem_access: &mut impl ExtrinsicsMemoryAccess,
+    argc_out: u32,
+    argv_buf_size_out: u32,
 ) -> Result<(ContextInner, ExtrinsicsAction), WasiCallErr> {
-    let argc_out = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    let argv_buf_size_out = u32::try_from(params.next().unwrap().into_i32().unwrap())?;
-    assert!(params.next().is_none());
-
     mem_access.write_memory(argc_out, &u32::try_from(list.len())?.to_le_bytes())?;
     let argv_buf_size = list
         .iter()
@@ -1405,9 +3579,8 @@ fn filestat_from_inode(inode: &Arc<Inode>) -> wasi::Filestat {
         nlink: 1, // TODO:
         size: match &**inode {
             Inode::Directory { .. } => 0,
-            Inode::File { content } => {
-                wasi::Filesize::try_from(content.len()).unwrap_or(wasi::Filesize::max_value())
-            }
+            Inode::File { content } => wasi::Filesize::try_from(content.lock().len())
+                .unwrap_or(wasi::Filesize::max_value()),
         },
         atim: 0, // TODO:
         mtim: 0, // TODO:
@@ -1415,8 +3588,14 @@ fn filestat_from_inode(inode: &Arc<Inode>) -> wasi::Filestat {
     }
 }
 
+/// Resolves `path` relative to the directory `root`.
+///
+/// A `..` component designates the parent of the current directory. Since the file descriptor
+/// of `root` only gives access to its content, `..` in `root` designates `root` itself.
 fn resolve_path(root: &Arc<Inode>, path: &str) -> Option<Arc<Inode>> {
-    let mut current = root.clone();
+    // Directories traversed so far, not including `root`. The last element is the current
+    // directory.
+    let mut traversed: Vec<Arc<Inode>> = Vec::new();
 
     for component in path.split('/') {
         if component == "." {
@@ -1424,10 +3603,14 @@ fn resolve_path(root: &Arc<Inode>, path: &str) -> Option<Arc<Inode>> {
         }
 
         if component == ".." {
-            unimplemented!()
+            if let Some(Inode::File { .. }) = traversed.last().map(|i| &**i) {
+                return None;
+            }
+            traversed.pop();
+            continue;
         }
 
-        let next = match &*current {
+        let next = match traversed.last().map_or(&**root, |i| &**i) {
             Inode::File { .. } => return None,
             Inode::Directory { entries } => {
                 let entries = entries.lock();
@@ -1435,8 +3618,100 @@ fn resolve_path(root: &Arc<Inode>, path: &str) -> Option<Arc<Inode>> {
             }
         };
 
-        current = next;
+        traversed.push(next);
     }
 
-    Some(current)
+    Some(traversed.pop().unwrap_or_else(|| root.clone()))
+}
+
+/// Returns true if `inode` is `directory` or one of its descendants.
+fn is_within(inode: &Arc<Inode>, directory: &Arc<Inode>) -> bool {
+    let mut to_visit = vec![directory.clone()];
+
+    while let Some(current) = to_visit.pop() {
+        if Arc::ptr_eq(&current, inode) {
+            return true;
+        }
+
+        if let Inode::Directory { entries } = &*current {
+            to_visit.extend(entries.lock().values().cloned());
+        }
+    }
+
+    false
+}
+
+/// Resolves all the components of `path` but the last one, and returns the directory they
+/// designate and the name of the last component.
+///
+/// Returns `None` if this directory doesn't exist, or if the last component is empty, `.`,
+/// or `..`.
+fn resolve_parent<'a>(root: &Arc<Inode>, path: &'a str) -> Option<(Arc<Inode>, &'a str)> {
+    let path = path.trim_end_matches('/');
+    let (parent, name) = match path.rfind('/') {
+        Some(pos) => (&path[..pos], &path[pos + 1..]),
+        None => (".", path),
+    };
+
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+
+    let parent = resolve_path(root, parent)?;
+    if let Inode::File { .. } = *parent {
+        return None;
+    }
+
+    Some((parent, name))
+}
+
+fn write_filestat(
+    mem_access: &mut impl ExtrinsicsMemoryAccess,
+    filestat_out_buf: u32,
+    filestat: &wasi::Filestat,
+) -> Result<(), WasiCallErr> {
+    // Note: this is a bit of dark magic, but it is the only solution at the moment.
+    // Can be tested with the following snippet:
+    // ```c
+    // #include <stdio.h>
+    // #include <wasi/api.h>
+    // int main() {
+    //     __wasi_filestat_t* ptr = (__wasi_filestat_t*)0x1000;
+    //     printf("%p %p %p %p %p %p %p %p %p %d\n", ptr, &ptr->dev, &ptr->ino, &ptr->filetype, &ptr->nlink, &ptr->size, &ptr->atim, &ptr->mtim, &ptr->ctim, sizeof(__wasi_filestat_t));
+    //     return 0;
+    // }
+    // ```
+    // Which prints `0x1000 0x1000 0x1008 0x1010 0x1018 0x1020 0x1028 0x1030 0x1038 64`
+    mem_access.write_memory(filestat_out_buf, &[0; 64])?;
+    mem_access.write_memory(filestat_out_buf, &filestat.dev.to_le_bytes())?;
+    mem_access.write_memory(
+        filestat_out_buf.checked_add(8).ok_or(WasiCallErr)?,
+        &filestat.ino.to_le_bytes(),
+    )?;
+    mem_access.write_memory(
+        filestat_out_buf.checked_add(16).ok_or(WasiCallErr)?,
+        &filestat.filetype.to_le_bytes(),
+    )?;
+    mem_access.write_memory(
+        filestat_out_buf.checked_add(24).ok_or(WasiCallErr)?,
+        &filestat.nlink.to_le_bytes(),
+    )?;
+    mem_access.write_memory(
+        filestat_out_buf.checked_add(32).ok_or(WasiCallErr)?,
+        &filestat.size.to_le_bytes(),
+    )?;
+    mem_access.write_memory(
+        filestat_out_buf.checked_add(40).ok_or(WasiCallErr)?,
+        &filestat.atim.to_le_bytes(),
+    )?;
+    mem_access.write_memory(
+        filestat_out_buf.checked_add(48).ok_or(WasiCallErr)?,
+        &filestat.mtim.to_le_bytes(),
+    )?;
+    mem_access.write_memory(
+        filestat_out_buf.checked_add(56).ok_or(WasiCallErr)?,
+        &filestat.ctim.to_le_bytes(),
+    )?;
+
+    Ok(())
 }
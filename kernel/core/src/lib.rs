@@ -53,8 +53,10 @@
 //! - `interface`. The interface named `interface` allows programs to register themselves as
 //! provider of an interface. If a program then emits a message targetting the interface, then
 //! the registered program will be in charge of treating the message.
-//! - `threads`. The interface named `threads` provides a few utilities related to multithreading
-//! (TODO: this isn't really done yet)
+//! - `threads`. The interface named `threads` makes it possible to start secondary threads
+//! within the current process, and to wait for them to finish.
+//! - `process`. The interface named `process` makes it possible to start other programs, to
+//! wait for them to end, and to kill them.
 //!
 //! > **Note**: A very common workflow for a program is, immediately after it starts, to emit a
 //! >           message on the `interface` interface in order to register itself as the handler of
@@ -99,9 +101,24 @@
 //! > **Note**: As a general rule in IT, the only two timeout values that make sense are *0*
 //! >           and *infinite*.
 //!
-//! While this hasn't been implemented yet, the best way to deal with this kind of situation is
-//! to somehow report to the user the list of programs being stuck waiting for an interface
-//! handler.
+//! The best way to deal with this kind of situation is to report to the user the list of
+//! programs being stuck waiting for an interface handler. This list can be obtained with
+//! [`System::stuck_interfaces`], or through the `kernel-debug` interface. Additionally,
+//! [`SystemBuilder::with_stuck_emitter_warning`] makes [`System::run`] generate an event when
+//! a program has been waiting for too long.
+//!
+//! # Restricted interfaces
+//!
+//! By default, all programs can emit messages on all interfaces, including the ones handled by
+//! the kernel. Interfaces passed to [`SystemBuilder::with_restricted_interface`], however, only
+//! accept messages from the processes that have been granted the right to do so. Emitting a
+//! message on a restricted interface without this right fails immediately, no matter whether the
+//! emitter allows delays.
+//!
+//! Rights are granted to startup processes when building the [`System`], and later by the
+//! handler of the interface through the `interface` interface, or by calling
+//! [`System::grant_interface`] for native interfaces. The list of rights can be obtained
+//! through the `kernel-debug` interface.
 //!
 
 #![warn(missing_docs)]
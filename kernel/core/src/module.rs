@@ -13,15 +13,28 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use core::fmt;
+use core::{convert::TryFrom as _, fmt};
+use parity_wasm::{builder, elements};
+
+pub mod fuel;
+pub mod memory_grow;
+pub mod stack_pointer;
 
 /// Represents a successfully-parsed binary.
 ///
 /// This is the equivalent of an [ELF](https://en.wikipedia.org/wiki/Executable_and_Linkable_Format)
 /// or a [PE](https://en.wikipedia.org/wiki/Portable_Executable).
 pub struct Module {
-    inner: wasmi::Module,
+    /// Module after instrumentation, as passed to the engines. Each engine compiles it when
+    /// instantiating the module.
+    instrumented: elements::Module,
     hash: ModuleHash,
+    /// Initial size, in pages, of the memory defined by the module, if any. Doesn't include
+    /// imported memories.
+    memory_initial_pages: Option<u32>,
+    /// Initial number of elements of the table defined by the module, if any. Doesn't include
+    /// imported tables.
+    table_initial_elements: Option<u32>,
 }
 
 /// Hash of a module.
@@ -38,16 +51,60 @@ pub struct FromBase58Error {}
 
 impl Module {
     /// Parses a module from WASM bytes.
+    ///
+    /// The code of the module is modified in order to support preemption, resource limits, and
+    /// multiple threads. See the [`fuel`], [`memory_grow`], and [`stack_pointer`] modules.
+    ///
+    /// Only the structure of the binary is checked here. The code is validated by the
+    /// [`Engine`](crate::scheduler::Engine) when the module is instantiated.
     pub fn from_bytes(buffer: impl AsRef<[u8]>) -> Result<Self, FromBytesError> {
-        let inner = wasmi::Module::from_buffer(buffer.as_ref()).map_err(|_| FromBytesError {})?;
+        let parsed = parity_wasm::deserialize_buffer::<elements::Module>(buffer.as_ref())
+            .map_err(|_| FromBytesError {})?;
+
+        let memory_initial_pages = parsed
+            .memory_section()
+            .and_then(|s| s.entries().first())
+            .map(|m| m.limits().initial());
+        let table_initial_elements = parsed
+            .table_section()
+            .and_then(|s| s.entries().first())
+            .map(|t| t.limits().initial());
+
+        let instrumented = stack_pointer::inject(memory_grow::inject(fuel::inject(
+            parsed,
+            cfg!(feature = "backtraces"),
+        )));
         let hash = ModuleHash::from_bytes(buffer);
 
-        Ok(Module { inner, hash })
+        Ok(Module {
+            instrumented,
+            hash,
+            memory_initial_pages,
+            table_initial_elements,
+        })
+    }
+
+    /// Returns the module after the instrumentation for fuel metering and memory growth has
+    /// been applied.
+    ///
+    /// This is what implementations of [`Engine`](crate::scheduler::Engine) are expected to
+    /// compile or interpret.
+    pub fn instrumented(&self) -> &elements::Module {
+        &self.instrumented
     }
 
-    /// Returns a reference to the internal module.
-    pub(crate) fn as_ref(&self) -> &wasmi::Module {
-        &self.inner
+    /// Returns the initial size, in pages, of the memory defined by the module, if any.
+    ///
+    /// Imported memories aren't taken into account.
+    pub(crate) fn memory_initial_pages(&self) -> Option<u32> {
+        self.memory_initial_pages
+    }
+
+    /// Returns the initial number of elements of the table defined by the module, if any.
+    ///
+    /// Imported tables aren't taken into account.
+    pub(crate) fn table_initial_elements(&self) -> Option<u32> {
+        self.table_initial_elements
     }
 
     /// Returns the hash of that module.
@@ -103,6 +160,81 @@ impl fmt::Debug for ModuleHash {
     }
 }
 
+/// Adds to the module an import of a function with the given name and signature, and shifts the
+/// indices of all the existing functions accordingly.
+///
+/// Returns the modified module and the index of the newly-imported function.
+fn inject_function_import(
+    module: elements::Module,
+    import_module: &str,
+    import_field: &str,
+    params: &[elements::ValueType],
+    return_type: Option<elements::ValueType>,
+) -> (elements::Module, u32) {
+    // The new import is added after the existing function imports. All the function indices
+    // greater or equal to this one need to be shifted by one.
+    let new_func = u32::try_from(module.import_count(elements::ImportCountType::Function)).unwrap();
+
+    let mut module = {
+        let mut module_builder = builder::from_module(module);
+        let signature = module_builder.push_signature(
+            builder::signature()
+                .with_params(params.to_vec())
+                .with_return_type(return_type)
+                .build_sig(),
+        );
+        module_builder.push_import(
+            builder::import()
+                .module(import_module)
+                .field(import_field)
+                .external()
+                .func(signature)
+                .build(),
+        );
+        module_builder.build()
+    };
+
+    if let Some(code) = module.code_section_mut() {
+        for body in code.bodies_mut() {
+            for instruction in body.code_mut().elements_mut() {
+                if let elements::Instruction::Call(func_index) = instruction {
+                    if *func_index >= new_func {
+                        *func_index += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(exports) = module.export_section_mut() {
+        for export in exports.entries_mut() {
+            if let elements::Internal::Function(func_index) = export.internal_mut() {
+                if *func_index >= new_func {
+                    *func_index += 1;
+                }
+            }
+        }
+    }
+
+    if let Some(elements) = module.elements_section_mut() {
+        for segment in elements.entries_mut() {
+            for func_index in segment.members_mut() {
+                if *func_index >= new_func {
+                    *func_index += 1;
+                }
+            }
+        }
+    }
+
+    if let Some(start) = module.start_section() {
+        if start >= new_func {
+            module.set_start_section(start + 1);
+        }
+    }
+
+    (module, new_func)
+}
+
 impl fmt::Display for FromBase58Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "FromBase58Error")
@@ -0,0 +1,256 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//! Injection of fuel metering in a module.
+//!
+//! The interpreter we use doesn't have any way to interrupt a running function. In order to
+//! support preemption, we modify the code of each function so that it calls a special import
+//! at the start of the function body and at the start of each loop. The parameter passed to
+//! this import is an estimate of the number of instructions that are going to be executed
+//! before the next call.
+//!
+//! If call stack tracking is enabled, the call at the start of the function body is instead made
+//! to the "enter function" import, which additionally receives the index of the function within
+//! the original module. The body is wrapped within a block, and the "leave function" import is
+//! called right before the function returns. This lets the virtual machine keep track of the
+//! call stack, in order to report it when a trap happens.
+//!
+//! Tracking the call stack adds two host function calls to every function call. It is enabled
+//! through the `backtraces` feature of this crate, which is on by default. The `keccak`
+//! benchmark, which spends most of its time in loops, is about 3% slower with it, but code that
+//! calls many small functions pays a higher price.
+//!
+//! These imports are then resolved by the virtual machine, and don't correspond to any
+//! extrinsic.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom as _;
+use parity_wasm::elements;
+
+/// Name of the module of the imports injected in each module.
+pub const FUEL_IMPORT_MODULE: &str = "redshirt-vm";
+/// Name of the function of the import injected in each module. Its signature is `(i32) -> ()`.
+pub const FUEL_IMPORT_FUNCTION: &str = "consume_fuel";
+/// Name of the function called at the start of each function body. Its signature is
+/// `(i32, i32) -> ()`. The first parameter is the fuel to consume, as for
+/// [`FUEL_IMPORT_FUNCTION`], and the second parameter the index of the function being entered.
+pub const FUNCTION_ENTER_IMPORT_FUNCTION: &str = "enter_function";
+/// Name of the function called right before each function returns. Its signature is
+/// `() -> ()`.
+pub const FUNCTION_LEAVE_IMPORT_FUNCTION: &str = "leave_function";
+
+/// Modifies the given module to call the fuel-consumption imports.
+///
+/// If `track_call_stack` is true, the "enter function" and "leave function" imports are called
+/// as well. The function indices passed to the "enter function" import are the ones of the
+/// module given as parameter. As such, this must be called before any other function import is
+/// injected.
+pub fn inject(module: elements::Module, track_call_stack: bool) -> elements::Module {
+    let num_imported_functions =
+        u32::try_from(module.import_count(elements::ImportCountType::Function)).unwrap();
+
+    // For each function body, the type of the block that wraps it.
+    let block_types = {
+        let types = module.type_section().map_or(&[][..], |s| s.types());
+        module
+            .function_section()
+            .map_or(&[][..], |s| s.entries())
+            .iter()
+            .map(|func| match types.get(func.type_ref() as usize) {
+                Some(elements::Type::Function(ty)) => match ty.return_type() {
+                    Some(ty) => elements::BlockType::Value(ty),
+                    None => elements::BlockType::NoResult,
+                },
+                // Invalid type reference. The module will be rejected by the validation.
+                None => elements::BlockType::NoResult,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let (module, fuel_func) = super::inject_function_import(
+        module,
+        FUEL_IMPORT_MODULE,
+        FUEL_IMPORT_FUNCTION,
+        &[elements::ValueType::I32],
+        None,
+    );
+    let (mut module, call_stack_funcs) = if track_call_stack {
+        let (module, enter_func) = super::inject_function_import(
+            module,
+            FUEL_IMPORT_MODULE,
+            FUNCTION_ENTER_IMPORT_FUNCTION,
+            &[elements::ValueType::I32, elements::ValueType::I32],
+            None,
+        );
+        let (module, leave_func) = super::inject_function_import(
+            module,
+            FUEL_IMPORT_MODULE,
+            FUNCTION_LEAVE_IMPORT_FUNCTION,
+            &[],
+            None,
+        );
+        (module, Some((enter_func, leave_func)))
+    } else {
+        (module, None)
+    };
+
+    if let Some(code) = module.code_section_mut() {
+        for (body_index, body) in code.bodies_mut().iter_mut().enumerate() {
+            let funcs = InjectedFunctions {
+                fuel: fuel_func,
+                call_stack: call_stack_funcs,
+            };
+            let function_index = num_imported_functions + u32::try_from(body_index).unwrap();
+            let block_type = block_types
+                .get(body_index)
+                .copied()
+                .unwrap_or(elements::BlockType::NoResult);
+            inject_function_body(
+                body.code_mut().elements_mut(),
+                &funcs,
+                function_index,
+                block_type,
+            );
+        }
+    }
+
+    module
+}
+
+/// Indices of the functions injected by [`inject`].
+struct InjectedFunctions {
+    fuel: u32,
+    /// "Enter function" and "leave function" imports, if the call stack is tracked.
+    call_stack: Option<(u32, u32)>,
+}
+
+/// Modifies the body of a function so that it calls the injected functions.
+///
+/// `function_index` is the value passed to the "enter function" import, and `block_type` the
+/// type of the block that wraps the body, which must match the return type of the function.
+fn inject_function_body(
+    instructions: &mut Vec<elements::Instruction>,
+    funcs: &InjectedFunctions,
+    function_index: u32,
+    block_type: elements::BlockType,
+) {
+    // A function body must always end with an `end`. If this isn't the case, the function will
+    // be rejected by the validation anyway.
+    if !matches!(instructions.last(), Some(elements::Instruction::End)) {
+        return;
+    }
+
+    // List of positions where to insert a call, and the cost to pass to that call.
+    let mut checkpoints: Vec<(usize, u32)> = Vec::new();
+    checkpoints.push((0, 0));
+
+    // For each entered block, the index within `checkpoints` of the checkpoint that
+    // instructions within this block are accounted for. Only `loop`s create a new checkpoint,
+    // as other blocks can't branch backwards.
+    let mut blocks: Vec<usize> = Vec::new();
+    blocks.push(0);
+
+    for (position, instruction) in instructions.iter().enumerate() {
+        // `blocks` can only be empty if there are instructions after the final `end`, which is
+        // forbidden by the specification. Such a function body will be rejected by the
+        // validation anyway.
+        let current = match blocks.last() {
+            Some(c) => *c,
+            None => break,
+        };
+        checkpoints[current].1 = checkpoints[current].1.saturating_add(1);
+
+        match instruction {
+            elements::Instruction::Block(_) | elements::Instruction::If(_) => {
+                blocks.push(current);
+            }
+            elements::Instruction::Loop(_) => {
+                checkpoints.push((position + 1, 0));
+                blocks.push(checkpoints.len() - 1);
+            }
+            elements::Instruction::End => {
+                blocks.pop();
+            }
+            _ => {}
+        }
+    }
+
+    let (enter, leave) = match funcs.call_stack {
+        Some(f) => f,
+        None => {
+            let mut new_instructions =
+                Vec::with_capacity(instructions.len() + checkpoints.len() * 2);
+            let mut checkpoints = checkpoints.into_iter().peekable();
+            for (position, instruction) in instructions.drain(..).enumerate() {
+                while let Some((_, cost)) = checkpoints.next_if(|(p, _)| *p == position) {
+                    new_instructions.push(elements::Instruction::I32Const(cost as i32));
+                    new_instructions.push(elements::Instruction::Call(funcs.fuel));
+                }
+                new_instructions.push(instruction);
+            }
+            *instructions = new_instructions;
+            return;
+        }
+    };
+
+    let body_len = instructions.len() - 1;
+    let mut new_instructions = Vec::with_capacity(instructions.len() + checkpoints.len() * 2 + 5);
+    let mut checkpoints = checkpoints.into_iter().peekable();
+
+    // Number of blocks entered within the body, not counting the block that wraps it.
+    let mut depth: u32 = 0;
+
+    for (position, instruction) in instructions.drain(..body_len).enumerate() {
+        while let Some((_, cost)) = checkpoints.next_if(|(p, _)| *p == position) {
+            new_instructions.push(elements::Instruction::I32Const(cost as i32));
+            if position == 0 {
+                new_instructions.push(elements::Instruction::I32Const(function_index as i32));
+                new_instructions.push(elements::Instruction::Call(enter));
+                new_instructions.push(elements::Instruction::Block(block_type));
+            } else {
+                new_instructions.push(elements::Instruction::Call(funcs.fuel));
+            }
+        }
+
+        match instruction {
+            elements::Instruction::Block(_)
+            | elements::Instruction::Loop(_)
+            | elements::Instruction::If(_) => depth += 1,
+            elements::Instruction::End => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+
+        // `return` would skip the call to the "leave function" import. Replace it with a branch
+        // to the end of the block that wraps the body, which has the same effect on the stack.
+        if let elements::Instruction::Return = instruction {
+            new_instructions.push(elements::Instruction::Br(depth));
+        } else {
+            new_instructions.push(instruction);
+        }
+    }
+
+    // Body consisting only of the final `end`.
+    if new_instructions.is_empty() {
+        new_instructions.push(elements::Instruction::I32Const(1));
+        new_instructions.push(elements::Instruction::I32Const(function_index as i32));
+        new_instructions.push(elements::Instruction::Call(enter));
+        new_instructions.push(elements::Instruction::Block(block_type));
+    }
+
+    new_instructions.push(elements::Instruction::End);
+    new_instructions.push(elements::Instruction::Call(leave));
+    new_instructions.push(elements::Instruction::End);
+
+    *instructions = new_instructions;
+}
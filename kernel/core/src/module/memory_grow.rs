@@ -0,0 +1,53 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Interception of `memory.grow` instructions.
+//!
+//! In order to enforce a limit to the amount of memory a process can use, we replace every
+//! `memory.grow` instruction with a call to a special import. This import has the same
+//! signature as the instruction, and is resolved by the virtual machine, which can then check
+//! the size of the memory against the limits of the process.
+
+use parity_wasm::elements;
+
+/// Name of the module of the import injected in each module.
+pub const MEMORY_GROW_IMPORT_MODULE: &str = "redshirt-vm";
+/// Name of the function of the import injected in each module. Its signature is `(i32) -> i32`,
+/// which is the same as the `memory.grow` instruction.
+pub const MEMORY_GROW_IMPORT_FUNCTION: &str = "memory_grow";
+
+/// Modifies the given module to call the memory-grow import instead of using the `memory.grow`
+/// instruction.
+pub fn inject(module: elements::Module) -> elements::Module {
+    let (mut module, grow_func) = super::inject_function_import(
+        module,
+        MEMORY_GROW_IMPORT_MODULE,
+        MEMORY_GROW_IMPORT_FUNCTION,
+        &[elements::ValueType::I32],
+        Some(elements::ValueType::I32),
+    );
+
+    if let Some(code) = module.code_section_mut() {
+        for body in code.bodies_mut() {
+            for instruction in body.code_mut().elements_mut() {
+                if let elements::Instruction::GrowMemory(_) = instruction {
+                    *instruction = elements::Instruction::Call(grow_func);
+                }
+            }
+        }
+    }
+
+    module
+}
@@ -0,0 +1,66 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Exporting the stack pointer of a module.
+//!
+//! Compilers such as LLVM store the current position of the stack in a mutable global. Since all
+//! the threads of a process share the same globals, the virtual machine must save and restore
+//! this global whenever it switches between threads, so that each thread can have its own stack.
+//!
+//! The binary doesn't indicate which global is the stack pointer. Like LLVM does, we assume that
+//! it is the first global defined by the module, provided that this global is a mutable `i32`,
+//! and we export it under the name [`STACK_POINTER_EXPORT`].
+
+use core::convert::TryFrom as _;
+use parity_wasm::{builder, elements};
+
+/// Name of the export injected in each module that has a stack pointer.
+pub const STACK_POINTER_EXPORT: &str = "redshirt_stack_pointer";
+
+/// Modifies the given module to export its stack pointer, if it has one.
+pub fn inject(module: elements::Module) -> elements::Module {
+    let is_stack_pointer = module
+        .global_section()
+        .and_then(|s| s.entries().first())
+        .map_or(false, |g| {
+            g.global_type().is_mutable()
+                && g.global_type().content_type() == elements::ValueType::I32
+        });
+    if !is_stack_pointer {
+        return module;
+    }
+
+    let already_exported = module.export_section().map_or(false, |s| {
+        s.entries()
+            .iter()
+            .any(|e| e.field() == STACK_POINTER_EXPORT)
+    });
+    if already_exported {
+        return module;
+    }
+
+    // Imported globals come first in the index space.
+    let index = u32::try_from(module.import_count(elements::ImportCountType::Global)).unwrap();
+
+    let mut module_builder = builder::from_module(module);
+    module_builder.push_export(
+        builder::export()
+            .field(STACK_POINTER_EXPORT)
+            .internal()
+            .global(index)
+            .build(),
+    );
+    module_builder.build()
+}
@@ -17,11 +17,16 @@
 //!
 //! This module is lower-level than [`system`](super::system). It doesn't hardcode any interface.
 
+mod exit_reason;
 mod extrinsics;
 mod ipc;
 mod processes;
 mod tests;
 mod vm;
 
+pub use self::exit_reason::ProcessExitReason;
 pub use self::ipc::{Core, CoreBuilder, CoreProcess, CoreRunOutcome, ExecuteOut, ReadyToRun};
-pub use self::vm::NewErr;
+pub use self::vm::{
+    DefaultEngine, Engine, EngineInstance, EngineRunOutcome, ImportErr, NewErr, ProcessLimits,
+    RunErr, StartErr, Trap, TrapKind, WasmiEngine,
+};
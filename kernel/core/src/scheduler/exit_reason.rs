@@ -0,0 +1,89 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{scheduler::vm, WasmValue};
+
+use alloc::string::String;
+use core::fmt;
+
+/// Reason why a process has stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessExitReason {
+    /// The main thread of the process has returned, or the process has voluntarily exited.
+    Exited {
+        /// Exit code of the process. By convention, `0` means success.
+        code: i32,
+    },
+
+    /// A thread of the process has trapped while executing.
+    Trapped(vm::Trap),
+
+    /// The process has called an extrinsic with invalid parameters, or the extrinsic has
+    /// requested the process to be crashed.
+    ExtrinsicCrashed {
+        /// Human-readable explanation of the crash.
+        message: String,
+    },
+
+    /// The process has been forcefully stopped from the outside.
+    Killed,
+
+    /// The process has attempted to grow its memory above the limit it was started with.
+    OutOfMemory,
+}
+
+impl ProcessExitReason {
+    /// Builds the reason corresponding to the main thread having returned the given value.
+    ///
+    /// If the main function returns an integer, it is used as the exit code. Otherwise, the
+    /// exit code is `0`.
+    pub(crate) fn from_main_return_value(value: Option<WasmValue>) -> Self {
+        let code = match value {
+            Some(WasmValue::I32(v)) => v,
+            Some(WasmValue::I64(v)) => v as i32,
+            _ => 0,
+        };
+
+        ProcessExitReason::Exited { code }
+    }
+
+    /// Returns true if the process has exited with a code of `0`.
+    pub fn is_success(&self) -> bool {
+        matches!(self, ProcessExitReason::Exited { code: 0 })
+    }
+}
+
+impl From<vm::Trap> for ProcessExitReason {
+    fn from(trap: vm::Trap) -> Self {
+        match trap.kind() {
+            vm::TrapKind::MemoryLimitExceeded => ProcessExitReason::OutOfMemory,
+            _ => ProcessExitReason::Trapped(trap),
+        }
+    }
+}
+
+impl fmt::Display for ProcessExitReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProcessExitReason::Exited { code } => write!(f, "Exited with code {}", code),
+            ProcessExitReason::Trapped(trap) => write!(f, "Trapped: {}", trap),
+            ProcessExitReason::ExtrinsicCrashed { message } => {
+                write!(f, "Crashed in extrinsic: {}", message)
+            }
+            ProcessExitReason::Killed => write!(f, "Killed"),
+            ProcessExitReason::OutOfMemory => write!(f, "Out of memory"),
+        }
+    }
+}
@@ -33,17 +33,18 @@
 //! they are waiting for a notification.
 
 use crate::extrinsics::{
-    Extrinsics, ExtrinsicsAction, ExtrinsicsMemoryAccess, ExtrinsicsMemoryAccessErr,
+    Extrinsics, ExtrinsicsAction, ExtrinsicsMemoryAccess, ExtrinsicsMemoryAccessErr, LaunchConfig,
+    MessageToEmit, SchedulerCall,
 };
 use crate::module::Module;
-use crate::scheduler::{processes, vm};
+use crate::scheduler::{processes, vm, vm::Engine, ProcessExitReason};
 use crate::sig;
 use crate::{InterfaceHash, MessageId};
 
-use alloc::vec::Vec;
-use core::{convert::TryFrom as _, fmt, iter, mem, ops::Range};
+use alloc::{collections::VecDeque, format, vec, vec::Vec};
+use core::{convert::TryFrom as _, fmt, mem, ops::Range, time::Duration};
 use crossbeam_queue::SegQueue;
-use redshirt_syscalls::{EncodedMessage, Pid, ThreadId};
+use redshirt_syscalls::{EncodedMessage, EncodedMessageRef, Pid, ThreadId};
 
 mod calls;
 
@@ -54,70 +55,75 @@ pub use calls::WaitEntry;
 ///
 /// The generic parameters `TPud` and `TTud` are "user data"s that are stored respectively per
 /// process and per thread, and allows the user to put extra information associated to a process
-/// or a thread.
-pub struct ProcessesCollectionExtrinsics<TPud, TTud, TExt: Extrinsics> {
+/// or a thread. The generic parameter `TEng` is the [`Engine`] that executes the processes.
+pub struct ProcessesCollectionExtrinsics<TPud, TTud, TExt: Extrinsics, TEng: Engine> {
     inner: processes::ProcessesCollection<
         Extrinsic<TExt::ExtrinsicId>,
         LocalProcessUserData<TPud, TExt>,
         LocalThreadUserData<TTud, TExt::Context>,
+        TEng,
     >,
 
     /// List of threads that `inner` considers "interrupted" but that we expose as "ready". We
     /// have to process the external extrinsics for this thread.
     ///
-    /// The threads here must always be in the [`LocalThreadState::OtherExtrinsicApplyAction`]
-    /// or the [`LocalThreadState::OtherExtrinsicReportWait`] state.
+    /// The threads here must always be in the [`LocalThreadState::OtherExtrinsicApplyAction`],
+    /// [`LocalThreadState::OtherExtrinsicReportEmit`] or
+    /// [`LocalThreadState::OtherExtrinsicReportWait`] state.
     // TODO: we have to notify wakers when we push an element
     local_run_queue: SegQueue<ThreadId>,
 }
 
 /// Prototype for a `ProcessesCollectionExtrinsics` under construction.
-pub struct Builder<TExt: Extrinsics> {
-    inner: processes::ProcessesCollectionBuilder<Extrinsic<TExt::ExtrinsicId>>,
+pub struct Builder<TExt: Extrinsics, TEng: Engine> {
+    inner: processes::ProcessesCollectionBuilder<Extrinsic<TExt::ExtrinsicId>, TEng>,
 }
 
 /// Access to a process within the collection.
-pub struct ProcAccess<'a, TPud, TTud, TExt: Extrinsics> {
-    parent: &'a ProcessesCollectionExtrinsics<TPud, TTud, TExt>,
+pub struct ProcAccess<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> {
+    parent: &'a ProcessesCollectionExtrinsics<TPud, TTud, TExt, TEng>,
     inner: processes::ProcAccess<
         'a,
         Extrinsic<TExt::ExtrinsicId>,
         LocalProcessUserData<TPud, TExt>,
         LocalThreadUserData<TTud, TExt::Context>,
+        TEng,
     >,
 }
 
 /// Access to a thread within the collection that is in an interrupted state.
 ///
 /// Implements the [`ThreadAccessAccess`] trait.
-pub enum ThreadAccess<'a, TPud, TTud, TExt: Extrinsics> {
-    EmitMessage(ThreadEmitMessage<'a, TPud, TTud, TExt>),
-    WaitNotification(ThreadWaitNotif<'a, TPud, TTud, TExt>),
+pub enum ThreadAccess<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> {
+    EmitMessage(ThreadEmitMessage<'a, TPud, TTud, TExt, TEng>),
+    WaitNotification(ThreadWaitNotif<'a, TPud, TTud, TExt, TEng>),
 }
 
 /// Access to a thread within the collection.
 ///
 /// Implements the [`ThreadAccessAccess`] trait.
-pub struct ThreadEmitMessage<'a, TPud, TTud, TExt: Extrinsics> {
-    process: ProcAccess<'a, TPud, TTud, TExt>,
+pub struct ThreadEmitMessage<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> {
+    process: ProcAccess<'a, TPud, TTud, TExt, TEng>,
     inner: processes::ThreadAccess<
         'a,
         Extrinsic<TExt::ExtrinsicId>,
         LocalProcessUserData<TPud, TExt>,
         LocalThreadUserData<TTud, TExt::Context>,
+        TEng,
     >,
 }
 
 /// Access to a thread within the collection.
 ///
 /// Implements the [`ThreadAccessAccess`] trait.
-pub struct ThreadWaitNotif<'a, TPud, TTud, TExt: Extrinsics> {
-    process: ProcAccess<'a, TPud, TTud, TExt>,
+pub struct ThreadWaitNotif<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> {
+    process: ProcAccess<'a, TPud, TTud, TExt, TEng>,
     inner: processes::ThreadAccess<
         'a,
         Extrinsic<TExt::ExtrinsicId>,
         LocalProcessUserData<TPud, TExt>,
         LocalThreadUserData<TTud, TExt::Context>,
+        TEng,
     >,
 }
 
@@ -146,6 +152,22 @@ pub trait ThreadAccessAccess<'a> {
     fn user_data(&mut self) -> &mut Self::ThreadUserData;
 }
 
+/// Reason why the emission of a message is refused. Passed to
+/// [`ThreadEmitMessage::refuse_emit`].
+///
+/// Each reason corresponds to a different error code returned to the program.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EmitRefusal {
+    /// No handler is available for the interface.
+    NoHandler,
+    /// The process has too many messages waiting for an answer.
+    TooManyPendingMessages,
+    /// The queue of notifications of the process is full.
+    NotificationsQueueFull,
+    /// The process isn't allowed to emit messages on the interface.
+    Denied,
+}
+
 /// Error that can happen when calling `interrupted_thread_by_id`.
 #[derive(Debug)]
 pub enum ThreadByIdErr {
@@ -180,6 +202,9 @@ struct LocalProcessUserData<TPud, TExt> {
 struct LocalThreadUserData<TTud, TExtCtxt> {
     /// State of a thread.
     state: LocalThreadState<TExtCtxt>,
+    /// If the thread is in the middle of a call to a function handled by the scheduler, context
+    /// returned by [`Extrinsics::start_scheduler_call`], if any.
+    scheduler_call: Option<TExtCtxt>,
     /// User data decided by the user. When the thread is locked, this user data is extracted
     /// and stored locally in the lock. The data is put back when the thread is unlocked.
     external_user_data: TTud,
@@ -203,16 +228,22 @@ enum LocalThreadState<TExtCtxt> {
         action: ExtrinsicsAction,
     },
 
+    /// Thread must be reported as a thread that wants to emit a message through the API, then
+    /// transition to [`LocalThreadState::OtherExtrinsicEmit`].
+    OtherExtrinsicReportEmit {
+        /// Abstract context used to drive the extrinsic call.
+        context: TExtCtxt,
+        /// Messages emitted by the extrinsic. Always contains at least one message to emit.
+        messages: OtherExtrinsicMessages,
+    },
+
     /// Thread is running a non-hardcoded extrinsic that wants to emit a message.
     OtherExtrinsicEmit {
         /// Abstract context used to drive the extrinsic call.
         context: TExtCtxt,
-        /// Interface to emit the message on.
-        interface: InterfaceHash,
-        /// Message to emit.
-        message: EncodedMessage,
-        /// True if a message is expected.
-        response_expected: bool,
+        /// Messages emitted by the extrinsic. The message to emit is the first of
+        /// [`OtherExtrinsicMessages::to_emit`].
+        messages: OtherExtrinsicMessages,
     },
 
     /// Thread must be reported as a waiting thread through the API, then transition to
@@ -220,16 +251,16 @@ enum LocalThreadState<TExtCtxt> {
     OtherExtrinsicReportWait {
         /// Abstract context used to drive the extrinsic call.
         context: TExtCtxt,
-        /// Message for which we are awaiting a response.
-        message: MessageId,
+        /// Messages emitted by the extrinsic. All of them have been emitted.
+        messages: OtherExtrinsicMessages,
     },
 
-    /// Thread is running a non-hardcoded extrinsic waiting for a response.
+    /// Thread is running a non-hardcoded extrinsic waiting for responses.
     OtherExtrinsicWait {
         /// Abstract context used to drive the extrinsic call.
         context: TExtCtxt,
-        /// Message for which we are awaiting a response.
-        message: MessageId,
+        /// Messages emitted by the extrinsic. All of them have been emitted.
+        messages: OtherExtrinsicMessages,
     },
 
     /// The thread is sleeping and waiting for a notification to come.
@@ -243,38 +274,77 @@ enum LocalThreadState<TExtCtxt> {
     Poisoned,
 }
 
+/// Messages emitted by a non-hardcoded extrinsic, and the responses received so far.
+#[derive(Debug)]
+struct OtherExtrinsicMessages {
+    /// Messages that remain to be emitted. The first one is the next to be emitted.
+    to_emit: VecDeque<MessageToEmit>,
+    /// For each message already emitted, the response to wait for, or [`WaitEntry::Empty`] if no
+    /// response is expected or if it has already been received. In
+    /// [`ResponsesMode::First`], preceded with the messages passed through `wait_also`.
+    wait_entries: Vec<WaitEntry>,
+    /// For each entry of [`OtherExtrinsicMessages::wait_entries`], the identifier of the message,
+    /// or `None` if no response is expected.
+    message_ids: Vec<Option<MessageId>>,
+    /// For each entry of [`OtherExtrinsicMessages::wait_entries`], the response if it has
+    /// already been received. A response is `Err` if the message has been answered with an
+    /// error, for example because its handler has crashed.
+    responses: Vec<Option<Result<EncodedMessage, ()>>>,
+    /// How the responses are injected back.
+    mode: ResponsesMode,
+}
+
+/// How the responses to the messages emitted by a non-hardcoded extrinsic are injected back.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ResponsesMode {
+    /// The message comes from [`ExtrinsicsAction::EmitMessage`]. Its response is injected with
+    /// [`Extrinsics::inject_message_response`].
+    Single,
+    /// The messages come from [`ExtrinsicsAction::EmitMessages`]. All the responses are waited
+    /// for, then injected with [`Extrinsics::inject_message_responses`].
+    All,
+    /// The messages come from [`ExtrinsicsAction::EmitMessagesWaitFirst`]. The first response
+    /// is injected with [`Extrinsics::inject_first_response`].
+    First {
+        /// Number of messages passed through `wait_also`, at the start of
+        /// [`OtherExtrinsicMessages::wait_entries`].
+        num_wait_also: usize,
+    },
+}
+
 /// Event returned by [`ProcessesCollectionExtrinsics::run`].
-pub enum ExecuteOut<'a, TPud, TTud, TExt: Extrinsics> {
+pub enum ExecuteOut<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> {
     /// Event directly generated.
-    Direct(RunOneOutcome<'a, TPud, TTud, TExt>),
+    Direct(RunOneOutcome<'a, TPud, TTud, TExt, TEng>),
     /// Ready to execute a bit of a thread.
-    ReadyToRun(ReadyToRun<'a, TPud, TTud, TExt>),
+    ReadyToRun(ReadyToRun<'a, TPud, TTud, TExt, TEng>),
 }
 
 /// Ready to resume one of the threads of a process.
 #[must_use]
-pub struct ReadyToRun<'a, TPud, TTud, TExt: Extrinsics> {
-    collection: &'a ProcessesCollectionExtrinsics<TPud, TTud, TExt>,
+pub struct ReadyToRun<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> {
+    collection: &'a ProcessesCollectionExtrinsics<TPud, TTud, TExt, TEng>,
     inner: processes::ReadyToRun<
         'a,
         Extrinsic<TExt::ExtrinsicId>,
         LocalProcessUserData<TPud, TExt>,
         LocalThreadUserData<TTud, TExt::Context>,
+        TEng,
     >,
 }
 
-impl<'a, TPud, TTud, TExt: Extrinsics> ReadyToRun<'a, TPud, TTud, TExt> {
+impl<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> ReadyToRun<'a, TPud, TTud, TExt, TEng> {
     /// Performs the actual execution.
     ///
     /// Returns `None` if the execution doesn't lead to any event in particular.
-    pub fn run(mut self) -> Option<RunOneOutcome<'a, TPud, TTud, TExt>> {
+    pub fn run(mut self) -> Option<RunOneOutcome<'a, TPud, TTud, TExt, TEng>> {
         self.collection.inner_event(self.inner.run())
     }
 }
 
 /// Outcome of the [`run`](ProcessesCollectionExtrinsics::run) function.
 #[derive(Debug)]
-pub enum RunOneOutcome<'a, TPud, TTud, TExt: Extrinsics> {
+pub enum RunOneOutcome<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> {
     /// Either the main thread of a process has finished, or a fatal error was encountered.
     ///
     /// The process no longer exists.
@@ -291,7 +361,7 @@ pub enum RunOneOutcome<'a, TPud, TTud, TExt: Extrinsics> {
         dead_threads: Vec<(ThreadId, TTud)>,
 
         /// Value returned by the main thread that has finished, or error that happened.
-        outcome: Result<Option<crate::WasmValue>, wasmi::Trap>,
+        outcome: ProcessExitReason,
     },
 
     /// A thread in a process has finished.
@@ -300,7 +370,7 @@ pub enum RunOneOutcome<'a, TPud, TTud, TExt: Extrinsics> {
         thread_id: ThreadId,
 
         /// Process whose thread has finished.
-        process: ProcAccess<'a, TPud, TTud, TExt>,
+        process: ProcAccess<'a, TPud, TTud, TExt, TEng>,
 
         /// User data of the thread.
         user_data: TTud,
@@ -310,10 +380,10 @@ pub enum RunOneOutcome<'a, TPud, TTud, TExt: Extrinsics> {
     },
 
     /// A thread in a process wants to emit a message.
-    ThreadEmitMessage(ThreadEmitMessage<'a, TPud, TTud, TExt>),
+    ThreadEmitMessage(ThreadEmitMessage<'a, TPud, TTud, TExt, TEng>),
 
     /// A thread in a process is waiting for an incoming message.
-    ThreadWaitNotification(ThreadWaitNotif<'a, TPud, TTud, TExt>),
+    ThreadWaitNotification(ThreadWaitNotif<'a, TPud, TTud, TExt, TEng>),
 
     /// A thread in a process wants to notify that a message is to be cancelled.
     ThreadCancelMessage {
@@ -321,14 +391,14 @@ pub enum RunOneOutcome<'a, TPud, TTud, TExt: Extrinsics> {
         thread_id: ThreadId,
 
         /// Process that the thread belongs to.
-        process: ProcAccess<'a, TPud, TTud, TExt>,
+        process: ProcAccess<'a, TPud, TTud, TExt, TEng>,
 
         /// Message that must be cancelled.
         message_id: MessageId,
     },
 }
 
-impl<TPud, TTud, TExt> ProcessesCollectionExtrinsics<TPud, TTud, TExt>
+impl<TPud, TTud, TExt, TEng: Engine> ProcessesCollectionExtrinsics<TPud, TTud, TExt, TEng>
 where
     TExt: Extrinsics,
 {
@@ -341,23 +411,31 @@ where
     ///
     /// A single main thread (whose user data is passed by parameter) is automatically created and
     /// is paused at the start of the "_start" function of the module.
+    ///
+    /// The extrinsics of the process are initialized using `launch_config`.
+    ///
+    /// `setup` is called with the [`Pid`] of the new process before any of its threads can run.
     pub fn execute(
         &self,
         module: &Module,
+        limits: vm::ProcessLimits,
+        launch_config: LaunchConfig,
         proc_user_data: TPud,
         main_thread_user_data: TTud,
-    ) -> Result<(ProcAccess<TPud, TTud, TExt>, ThreadId), vm::NewErr> {
+        setup: impl FnOnce(Pid),
+    ) -> Result<(ProcAccess<TPud, TTud, TExt, TEng>, ThreadId), vm::NewErr> {
         let proc_user_data = LocalProcessUserData {
-            extrinsics: Default::default(),
+            extrinsics: TExt::with_launch_config(launch_config),
             external_user_data: proc_user_data,
         };
         let main_thread_user_data = LocalThreadUserData {
             state: LocalThreadState::ReadyToRun,
+            scheduler_call: None,
             external_user_data: main_thread_user_data,
         };
         let (inner, main_tid) =
             self.inner
-                .execute(module, proc_user_data, main_thread_user_data)?;
+                .execute(module, limits, proc_user_data, main_thread_user_data, setup)?;
         Ok((
             ProcAccess {
                 parent: self,
@@ -370,7 +448,7 @@ where
     /// Runs one thread amongst the collection.
     ///
     /// Which thread is run is implementation-defined and no guarantee is made.
-    pub async fn run<'a>(&'a self) -> ExecuteOut<'a, TPud, TTud, TExt> {
+    pub async fn run<'a>(&'a self) -> ExecuteOut<'a, TPud, TTud, TExt, TEng> {
         loop {
             while let Some(tid) = self.local_run_queue.pop() {
                 // It is possible that the thread no longer exists, for example if the process crashed.
@@ -383,9 +461,23 @@ where
                     &mut thread.user_data_mut().state,
                     LocalThreadState::Poisoned,
                 ) {
-                    LocalThreadState::OtherExtrinsicReportWait { context, message } => {
+                    LocalThreadState::OtherExtrinsicReportEmit { context, messages } => {
+                        thread.user_data_mut().state =
+                            LocalThreadState::OtherExtrinsicEmit { context, messages };
+                        let process = ProcAccess {
+                            parent: self,
+                            inner: thread.process(),
+                        };
+                        return ExecuteOut::Direct(RunOneOutcome::ThreadEmitMessage(
+                            ThreadEmitMessage {
+                                process,
+                                inner: thread,
+                            },
+                        ));
+                    }
+                    LocalThreadState::OtherExtrinsicReportWait { context, messages } => {
                         thread.user_data_mut().state =
-                            LocalThreadState::OtherExtrinsicWait { context, message };
+                            LocalThreadState::OtherExtrinsicWait { context, messages };
                         let process = ProcAccess {
                             parent: self,
                             inner: thread.process(),
@@ -397,36 +489,82 @@ where
                             },
                         ));
                     }
-                    LocalThreadState::OtherExtrinsicApplyAction { context, action } => match action
-                    {
-                        ExtrinsicsAction::ProgramCrash => unimplemented!(),
-                        ExtrinsicsAction::Resume(value) => {
-                            thread.user_data_mut().state = LocalThreadState::ReadyToRun;
-                            thread.resume(value)
-                        }
-                        ExtrinsicsAction::EmitMessage {
-                            interface,
-                            message,
-                            response_expected,
-                        } => {
-                            thread.user_data_mut().state = LocalThreadState::OtherExtrinsicEmit {
-                                context,
+                    LocalThreadState::OtherExtrinsicApplyAction { context, action } => {
+                        let messages = match action {
+                            ExtrinsicsAction::ProgramCrash => {
+                                thread.process().abort(ProcessExitReason::ExtrinsicCrashed {
+                                    message: "crash requested by the extrinsic".into(),
+                                });
+                                continue;
+                            }
+                            ExtrinsicsAction::ProgramExit(code) => {
+                                thread.process().abort(ProcessExitReason::Exited { code });
+                                continue;
+                            }
+                            ExtrinsicsAction::Resume(value) => {
+                                thread.user_data_mut().state = LocalThreadState::ReadyToRun;
+                                thread.resume(value);
+                                continue;
+                            }
+                            ExtrinsicsAction::EmitMessage {
                                 interface,
                                 message,
                                 response_expected,
-                            };
-                            let process = ProcAccess {
-                                parent: self,
-                                inner: thread.process(),
-                            };
-                            return ExecuteOut::Direct(RunOneOutcome::ThreadEmitMessage(
-                                ThreadEmitMessage {
-                                    process,
-                                    inner: thread,
-                                },
-                            ));
+                            } => {
+                                let to_emit = MessageToEmit {
+                                    interface,
+                                    message,
+                                    response_expected,
+                                };
+                                OtherExtrinsicMessages::new(
+                                    vec![to_emit],
+                                    Vec::new(),
+                                    ResponsesMode::Single,
+                                )
+                            }
+                            ExtrinsicsAction::EmitMessages(to_emit) => {
+                                OtherExtrinsicMessages::new(to_emit, Vec::new(), ResponsesMode::All)
+                            }
+                            ExtrinsicsAction::EmitMessagesWaitFirst {
+                                messages,
+                                wait_also,
+                            } => {
+                                let mode = ResponsesMode::First {
+                                    num_wait_also: wait_also.len(),
+                                };
+                                OtherExtrinsicMessages::new(messages, wait_also, mode)
+                            }
+                        };
+
+                        // If there is no message to emit, directly wait for the responses, or
+                        // inject them immediately if there is nothing to wait for.
+                        if messages.to_emit.is_empty() {
+                            if messages.is_waiting() {
+                                thread.user_data_mut().state =
+                                    LocalThreadState::OtherExtrinsicReportWait {
+                                        context,
+                                        messages,
+                                    };
+                            } else {
+                                inject_responses(&mut thread, context, messages);
+                            }
+                            self.local_run_queue.push(thread.tid());
+                            continue;
                         }
-                    },
+
+                        thread.user_data_mut().state =
+                            LocalThreadState::OtherExtrinsicEmit { context, messages };
+                        let process = ProcAccess {
+                            parent: self,
+                            inner: thread.process(),
+                        };
+                        return ExecuteOut::Direct(RunOneOutcome::ThreadEmitMessage(
+                            ThreadEmitMessage {
+                                process,
+                                inner: thread,
+                            },
+                        ));
+                    }
                     _ => unreachable!(),
                 }
             }
@@ -454,8 +592,9 @@ where
             Extrinsic<TExt::ExtrinsicId>,
             LocalProcessUserData<TPud, TExt>,
             LocalThreadUserData<TTud, TExt::Context>,
+            TEng,
         >,
-    ) -> Option<RunOneOutcome<'a, TPud, TTud, TExt>> {
+    ) -> Option<RunOneOutcome<'a, TPud, TTud, TExt, TEng>> {
         match outcome {
             processes::RunOneOutcome::ProcessFinished {
                 pid,
@@ -475,6 +614,7 @@ where
             }
 
             processes::RunOneOutcome::StartProcessAbort { .. } => None,
+            processes::RunOneOutcome::Preempted { .. } => None,
 
             processes::RunOneOutcome::ThreadFinished {
                 process,
@@ -500,6 +640,9 @@ where
                 params,
             } => {
                 debug_assert!(thread.user_data().state.is_ready_to_run());
+                let scheduler_call =
+                    start_scheduler_call(&mut thread, SchedulerCall::NextNotification, &params);
+                thread.user_data_mut().scheduler_call = scheduler_call;
                 match calls::parse_extrinsic_next_notification(&mut thread, params) {
                     Ok(next_msg) => {
                         thread.user_data_mut().state = LocalThreadState::NotificationWait(next_msg);
@@ -512,8 +655,10 @@ where
                             inner: thread,
                         }))
                     }
-                    Err(_) => {
-                        thread.process().abort();
+                    Err(err) => {
+                        thread.process().abort(ProcessExitReason::ExtrinsicCrashed {
+                            message: format!("next_notification: {:?}", err),
+                        });
                         None
                     }
                 }
@@ -525,6 +670,9 @@ where
                 params,
             } => {
                 debug_assert!(thread.user_data().state.is_ready_to_run());
+                let scheduler_call =
+                    start_scheduler_call(&mut thread, SchedulerCall::EmitMessage, &params);
+                thread.user_data_mut().scheduler_call = scheduler_call;
                 match calls::parse_extrinsic_emit_message(&mut thread, params) {
                     Ok(emit_msg) => {
                         thread.user_data_mut().state = LocalThreadState::EmitMessage(emit_msg);
@@ -537,8 +685,10 @@ where
                             inner: thread,
                         }))
                     }
-                    Err(_) => {
-                        thread.process().abort();
+                    Err(err) => {
+                        thread.process().abort(ProcessExitReason::ExtrinsicCrashed {
+                            message: format!("emit_message: {:?}", err),
+                        });
                         None
                     }
                 }
@@ -550,11 +700,14 @@ where
                 params,
             } => {
                 debug_assert!(thread.user_data().state.is_ready_to_run());
+                let scheduler_call =
+                    start_scheduler_call(&mut thread, SchedulerCall::CancelMessage, &params);
+                thread.user_data_mut().scheduler_call = scheduler_call;
                 match calls::parse_extrinsic_cancel_message(&mut thread, params) {
                     Ok(emit_cancel) => {
                         let process = thread.process();
                         let thread_id = thread.tid();
-                        thread.resume(None);
+                        resume_scheduler_call(thread, &self.local_run_queue, None);
                         Some(RunOneOutcome::ThreadCancelMessage {
                             process: ProcAccess {
                                 parent: self,
@@ -564,8 +717,10 @@ where
                             message_id: emit_cancel,
                         })
                     }
-                    Err(_) => {
-                        thread.process().abort();
+                    Err(err) => {
+                        thread.process().abort(ProcessExitReason::ExtrinsicCrashed {
+                            message: format!("cancel_message: {:?}", err),
+                        });
                         None
                     }
                 }
@@ -592,6 +747,19 @@ where
         }
     }
 
+    /// Returns an iterator to all the processes that exist in the collection.
+    ///
+    /// This is equivalent to calling [`ProcessesCollectionExtrinsics::process_by_id`] for each
+    /// possible ID.
+    pub fn processes<'a>(
+        &'a self,
+    ) -> impl ExactSizeIterator<Item = ProcAccess<'a, TPud, TTud, TExt, TEng>> + 'a {
+        self.inner.processes().map(move |inner| ProcAccess {
+            parent: self,
+            inner,
+        })
+    }
+
     /// Returns a process by its [`Pid`], if it exists.
     ///
     /// This function returns a "lock".
@@ -600,7 +768,7 @@ where
     ///
     /// If a program crashes or finishes while a lock is held, it is marked as dying and the
     /// termination is delayed until the point when all locks have been released.
-    pub fn process_by_id(&self, pid: Pid) -> Option<ProcAccess<TPud, TTud, TExt>> {
+    pub fn process_by_id(&self, pid: Pid) -> Option<ProcAccess<TPud, TTud, TExt, TEng>> {
         Some(ProcAccess {
             parent: self,
             inner: self.inner.process_by_id(pid)?,
@@ -620,7 +788,7 @@ where
     pub fn interrupted_thread_by_id(
         &self,
         id: ThreadId,
-    ) -> Result<ThreadAccess<TPud, TTud, TExt>, ThreadByIdErr> {
+    ) -> Result<ThreadAccess<TPud, TTud, TExt, TEng>, ThreadByIdErr> {
         let inner = self
             .inner
             .interrupted_thread_by_id(id)
@@ -632,6 +800,7 @@ where
                 unreachable!()
             }
             LocalThreadState::OtherExtrinsicApplyAction { .. }
+            | LocalThreadState::OtherExtrinsicReportEmit { .. }
             | LocalThreadState::OtherExtrinsicReportWait { .. } => {
                 Err(ThreadByIdErr::RunningOrDead)
             }
@@ -654,9 +823,10 @@ where
     }
 }
 
-impl<TExt> Builder<TExt>
+impl<TExt, TEng> Builder<TExt, TEng>
 where
     TExt: Extrinsics,
+    TEng: Engine,
 {
     /// Initializes a new builder using the given random seed.
     ///
@@ -705,8 +875,27 @@ where
         self.inner.reserve_pid()
     }
 
+    /// Sets the maximum amount of fuel that a thread can consume before being preempted.
+    ///
+    /// See [`processes::ProcessesCollectionBuilder::with_fuel_per_run`].
+    pub fn with_fuel_per_run(mut self, fuel: u64) -> Self {
+        self.inner = self.inner.with_fuel_per_run(fuel);
+        self
+    }
+
+    /// Sets the function that returns the current value of the monotonic clock.
+    ///
+    /// See [`processes::ProcessesCollectionBuilder::with_monotonic_clock`].
+    pub fn with_monotonic_clock(
+        mut self,
+        clock: impl Fn() -> Duration + Send + Sync + 'static,
+    ) -> Self {
+        self.inner = self.inner.with_monotonic_clock(clock);
+        self
+    }
+
     /// Turns the builder into a [`ProcessesCollectionExtrinsics`].
-    pub fn build<TPud, TTud>(self) -> ProcessesCollectionExtrinsics<TPud, TTud, TExt> {
+    pub fn build<TPud, TTud>(self) -> ProcessesCollectionExtrinsics<TPud, TTud, TExt, TEng> {
         ProcessesCollectionExtrinsics {
             inner: self.inner.build(),
             local_run_queue: SegQueue::new(),
@@ -714,7 +903,7 @@ where
     }
 }
 
-impl<'a, TPud, TTud, TExt> ProcAccess<'a, TPud, TTud, TExt>
+impl<'a, TPud, TTud, TExt, TEng: Engine> ProcAccess<'a, TPud, TTud, TExt, TEng>
 where
     TExt: Extrinsics,
 {
@@ -729,9 +918,21 @@ where
         &self.inner.user_data().external_user_data
     }
 
+    /// Returns the total time spent executing the threads of this process.
+    pub fn cpu_time(&self) -> Duration {
+        self.inner.cpu_time()
+    }
+
+    /// Returns the instance of [`Extrinsics`] dedicated to this process.
+    pub fn extrinsics(&self) -> &TExt {
+        &self.inner.user_data().extrinsics
+    }
+
     /// Adds a new thread to the process, starting the function with the given index and passing
     /// the given parameters.
     ///
+    /// See [`vm::ProcessStateMachine::start_thread_by_id`] for the meaning of `stack_pointer`.
+    ///
     /// > **Note**: The "function ID" is the index of the function in the WASM module. WASM
     /// >           doesn't have function pointers. Instead, all the functions are part of a single
     /// >           global array of functions.
@@ -739,30 +940,33 @@ where
         &self,
         fn_index: u32,
         params: Vec<crate::WasmValue>,
+        stack_pointer: Option<i32>,
         user_data: TTud,
     ) -> Result<ThreadId, vm::StartErr> {
         self.inner.start_thread(
             fn_index,
             params,
+            stack_pointer,
             LocalThreadUserData {
                 state: LocalThreadState::ReadyToRun,
+                scheduler_call: None,
                 external_user_data: user_data,
             },
         )
     }
 
-    /// Marks the process as aborting.
+    /// Marks the process as aborting, for the given reason.
     ///
     /// The termination will happen after all locks to this process have been released.
     ///
     /// Calling [`abort`](ProcAccess::abort) a second time or more has no
     /// effect.
-    pub fn abort(&self) {
-        self.inner.abort();
+    pub fn abort(&self, reason: ProcessExitReason) {
+        self.inner.abort(reason);
     }
 }
 
-impl<'a, TPud, TTud, TExt> fmt::Debug for ProcAccess<'a, TPud, TTud, TExt>
+impl<'a, TPud, TTud, TExt, TEng: Engine> fmt::Debug for ProcAccess<'a, TPud, TTud, TExt, TEng>
 where
     TExt: Extrinsics + fmt::Debug,
     TPud: fmt::Debug,
@@ -772,24 +976,25 @@ where
     }
 }
 
-impl<'a, TPud, TTud, TExt: Extrinsics> From<ThreadEmitMessage<'a, TPud, TTud, TExt>>
-    for ThreadAccess<'a, TPud, TTud, TExt>
+impl<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine>
+    From<ThreadEmitMessage<'a, TPud, TTud, TExt, TEng>>
+    for ThreadAccess<'a, TPud, TTud, TExt, TEng>
 {
-    fn from(thread: ThreadEmitMessage<'a, TPud, TTud, TExt>) -> Self {
+    fn from(thread: ThreadEmitMessage<'a, TPud, TTud, TExt, TEng>) -> Self {
         ThreadAccess::EmitMessage(thread)
     }
 }
 
-impl<'a, TPud, TTud, TExt: Extrinsics> From<ThreadWaitNotif<'a, TPud, TTud, TExt>>
-    for ThreadAccess<'a, TPud, TTud, TExt>
+impl<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine>
+    From<ThreadWaitNotif<'a, TPud, TTud, TExt, TEng>> for ThreadAccess<'a, TPud, TTud, TExt, TEng>
 {
-    fn from(thread: ThreadWaitNotif<'a, TPud, TTud, TExt>) -> Self {
+    fn from(thread: ThreadWaitNotif<'a, TPud, TTud, TExt, TEng>) -> Self {
         ThreadAccess::WaitNotification(thread)
     }
 }
 
-impl<'a, TPud, TTud, TExt: Extrinsics> ThreadAccessAccess<'a>
-    for ThreadAccess<'a, TPud, TTud, TExt>
+impl<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> ThreadAccessAccess<'a>
+    for ThreadAccess<'a, TPud, TTud, TExt, TEng>
 {
     type ProcessUserData = TPud;
     type ThreadUserData = TTud;
@@ -823,7 +1028,7 @@ impl<'a, TPud, TTud, TExt: Extrinsics> ThreadAccessAccess<'a>
     }
 }
 
-impl<'a, TPud, TTud, TExt> fmt::Debug for ThreadAccess<'a, TPud, TTud, TExt>
+impl<'a, TPud, TTud, TExt, TEng: Engine> fmt::Debug for ThreadAccess<'a, TPud, TTud, TExt, TEng>
 where
     TExt: Extrinsics,
 {
@@ -835,14 +1040,19 @@ where
     }
 }
 
-impl<'a, TPud, TTud, TExt: Extrinsics> ThreadEmitMessage<'a, TPud, TTud, TExt> {
+impl<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> ThreadEmitMessage<'a, TPud, TTud, TExt, TEng> {
+    /// Unlocks the thread and returns the process it belongs to.
+    pub fn into_process(self) -> ProcAccess<'a, TPud, TTud, TExt, TEng> {
+        self.process
+    }
+
     /// Returns true if the caller wants an answer to the message.
     pub fn needs_answer(&mut self) -> bool {
         match self.inner.user_data().state {
             LocalThreadState::EmitMessage(ref emit) => emit.message_id_write.is_some(),
-            LocalThreadState::OtherExtrinsicEmit {
-                response_expected, ..
-            } => response_expected,
+            LocalThreadState::OtherExtrinsicEmit { ref messages, .. } => {
+                messages.to_emit[0].response_expected
+            }
             _ => unreachable!(),
         }
     }
@@ -851,7 +1061,9 @@ impl<'a, TPud, TTud, TExt: Extrinsics> ThreadEmitMessage<'a, TPud, TTud, TExt> {
     pub fn emit_interface(&mut self) -> &InterfaceHash {
         match self.inner.user_data().state {
             LocalThreadState::EmitMessage(ref emit) => &emit.interface,
-            LocalThreadState::OtherExtrinsicEmit { ref interface, .. } => interface,
+            LocalThreadState::OtherExtrinsicEmit { ref messages, .. } => {
+                &messages.to_emit[0].interface
+            }
             _ => unreachable!(),
         }
     }
@@ -892,54 +1104,67 @@ impl<'a, TPud, TTud, TExt: Extrinsics> ThreadEmitMessage<'a, TPud, TTud, TExt> {
                 }
 
                 self.inner.user_data_mut().state = LocalThreadState::ReadyToRun;
-                self.inner.resume(Some(crate::WasmValue::I32(0)));
+                resume_scheduler_call(
+                    self.inner,
+                    &self.process.parent.local_run_queue,
+                    Some(crate::WasmValue::I32(0)),
+                );
                 emit.message
             }
             LocalThreadState::OtherExtrinsicEmit {
-                mut context,
-                message,
-                response_expected,
-                ..
+                context,
+                mut messages,
             } => {
-                if response_expected {
+                let emitted = messages.to_emit.pop_front().unwrap();
+                if emitted.response_expected {
                     let message_id = message_id.unwrap();
-                    self.inner.user_data_mut().state = LocalThreadState::OtherExtrinsicReportWait {
-                        context,
-                        message: message_id,
-                    };
-                    self.process.parent.local_run_queue.push(self.inner.tid());
+                    messages.wait_entries.push(WaitEntry::Answer(message_id));
+                    messages.message_ids.push(Some(message_id));
                 } else {
                     debug_assert!(message_id.is_none());
-                    let action = self
-                        .inner
-                        .process()
-                        .user_data()
-                        .extrinsics
-                        .inject_message_response(
-                            &mut context,
-                            None,
-                            &mut MemoryAccessImpl(&mut self.inner),
-                        );
+                    messages.wait_entries.push(WaitEntry::Empty);
+                    messages.message_ids.push(None);
+                }
+                messages.responses.push(None);
+
+                if !messages.to_emit.is_empty() {
                     self.inner.user_data_mut().state =
-                        LocalThreadState::OtherExtrinsicApplyAction { context, action };
-                    self.process.parent.local_run_queue.push(self.inner.tid());
+                        LocalThreadState::OtherExtrinsicReportEmit { context, messages };
+                } else if messages.is_waiting() {
+                    self.inner.user_data_mut().state =
+                        LocalThreadState::OtherExtrinsicReportWait { context, messages };
+                } else {
+                    inject_responses(&mut self.inner, context, messages);
                 }
+                self.process.parent.local_run_queue.push(self.inner.tid());
 
-                message
+                emitted.message
             }
             _ => unreachable!(),
         }
     }
 
     /// Resumes the thread, signalling an error in the emission.
-    pub fn refuse_emit(mut self) {
+    ///
+    /// The reason is reported to the program as an error code.
+    pub fn refuse_emit(mut self, reason: EmitRefusal) {
         match mem::replace(
             &mut self.inner.user_data_mut().state,
             LocalThreadState::Poisoned,
         ) {
             LocalThreadState::EmitMessage(_) => {
+                let error_code = match reason {
+                    EmitRefusal::NoHandler => 1,
+                    EmitRefusal::TooManyPendingMessages => 2,
+                    EmitRefusal::NotificationsQueueFull => 3,
+                    EmitRefusal::Denied => 4,
+                };
                 self.inner.user_data_mut().state = LocalThreadState::ReadyToRun;
-                self.inner.resume(Some(crate::WasmValue::I32(1)));
+                resume_scheduler_call(
+                    self.inner,
+                    &self.process.parent.local_run_queue,
+                    Some(crate::WasmValue::I32(error_code)),
+                );
             }
             LocalThreadState::OtherExtrinsicEmit { context, .. } => {
                 // TODO: don't know what else to do here than crash the program
@@ -954,8 +1179,8 @@ impl<'a, TPud, TTud, TExt: Extrinsics> ThreadEmitMessage<'a, TPud, TTud, TExt> {
     }
 }
 
-impl<'a, TPud, TTud, TExt: Extrinsics> ThreadAccessAccess<'a>
-    for ThreadEmitMessage<'a, TPud, TTud, TExt>
+impl<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> ThreadAccessAccess<'a>
+    for ThreadEmitMessage<'a, TPud, TTud, TExt, TEng>
 {
     type ProcessUserData = TPud;
     type ThreadUserData = TTud;
@@ -977,15 +1202,17 @@ impl<'a, TPud, TTud, TExt: Extrinsics> ThreadAccessAccess<'a>
     }
 }
 
-impl<'a, TPud, TTud, TExt: Extrinsics> fmt::Debug for ThreadEmitMessage<'a, TPud, TTud, TExt> {
+impl<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> fmt::Debug
+    for ThreadEmitMessage<'a, TPud, TTud, TExt, TEng>
+{
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Debug::fmt(&self.inner, f)
     }
 }
 
-impl<'a, TPud, TTud, TExt: Extrinsics> ThreadWaitNotif<'a, TPud, TTud, TExt> {
+impl<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> ThreadWaitNotif<'a, TPud, TTud, TExt, TEng> {
     /// Unlocks the thread and returns the process it belongs to.
-    pub fn into_process(self) -> ProcAccess<'a, TPud, TTud, TExt> {
+    pub fn into_process(self) -> ProcAccess<'a, TPud, TTud, TExt, TEng> {
         self.process
     }
 
@@ -996,8 +1223,8 @@ impl<'a, TPud, TTud, TExt: Extrinsics> ThreadWaitNotif<'a, TPud, TTud, TExt> {
             LocalThreadState::NotificationWait(ref wait) => {
                 either::Either::Left(wait.notifs_ids.iter().cloned())
             }
-            LocalThreadState::OtherExtrinsicWait { message, .. } => {
-                either::Either::Right(iter::once(WaitEntry::Answer(message)))
+            LocalThreadState::OtherExtrinsicWait { ref messages, .. } => {
+                either::Either::Right(messages.wait_entries.iter().cloned())
             }
             _ => unreachable!(),
         }
@@ -1049,7 +1276,11 @@ impl<'a, TPud, TTud, TExt: Extrinsics> ThreadWaitNotif<'a, TPud, TTud, TExt> {
                 match self.inner.write_memory(wait.out_pointer, &notif.0) {
                     Ok(()) => {}
                     Err(_) => {
-                        self.inner.process().abort();
+                        self.inner
+                            .process()
+                            .abort(ProcessExitReason::ExtrinsicCrashed {
+                                message: "next_notification: output buffer out of range".into(),
+                            });
                         return;
                     }
                 };
@@ -1061,34 +1292,44 @@ impl<'a, TPud, TTud, TExt: Extrinsics> ThreadWaitNotif<'a, TPud, TTud, TExt> {
                 ) {
                     Ok(()) => {}
                     Err(_) => {
-                        self.inner.process().abort();
+                        self.inner
+                            .process()
+                            .abort(ProcessExitReason::ExtrinsicCrashed {
+                                message: "next_notification: output buffer out of range".into(),
+                            });
                         return;
                     }
                 };
 
-                self.inner.resume(Some(crate::WasmValue::I32(
-                    i32::try_from(notif_size_u32).unwrap(),
-                )));
+                resume_scheduler_call(
+                    self.inner,
+                    &self.process.parent.local_run_queue,
+                    Some(crate::WasmValue::I32(
+                        i32::try_from(notif_size_u32).unwrap(),
+                    )),
+                );
             }
-            LocalThreadState::OtherExtrinsicWait { mut context, .. } => {
+            LocalThreadState::OtherExtrinsicWait {
+                context,
+                mut messages,
+            } => {
                 // TODO: the way this is handled is clearly not great; the API of this method
                 // should be improved
+                // Notifications are always built by the kernel, and decoding them can't fail.
                 let decoded = redshirt_syscalls::ffi::decode_notification(&notif.0).unwrap();
-                let message = decoded.actual_data.unwrap();
-
-                assert_eq!(index, 0);
-                let action = self
-                    .inner
-                    .process()
-                    .user_data()
-                    .extrinsics
-                    .inject_message_response(
-                        &mut context,
-                        Some(message),
-                        &mut MemoryAccessImpl(&mut self.inner),
-                    );
-                self.inner.user_data_mut().state =
-                    LocalThreadState::OtherExtrinsicApplyAction { context, action };
+                let response = decoded.actual_data.map(EncodedMessage::from);
+
+                assert_ne!(messages.wait_entries[index], WaitEntry::Empty);
+                messages.wait_entries[index] = WaitEntry::Empty;
+                messages.responses[index] = Some(response);
+
+                // The thread is reported as waiting again if other responses are missing.
+                if messages.is_waiting() {
+                    self.inner.user_data_mut().state =
+                        LocalThreadState::OtherExtrinsicReportWait { context, messages };
+                } else {
+                    inject_responses(&mut self.inner, context, messages);
+                }
                 self.process.parent.local_run_queue.push(self.inner.tid());
             }
             _ => unreachable!(),
@@ -1107,9 +1348,11 @@ impl<'a, TPud, TTud, TExt: Extrinsics> ThreadWaitNotif<'a, TPud, TTud, TExt> {
         });
 
         self.inner.user_data_mut().state = LocalThreadState::ReadyToRun;
-        self.inner.resume(Some(crate::WasmValue::I32(
-            i32::try_from(notif_size).unwrap(),
-        )));
+        resume_scheduler_call(
+            self.inner,
+            &self.process.parent.local_run_queue,
+            Some(crate::WasmValue::I32(i32::try_from(notif_size).unwrap())),
+        );
     }
 
     /// Resume the thread, indicating that no notification is available.
@@ -1127,12 +1370,16 @@ impl<'a, TPud, TTud, TExt: Extrinsics> ThreadWaitNotif<'a, TPud, TTud, TExt> {
         }
 
         self.inner.user_data_mut().state = LocalThreadState::ReadyToRun;
-        self.inner.resume(Some(crate::WasmValue::I32(0)));
+        resume_scheduler_call(
+            self.inner,
+            &self.process.parent.local_run_queue,
+            Some(crate::WasmValue::I32(0)),
+        );
     }
 }
 
-impl<'a, TPud, TTud, TExt: Extrinsics> ThreadAccessAccess<'a>
-    for ThreadWaitNotif<'a, TPud, TTud, TExt>
+impl<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> ThreadAccessAccess<'a>
+    for ThreadWaitNotif<'a, TPud, TTud, TExt, TEng>
 {
     type ProcessUserData = TPud;
     type ThreadUserData = TTud;
@@ -1154,12 +1401,46 @@ impl<'a, TPud, TTud, TExt: Extrinsics> ThreadAccessAccess<'a>
     }
 }
 
-impl<'a, TPud, TTud, TExt: Extrinsics> fmt::Debug for ThreadWaitNotif<'a, TPud, TTud, TExt> {
+impl<'a, TPud, TTud, TExt: Extrinsics, TEng: Engine> fmt::Debug
+    for ThreadWaitNotif<'a, TPud, TTud, TExt, TEng>
+{
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Debug::fmt(&self.inner, f)
     }
 }
 
+impl OtherExtrinsicMessages {
+    /// Initializes the state of an extrinsic that is about to emit the given messages, and
+    /// wait for the responses to the messages of `wait_also`, which have already been emitted.
+    fn new(to_emit: Vec<MessageToEmit>, wait_also: Vec<MessageId>, mode: ResponsesMode) -> Self {
+        let capacity = wait_also.len() + to_emit.len();
+        let mut messages = OtherExtrinsicMessages {
+            wait_entries: Vec::with_capacity(capacity),
+            message_ids: Vec::with_capacity(capacity),
+            responses: Vec::with_capacity(capacity),
+            to_emit: VecDeque::from(to_emit),
+            mode,
+        };
+
+        for message_id in wait_also {
+            messages.wait_entries.push(WaitEntry::Answer(message_id));
+            messages.message_ids.push(Some(message_id));
+            messages.responses.push(None);
+        }
+
+        messages
+    }
+
+    /// Returns true if responses must still arrive before they are injected.
+    fn is_waiting(&self) -> bool {
+        let missing = self.wait_entries.iter().any(|e| *e != WaitEntry::Empty);
+        match self.mode {
+            ResponsesMode::Single | ResponsesMode::All => missing,
+            ResponsesMode::First { .. } => missing && self.responses.iter().all(|r| r.is_none()),
+        }
+    }
+}
+
 impl<TExtCtxt> LocalThreadState<TExtCtxt> {
     /// True if `self` is equal to [`LocalThreadState::ReadyToRun`].
     fn is_ready_to_run(&self) -> bool {
@@ -1170,13 +1451,134 @@ impl<TExtCtxt> LocalThreadState<TExtCtxt> {
     }
 }
 
+/// Injects the responses to the messages emitted by a non-hardcoded extrinsic, then puts the
+/// thread in the [`LocalThreadState::OtherExtrinsicApplyAction`] state.
+///
+/// The thread must then be pushed to [`ProcessesCollectionExtrinsics::local_run_queue`].
+fn inject_responses<TPud, TTud, TExt: Extrinsics, TEng: Engine>(
+    thread: &mut processes::ThreadAccess<
+        '_,
+        Extrinsic<TExt::ExtrinsicId>,
+        LocalProcessUserData<TPud, TExt>,
+        LocalThreadUserData<TTud, TExt::Context>,
+        TEng,
+    >,
+    mut context: TExt::Context,
+    mut messages: OtherExtrinsicMessages,
+) {
+    debug_assert!(messages.to_emit.is_empty());
+    debug_assert!(!messages.is_waiting());
+
+    let process = thread.process();
+    let extrinsics = &process.user_data().extrinsics;
+    let action = match messages.mode {
+        ResponsesMode::Single => {
+            debug_assert_eq!(messages.responses.len(), 1);
+            let response = messages.responses.into_iter().next().unwrap();
+            extrinsics.inject_message_response(
+                &mut context,
+                response
+                    .as_ref()
+                    .map(|r| r.as_ref().map(EncodedMessageRef::from).map_err(|_| ())),
+                &mut MemoryAccessImpl(&mut *thread),
+            )
+        }
+        ResponsesMode::All => extrinsics.inject_message_responses(
+            &mut context,
+            messages.responses,
+            &mut MemoryAccessImpl(&mut *thread),
+        ),
+        ResponsesMode::First { num_wait_also } => {
+            match messages.responses.iter().position(|r| r.is_some()) {
+                Some(index) => {
+                    // A response can only have arrived for a message that expects one.
+                    let message_id = messages.message_ids[index].unwrap();
+                    let response = messages.responses[index].take().unwrap();
+                    let emitted = messages.message_ids.split_off(num_wait_also);
+                    extrinsics.inject_first_response(
+                        &mut context,
+                        emitted,
+                        message_id,
+                        response,
+                        &mut MemoryAccessImpl(&mut *thread),
+                    )
+                }
+                // Nothing to wait for.
+                None => ExtrinsicsAction::ProgramCrash,
+            }
+        }
+    };
+
+    thread.user_data_mut().state = LocalThreadState::OtherExtrinsicApplyAction { context, action };
+}
+
+/// Calls [`Extrinsics::start_scheduler_call`] for a thread that has just called one of the
+/// functions handled by the scheduler.
+fn start_scheduler_call<TPud, TTud, TExt: Extrinsics, TEng: Engine>(
+    thread: &mut processes::ThreadAccess<
+        '_,
+        Extrinsic<TExt::ExtrinsicId>,
+        LocalProcessUserData<TPud, TExt>,
+        LocalThreadUserData<TTud, TExt::Context>,
+        TEng,
+    >,
+    call: SchedulerCall,
+    params: &[crate::WasmValue],
+) -> Option<TExt::Context> {
+    let thread_id = thread.tid();
+    let process = thread.process();
+    process.user_data().extrinsics.start_scheduler_call(
+        thread_id,
+        call,
+        params.iter().cloned(),
+        &mut MemoryAccessImpl(thread),
+    )
+}
+
+/// Resumes a thread that has called one of the functions handled by the scheduler, returning
+/// `value` to the program. The thread must be in the [`LocalThreadState::ReadyToRun`] state.
+///
+/// If [`Extrinsics::start_scheduler_call`] has returned a context for this call, the thread is
+/// instead put in the [`LocalThreadState::OtherExtrinsicApplyAction`] state with the action
+/// returned by [`Extrinsics::finish_scheduler_call`], and pushed to `local_run_queue`.
+fn resume_scheduler_call<TPud, TTud, TExt: Extrinsics, TEng: Engine>(
+    mut thread: processes::ThreadAccess<
+        '_,
+        Extrinsic<TExt::ExtrinsicId>,
+        LocalProcessUserData<TPud, TExt>,
+        LocalThreadUserData<TTud, TExt::Context>,
+        TEng,
+    >,
+    local_run_queue: &SegQueue<ThreadId>,
+    value: Option<crate::WasmValue>,
+) {
+    debug_assert!(thread.user_data().state.is_ready_to_run());
+
+    let mut context = match thread.user_data_mut().scheduler_call.take() {
+        Some(c) => c,
+        None => {
+            thread.resume(value);
+            return;
+        }
+    };
+
+    let process = thread.process();
+    let action = process.user_data().extrinsics.finish_scheduler_call(
+        &mut context,
+        value,
+        &mut MemoryAccessImpl(&mut thread),
+    );
+    thread.user_data_mut().state = LocalThreadState::OtherExtrinsicApplyAction { context, action };
+    local_run_queue.push(thread.tid());
+}
+
 /// Implementation of the [`ExtrinsicsMemoryAccess`] trait for a process.
-struct MemoryAccessImpl<'a, 'b, TExtr, TPud, TTud>(
-    &'a mut processes::ThreadAccess<'b, TExtr, TPud, TTud>,
+struct MemoryAccessImpl<'a, 'b, TExtr, TPud, TTud, TEng: Engine>(
+    &'a mut processes::ThreadAccess<'b, TExtr, TPud, TTud, TEng>,
 );
 
-impl<'a, 'b, TExtr, TPud, TTud> ExtrinsicsMemoryAccess
-    for MemoryAccessImpl<'a, 'b, TExtr, TPud, TTud>
+impl<'a, 'b, TExtr, TPud, TTud, TEng: Engine> ExtrinsicsMemoryAccess
+    for MemoryAccessImpl<'a, 'b, TExtr, TPud, TTud, TEng>
 {
     fn read_memory(&self, range: Range<u32>) -> Result<Vec<u8>, ExtrinsicsMemoryAccessErr> {
         self.0
@@ -1189,4 +1591,16 @@ impl<'a, 'b, TExtr, TPud, TTud> ExtrinsicsMemoryAccess
             .write_memory(offset, data)
             .map_err(|()| ExtrinsicsMemoryAccessErr::OutOfRange)
     }
+
+    fn process_cpu_time(&self) -> Duration {
+        self.0.process().cpu_time()
+    }
+
+    fn thread_cpu_time(&self) -> Duration {
+        self.0.cpu_time()
+    }
+
+    fn monotonic_clock(&self) -> Duration {
+        self.0.monotonic_clock()
+    }
 }
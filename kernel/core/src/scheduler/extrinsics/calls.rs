@@ -15,7 +15,7 @@
 
 //! Helpers for parsing the hardcoded functions that can be called by the WASM program.
 
-use crate::scheduler::processes;
+use crate::scheduler::{processes, vm::Engine};
 use crate::{InterfaceHash, InvalidMessageIdErr, MessageId};
 
 use alloc::vec::Vec;
@@ -28,8 +28,8 @@ use redshirt_syscalls::EncodedMessage;
 /// has no side effect.
 ///
 /// Returns an error if the call is invalid.
-pub fn parse_extrinsic_next_notification<TExtr, TPud, TTud>(
-    thread: &mut processes::ThreadAccess<TExtr, TPud, TTud>,
+pub fn parse_extrinsic_next_notification<TExtr, TPud, TTud, TEng: Engine>(
+    thread: &mut processes::ThreadAccess<TExtr, TPud, TTud, TEng>,
     params: Vec<crate::WasmValue>,
 ) -> Result<NotificationWait, ExtrinsicNextNotificationErr> {
     // We use an assert here rather than a runtime check because the WASM VM (rather than us) is
@@ -145,8 +145,8 @@ pub enum ExtrinsicNextNotificationErr {
 /// has no side effect.
 ///
 /// Returns an error if the call is invalid.
-pub fn parse_extrinsic_emit_message<TExtr, TPud, TTud>(
-    thread: &mut processes::ThreadAccess<TExtr, TPud, TTud>,
+pub fn parse_extrinsic_emit_message<TExtr, TPud, TTud, TEng: Engine>(
+    thread: &mut processes::ThreadAccess<TExtr, TPud, TTud, TEng>,
     params: Vec<crate::WasmValue>,
 ) -> Result<EmitMessage, ExtrinsicEmitMessageErr> {
     // We use an assert here rather than a runtime check because the WASM VM (rather than us) is
@@ -265,8 +265,8 @@ pub enum ExtrinsicEmitMessageErr {
 /// has no side effect.
 ///
 /// Returns an error if the call is invalid.
-pub fn parse_extrinsic_cancel_message<TExtr, TPud, TTud>(
-    thread: &mut processes::ThreadAccess<TExtr, TPud, TTud>,
+pub fn parse_extrinsic_cancel_message<TExtr, TPud, TTud, TEng: Engine>(
+    thread: &mut processes::ThreadAccess<TExtr, TPud, TTud, TEng>,
     params: Vec<crate::WasmValue>,
 ) -> Result<MessageId, ExtrinsicCancelMessageErr> {
     // We use an assert here rather than a runtime check because the WASM VM (rather than us) is
@@ -14,18 +14,24 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{
-    extrinsics::Extrinsics,
+    extrinsics::{Extrinsics, LaunchConfig},
     id_pool::IdPool,
     module::Module,
     scheduler::{
         extrinsics::{self, ThreadAccessAccess as _},
-        vm,
+        vm::{self, DefaultEngine, Engine},
+        ProcessExitReason,
     },
     InterfaceHash,
 };
 
 use alloc::vec::Vec;
-use core::convert::TryFrom as _;
+use core::{
+    convert::TryFrom as _,
+    mem,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 use crossbeam_queue::SegQueue;
 use hashbrown::{hash_map::Entry, HashMap};
 use redshirt_syscalls::{EncodedMessage, MessageId, Pid, ThreadId};
@@ -58,6 +64,31 @@ mod waiting_threads;
 /// a [`MessageId`] for API-related purposes. This [`MessageId`] isn't expected to ever reach a
 /// program's user space. As soon as the message is accepted or refused, the [`MessageId`] is
 /// discarded.
+///
+/// # Limits
+///
+/// In order to prevent a misbehaving process from exhausting the memory of the kernel, two limits
+/// are enforced:
+///
+/// - The number of messages emitted by a process that expect an answer and that haven't been
+///   answered yet. Cancelled messages continue to count towards this limit until they have been
+///   answered. Trying to emit a message above this limit immediately fails.
+///
+/// - The number of notifications in the queue of a process. If this limit is reached, messages
+///   destined to this process, as indicated with [`Core::check_recipient_queue`], are put on hold
+///   and the emitting thread is blocked until the process has pulled notifications from its
+///   queue. The same applies to messages that expect an answer emitted by the process itself,
+///   as answers that have arrived no longer count towards the previous limit but stay in the
+///   queue until they are pulled. If the emitter doesn't allow delays, the emission immediately
+///   fails instead.
+///
+/// These limits can be configured with [`CoreBuilder::with_max_pending_messages`] and
+/// [`CoreBuilder::with_max_queued_notifications`].
+///
+/// # Engine
+///
+/// The code of the processes is executed by the [`Engine`] passed as second generic parameter,
+/// which defaults to [`DefaultEngine`]. See the [`Engine`] trait for more information.
 //
 // # Implementation notes
 //
@@ -79,7 +110,7 @@ mod waiting_threads;
 // from the fact that he process ID is no longer valid. This wouldn't be possible if process IDs
 // were reused.
 //
-pub struct Core<TExt: Extrinsics> {
+pub struct Core<TExt: Extrinsics, TEng: Engine = DefaultEngine> {
     /// Pool of identifiers where `MessageId`s are allocated.
     id_pool: IdPool,
 
@@ -87,7 +118,7 @@ pub struct Core<TExt: Extrinsics> {
     pending_events: SegQueue<CoreRunOutcome>,
 
     /// List of running processes.
-    processes: extrinsics::ProcessesCollectionExtrinsics<Process, (), TExt>,
+    processes: extrinsics::ProcessesCollectionExtrinsics<Process, (), TExt, TEng>,
 
     /// List of messages that have been emitted by a thread but haven't been accepted or refused
     /// yet. Stores the emitter of the message.
@@ -95,29 +126,48 @@ pub struct Core<TExt: Extrinsics> {
         Spinlock<HashMap<MessageId, (Pid, ThreadId), nohash_hasher::BuildNoHashHasher<u64>>>,
 
     /// List of messages that have been emitted by a process but haven't been answered yet. Stores
-    /// the emitter of the message.
+    /// the emitter of the message, and whether the message has been cancelled.
     pending_answer_messages:
-        Spinlock<HashMap<MessageId, Pid, nohash_hasher::BuildNoHashHasher<u64>>>,
+        Spinlock<HashMap<MessageId, (Pid, bool), nohash_hasher::BuildNoHashHasher<u64>>>,
+
+    /// Maximum number of messages that a process can have waiting for an answer.
+    max_pending_messages: usize,
+
+    /// Maximum number of notifications in the queue of a process before emitting messages is
+    /// blocked.
+    max_queued_notifications: usize,
 }
 
+/// Default value for [`Core::max_pending_messages`].
+const DEFAULT_MAX_PENDING_MESSAGES: usize = 4096;
+
+/// Default value for [`Core::max_queued_notifications`].
+const DEFAULT_MAX_QUEUED_NOTIFICATIONS: usize = 4096;
+
 /// Prototype for a `Core` under construction.
-pub struct CoreBuilder<TExt: Extrinsics> {
+pub struct CoreBuilder<TExt: Extrinsics, TEng: Engine = DefaultEngine> {
     /// Builder for the [`processes`][Core::processes] field in [`Core`].
-    inner_builder: extrinsics::Builder<TExt>,
+    inner_builder: extrinsics::Builder<TExt, TEng>,
 
     /// Randomness seed used to initialize [`Core::id_pool`].
     seed: [u8; 32],
+
+    /// See the corresponding field in [`Core`].
+    max_pending_messages: usize,
+
+    /// See the corresponding field in [`Core`].
+    max_queued_notifications: usize,
 }
 
 /// Event returned by [`Core::run`].
-pub enum ExecuteOut<'a, TExt: Extrinsics> {
+pub enum ExecuteOut<'a, TExt: Extrinsics, TEng: Engine = DefaultEngine> {
     /// Event directly generated.
     Direct(CoreRunOutcome),
     /// Ready to execute a bit of a thread.
-    ReadyToRun(ReadyToRun<'a, TExt>),
+    ReadyToRun(ReadyToRun<'a, TExt, TEng>),
 }
 
-impl<'a, TExt: Extrinsics> ExecuteOut<'a, TExt> {
+impl<'a, TExt: Extrinsics, TEng: Engine> ExecuteOut<'a, TExt, TEng> {
     pub fn or_run(self) -> Option<CoreRunOutcome> {
         match self {
             ExecuteOut::Direct(ev) => Some(ev),
@@ -128,12 +178,12 @@ impl<'a, TExt: Extrinsics> ExecuteOut<'a, TExt> {
 
 /// Ready to resume one of the threads of a process.
 #[must_use]
-pub struct ReadyToRun<'a, TExt: Extrinsics> {
-    core: &'a Core<TExt>,
-    inner: extrinsics::ReadyToRun<'a, Process, (), TExt>,
+pub struct ReadyToRun<'a, TExt: Extrinsics, TEng: Engine = DefaultEngine> {
+    core: &'a Core<TExt, TEng>,
+    inner: extrinsics::ReadyToRun<'a, Process, (), TExt, TEng>,
 }
 
-impl<'a, TExt: Extrinsics> ReadyToRun<'a, TExt> {
+impl<'a, TExt: Extrinsics, TEng: Engine> ReadyToRun<'a, TExt, TEng> {
     /// Performs the actual execution.
     ///
     /// Returns `None` if the execution doesn't lead to any event in particular.
@@ -152,11 +202,20 @@ pub enum CoreRunOutcome {
         /// Id of the program that has stopped.
         pid: Pid,
 
-        /// How the program ended. If `Ok`, it has gracefully terminated. If `Err`, something
-        /// bad happened.
-        // TODO: force Ok to i32?
-        // TODO: don't expose wasmi in error
-        outcome: Result<Option<crate::WasmValue>, wasmi::Trap>,
+        /// How the program ended.
+        outcome: ProcessExitReason,
+    },
+
+    /// A thread of a process, other than its main thread, has finished.
+    ///
+    /// The process is still alive.
+    ThreadFinished {
+        /// Id of the process the thread belonged to.
+        pid: Pid,
+        /// Id of the thread that has finished.
+        thread_id: ThreadId,
+        /// Value returned by the function that the thread was executing.
+        value: Option<crate::WasmValue>,
     },
 
     /// A process wants to emit a message on an interface.
@@ -192,17 +251,29 @@ struct Process {
 
     /// List of threads that are frozen waiting for new notifications.
     wait_notifications_threads: waiting_threads::WaitingThreads,
+
+    /// Number of messages emitted by the process that expect an answer and that haven't been
+    /// answered yet. Includes messages that have been cancelled.
+    num_pending_answers: AtomicUsize,
+
+    /// List of threads that want to emit a message but are blocked because the notifications
+    /// queue is full.
+    blocked_emitters: Spinlock<Vec<ThreadId>>,
+
+    /// List of messages destined to this process and that are put on hold because the
+    /// notifications queue is full. See [`Core::check_recipient_queue`].
+    blocked_messages: Spinlock<Vec<MessageId>>,
 }
 
 /// Access to a process within the core.
-pub struct CoreProcess<'a, TExt: Extrinsics> {
+pub struct CoreProcess<'a, TExt: Extrinsics, TEng: Engine = DefaultEngine> {
     /// Access to the process within the inner collection.
-    process: extrinsics::ProcAccess<'a, Process, (), TExt>,
+    process: extrinsics::ProcAccess<'a, Process, (), TExt, TEng>,
 }
 
-impl<TExt: Extrinsics> Core<TExt> {
+impl<TExt: Extrinsics, TEng: Engine> Core<TExt, TEng> {
     /// Run the core once.
-    pub async fn run<'a>(&'a self) -> ExecuteOut<'a, TExt> {
+    pub async fn run<'a>(&'a self) -> ExecuteOut<'a, TExt, TEng> {
         loop {
             if let Some(ev) = self.run_inner().await {
                 break ev;
@@ -212,7 +283,7 @@ impl<TExt: Extrinsics> Core<TExt> {
 
     /// Same as [`Core::run`]. Returns `None` if no event should be returned and we should loop
     /// again.
-    async fn run_inner<'a>(&'a self) -> Option<ExecuteOut<'a, TExt>> {
+    async fn run_inner<'a>(&'a self) -> Option<ExecuteOut<'a, TExt, TEng>> {
         if let Some(ev) = self.pending_events.pop() {
             return Some(ExecuteOut::Direct(ev));
         }
@@ -232,17 +303,34 @@ impl<TExt: Extrinsics> Core<TExt> {
 
     fn inner_event(
         &self,
-        run_outcome: extrinsics::RunOneOutcome<Process, (), TExt>,
+        run_outcome: extrinsics::RunOneOutcome<Process, (), TExt, TEng>,
     ) -> Option<CoreRunOutcome> {
         match run_outcome {
-            extrinsics::RunOneOutcome::ProcessFinished { pid, outcome, .. } => {
+            extrinsics::RunOneOutcome::ProcessFinished {
+                pid,
+                outcome,
+                user_data,
+                ..
+            } => {
+                // Messages put on hold for this process are emitted again, so that the user can
+                // route them somewhere else.
+                for message_id in user_data.blocked_messages.into_inner() {
+                    self.reemit_blocked_message(message_id);
+                }
+
                 Some(CoreRunOutcome::ProgramFinished { pid, outcome })
             }
 
-            extrinsics::RunOneOutcome::ThreadFinished { .. } => {
-                // TODO: report
-                None
-            }
+            extrinsics::RunOneOutcome::ThreadFinished {
+                thread_id,
+                process,
+                value,
+                ..
+            } => Some(CoreRunOutcome::ThreadFinished {
+                pid: process.pid(),
+                thread_id,
+                value,
+            }),
 
             extrinsics::RunOneOutcome::ThreadWaitNotification(thread) => {
                 // A thread has asked for new incoming notifications.
@@ -261,6 +349,7 @@ impl<TExt: Extrinsics> Core<TExt> {
                     .notifications_queue
                     .total_notifications_pushed();
 
+                let pid = thread.pid();
                 if let Err(thread) = try_resume_notification_wait_thread(thread) {
                     // The thread couldn't be resumed.
                     let tid = thread.tid();
@@ -279,39 +368,27 @@ impl<TExt: Extrinsics> Core<TExt> {
                     if total_notifications_pushed_before != total_notifications_pushed_after {
                         self.try_resume_notification_wait(process);
                     }
+                } else {
+                    // A notification might have been pulled from the queue.
+                    self.try_unblock_emitters(pid);
                 }
 
                 None
             }
 
-            extrinsics::RunOneOutcome::ThreadEmitMessage(mut thread) => {
-                let emitter_pid = thread.pid();
-                let interface = thread.emit_interface().clone();
-                let needs_answer = thread.needs_answer();
-                let message_id = self.id_pool.assign();
-
-                self.pending_accept_messages
-                    .lock()
-                    .insert(message_id, (emitter_pid, thread.tid()));
-
-                Some(CoreRunOutcome::InterfaceMessage {
-                    pid: emitter_pid,
-                    message_id,
-                    needs_answer,
-                    immediate: !thread.allow_delay(),
-                    interface,
-                })
-            }
+            extrinsics::RunOneOutcome::ThreadEmitMessage(thread) => self.emit_message(thread),
 
             extrinsics::RunOneOutcome::ThreadCancelMessage {
                 message_id,
                 process,
                 ..
             } => {
+                // The message continues to count towards the limit of pending messages until it
+                // is answered. We only mark it as cancelled.
                 let mut pending_answer_messages = self.pending_answer_messages.lock();
-                if let Entry::Occupied(entry) = pending_answer_messages.entry(message_id) {
-                    if *entry.get() == process.pid() {
-                        entry.remove();
+                if let Entry::Occupied(mut entry) = pending_answer_messages.entry(message_id) {
+                    if entry.get().0 == process.pid() {
+                        entry.get_mut().1 = true;
                     }
                 }
 
@@ -320,8 +397,155 @@ impl<TExt: Extrinsics> Core<TExt> {
         }
     }
 
+    /// Handles a thread that wants to emit a message.
+    ///
+    /// Enforces the limits of the process, and either refuses the emission, blocks the thread,
+    /// or returns the corresponding [`CoreRunOutcome::InterfaceMessage`].
+    fn emit_message(
+        &self,
+        mut thread: extrinsics::ThreadEmitMessage<Process, (), TExt, TEng>,
+    ) -> Option<CoreRunOutcome> {
+        let needs_answer = thread.needs_answer();
+
+        if needs_answer {
+            let queue_len = thread.process_user_data().notifications_queue.len();
+            if queue_len >= self.max_queued_notifications {
+                if !thread.allow_delay() {
+                    thread.refuse_emit(extrinsics::EmitRefusal::NotificationsQueueFull);
+                    return None;
+                }
+
+                // Block the thread until notifications have been pulled from the queue.
+                let tid = thread.tid();
+                let process = thread.into_process();
+
+                // It is important for the lock to the thread to have been dropped at this
+                // point (i.e. `thread` is destroyed), as the thread can be unblocked by another
+                // CPU as soon as it is pushed to the list.
+                process.user_data().blocked_emitters.lock().push(tid);
+
+                // The queue might have shrunk in-between, in which case we try again
+                // immediately.
+                let queue_len = process.user_data().notifications_queue.len();
+                if queue_len < self.max_queued_notifications {
+                    self.try_unblock_emitters(process.pid());
+                }
+
+                return None;
+            }
+
+            let max_pending_messages = self.max_pending_messages;
+            let num_pending_answers = &thread.process_user_data().num_pending_answers;
+            let reserved =
+                num_pending_answers.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                    if n >= max_pending_messages {
+                        None
+                    } else {
+                        Some(n + 1)
+                    }
+                });
+            if reserved.is_err() {
+                thread.refuse_emit(extrinsics::EmitRefusal::TooManyPendingMessages);
+                return None;
+            }
+        }
+
+        let emitter_pid = thread.pid();
+        let interface = thread.emit_interface().clone();
+        let message_id = self.id_pool.assign();
+
+        self.pending_accept_messages
+            .lock()
+            .insert(message_id, (emitter_pid, thread.tid()));
+
+        Some(CoreRunOutcome::InterfaceMessage {
+            pid: emitter_pid,
+            message_id,
+            needs_answer,
+            immediate: !thread.allow_delay(),
+            interface,
+        })
+    }
+
+    /// Checks whether the notifications queue of the given process is below the limit, and if so
+    /// unblocks all the threads that are waiting to emit a message and all the messages destined
+    /// to this process that have been put on hold.
+    ///
+    /// The events generated by the emissions are pushed to [`Core::pending_events`].
+    ///
+    /// > **Note**: Notifications are only ever pulled from the queue when a thread is resumed,
+    /// >           meaning that a call to [`Core::run`] will soon process these pending events.
+    fn try_unblock_emitters(&self, pid: Pid) {
+        let (blocked_emitters, blocked_messages) = {
+            let process = match self.processes.process_by_id(pid) {
+                Some(p) => p,
+                None => return,
+            };
+
+            if process.user_data().notifications_queue.len() >= self.max_queued_notifications {
+                return;
+            }
+
+            let blocked_emitters = mem::take(&mut *process.user_data().blocked_emitters.lock());
+            let blocked_messages = mem::take(&mut *process.user_data().blocked_messages.lock());
+            (blocked_emitters, blocked_messages)
+        };
+
+        for message_id in blocked_messages {
+            self.reemit_blocked_message(message_id);
+        }
+
+        for tid in blocked_emitters {
+            match self.processes.interrupted_thread_by_id(tid) {
+                Ok(extrinsics::ThreadAccess::EmitMessage(thread)) => {
+                    if let Some(event) = self.emit_message(thread) {
+                        self.pending_events.push(event);
+                    }
+                }
+                // The process has been killed in-between.
+                Err(extrinsics::ThreadByIdErr::RunningOrDead) => {}
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Pushes to [`Core::pending_events`] a new [`CoreRunOutcome::InterfaceMessage`] for a
+    /// message that has been put on hold by [`Core::check_recipient_queue`].
+    fn reemit_blocked_message(&self, message_id: MessageId) {
+        let tid = match self.pending_accept_messages.lock().get(&message_id) {
+            Some((_, tid)) => *tid,
+            None => return,
+        };
+
+        match self.processes.interrupted_thread_by_id(tid) {
+            Ok(extrinsics::ThreadAccess::EmitMessage(mut thread)) => {
+                self.pending_events.push(CoreRunOutcome::InterfaceMessage {
+                    pid: thread.pid(),
+                    message_id,
+                    needs_answer: thread.needs_answer(),
+                    immediate: !thread.allow_delay(),
+                    interface: thread.emit_interface().clone(),
+                });
+            }
+            // The emitter has been killed in-between.
+            Err(extrinsics::ThreadByIdErr::RunningOrDead) => {
+                self.pending_accept_messages.lock().remove(&message_id);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns an iterator to all the processes that exist.
+    pub fn processes<'a>(
+        &'a self,
+    ) -> impl ExactSizeIterator<Item = CoreProcess<'a, TExt, TEng>> + 'a {
+        self.processes
+            .processes()
+            .map(|process| CoreProcess { process })
+    }
+
     /// Returns an object granting access to a process, if it exists.
-    pub fn process_by_id(&self, pid: Pid) -> Option<CoreProcess<TExt>> {
+    pub fn process_by_id(&self, pid: Pid) -> Option<CoreProcess<TExt, TEng>> {
         let p = self.processes.process_by_id(pid)?;
         Some(CoreProcess { process: p })
     }
@@ -337,7 +561,9 @@ impl<TExt: Extrinsics> Core<TExt> {
     pub fn accept_interface_message(&self, message_id: MessageId) -> Option<(Pid, EncodedMessage)> {
         let (pid, tid) = self.pending_accept_messages.lock().remove(&message_id)?;
 
-        self.pending_answer_messages.lock().insert(message_id, pid);
+        self.pending_answer_messages
+            .lock()
+            .insert(message_id, (pid, false));
 
         match self.processes.interrupted_thread_by_id(tid).unwrap() {
             extrinsics::ThreadAccess::EmitMessage(mut thread) => {
@@ -362,6 +588,75 @@ impl<TExt: Extrinsics> Core<TExt> {
     /// Might panic if the message is in the wrong state.
     ///
     pub fn reject_immediate_interface_message(&self, message_id: MessageId) {
+        self.refuse_interface_message(message_id, extrinsics::EmitRefusal::NoHandler);
+    }
+
+    /// After [`CoreRunOutcome::InterfaceMessage`] is generated, use this method to indicate that
+    /// the message is destined to `recipient`, typically the handler of the interface the message
+    /// has been emitted on, before accepting it.
+    ///
+    /// Returns `true` if the message can be accepted. Returns `false` if the notifications queue
+    /// of `recipient` is full, in which case the message must be left alone. If `immediate` is
+    /// true, the message is then refused with [`extrinsics::EmitRefusal::NotificationsQueueFull`].
+    /// Otherwise the message is put on hold, and a new [`CoreRunOutcome::InterfaceMessage`] with
+    /// the same [`MessageId`] is generated once `recipient` has pulled notifications from its
+    /// queue or has terminated.
+    ///
+    /// `immediate` must be the value of [`CoreRunOutcome::InterfaceMessage::immediate`].
+    pub fn check_recipient_queue(
+        &self,
+        message_id: MessageId,
+        immediate: bool,
+        recipient: Pid,
+    ) -> bool {
+        {
+            let process = match self.processes.process_by_id(recipient) {
+                Some(p) => p,
+                None => return true,
+            };
+
+            if process.user_data().notifications_queue.len() < self.max_queued_notifications {
+                return true;
+            }
+
+            if !immediate {
+                // As long as `process` is alive, the process can't terminate. The message is
+                // therefore guaranteed to be either unblocked or emitted again when the process
+                // terminates.
+                process.user_data().blocked_messages.lock().push(message_id);
+            }
+        }
+
+        if immediate {
+            self.refuse_interface_message(
+                message_id,
+                extrinsics::EmitRefusal::NotificationsQueueFull,
+            );
+        } else {
+            // The queue might have shrunk in-between, in which case the message is emitted
+            // again immediately.
+            self.try_unblock_emitters(recipient);
+        }
+
+        false
+    }
+
+    /// After [`CoreRunOutcome::InterfaceMessage`] is generated, use this method to notify that
+    /// the emitter isn't allowed to emit messages on this interface.
+    ///
+    /// Contrary to [`Core::reject_immediate_interface_message`], this can be used no matter the
+    /// value of [`CoreRunOutcome::InterfaceMessage::immediate`].
+    ///
+    /// # Panic
+    ///
+    /// Might panic if the message is in the wrong state.
+    ///
+    pub fn deny_interface_message(&self, message_id: MessageId) {
+        self.refuse_interface_message(message_id, extrinsics::EmitRefusal::Denied);
+    }
+
+    /// Resumes the thread that has emitted the given message, signalling an error.
+    fn refuse_interface_message(&self, message_id: MessageId, reason: extrinsics::EmitRefusal) {
         let (_, tid) = match self.pending_accept_messages.lock().remove(&message_id) {
             Some(v) => v,
             None => return, // Process might have been killed in-between.
@@ -369,20 +664,38 @@ impl<TExt: Extrinsics> Core<TExt> {
 
         match self.processes.interrupted_thread_by_id(tid) {
             Ok(extrinsics::ThreadAccess::EmitMessage(mut thread)) => {
-                assert!(!thread.allow_delay());
-                thread.refuse_emit();
+                if reason == extrinsics::EmitRefusal::NoHandler {
+                    assert!(!thread.allow_delay());
+                }
+                if thread.needs_answer() {
+                    thread
+                        .process_user_data()
+                        .num_pending_answers
+                        .fetch_sub(1, Ordering::SeqCst);
+                }
+                thread.refuse_emit(reason);
             }
             Err(extrinsics::ThreadByIdErr::RunningOrDead) => {}
             _ => unreachable!(),
         }
     }
 
+    /// Allocates a new [`MessageId`] that isn't tied to any thread, for messages that are emitted
+    /// by the user of the [`Core`] itself.
+    ///
+    /// The identifier is guaranteed to never collide with the identifiers of messages emitted by
+    /// processes. Passing it to [`Core::answer_message`] has no effect.
+    pub fn new_message_id(&self) -> MessageId {
+        self.id_pool.assign()
+    }
+
     /// Set the answer to a message previously passed to [`Core::accept_interface_message`].
     ///
-    /// This pushes a notification to the process.
+    /// This pushes a notification to the process, unless the message has been cancelled.
     pub fn answer_message(&self, message_id: MessageId, response: Result<EncodedMessage, ()>) {
-        let emitter_pid = match self.pending_answer_messages.lock().remove(&message_id) {
-            Some(pid) => pid,
+        let (emitter_pid, cancelled) = match self.pending_answer_messages.lock().remove(&message_id)
+        {
+            Some(v) => v,
             None => {
                 // Should happen if and only if the process that emitted the message has been
                 // aborted. MessageIds are never reused, therefore guaranteeing that this answer
@@ -394,9 +707,16 @@ impl<TExt: Extrinsics> Core<TExt> {
         if let Some(process) = self.processes.process_by_id(emitter_pid) {
             process
                 .user_data()
-                .notifications_queue
-                .push(message_id, response);
-            self.try_resume_notification_wait(process);
+                .num_pending_answers
+                .fetch_sub(1, Ordering::SeqCst);
+
+            if !cancelled {
+                process
+                    .user_data()
+                    .notifications_queue
+                    .push(message_id, response);
+                self.try_resume_notification_wait(process);
+            }
         } else {
             // It is possible for the emitter of the message to have stopped or crashed, and we
             // had not updated `active_messages` yet.
@@ -405,20 +725,59 @@ impl<TExt: Extrinsics> Core<TExt> {
 
     /// Start executing the module passed as parameter.
     ///
-    /// Each import of the [`Module`](crate::module::Module) is resolved.
-    pub fn execute(&self, module: &Module) -> Result<(CoreProcess<TExt>, ThreadId), vm::NewErr> {
+    /// Each import of the [`Module`](crate::module::Module) is resolved. The process is
+    /// forbidden from using more resources than the given limits.
+    pub fn execute(
+        &self,
+        module: &Module,
+        limits: vm::ProcessLimits,
+    ) -> Result<(CoreProcess<TExt, TEng>, ThreadId), vm::NewErr> {
+        self.execute_with_launch_config(module, limits, Default::default())
+    }
+
+    /// Same as [`execute`](Core::execute), but passes the given configuration to the
+    /// [`Extrinsics`] of the process.
+    pub fn execute_with_launch_config(
+        &self,
+        module: &Module,
+        limits: vm::ProcessLimits,
+        launch_config: LaunchConfig,
+    ) -> Result<(CoreProcess<TExt, TEng>, ThreadId), vm::NewErr> {
+        self.execute_with_setup(module, limits, launch_config, |_| {})
+    }
+
+    /// Same as [`execute_with_launch_config`](Core::execute_with_launch_config), but calls
+    /// `setup` with the [`Pid`] of the new process before any of its threads can run.
+    ///
+    /// This makes it possible to attach information to the process, such as the rights it has,
+    /// without the risk of the process emitting messages or ending in the meanwhile.
+    pub fn execute_with_setup(
+        &self,
+        module: &Module,
+        limits: vm::ProcessLimits,
+        launch_config: LaunchConfig,
+        setup: impl FnOnce(Pid),
+    ) -> Result<(CoreProcess<TExt, TEng>, ThreadId), vm::NewErr> {
         let proc_metadata = Process {
             notifications_queue: notifications_queue::NotificationsQueue::new(),
             wait_notifications_threads: waiting_threads::WaitingThreads::new(),
+            num_pending_answers: AtomicUsize::new(0),
+            blocked_emitters: Spinlock::new(Vec::new()),
+            blocked_messages: Spinlock::new(Vec::new()),
         };
 
-        let (process, main_tid) = self.processes.execute(module, proc_metadata, ())?;
+        let (process, main_tid) =
+            self.processes
+                .execute(module, limits, launch_config, proc_metadata, (), setup)?;
 
         Ok((CoreProcess { process }, main_tid))
     }
 
     /// Tries to resume all the threads of the process that are waiting for an notification.
-    fn try_resume_notification_wait(&self, process: extrinsics::ProcAccess<Process, (), TExt>) {
+    fn try_resume_notification_wait(
+        &self,
+        process: extrinsics::ProcAccess<Process, (), TExt, TEng>,
+    ) {
         // The actual work being done here is actually quite complicated in order to ensure that
         // each `ThreadId` is only accessed once at a time, but the exposed API is very simple.
         for thread_access in process.user_data().wait_notifications_threads.access() {
@@ -434,44 +793,68 @@ impl<TExt: Extrinsics> Core<TExt> {
                 thread_access.remove();
             }
         }
+
+        // A notification might have been pulled from the queue.
+        self.try_unblock_emitters(process.pid());
     }
 }
 
-impl<'a, TExt: Extrinsics> CoreProcess<'a, TExt> {
+impl<'a, TExt: Extrinsics, TEng: Engine> CoreProcess<'a, TExt, TEng> {
     /// Returns the [`Pid`] of the process.
     pub fn pid(&self) -> Pid {
         self.process.pid()
     }
 
+    /// Returns the total time spent executing the threads of the process.
+    pub fn cpu_time(&self) -> Duration {
+        self.process.cpu_time()
+    }
+
+    /// Returns the instance of [`Extrinsics`] dedicated to the process.
+    pub fn extrinsics(&self) -> &TExt {
+        self.process.extrinsics()
+    }
+
     /// Adds a new thread to the process, starting the function with the given index and passing
     /// the given parameters.
+    ///
+    /// `stack_pointer` is the initial value of the stack pointer of the new thread. It must point
+    /// to the end of a memory area that the process has reserved for the stack of that thread.
+    ///
+    /// A [`CoreRunOutcome::ThreadFinished`] event is generated when the thread finishes, unless
+    /// the entire process ends first.
     pub fn start_thread(
-        self,
+        &self,
         fn_index: u32,
         params: Vec<crate::WasmValue>,
-    ) -> Result<(), vm::StartErr> {
-        self.process.start_thread(fn_index, params, ())?;
-        Ok(())
+        stack_pointer: Option<i32>,
+    ) -> Result<ThreadId, vm::StartErr> {
+        self.process
+            .start_thread(fn_index, params, stack_pointer, ())
     }
 
     /// Starts killing the process.
-    // TODO: more docs
+    ///
+    /// The process will be reported as finished with [`ProcessExitReason::Killed`] once all the
+    /// locks to this process have been released.
     pub fn abort(&self) {
-        self.process.abort();
+        self.process.abort(ProcessExitReason::Killed);
     }
 }
 
-impl<TExt: Extrinsics> CoreBuilder<TExt> {
+impl<TExt: Extrinsics, TEng: Engine> CoreBuilder<TExt, TEng> {
     /// Initializes a new [`CoreBuilder`] using the given random seed.
     ///
     /// The seed is used in determine how [`Pid`]s and [`MessageId`]s are generated. The same
     /// seed will result in the same sequence of [`Pid`]s and [`MessageId`]s.
-    pub fn with_seed(seed: [u8; 64]) -> CoreBuilder<TExt> {
+    pub fn with_seed(seed: [u8; 64]) -> CoreBuilder<TExt, TEng> {
         CoreBuilder {
             inner_builder: extrinsics::Builder::with_seed(
                 <[u8; 32]>::try_from(&seed[..32]).unwrap(),
             ),
             seed: <[u8; 32]>::try_from(&seed[32..]).unwrap(),
+            max_pending_messages: DEFAULT_MAX_PENDING_MESSAGES,
+            max_queued_notifications: DEFAULT_MAX_QUEUED_NOTIFICATIONS,
         }
     }
 
@@ -485,14 +868,54 @@ impl<TExt: Extrinsics> CoreBuilder<TExt> {
         self.inner_builder.reserve_pid()
     }
 
+    /// Sets the maximum amount of fuel that a thread can consume before being preempted and
+    /// another thread given the chance to run.
+    ///
+    /// By default, threads are never preempted.
+    pub fn with_fuel_per_run(mut self, fuel: u64) -> Self {
+        self.inner_builder = self.inner_builder.with_fuel_per_run(fuel);
+        self
+    }
+
+    /// Sets the function that returns the current value of the monotonic clock.
+    ///
+    /// The clock is used to measure the CPU time of processes and threads. By default, the clock
+    /// always returns zero.
+    pub fn with_monotonic_clock(
+        mut self,
+        clock: impl Fn() -> Duration + Send + Sync + 'static,
+    ) -> Self {
+        self.inner_builder = self.inner_builder.with_monotonic_clock(clock);
+        self
+    }
+
+    /// Sets the maximum number of messages that a process can have waiting for an answer.
+    ///
+    /// Cancelled messages continue to count towards this limit until they have been answered.
+    /// Emitting a message above this limit fails.
+    pub fn with_max_pending_messages(mut self, max: usize) -> Self {
+        self.max_pending_messages = max;
+        self
+    }
+
+    /// Sets the maximum number of notifications in the queue of a process. Above this limit,
+    /// messages destined to the process, and messages expecting an answer emitted by the process,
+    /// are blocked until the process pulls notifications from its queue.
+    pub fn with_max_queued_notifications(mut self, max: usize) -> Self {
+        self.max_queued_notifications = max;
+        self
+    }
+
     /// Turns the builder into a [`Core`].
-    pub fn build(self) -> Core<TExt> {
+    pub fn build(self) -> Core<TExt, TEng> {
         Core {
             pending_events: SegQueue::new(),
             processes: self.inner_builder.build(),
             id_pool: IdPool::with_seed(self.seed),
             pending_accept_messages: Spinlock::new(HashMap::default()),
             pending_answer_messages: Spinlock::new(HashMap::default()),
+            max_pending_messages: self.max_pending_messages,
+            max_queued_notifications: self.max_queued_notifications,
         }
     }
 }
@@ -501,9 +924,9 @@ impl<TExt: Extrinsics> CoreBuilder<TExt> {
 /// resume said thread.
 ///
 /// Returns back the thread within an `Err` if it couldn't be resumed.
-fn try_resume_notification_wait_thread<TExt: Extrinsics>(
-    mut thread: extrinsics::ThreadWaitNotif<Process, (), TExt>,
-) -> Result<(), extrinsics::ThreadWaitNotif<Process, (), TExt>> {
+fn try_resume_notification_wait_thread<TExt: Extrinsics, TEng: Engine>(
+    mut thread: extrinsics::ThreadWaitNotif<Process, (), TExt, TEng>,
+) -> Result<(), extrinsics::ThreadWaitNotif<Process, (), TExt, TEng>> {
     // Note that the code below is a bit weird and unelegant, but this is to bypass spurious
     // borrowing errors.
     let (entry_size, index_and_notif) = {
@@ -63,6 +63,11 @@ impl NotificationsQueue {
         }
     }
 
+    /// Returns the number of notifications currently in the queue.
+    pub fn len(&self) -> usize {
+        self.guarded.lock().queue.len()
+    }
+
     /// Returns the total number of notifications that have been pushed to this queue.
     pub fn total_notifications_pushed(&self) -> u64 {
         self.guarded.lock().total_notifications_pushed
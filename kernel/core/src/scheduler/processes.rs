@@ -56,6 +56,19 @@
 //! If a process finishes (either by normal termination or because of a crash), the emission of
 //! the corresponding [`RunOneOutcome::ProcessFinished`] event will be delayed until no instance
 //! of [`ProcAccess`] corresponding to that process exist anymore.
+//!
+//! # Preemption
+//!
+//! If a fuel budget has been configured with [`ProcessesCollectionBuilder::with_fuel_per_run`],
+//! each call to [`ReadyToRun::run`] executes at most this amount of fuel. If the budget is
+//! exhausted, the thread is automatically put back at the end of the execution queue and
+//! [`RunOneOutcome::Preempted`] is returned.
+//!
+//! # CPU time
+//!
+//! If a clock has been configured with [`ProcessesCollectionBuilder::with_monotonic_clock`], the
+//! time spent in each call to [`ReadyToRun::run`] is added to the CPU time of the thread being
+//! executed and of its process. See [`ProcAccess::cpu_time`] and [`ThreadAccess::cpu_time`].
 
 // Implementation notes.
 //
@@ -111,7 +124,16 @@
 // process. The last step is to report that death to the user through a
 // `RunOneOutcome::ProcessFinished`, which happens as soon as `run` is called.
 
-use crate::{id_pool::IdPool, module::Module, primitives::Signature, scheduler::vm, Pid, ThreadId};
+use crate::{
+    id_pool::IdPool,
+    module::Module,
+    primitives::Signature,
+    scheduler::{
+        vm::{self, DefaultEngine, Engine},
+        ProcessExitReason,
+    },
+    Pid, ThreadId,
+};
 
 use alloc::{
     borrow::Cow,
@@ -124,9 +146,11 @@ use alloc::{
 use core::{
     fmt,
     future::Future,
+    marker::PhantomData,
     mem,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 use crossbeam_queue::SegQueue;
 use fnv::FnvBuildHasher;
@@ -151,8 +175,8 @@ const PROCESSES_MIN_CAPACITY: usize = 128;
 ///
 /// The generic parameters `TPud` and `TTud` are "user data"s that are stored respectively per
 /// process and per thread, and allows the user to put extra information associated to a process
-/// or a thread.
-pub struct ProcessesCollection<TExtr, TPud, TTud> {
+/// or a thread. The generic parameter `TEng` is the [`Engine`] that executes the processes.
+pub struct ProcessesCollection<TExtr, TPud, TTud, TEng: Engine> {
     /// Allocations of process IDs and thread IDs.
     pid_tid_pool: IdPool,
 
@@ -165,7 +189,7 @@ pub struct ProcessesCollection<TExtr, TPud, TTud> {
     /// in this queue `N` times, it means that `N` of its threads are ready to run. There isn't
     /// any unnecessary entry.
     // TODO: use something better than a naive round robin?
-    execution_queue: SegQueue<Arc<Process<TPud, TTud>>>,
+    execution_queue: SegQueue<Arc<Process<TPud, TTud, TEng>>>,
 
     /// List of threads waiting to be resumed, plus their user data and the process they belong to.
     /// Doesn't contains threads that have been locked by the user with
@@ -174,7 +198,7 @@ pub struct ProcessesCollection<TExtr, TPud, TTud> {
     // TODO: find a solution for that mutex?
     // TODO: call shrink_to_fit from time to time?
     interrupted_threads:
-        Spinlock<HashMap<ThreadId, (TTud, Arc<Process<TPud, TTud>>), BuildNoHashHasher<u64>>>,
+        Spinlock<HashMap<ThreadId, (TTud, Arc<Process<TPud, TTud, TEng>>), BuildNoHashHasher<u64>>>,
 
     /// List of all processes currently alive.
     ///
@@ -182,7 +206,7 @@ pub struct ProcessesCollection<TExtr, TPud, TTud> {
     /// queue and the interrupted threads, thereby guaranteeing that they are alive only if they
     /// can potentially continue running.
     // TODO: find a solution for that mutex?
-    processes: Spinlock<HashMap<Pid, Weak<Process<TPud, TTud>>, BuildNoHashHasher<u64>>>,
+    processes: Spinlock<HashMap<Pid, Weak<Process<TPud, TTud, TEng>>, BuildNoHashHasher<u64>>>,
 
     /// List of functions that processes can call.
     /// The key of this map is an arbitrary `usize` that we pass to the WASM virtual machine.
@@ -197,38 +221,45 @@ pub struct ProcessesCollection<TExtr, TPud, TTud> {
         HashMap<(Cow<'static, str>, Cow<'static, str>), (usize, Signature), FnvBuildHasher>,
 
     /// Queue of process deaths to report to the external API.
-    death_reports: SegQueue<(
-        Pid,
-        TPud,
-        Vec<(ThreadId, TTud)>,
-        Result<Option<crate::WasmValue>, wasmi::Trap>,
-    )>,
+    death_reports: SegQueue<(Pid, TPud, Vec<(ThreadId, TTud)>, ProcessExitReason)>,
+
+    /// Maximum amount of fuel that a thread is allowed to consume in a single call to
+    /// [`ReadyToRun::run`]. `None` if there is no limit.
+    fuel_per_run: Option<u64>,
+
+    /// Function that returns the current value of the monotonic clock. Used to measure the CPU
+    /// time of processes and threads.
+    monotonic_clock: Box<dyn Fn() -> Duration + Send + Sync>,
 }
 
 /// Description of a process. Always addressed through an `Arc`.
 ///
 /// Note that the process might be dead.
-struct Process<TPud, TTud> {
+struct Process<TPud, TTud, TEng: Engine> {
     /// Identifier of the process.
     pid: Pid,
 
     /// Part of the state behind a mutex.
     // TODO: it's obviously not great to have a Mutex here; this should be refactored once the
     // `vm` module supports multithreading
-    lock: Spinlock<ProcessLock<TTud>>,
+    lock: Spinlock<ProcessLock<TTud, TEng>>,
 
     /// User-chosen data (opaque to us) that describes the process.
     user_data: TPud,
 }
 
 /// Part of each process's state that is behind a mutex.
-struct ProcessLock<TTud> {
+struct ProcessLock<TTud, TEng: Engine> {
     /// The actual Wasm virtual machine. Do not use if `dead` is `Some`.
-    vm: vm::ProcessStateMachine<Thread>,
+    vm: vm::ProcessStateMachine<Thread, TEng>,
 
     /// Queue of threads that are ready to be resumed.
     threads_to_resume: VecDeque<(ThreadId, TTud, Option<crate::WasmValue>)>,
 
+    /// Total time spent executing the threads of this process, including threads that have
+    /// finished.
+    cpu_time: Duration,
+
     /// If `Some`, then the process has been marked for death. The virtual machine must no longer
     /// be used (as it might be in a poisoned state), and we are in the process of collecting all
     /// the threads user datas into the [`ProcessDeadState`] before notifying the user.
@@ -241,16 +272,19 @@ struct ProcessDeadState<TTud> {
     dead_threads: Vec<(ThreadId, TTud)>,
 
     /// Why the process ended. Never modified once set.
-    outcome: Result<Option<crate::WasmValue>, wasmi::Trap>,
+    outcome: ProcessExitReason,
 }
 
 /// Additional data associated to a thread. Stored within the [`vm::ProcessStateMachine`].
 struct Thread {
     /// Identifier of the thread.
     thread_id: ThreadId,
+
+    /// Total time spent executing this thread.
+    cpu_time: Duration,
 }
 
-impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
+impl<TExtr, TPud, TTud, TEng: Engine> ProcessesCollection<TExtr, TPud, TTud, TEng> {
     /// Creates a new process from the given module.
     ///
     /// The closure is called for each import that the module has. It must assign a number to each
@@ -260,20 +294,29 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
     ///
     /// A single main thread (whose user data is passed by parameter) is automatically created and
     /// is paused at the start of the "_start" function of the module.
+    ///
+    /// The process is forbidden from using more resources than the given limits.
+    ///
+    /// `setup` is called with the [`Pid`] of the new process after it has been successfully
+    /// created, but before any of its threads can run.
     pub fn execute(
         &self,
         module: &Module,
+        limits: vm::ProcessLimits,
         proc_user_data: TPud,
         main_thread_user_data: TTud,
-    ) -> Result<(ProcAccess<TExtr, TPud, TTud>, ThreadId), vm::NewErr> {
+        setup: impl FnOnce(Pid),
+    ) -> Result<(ProcAccess<TExtr, TPud, TTud, TEng>, ThreadId), vm::NewErr> {
         let main_thread_id = self.pid_tid_pool.assign(); // TODO: check for duplicates?
 
         let state_machine = {
             let extrinsics_id_assign = &self.extrinsics_id_assign;
-            vm::ProcessStateMachine::new(
+            vm::ProcessStateMachine::with_engine(
                 module,
+                limits,
                 Thread {
                     thread_id: main_thread_id,
+                    cpu_time: Duration::new(0, 0),
                 },
                 move |interface, function, obtained_signature| {
                     if let Some((index, expected_signature)) =
@@ -282,11 +325,13 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
                         if expected_signature == obtained_signature {
                             return Ok(*index);
                         } else {
-                            // TODO: way to report the signature mismatch?
+                            return Err(vm::ImportErr::BadSignature {
+                                expected: expected_signature.clone(),
+                            });
                         }
                     }
 
-                    Err(())
+                    Err(vm::ImportErr::NotFound)
                 },
             )?
         };
@@ -302,11 +347,14 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
                     queue.push_back((main_thread_id, main_thread_user_data, None));
                     queue
                 },
+                cpu_time: Duration::new(0, 0),
                 dead: None,
             }),
             user_data: proc_user_data,
         });
 
+        setup(new_pid);
+
         {
             let mut processes = self.processes.lock();
             processes.insert(new_pid, Arc::downgrade(&process));
@@ -331,7 +379,7 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
     /// Find a thread that is ready to be run.
     ///
     /// Which thread is picked is implementation-defined and no guarantee is made.
-    pub fn run(&self) -> RunFuture<TExtr, TPud, TTud> {
+    pub fn run(&self) -> RunFuture<TExtr, TPud, TTud, TEng> {
         RunFuture(self, self.wakers.register())
     }
 
@@ -341,7 +389,7 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
     /// ID.
     pub fn processes<'a>(
         &'a self,
-    ) -> impl ExactSizeIterator<Item = ProcAccess<'a, TExtr, TPud, TTud>> + 'a {
+    ) -> impl ExactSizeIterator<Item = ProcAccess<'a, TExtr, TPud, TTud, TEng>> + 'a {
         let processes = self.processes.lock();
 
         // TODO: what if process is in death_reports?
@@ -376,7 +424,7 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
     /// existing lock, or if you hold a lock to one of its threads. However, it can return `None`
     /// for a process that has crashed or finished before said crash or termination has been
     /// reported with the [`run`](ProcessesCollection::run) method.
-    pub fn process_by_id(&self, pid: Pid) -> Option<ProcAccess<TExtr, TPud, TTud>> {
+    pub fn process_by_id(&self, pid: Pid) -> Option<ProcAccess<TExtr, TPud, TTud, TEng>> {
         let processes = self.processes.lock();
 
         // TODO: what if process is in death_reports?
@@ -403,7 +451,7 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
     pub fn interrupted_thread_by_id(
         &self,
         id: ThreadId,
-    ) -> Option<ThreadAccess<TExtr, TPud, TTud>> {
+    ) -> Option<ThreadAccess<TExtr, TPud, TTud, TEng>> {
         let mut interrupted_threads = self.interrupted_threads.lock();
 
         // TODO: what if thread has been moved in dead_threads?
@@ -423,7 +471,7 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
 
     /// If the `process` passed as parameter is the last strong reference, then cleans the state
     /// of `self` and reports the process's death to the user.
-    fn try_report_process_death(&self, process: Arc<Process<TPud, TTud>>) {
+    fn try_report_process_death(&self, process: Arc<Process<TPud, TTud, TEng>>) {
         let mut process = match Arc::try_unwrap(process) {
             Ok(p) => p,
             Err(_) => return,
@@ -448,7 +496,7 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
 }
 
 /// Prototype for a [`ProcessesCollection`] under construction.
-pub struct ProcessesCollectionBuilder<TExtr> {
+pub struct ProcessesCollectionBuilder<TExtr, TEng: Engine = DefaultEngine> {
     /// See the corresponding field in `ProcessesCollection`.
     pid_tid_pool: IdPool,
     /// See the corresponding field in `ProcessesCollection`.
@@ -456,18 +504,27 @@ pub struct ProcessesCollectionBuilder<TExtr> {
     /// See the corresponding field in `ProcessesCollection`.
     extrinsics_id_assign:
         HashMap<(Cow<'static, str>, Cow<'static, str>), (usize, Signature), FnvBuildHasher>,
+    /// See the corresponding field in `ProcessesCollection`.
+    fuel_per_run: Option<u64>,
+    /// See the corresponding field in `ProcessesCollection`.
+    monotonic_clock: Box<dyn Fn() -> Duration + Send + Sync>,
+    /// Marker for the [`Engine`] of the `ProcessesCollection`.
+    marker: PhantomData<TEng>,
 }
 
-impl<TExtr> ProcessesCollectionBuilder<TExtr> {
+impl<TExtr, TEng: Engine> ProcessesCollectionBuilder<TExtr, TEng> {
     /// Initializes a new builder using the given random seed.
     ///
     /// The seed is used in determine how [`Pid`]s are generated. The same seed will result in
     /// the same sequence of [`Pid`]s.
-    pub fn with_seed(seed: [u8; 32]) -> ProcessesCollectionBuilder<TExtr> {
+    pub fn with_seed(seed: [u8; 32]) -> ProcessesCollectionBuilder<TExtr, TEng> {
         ProcessesCollectionBuilder {
             pid_tid_pool: IdPool::with_seed(seed),
             extrinsics: Default::default(),
             extrinsics_id_assign: Default::default(),
+            fuel_per_run: None,
+            monotonic_clock: Box::new(|| Duration::new(0, 0)),
+            marker: PhantomData,
         }
     }
 
@@ -515,8 +572,30 @@ impl<TExtr> ProcessesCollectionBuilder<TExtr> {
         self
     }
 
+    /// Sets the maximum amount of fuel that a thread can consume before being preempted.
+    ///
+    /// By default, threads are never preempted and only stop running when they call an
+    /// extrinsic or finish.
+    pub fn with_fuel_per_run(mut self, fuel: u64) -> Self {
+        self.fuel_per_run = Some(fuel);
+        self
+    }
+
+    /// Sets the function that returns the current value of the monotonic clock.
+    ///
+    /// The clock is used to measure the CPU time of processes and threads, and can also be read
+    /// with [`ThreadAccess::monotonic_clock`]. By default, the clock always returns zero, meaning
+    /// that the CPU time of everything is always zero.
+    pub fn with_monotonic_clock(
+        mut self,
+        clock: impl Fn() -> Duration + Send + Sync + 'static,
+    ) -> Self {
+        self.monotonic_clock = Box::new(clock);
+        self
+    }
+
     /// Turns the builder into a [`ProcessesCollection`].
-    pub fn build<TPud, TTud>(mut self) -> ProcessesCollection<TExtr, TPud, TTud> {
+    pub fn build<TPud, TTud>(mut self) -> ProcessesCollection<TExtr, TPud, TTud, TEng> {
         // We're not going to modify these fields ever again, so let's free some memory.
         self.extrinsics.shrink_to_fit();
         self.extrinsics_id_assign.shrink_to_fit();
@@ -537,13 +616,15 @@ impl<TExtr> ProcessesCollectionBuilder<TExtr> {
             extrinsics: self.extrinsics,
             extrinsics_id_assign: self.extrinsics_id_assign,
             death_reports: SegQueue::new(),
+            fuel_per_run: self.fuel_per_run,
+            monotonic_clock: self.monotonic_clock,
         }
     }
 }
 
 /// Outcome of the [`run`](ReadyToRun::run) function.
 #[derive(Debug)]
-pub enum RunOneOutcome<'a, TExtr, TPud, TTud> {
+pub enum RunOneOutcome<'a, TExtr, TPud, TTud, TEng: Engine> {
     /// Either the main thread of a process has finished, or a fatal error was encountered.
     ///
     /// The process no longer exists.
@@ -559,8 +640,8 @@ pub enum RunOneOutcome<'a, TExtr, TPud, TTud> {
         /// These threads no longer exist.
         dead_threads: Vec<(ThreadId, TTud)>,
 
-        /// Value returned by the main thread that has finished, or error that happened.
-        outcome: Result<Option<crate::WasmValue>, wasmi::Trap>,
+        /// Why the process has stopped.
+        outcome: ProcessExitReason,
     },
 
     /// A thread in a process has finished.
@@ -569,7 +650,7 @@ pub enum RunOneOutcome<'a, TExtr, TPud, TTud> {
         thread_id: ThreadId,
 
         /// Process whose thread has finished.
-        process: ProcAccess<'a, TExtr, TPud, TTud>,
+        process: ProcAccess<'a, TExtr, TPud, TTud, TEng>,
 
         /// User data of the thread.
         user_data: TTud,
@@ -585,7 +666,7 @@ pub enum RunOneOutcome<'a, TExtr, TPud, TTud> {
     /// again, you must pass back the outcome of calling that function.
     Interrupted {
         /// Thread that has been interrupted.
-        thread: ThreadAccess<'a, TExtr, TPud, TTud>,
+        thread: ThreadAccess<'a, TExtr, TPud, TTud, TEng>,
 
         /// Identifier of the function to call. Corresponds to the value provided at
         /// initialization when resolving imports.
@@ -595,7 +676,18 @@ pub enum RunOneOutcome<'a, TExtr, TPud, TTud> {
         params: Vec<crate::WasmValue>,
     },
 
-    /// Running the thread has resulted in a decision to terminate the process. A
+    /// The thread has consumed all the fuel it was allowed to consume and has been paused. It has
+    /// been automatically put back in the queue of threads ready to run.
+    Preempted {
+        /// Pid of the process the thread belongs to.
+        pid: Pid,
+
+        /// Thread that has been preempted.
+        thread_id: ThreadId,
+    },
+
+    /// Running the thread has resulted in a decision to terminate the process, or the process
+    /// was already being terminated by another of its threads. A
     /// [`RunOneOutcome::ProcessFinished`] will soon be emitted.
     StartProcessAbort {
         /// Pid of the process that is going to finish soon.
@@ -604,13 +696,13 @@ pub enum RunOneOutcome<'a, TExtr, TPud, TTud> {
 }
 
 /// Future that drives the [`ProcessesCollection::run`] method.
-pub struct RunFuture<'a, TExtr, TPud, TTud>(
-    &'a ProcessesCollection<TExtr, TPud, TTud>,
+pub struct RunFuture<'a, TExtr, TPud, TTud, TEng: Engine>(
+    &'a ProcessesCollection<TExtr, TPud, TTud, TEng>,
     wakers::Registration<'a>,
 );
 
-impl<'a, TExtr, TPud, TTud> Future for RunFuture<'a, TExtr, TPud, TTud> {
-    type Output = RunFutureOut<'a, TExtr, TPud, TTud>;
+impl<'a, TExtr, TPud, TTud, TEng: Engine> Future for RunFuture<'a, TExtr, TPud, TTud, TEng> {
+    type Output = RunFutureOut<'a, TExtr, TPud, TTud, TEng>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         let this = &mut *self;
@@ -654,15 +746,13 @@ impl<'a, TExtr, TPud, TTud> Future for RunFuture<'a, TExtr, TPud, TTud> {
             };
 
             // "Lock" the process's state machine for examination.
-            // TODO: this is ok right now because we only have a single thread per process
             let mut proc_state = process.lock.lock();
 
             // If the process was in `this.execution_queue`, then we are guaranteed that a
-            // thread was ready.
+            // thread was ready. Other threads of the same process might be ready as well, in
+            // which case the process is still present in `execution_queue`.
             let (tid, thread_user_data, resume_value) =
                 proc_state.threads_to_resume.pop_front().unwrap();
-            // TODO: a lot of code in this module assumes one thread per process, which this debug_assert checks
-            debug_assert!(proc_state.threads_to_resume.is_empty());
 
             // If the process is marked as dying, insert the thread in the dying state and see if
             // we can finalize the destruction.
@@ -686,27 +776,27 @@ impl<'a, TExtr, TPud, TTud> Future for RunFuture<'a, TExtr, TPud, TTud> {
     }
 }
 
-impl<'a, TExtr, TPud, TTud> Unpin for RunFuture<'a, TExtr, TPud, TTud> {}
+impl<'a, TExtr, TPud, TTud, TEng: Engine> Unpin for RunFuture<'a, TExtr, TPud, TTud, TEng> {}
 
 /// Event returned by [`RunFuture`].
-pub enum RunFutureOut<'a, TExtr, TPud, TTud> {
+pub enum RunFutureOut<'a, TExtr, TPud, TTud, TEng: Engine> {
     /// Event directly generated.
-    Direct(RunOneOutcome<'a, TExtr, TPud, TTud>),
+    Direct(RunOneOutcome<'a, TExtr, TPud, TTud, TEng>),
     /// Ready to execute a bit of a thread.
-    ReadyToRun(ReadyToRun<'a, TExtr, TPud, TTud>),
+    ReadyToRun(ReadyToRun<'a, TExtr, TPud, TTud, TEng>),
 }
 
 /// Ready to resume one of the threads of a process.
 #[must_use]
-pub struct ReadyToRun<'a, TExtr, TPud, TTud> {
+pub struct ReadyToRun<'a, TExtr, TPud, TTud, TEng: Engine> {
     /// The parent object.
-    collection: &'a ProcessesCollection<TExtr, TPud, TTud>,
+    collection: &'a ProcessesCollection<TExtr, TPud, TTud, TEng>,
     /// Process to execute.
     /// Always `Some` except during destruction.
     /// Since it isn't possible to safely hold an `Arc<Process>` and `SpinlockGuard<...>`
     /// referencing that `Arc<Process>` in the same struct, the `lock` field is force-locked while
     /// the `ReadyToRun` is alive.
-    process: Option<Arc<Process<TPud, TTud>>>,
+    process: Option<Arc<Process<TPud, TTud, TEng>>>,
     /// Id of the thread that we are going to run.
     tid: ThreadId,
     /// User data of the thread. Temporarily extracted from the global state. Always `Some`,
@@ -716,14 +806,26 @@ pub struct ReadyToRun<'a, TExtr, TPud, TTud> {
     resume_value: Option<crate::WasmValue>,
 }
 
-impl<'a, TExtr, TPud, TTud> ReadyToRun<'a, TExtr, TPud, TTud> {
+impl<'a, TExtr, TPud, TTud, TEng: Engine> ReadyToRun<'a, TExtr, TPud, TTud, TEng> {
     /// Performs the actual execution.
-    pub fn run(mut self) -> RunOneOutcome<'a, TExtr, TPud, TTud> {
+    pub fn run(mut self) -> RunOneOutcome<'a, TExtr, TPud, TTud, TEng> {
         // Lock the process, this time to execute the virtual machine.
         let mut proc_state = self.process.as_ref().unwrap().lock.lock();
 
+        // Another thread of the same process might have ended the process between the moment
+        // this `ReadyToRun` was created and now.
+        if let Some(proc_dead) = &mut proc_state.dead {
+            proc_dead
+                .dead_threads
+                .push((self.tid, self.thread_user_data.take().unwrap()));
+            return RunOneOutcome::StartProcessAbort {
+                pid: self.process.as_ref().unwrap().pid,
+            };
+        }
+
         // Now run a thread until something happens.
         // This takes most of the CPU time of this function.
+        let run_start = (self.collection.monotonic_clock)();
         let run_outcome = {
             debug_assert!(!proc_state.vm.is_poisoned());
             let thread_index = (0..proc_state.vm.num_threads())
@@ -733,8 +835,12 @@ impl<'a, TExtr, TPud, TTud> ReadyToRun<'a, TExtr, TPud, TTud> {
                 .vm
                 .thread(thread_index)
                 .unwrap()
-                .run(self.resume_value)
+                .run(self.resume_value, self.collection.fuel_per_run)
         };
+        // The clock is monotonic, but we saturate in order to be robust to bad implementations.
+        let run_duration = (self.collection.monotonic_clock)()
+            .checked_sub(run_start)
+            .unwrap_or(Duration::new(0, 0));
 
         match run_outcome {
             Err(vm::RunErr::BadValueTy { .. }) => panic!(), // TODO:
@@ -748,6 +854,7 @@ impl<'a, TExtr, TPud, TTud> ReadyToRun<'a, TExtr, TPud, TTud> {
             }) => {
                 debug_assert!(proc_state.vm.is_poisoned());
                 debug_assert!(proc_state.dead.is_none());
+                proc_state.cpu_time += run_duration;
 
                 // TODO: Vec::with_capacity?
                 let mut dead_threads = vec![(
@@ -769,7 +876,7 @@ impl<'a, TExtr, TPud, TTud> ReadyToRun<'a, TExtr, TPud, TTud> {
 
                 proc_state.dead = Some(ProcessDeadState {
                     dead_threads,
-                    outcome: Ok(return_value),
+                    outcome: ProcessExitReason::from_main_return_value(return_value),
                 });
 
                 RunOneOutcome::StartProcessAbort {
@@ -784,6 +891,7 @@ impl<'a, TExtr, TPud, TTud> ReadyToRun<'a, TExtr, TPud, TTud> {
                 ..
             }) => {
                 debug_assert!(Arc::strong_count(&self.process.as_ref().unwrap()) >= 2);
+                proc_state.cpu_time += run_duration;
                 drop(proc_state);
                 RunOneOutcome::ThreadFinished {
                     thread_id: user_data.thread_id,
@@ -808,7 +916,9 @@ impl<'a, TExtr, TPud, TTud> ReadyToRun<'a, TExtr, TPud, TTud> {
                     Some(e) => e,
                     None => unreachable!(),
                 };
+                thread.user_data().cpu_time += run_duration;
                 let tid = thread.user_data().thread_id;
+                proc_state.cpu_time += run_duration;
                 drop(proc_state);
                 RunOneOutcome::Interrupted {
                     thread: ThreadAccess {
@@ -823,6 +933,31 @@ impl<'a, TExtr, TPud, TTud> ReadyToRun<'a, TExtr, TPud, TTud> {
                 }
             }
 
+            // Thread has run out of fuel. Put it back in the queue.
+            Ok(vm::ExecOutcome::Preempted { mut thread }) => {
+                thread.user_data().cpu_time += run_duration;
+                let tid = thread.user_data().thread_id;
+                drop(thread);
+                proc_state.cpu_time += run_duration;
+
+                proc_state.threads_to_resume.push_back((
+                    tid,
+                    self.thread_user_data.take().unwrap(),
+                    None,
+                ));
+                drop(proc_state);
+
+                self.collection
+                    .execution_queue
+                    .push(self.process.as_ref().unwrap().clone());
+                self.collection.wakers.notify_one();
+
+                RunOneOutcome::Preempted {
+                    pid: self.process.as_ref().unwrap().pid,
+                    thread_id: tid,
+                }
+            }
+
             // An error happened during the execution. We kill the entire process.
             Ok(vm::ExecOutcome::Errored { error, mut thread }) => {
                 // TODO: Vec::with_capacity?
@@ -834,6 +969,7 @@ impl<'a, TExtr, TPud, TTud> ReadyToRun<'a, TExtr, TPud, TTud> {
 
                 debug_assert!(proc_state.vm.is_poisoned());
                 debug_assert!(proc_state.dead.is_none());
+                proc_state.cpu_time += run_duration;
 
                 // TODO: possible deadlock?
                 let mut threads = self.collection.interrupted_threads.lock();
@@ -849,7 +985,7 @@ impl<'a, TExtr, TPud, TTud> ReadyToRun<'a, TExtr, TPud, TTud> {
 
                 proc_state.dead = Some(ProcessDeadState {
                     dead_threads,
-                    outcome: Err(error),
+                    outcome: From::from(error),
                 });
 
                 RunOneOutcome::StartProcessAbort {
@@ -860,7 +996,7 @@ impl<'a, TExtr, TPud, TTud> ReadyToRun<'a, TExtr, TPud, TTud> {
     }
 }
 
-impl<'a, TExtr, TPud, TTud> Drop for ReadyToRun<'a, TExtr, TPud, TTud> {
+impl<'a, TExtr, TPud, TTud, TEng: Engine> Drop for ReadyToRun<'a, TExtr, TPud, TTud, TEng> {
     fn drop(&mut self) {
         // In the situation where the user didn't call `run`, we push back the thread to the
         // queue.
@@ -884,15 +1020,15 @@ impl<'a, TExtr, TPud, TTud> Drop for ReadyToRun<'a, TExtr, TPud, TTud> {
 }
 
 /// Access to a process within the collection.
-pub struct ProcAccess<'a, TExtr, TPud, TTud> {
-    collection: &'a ProcessesCollection<TExtr, TPud, TTud>,
-    process: Option<Arc<Process<TPud, TTud>>>,
+pub struct ProcAccess<'a, TExtr, TPud, TTud, TEng: Engine> {
+    collection: &'a ProcessesCollection<TExtr, TPud, TTud, TEng>,
+    process: Option<Arc<Process<TPud, TTud, TEng>>>,
 
     /// Reference to the same field in [`ProcessesCollection`].
     pid_tid_pool: &'a IdPool,
 }
 
-impl<'a, TExtr, TPud, TTud> ProcAccess<'a, TExtr, TPud, TTud> {
+impl<'a, TExtr, TPud, TTud, TEng: Engine> ProcAccess<'a, TExtr, TPud, TTud, TEng> {
     /// Returns the [`Pid`] of the process. Allows later retrieval by calling
     /// [`process_by_id`](ProcessesCollection::process_by_id).
     pub fn pid(&self) -> Pid {
@@ -904,9 +1040,17 @@ impl<'a, TExtr, TPud, TTud> ProcAccess<'a, TExtr, TPud, TTud> {
         &self.process.as_ref().unwrap().user_data
     }
 
+    /// Returns the total time spent executing the threads of this process, including the threads
+    /// that have finished.
+    pub fn cpu_time(&self) -> Duration {
+        self.process.as_ref().unwrap().lock.lock().cpu_time
+    }
+
     /// Adds a new thread to the process, starting the function with the given index and passing
     /// the given parameters.
     ///
+    /// See [`vm::ProcessStateMachine::start_thread_by_id`] for the meaning of `stack_pointer`.
+    ///
     /// > **Note**: The "function ID" is the index of the function in the WASM module. WASM
     /// >           doesn't have function pointers. Instead, all the functions are part of a single
     /// >           global array of functions.
@@ -914,16 +1058,25 @@ impl<'a, TExtr, TPud, TTud> ProcAccess<'a, TExtr, TPud, TTud> {
         &self,
         fn_index: u32,
         params: Vec<crate::WasmValue>,
+        stack_pointer: Option<i32>,
         user_data: TTud,
     ) -> Result<ThreadId, vm::StartErr> {
         let thread_id = self.pid_tid_pool.assign(); // TODO: check for duplicates
-        let thread_data = Thread { thread_id };
+        let thread_data = Thread {
+            thread_id,
+            cpu_time: Duration::new(0, 0),
+        };
 
         let mut process_state = self.process.as_ref().unwrap().lock.lock();
 
+        // The virtual machine might be in a poisoned state if the process is dying.
+        if process_state.dead.is_some() {
+            return Err(vm::StartErr::Poisoned);
+        }
+
         process_state
             .vm
-            .start_thread_by_id(fn_index, params, thread_data)?;
+            .start_thread_by_id(fn_index, params, stack_pointer, thread_data)?;
 
         process_state
             .threads_to_resume
@@ -937,12 +1090,13 @@ impl<'a, TExtr, TPud, TTud> ProcAccess<'a, TExtr, TPud, TTud> {
         Ok(thread_id)
     }
 
-    /// Marks the process as aborting.
+    /// Marks the process as aborting, for the given reason.
     ///
     /// The termination will happen after all locks to this process have been released.
     ///
-    /// Calling [`abort`](ProcAccess::abort) a second time or more has no effect.
-    pub fn abort(&self) {
+    /// Calling [`abort`](ProcAccess::abort) a second time or more has no effect, and the reason
+    /// passed the first time is kept.
+    pub fn abort(&self, reason: ProcessExitReason) {
         let mut process_state = self.process.as_ref().unwrap().lock.lock();
 
         if process_state.dead.is_some() {
@@ -962,24 +1116,14 @@ impl<'a, TExtr, TPud, TTud> ProcAccess<'a, TExtr, TPud, TTud> {
             dead_threads.push((tid, user_data));
         }
 
-        // TODO: replace with better error once we no longer expose wasmi::Trap in the API
-        #[derive(Debug)]
-        struct HostErr;
-        impl wasmi::HostError for HostErr {}
-        impl fmt::Display for HostErr {
-            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                write!(f, "Aborted")
-            }
-        }
-
         process_state.dead = Some(ProcessDeadState {
             dead_threads,
-            outcome: Err(From::from(wasmi::TrapKind::Host(Box::new(HostErr)))),
+            outcome: reason,
         });
     }
 }
 
-impl<'a, TExtr, TPud, TTud> fmt::Debug for ProcAccess<'a, TExtr, TPud, TTud>
+impl<'a, TExtr, TPud, TTud, TEng: Engine> fmt::Debug for ProcAccess<'a, TExtr, TPud, TTud, TEng>
 where
     TPud: fmt::Debug,
 {
@@ -991,7 +1135,7 @@ where
     }
 }
 
-impl<'a, TExtr, TPud, TTud> Clone for ProcAccess<'a, TExtr, TPud, TTud> {
+impl<'a, TExtr, TPud, TTud, TEng: Engine> Clone for ProcAccess<'a, TExtr, TPud, TTud, TEng> {
     fn clone(&self) -> Self {
         ProcAccess {
             collection: self.collection,
@@ -1001,7 +1145,7 @@ impl<'a, TExtr, TPud, TTud> Clone for ProcAccess<'a, TExtr, TPud, TTud> {
     }
 }
 
-impl<'a, TExtr, TPud, TTud> Drop for ProcAccess<'a, TExtr, TPud, TTud> {
+impl<'a, TExtr, TPud, TTud, TEng: Engine> Drop for ProcAccess<'a, TExtr, TPud, TTud, TEng> {
     fn drop(&mut self) {
         self.collection
             .try_report_process_death(self.process.take().unwrap());
@@ -1009,9 +1153,9 @@ impl<'a, TExtr, TPud, TTud> Drop for ProcAccess<'a, TExtr, TPud, TTud> {
 }
 
 /// Access to a thread within the collection.
-pub struct ThreadAccess<'a, TExtr, TPud, TTud> {
-    collection: &'a ProcessesCollection<TExtr, TPud, TTud>,
-    process: Option<Arc<Process<TPud, TTud>>>,
+pub struct ThreadAccess<'a, TExtr, TPud, TTud, TEng: Engine> {
+    collection: &'a ProcessesCollection<TExtr, TPud, TTud, TEng>,
+    process: Option<Arc<Process<TPud, TTud, TEng>>>,
 
     /// Identifier of the thread. Must always match one of the user data in the virtual machine.
     tid: ThreadId,
@@ -1024,7 +1168,7 @@ pub struct ThreadAccess<'a, TExtr, TPud, TTud> {
     user_data: Option<TTud>,
 }
 
-impl<'a, TExtr, TPud, TTud> ThreadAccess<'a, TExtr, TPud, TTud> {
+impl<'a, TExtr, TPud, TTud, TEng: Engine> ThreadAccess<'a, TExtr, TPud, TTud, TEng> {
     /// Returns the id of the thread. Allows later retrieval by calling
     /// [`thread_by_id`](ProcessesCollection::interrupted_thread_by_id).
     ///
@@ -1040,7 +1184,7 @@ impl<'a, TExtr, TPud, TTud> ThreadAccess<'a, TExtr, TPud, TTud> {
     }
 
     /// Returns the process this thread belongs to.
-    pub fn process(&self) -> ProcAccess<'a, TExtr, TPud, TTud> {
+    pub fn process(&self) -> ProcAccess<'a, TExtr, TPud, TTud, TEng> {
         ProcAccess {
             collection: self.collection,
             process: self.process.clone(),
@@ -1048,6 +1192,28 @@ impl<'a, TExtr, TPud, TTud> ThreadAccess<'a, TExtr, TPud, TTud> {
         }
     }
 
+    /// Returns the total time spent executing this thread.
+    pub fn cpu_time(&self) -> Duration {
+        let mut lock = self.process.as_ref().unwrap().lock.lock();
+
+        // The virtual machine is poisoned if the process is dying. We don't have access to the
+        // thread anymore in that situation.
+        if lock.dead.is_some() {
+            return Duration::new(0, 0);
+        }
+
+        let thread_index = (0..lock.vm.num_threads())
+            .find(|n| lock.vm.thread(*n).unwrap().user_data().thread_id == self.tid)
+            .unwrap();
+        lock.vm.thread(thread_index).unwrap().user_data().cpu_time
+    }
+
+    /// Returns the current value of the clock passed to
+    /// [`ProcessesCollectionBuilder::with_monotonic_clock`].
+    pub fn monotonic_clock(&self) -> Duration {
+        (self.collection.monotonic_clock)()
+    }
+
     /// Write the data at the given memory location.
     ///
     /// Returns an error if the range is invalid or out of range.
@@ -1115,7 +1281,6 @@ impl<'a, TExtr, TPud, TTud> ThreadAccess<'a, TExtr, TPud, TTud> {
             let mut process_state = process.lock.lock();
             let process_state = &mut *process_state;
             if let Some(death_state) = &mut process_state.dead {
-                debug_assert!(process_state.vm.is_poisoned());
                 death_state.dead_threads.push((self.tid, user_data));
                 false
             } else {
@@ -1135,7 +1300,7 @@ impl<'a, TExtr, TPud, TTud> ThreadAccess<'a, TExtr, TPud, TTud> {
     }
 }
 
-impl<'a, TExtr, TPud, TTud> fmt::Debug for ThreadAccess<'a, TExtr, TPud, TTud> {
+impl<'a, TExtr, TPud, TTud, TEng: Engine> fmt::Debug for ThreadAccess<'a, TExtr, TPud, TTud, TEng> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("ThreadAccess")
             .field("pid", &self.pid())
@@ -1143,7 +1308,7 @@ impl<'a, TExtr, TPud, TTud> fmt::Debug for ThreadAccess<'a, TExtr, TPud, TTud> {
     }
 }
 
-impl<'a, TExtr, TPud, TTud> Drop for ThreadAccess<'a, TExtr, TPud, TTud> {
+impl<'a, TExtr, TPud, TTud, TEng: Engine> Drop for ThreadAccess<'a, TExtr, TPud, TTud, TEng> {
     fn drop(&mut self) {
         let process = match self.process.take() {
             Some(p) => p,
@@ -1157,7 +1322,6 @@ impl<'a, TExtr, TPud, TTud> Drop for ThreadAccess<'a, TExtr, TPud, TTud> {
             let process_state = &mut *process_state_lock;
 
             if let Some(death_state) = &mut process_state.dead {
-                debug_assert!(process_state.vm.is_poisoned());
                 death_state.dead_threads.push((self.tid, user_data));
             } else {
                 let mut interrupted_threads = self.collection.interrupted_threads.lock();
@@ -14,7 +14,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use super::{ProcessesCollectionBuilder, RunFutureOut, RunOneOutcome};
-use crate::sig;
+use crate::{scheduler::ProcessExitReason, sig};
 
 use futures::prelude::*;
 use hashbrown::HashSet;
@@ -42,7 +42,9 @@ fn basic() {
     "#
     );
     let processes = ProcessesCollectionBuilder::<()>::with_seed([0; 32]).build();
-    processes.execute(&module, (), ()).unwrap();
+    processes
+        .execute(&module, Default::default(), (), (), |_| {})
+        .unwrap();
     loop {
         let outcome = match futures::executor::block_on(processes.run()) {
             RunFutureOut::Direct(v) => v,
@@ -51,7 +53,7 @@ fn basic() {
         match outcome {
             RunOneOutcome::StartProcessAbort { .. } => {}
             RunOneOutcome::ProcessFinished { outcome, .. } => {
-                assert!(matches!(outcome.unwrap(), Some(crate::WasmValue::I32(5))));
+                assert_eq!(outcome, ProcessExitReason::Exited { code: 5 });
                 break;
             }
             _ => panic!(),
@@ -70,19 +72,100 @@ fn aborting_works() {
     "#
     );
     let processes = ProcessesCollectionBuilder::<()>::with_seed([0; 32]).build();
-    processes.execute(&module, (), ()).unwrap().0.abort();
+    processes
+        .execute(&module, Default::default(), (), (), |_| {})
+        .unwrap()
+        .0
+        .abort(ProcessExitReason::Killed);
     let outcome = match futures::executor::block_on(processes.run()) {
         RunFutureOut::Direct(v) => v,
         RunFutureOut::ReadyToRun(rtr) => rtr.run(),
     };
     match outcome {
         RunOneOutcome::ProcessFinished {
-            outcome: Err(_), ..
+            outcome: ProcessExitReason::Killed,
+            ..
         } => {}
         _ => panic!(),
     };
 }
 
+#[test]
+fn infinite_loop_preempted() {
+    let looping = from_wat!(
+        local,
+        r#"(module
+        (func $_start
+            (loop $loop
+                (br $loop)))
+        (export "_start" (func $_start)))
+    "#
+    );
+    let finishing = from_wat!(
+        local,
+        r#"(module
+        (func $_start (result i32)
+            i32.const 5)
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let processes = ProcessesCollectionBuilder::<()>::with_seed([0; 32])
+        .with_fuel_per_run(10_000)
+        .build();
+    let looping_pid = processes
+        .execute(&looping, Default::default(), (), (), |_| {})
+        .unwrap()
+        .0
+        .pid();
+    let finishing_pid = processes
+        .execute(&finishing, Default::default(), (), (), |_| {})
+        .unwrap()
+        .0
+        .pid();
+
+    // The looping process is scheduled first, but must not prevent the other process from
+    // finishing.
+    loop {
+        let outcome = match futures::executor::block_on(processes.run()) {
+            RunFutureOut::Direct(v) => v,
+            RunFutureOut::ReadyToRun(rtr) => rtr.run(),
+        };
+        match outcome {
+            RunOneOutcome::Preempted { pid, .. } => assert_eq!(pid, looping_pid),
+            RunOneOutcome::StartProcessAbort { .. } => {}
+            RunOneOutcome::ProcessFinished { pid, outcome, .. } => {
+                assert_eq!(pid, finishing_pid);
+                assert_eq!(outcome, ProcessExitReason::Exited { code: 5 });
+                break;
+            }
+            _ => panic!(),
+        };
+    }
+
+    processes
+        .process_by_id(looping_pid)
+        .unwrap()
+        .abort(ProcessExitReason::Killed);
+    loop {
+        let outcome = match futures::executor::block_on(processes.run()) {
+            RunFutureOut::Direct(v) => v,
+            RunFutureOut::ReadyToRun(rtr) => rtr.run(),
+        };
+        match outcome {
+            RunOneOutcome::ProcessFinished {
+                pid,
+                outcome: ProcessExitReason::Killed,
+                ..
+            } => {
+                assert_eq!(pid, looping_pid);
+                break;
+            }
+            _ => panic!(),
+        };
+    }
+}
+
 #[test]
 fn many_processes() {
     let module = from_wat!(
@@ -104,7 +187,11 @@ fn many_processes() {
     );
     let mut spawned_pids = HashSet::<_, fnv::FnvBuildHasher>::default();
     for _ in 0..num_processes {
-        let pid = processes.execute(&module, (), ()).unwrap().0.pid();
+        let pid = processes
+            .execute(&module, Default::default(), (), (), |_| {})
+            .unwrap()
+            .0
+            .pid();
         assert!(spawned_pids.insert(pid));
     }
 
@@ -129,10 +216,7 @@ fn many_processes() {
                 };
                 match outcome {
                     RunOneOutcome::ProcessFinished { pid, outcome, .. } => {
-                        assert!(matches!(
-                            outcome.unwrap(),
-                            Some(crate::WasmValue::I32(1234))
-                        ));
+                        assert_eq!(outcome, ProcessExitReason::Exited { code: 1234 });
                         local_finished.push(pid);
                     }
                     RunOneOutcome::Interrupted {
@@ -17,9 +17,20 @@
 
 use crate::extrinsics::NoExtrinsics;
 
+mod bad_extrinsic_call;
 mod basic_module;
+mod emit_messages;
 mod emit_not_available;
+mod launch_config;
+mod log_extrinsics;
+mod notifications_queue_full;
+mod proc_exit;
+mod threads;
+mod too_many_pending_messages;
 mod trapping_module;
+mod wasi_clocks;
+mod wasi_filesystem;
+mod wasi_sockets;
 
 #[test]
 fn send_sync() {
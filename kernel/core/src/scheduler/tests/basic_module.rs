@@ -14,7 +14,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use crate::extrinsics::NoExtrinsics;
-use crate::scheduler::{CoreBuilder, CoreRunOutcome};
+use crate::scheduler::{CoreBuilder, CoreRunOutcome, ProcessExitReason};
 use futures::prelude::*;
 
 #[test]
@@ -29,7 +29,7 @@ fn basic_module() {
     );
 
     let core = CoreBuilder::<NoExtrinsics>::with_seed([0; 64]).build();
-    let expected_pid = core.execute(&module).unwrap().0.pid();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
 
     let event = loop {
         if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
@@ -38,13 +38,9 @@ fn basic_module() {
     };
 
     match event {
-        CoreRunOutcome::ProgramFinished {
-            pid,
-            outcome: Ok(ret_val),
-            ..
-        } => {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
             assert_eq!(pid, expected_pid);
-            assert!(matches!(ret_val, Some(crate::WasmValue::I32(5))));
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 5 });
         }
         _ => panic!(),
     }
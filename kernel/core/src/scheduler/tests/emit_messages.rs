@@ -0,0 +1,399 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::extrinsics::{
+    wasi::WasiExtrinsics, Extrinsics, ExtrinsicsAction, ExtrinsicsMemoryAccess, LaunchConfig,
+    MessageToEmit, SupportedExtrinsic,
+};
+use crate::scheduler::{CoreBuilder, CoreRunOutcome, ProcessExitReason};
+use crate::{
+    sig, EncodedMessage, EncodedMessageRef, InterfaceHash, MessageId, ThreadId, WasmValue,
+};
+use alloc::vec::Vec;
+use core::iter;
+use futures::prelude::*;
+use spinning_top::Spinlock;
+
+/// Interface the messages of [`QueryExtrinsics`] are emitted on.
+const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([0xfe; 32]);
+
+/// Extrinsics with a single `test::query` function. Emits two messages at once, whose bodies
+/// are respectively `[1]` and `[2]`, and returns `10 * a + b`, where `a` and `b` are the first
+/// bytes of the responses to these messages, or `9` for an error answer.
+struct QueryExtrinsics;
+
+impl Extrinsics for QueryExtrinsics {
+    type ExtrinsicId = ();
+    type Context = ();
+    type Iterator = iter::Once<SupportedExtrinsic<()>>;
+
+    fn supported_extrinsics() -> Self::Iterator {
+        iter::once(SupportedExtrinsic {
+            id: (),
+            wasm_interface: "test".into(),
+            function_name: "query".into(),
+            signature: sig!(() -> I32),
+        })
+    }
+
+    fn with_launch_config(_: LaunchConfig) -> Self {
+        QueryExtrinsics
+    }
+
+    fn new_context(
+        &self,
+        _: ThreadId,
+        _: &(),
+        _: impl ExactSizeIterator<Item = WasmValue>,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ((), ExtrinsicsAction) {
+        let messages = (1..=2)
+            .map(|n| MessageToEmit {
+                interface: INTERFACE,
+                message: EncodedMessage(vec![n]),
+                response_expected: true,
+            })
+            .collect();
+        ((), ExtrinsicsAction::EmitMessages(messages))
+    }
+
+    fn inject_message_response(
+        &self,
+        _: &mut (),
+        _: Option<Result<EncodedMessageRef, ()>>,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        unreachable!()
+    }
+
+    fn inject_message_responses(
+        &self,
+        _: &mut (),
+        responses: Vec<Option<Result<EncodedMessage, ()>>>,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        // An error answer is reported as `9`.
+        let value = responses
+            .iter()
+            .map(|response| match response.as_ref().unwrap() {
+                Ok(response) => i32::from(response.0[0]),
+                Err(()) => 9,
+            })
+            .fold(0, |acc, n| acc * 10 + n);
+        ExtrinsicsAction::Resume(Some(WasmValue::I32(value)))
+    }
+
+    fn inject_first_response(
+        &self,
+        _: &mut (),
+        _: Vec<Option<MessageId>>,
+        _: MessageId,
+        _: Result<EncodedMessage, ()>,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        unreachable!()
+    }
+}
+
+#[test]
+fn responses_injected_in_order() {
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "test" "query" (func $query (result i32)))
+        (func $_start (result i32)
+            call $query)
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<QueryExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    // Both messages must be emitted without any of them being answered.
+    let mut emitted = Vec::new();
+    while emitted.len() < 2 {
+        match core.run().now_or_never().unwrap().or_run() {
+            Some(CoreRunOutcome::InterfaceMessage {
+                pid,
+                message_id,
+                needs_answer,
+                interface,
+                ..
+            }) => {
+                assert_eq!(pid, expected_pid);
+                assert_eq!(interface, INTERFACE);
+                assert!(needs_answer);
+                let (_, message) = core.accept_interface_message(message_id).unwrap();
+                emitted.push((message_id, message));
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    }
+
+    assert_eq!(emitted[0].1, EncodedMessage(vec![1]));
+    assert_eq!(emitted[1].1, EncodedMessage(vec![2]));
+
+    // Answer in the reverse order of emission.
+    core.answer_message(emitted[1].0, Ok(EncodedMessage(vec![4])));
+    core.answer_message(emitted[0].0, Ok(EncodedMessage(vec![3])));
+
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 34 });
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn error_answer_injected() {
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "test" "query" (func $query (result i32)))
+        (func $_start (result i32)
+            call $query)
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<QueryExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    let outcome = loop {
+        match core.run().now_or_never().unwrap().or_run() {
+            Some(CoreRunOutcome::InterfaceMessage { message_id, .. }) => {
+                let (_, message) = core.accept_interface_message(message_id).unwrap();
+                if message == EncodedMessage(vec![1]) {
+                    core.answer_message(message_id, Ok(EncodedMessage(vec![3])));
+                } else {
+                    core.answer_message(message_id, Err(()));
+                }
+            }
+            Some(CoreRunOutcome::ProgramFinished { pid, outcome }) => {
+                assert_eq!(pid, expected_pid);
+                break outcome;
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    };
+
+    assert_eq!(outcome, ProcessExitReason::Exited { code: 39 });
+}
+
+/// Extrinsics with a single `test::first` function. The first call emits two messages at once,
+/// whose bodies are respectively `[1]` and `[2]`, and returns the first byte of the first
+/// response to arrive. The next calls wait for the responses to the messages that haven't been
+/// answered yet, and return the first byte of the first response to arrive.
+#[derive(Default)]
+struct FirstExtrinsics {
+    /// Messages whose response hasn't been injected yet.
+    pending: Spinlock<Vec<MessageId>>,
+    /// True if the messages have already been emitted.
+    emitted: Spinlock<bool>,
+}
+
+impl Extrinsics for FirstExtrinsics {
+    type ExtrinsicId = ();
+    type Context = ();
+    type Iterator = iter::Once<SupportedExtrinsic<()>>;
+
+    fn supported_extrinsics() -> Self::Iterator {
+        iter::once(SupportedExtrinsic {
+            id: (),
+            wasm_interface: "test".into(),
+            function_name: "first".into(),
+            signature: sig!(() -> I32),
+        })
+    }
+
+    fn with_launch_config(_: LaunchConfig) -> Self {
+        Default::default()
+    }
+
+    fn new_context(
+        &self,
+        _: ThreadId,
+        _: &(),
+        _: impl ExactSizeIterator<Item = WasmValue>,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ((), ExtrinsicsAction) {
+        let messages = if !core::mem::replace(&mut *self.emitted.lock(), true) {
+            (1..=2)
+                .map(|n| MessageToEmit {
+                    interface: INTERFACE,
+                    message: EncodedMessage(vec![n]),
+                    response_expected: true,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let wait_also = self.pending.lock().clone();
+        let action = ExtrinsicsAction::EmitMessagesWaitFirst {
+            messages,
+            wait_also,
+        };
+        ((), action)
+    }
+
+    fn inject_message_response(
+        &self,
+        _: &mut (),
+        _: Option<Result<EncodedMessageRef, ()>>,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        unreachable!()
+    }
+
+    fn inject_message_responses(
+        &self,
+        _: &mut (),
+        _: Vec<Option<Result<EncodedMessage, ()>>>,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        unreachable!()
+    }
+
+    fn inject_first_response(
+        &self,
+        _: &mut (),
+        emitted: Vec<Option<MessageId>>,
+        message_id: MessageId,
+        response: Result<EncodedMessage, ()>,
+        _: &mut impl ExtrinsicsMemoryAccess,
+    ) -> ExtrinsicsAction {
+        let mut pending = self.pending.lock();
+        pending.extend(emitted.into_iter().map(Option::unwrap));
+        pending.retain(|id| *id != message_id);
+        let value = i32::from(response.unwrap().0[0]);
+        ExtrinsicsAction::Resume(Some(WasmValue::I32(value)))
+    }
+}
+
+#[test]
+fn first_response_injected() {
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "test" "first" (func $first (result i32)))
+        (func $_start (result i32)
+            (i32.add
+                (i32.mul (call $first) (i32.const 10))
+                (call $first)))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<FirstExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    let mut emitted = Vec::new();
+    while emitted.len() < 2 {
+        match core.run().now_or_never().unwrap().or_run() {
+            Some(CoreRunOutcome::InterfaceMessage { message_id, .. }) => {
+                let (_, message) = core.accept_interface_message(message_id).unwrap();
+                emitted.push((message_id, message));
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    }
+
+    assert_eq!(emitted[0].1, EncodedMessage(vec![1]));
+    assert_eq!(emitted[1].1, EncodedMessage(vec![2]));
+
+    // Answering the second message is enough for the first call to return. The response to the
+    // first message is then injected during the second call.
+    core.answer_message(emitted[1].0, Ok(EncodedMessage(vec![5])));
+    while let Some(out) = core.run().now_or_never() {
+        assert!(out.or_run().is_none());
+    }
+    core.answer_message(emitted[0].0, Ok(EncodedMessage(vec![7])));
+
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 57 });
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn wasi_log_lines_emitted_at_once() {
+    // Writes two lines to the standard output with a single call to `fd_write`, then exits.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\10\00\00\00\08\00\00\00")
+        (data (i32.const 16) "foo\nbar\n")
+        (func $_start
+            (call $proc_exit (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+            unreachable)
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    let mut lines = Vec::new();
+    let outcome = loop {
+        match core.run().now_or_never().unwrap().or_run() {
+            Some(CoreRunOutcome::InterfaceMessage {
+                pid,
+                message_id,
+                interface,
+                ..
+            }) => {
+                assert_eq!(pid, expected_pid);
+                assert_eq!(interface, redshirt_log_interface::ffi::INTERFACE);
+                let (_, message) = core.accept_interface_message(message_id).unwrap();
+                assert_eq!(message.0[0], u8::from(redshirt_log_interface::Level::Info));
+                lines.push(String::from_utf8(message.0[1..].to_vec()).unwrap());
+            }
+            Some(CoreRunOutcome::ProgramFinished { pid, outcome }) => {
+                assert_eq!(pid, expected_pid);
+                break outcome;
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    };
+
+    assert_eq!(outcome, ProcessExitReason::Exited { code: 0 });
+    assert_eq!(lines, ["foo", "bar"]);
+}
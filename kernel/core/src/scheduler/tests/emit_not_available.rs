@@ -97,7 +97,7 @@ fn emit_not_available() {
     );
 
     let core = CoreBuilder::<NoExtrinsics>::with_seed([0; 64]).build();
-    core.execute(&module).unwrap();
+    core.execute(&module, Default::default()).unwrap();
 
     match core.run().now_or_never().unwrap().or_run() {
         Some(CoreRunOutcome::InterfaceMessage { interface, .. }) => {
@@ -0,0 +1,167 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::extrinsics::{wasi::WasiExtrinsics, LaunchConfig};
+use crate::scheduler::{CoreBuilder, CoreRunOutcome, ProcessExitReason};
+use futures::prelude::*;
+use redshirt_syscalls::{Decode as _, Encode as _};
+
+#[test]
+fn args_passed_to_process() {
+    // Returns `argc * 1000 + argv_buf_size`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "args_sizes_get" (func $args_sizes_get (param i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (func $_start (result i32)
+            (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+            (i32.add
+                (i32.mul (i32.load (i32.const 0)) (i32.const 1000))
+                (i32.load (i32.const 4))))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let launch_config = LaunchConfig {
+        args: vec![b"a".to_vec(), b"bc".to_vec()],
+        ..Default::default()
+    };
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core
+        .execute_with_launch_config(&module, Default::default(), launch_config)
+        .unwrap()
+        .0
+        .pid();
+
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 2005 });
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn stdin_empty_by_default() {
+    // Reads from stdin and returns `errno * 1000 + nread`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\10\00\00\00\10\00\00\00")
+        (func $_start (result i32)
+            (i32.add
+                (i32.mul
+                    (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8))
+                    (i32.const 1000))
+                (i32.load (i32.const 8))))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    // No message is emitted on the `console` interface.
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 0 });
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn console_stdin() {
+    // Reads from stdin and returns `errno * 1000 + nread * 100 + first_byte - 'a'`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\10\00\00\00\10\00\00\00")
+        (func $_start (result i32)
+            (i32.add
+                (i32.add
+                    (i32.mul
+                        (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8))
+                        (i32.const 1000))
+                    (i32.mul (i32.load (i32.const 8)) (i32.const 100)))
+                (i32.sub (i32.load8_u (i32.const 16)) (i32.const 97))))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let launch_config = LaunchConfig {
+        console_stdin: true,
+        ..Default::default()
+    };
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core
+        .execute_with_launch_config(&module, Default::default(), launch_config)
+        .unwrap()
+        .0
+        .pid();
+
+    let outcome = loop {
+        match core.run().now_or_never().unwrap().or_run() {
+            Some(CoreRunOutcome::InterfaceMessage {
+                pid,
+                message_id,
+                interface,
+                ..
+            }) => {
+                assert_eq!(pid, expected_pid);
+                assert_eq!(interface, redshirt_console_interface::ffi::INTERFACE);
+                let (_, message) = core.accept_interface_message(message_id).unwrap();
+                match redshirt_console_interface::ffi::ConsoleMessage::decode(message).unwrap() {
+                    redshirt_console_interface::ffi::ConsoleMessage::Read { max_len } => {
+                        assert!(max_len >= 3)
+                    }
+                }
+                let response = redshirt_console_interface::ffi::ReadResponse {
+                    data: b"cd\n".to_vec(),
+                };
+                core.answer_message(message_id, Ok(response.encode()));
+            }
+            Some(CoreRunOutcome::ProgramFinished { pid, outcome }) => {
+                assert_eq!(pid, expected_pid);
+                break outcome;
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    };
+
+    assert_eq!(outcome, ProcessExitReason::Exited { code: 302 });
+}
@@ -0,0 +1,170 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::extrinsics::{log_calls::LogExtrinsics, wasi::WasiExtrinsics, NoExtrinsics};
+use crate::scheduler::{CoreBuilder, CoreRunOutcome, ProcessExitReason};
+use crate::EncodedMessage;
+use alloc::{format, string::String, vec, vec::Vec};
+use futures::prelude::*;
+
+#[test]
+fn traces_calls_when_enabled() {
+    // Creates the `foo` directory in the pre-opened root directory, then exits.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "path_create_directory" (func $path_create_directory (param i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "foo")
+        (func $_start
+            (drop (call $path_create_directory (i32.const 3) (i32.const 0) (i32.const 3)))
+            (call $proc_exit (i32.const 0))
+            unreachable)
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<LogExtrinsics<WasiExtrinsics>>::with_seed([0; 64]).build();
+    let expected_pid = {
+        let process = core.execute(&module, Default::default()).unwrap().0;
+        process.extrinsics().set_enabled(true);
+        process.pid()
+    };
+
+    let mut records = Vec::new();
+    let outcome = loop {
+        match core.run().now_or_never().unwrap().or_run() {
+            Some(CoreRunOutcome::InterfaceMessage {
+                pid,
+                message_id,
+                interface,
+                ..
+            }) => {
+                assert_eq!(pid, expected_pid);
+                assert_eq!(interface, redshirt_log_interface::ffi::INTERFACE);
+                let (_, message) = core.accept_interface_message(message_id).unwrap();
+                records.push(String::from_utf8(message.0[1..].to_vec()).unwrap());
+            }
+            Some(CoreRunOutcome::ProgramFinished { pid, outcome }) => {
+                assert_eq!(pid, expected_pid);
+                break outcome;
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    };
+
+    assert_eq!(outcome, ProcessExitReason::Exited { code: 0 });
+    assert_eq!(
+        records,
+        [
+            "call=wasi_snapshot_preview1::path_create_directory args=(fd=3, path=\"foo\", \
+            path_len=3) result=0 duration=0ns",
+            "call=wasi_snapshot_preview1::proc_exit args=(rval=0) result=<exit 0> duration=0ns",
+        ]
+    );
+}
+
+#[test]
+fn traces_scheduler_calls() {
+    // Emits a `Read` message on the `console` interface, waits for its answer, then cancels an
+    // unknown message.
+    let interface = <[u8; 32]>::from(redshirt_console_interface::ffi::INTERFACE)
+        .iter()
+        .map(|byte| format!("\\{:02x}", byte))
+        .collect::<String>();
+    let module = crate::Module::from_bytes(
+        wat::parse_str(format!(
+            r#"(module
+        (import "redshirt" "cancel_message" (func $cancel_message (param i32)))
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i64 i32) (result i32)))
+        (import "redshirt" "next_notification" (func $next_notification (param i32 i32 i32 i32 i64) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "{}")
+        (data (i32.const 32) "\40\00\00\00\05\00\00\00")
+        (data (i32.const 64) "\00\10\00\00\00")
+        (data (i32.const 104) "\2a\00\00\00\00\00\00\00")
+        (func $_start (result i32)
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i64.const 1) (i32.const 96)))
+            (drop (call $next_notification (i32.const 96) (i32.const 1) (i32.const 128) (i32.const 64) (i64.const 1)))
+            (call $cancel_message (i32.const 104))
+            (i32.const 0))
+        (export "_start" (func $_start)))"#,
+            interface
+        ))
+        .unwrap(),
+    )
+    .unwrap();
+
+    let core = CoreBuilder::<LogExtrinsics<NoExtrinsics>>::with_seed([0; 64]).build();
+    let expected_pid = {
+        let process = core.execute(&module, Default::default()).unwrap().0;
+        process.extrinsics().set_enabled(true);
+        process.pid()
+    };
+
+    let mut records = Vec::new();
+    let mut read_message_id = None;
+    loop {
+        match core.run().now_or_never().unwrap().or_run() {
+            Some(CoreRunOutcome::InterfaceMessage {
+                pid,
+                message_id,
+                interface,
+                ..
+            }) => {
+                assert_eq!(pid, expected_pid);
+                let (_, message) = core.accept_interface_message(message_id).unwrap();
+                if interface == redshirt_console_interface::ffi::INTERFACE {
+                    read_message_id = Some(u64::from(message_id));
+                    core.answer_message(message_id, Ok(EncodedMessage(vec![1, 2, 3])));
+                } else {
+                    assert_eq!(interface, redshirt_log_interface::ffi::INTERFACE);
+                    records.push(String::from_utf8(message.0[1..].to_vec()).unwrap());
+                }
+            }
+            Some(CoreRunOutcome::ProgramFinished { pid, outcome }) => {
+                assert_eq!(pid, expected_pid);
+                assert_eq!(outcome, ProcessExitReason::Exited { code: 0 });
+                break;
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    }
+
+    let read_message_id = read_message_id.unwrap();
+    assert_eq!(
+        records,
+        [
+            format!(
+                "call=redshirt::emit_message args=(interface={:?}, message=Read {{ max_len: 16 }}, \
+                message_len=1, flags=0x1, message_id={}) result=0 duration=0ns",
+                redshirt_console_interface::ffi::INTERFACE,
+                read_message_id
+            ),
+            format!(
+                "call=redshirt::next_notification args=(to_poll=[{0}], to_poll_len=1, \
+                out=Answer {{ message_id: {0}, body: \"\\x01\\x02\\x03\" }}, out_len=64, \
+                flags=0x1) result=17 duration=0ns",
+                read_message_id
+            ),
+            String::from(
+                "call=redshirt::cancel_message args=(message_id=42) result=() duration=0ns"
+            ),
+        ]
+    );
+}
@@ -0,0 +1,236 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::extrinsics::NoExtrinsics;
+use crate::scheduler::{Core, CoreBuilder, CoreRunOutcome, ProcessExitReason};
+use crate::{EncodedMessage, MessageId, Pid};
+use futures::prelude::*;
+
+/// Runs the core until it generates an event, or returns `None` if there is nothing to run.
+fn next_event(core: &Core<NoExtrinsics>) -> Option<CoreRunOutcome> {
+    loop {
+        if let Some(ev) = core.run().now_or_never()?.or_run() {
+            return Some(ev);
+        }
+    }
+}
+
+/// Starts a process that emits two messages `a` and `b` that need an answer, then waits for the
+/// answer to `b`, then for the answer to `a`.
+///
+/// Returns the pid of the process and the identifiers of `a` and `b`, after `a` has been
+/// answered. The process has therefore one notification in its queue, and doesn't pull it until
+/// `b` is answered.
+fn spawn_recipient(core: &Core<NoExtrinsics>) -> (Pid, MessageId, MessageId) {
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i64 i32) (result i32)))
+        (import "redshirt" "next_notification" (func $next_notification (param i32 i32 i32 i32 i64) (result i32)))
+        (memory $memory 1)
+        (data (i32.const 32) "\40\00\00\00\01\00\00\00")
+        (func $_start
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i64.const 1) (i32.const 128)))
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i64.const 1) (i32.const 136)))
+            (drop (call $next_notification (i32.const 136) (i32.const 1) (i32.const 256) (i32.const 64) (i64.const 1)))
+            (drop (call $next_notification (i32.const 128) (i32.const 1) (i32.const 256) (i32.const 64) (i64.const 1))))
+        (export "memory" (memory 0))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    let mut message_ids = Vec::new();
+    while message_ids.len() < 2 {
+        match next_event(core) {
+            Some(CoreRunOutcome::InterfaceMessage {
+                pid: emitter,
+                message_id,
+                ..
+            }) => {
+                assert_eq!(emitter, pid);
+                assert!(core.accept_interface_message(message_id).is_some());
+                message_ids.push(message_id);
+            }
+            _ => panic!(),
+        }
+    }
+
+    core.answer_message(message_ids[0], Ok(EncodedMessage(Vec::new())));
+    assert!(next_event(core).is_none());
+    (pid, message_ids[0], message_ids[1])
+}
+
+/// Starts a process that emits a message with the given flags and traps if `emit_message`
+/// doesn't return the given value.
+fn spawn_emitter(core: &Core<NoExtrinsics>, flags: i64, expected_ret: i32) -> Pid {
+    let module = wat::parse_str(format!(
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i64 i32) (result i32)))
+        (memory $memory 1)
+        (data (i32.const 32) "\40\00\00\00\01\00\00\00")
+        (func $_start
+            (if (i32.ne (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i64.const {}) (i32.const 128)) (i32.const {}))
+                (then (unreachable))))
+        (export "memory" (memory 0))
+        (export "_start" (func $_start)))
+    "#,
+        flags, expected_ret
+    ))
+    .unwrap();
+    let module = crate::Module::from_bytes(&module).unwrap();
+    core.execute(&module, Default::default()).unwrap().0.pid()
+}
+
+/// Runs the core until the given process has exited successfully.
+fn wait_exited(core: &Core<NoExtrinsics>, expected_pid: Pid) {
+    match next_event(core) {
+        Some(CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: ProcessExitReason::Exited { .. },
+            ..
+        }) => assert_eq!(pid, expected_pid),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn emitter_queue_full() {
+    // Emits two messages that need an answer without allowing delays. The first message is
+    // answered before the second emission, which must fail with the error code corresponding to
+    // a full notifications queue.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i64 i32) (result i32)))
+        (memory $memory 1)
+        (data (i32.const 32) "\40\00\00\00\01\00\00\00")
+        (func $_start
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i64.const 1) (i32.const 128)))
+            (if (i32.ne (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i64.const 1) (i32.const 128)) (i32.const 3))
+                (then (unreachable))))
+        (export "memory" (memory 0))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<NoExtrinsics>::with_seed([0; 64])
+        .with_max_queued_notifications(1)
+        .build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    match next_event(&core) {
+        Some(CoreRunOutcome::InterfaceMessage {
+            pid, message_id, ..
+        }) => {
+            assert_eq!(pid, expected_pid);
+            assert!(core.accept_interface_message(message_id).is_some());
+            core.answer_message(message_id, Ok(EncodedMessage(Vec::new())));
+        }
+        _ => panic!(),
+    }
+
+    wait_exited(&core, expected_pid);
+}
+
+#[test]
+fn recipient_queue_full() {
+    let core = CoreBuilder::<NoExtrinsics>::with_seed([0; 64])
+        .with_max_queued_notifications(1)
+        .build();
+    let (recipient, _, b) = spawn_recipient(&core);
+
+    // The emitter doesn't allow delays, and must receive the error code corresponding to a full
+    // notifications queue.
+    let emitter = spawn_emitter(&core, 0, 3);
+    match next_event(&core) {
+        Some(CoreRunOutcome::InterfaceMessage {
+            pid,
+            message_id,
+            immediate,
+            ..
+        }) => {
+            assert_eq!(pid, emitter);
+            assert!(immediate);
+            assert!(!core.check_recipient_queue(message_id, immediate, recipient));
+            assert!(core.accept_interface_message(message_id).is_none());
+        }
+        _ => panic!(),
+    }
+    wait_exited(&core, emitter);
+
+    core.answer_message(b, Ok(EncodedMessage(Vec::new())));
+    wait_exited(&core, recipient);
+}
+
+#[test]
+fn blocked_until_recipient_queue_drains() {
+    let core = CoreBuilder::<NoExtrinsics>::with_seed([0; 64])
+        .with_max_queued_notifications(1)
+        .build();
+    let (recipient, _, b) = spawn_recipient(&core);
+
+    // The emitter allows delays. Its message is put on hold as long as the queue of the recipient
+    // is full.
+    let emitter = spawn_emitter(&core, 2, 0);
+    let blocked_message = match next_event(&core) {
+        Some(CoreRunOutcome::InterfaceMessage {
+            pid,
+            message_id,
+            immediate,
+            ..
+        }) => {
+            assert_eq!(pid, emitter);
+            assert!(!immediate);
+            assert!(!core.check_recipient_queue(message_id, immediate, recipient));
+            message_id
+        }
+        _ => panic!(),
+    };
+    assert!(next_event(&core).is_none());
+
+    // Answering `b` makes the recipient pull `b` then `a` from its queue, at which point the
+    // message is emitted again.
+    core.answer_message(b, Ok(EncodedMessage(Vec::new())));
+    let mut running = vec![recipient, emitter];
+    let mut accepted = false;
+    while !running.is_empty() {
+        match next_event(&core) {
+            Some(CoreRunOutcome::InterfaceMessage {
+                pid,
+                message_id,
+                immediate,
+                ..
+            }) => {
+                assert_eq!(pid, emitter);
+                assert_eq!(message_id, blocked_message);
+                assert!(!accepted);
+                assert!(core.check_recipient_queue(message_id, immediate, recipient));
+                assert!(core.accept_interface_message(message_id).is_some());
+                accepted = true;
+            }
+            Some(CoreRunOutcome::ProgramFinished {
+                pid,
+                outcome: ProcessExitReason::Exited { .. },
+                ..
+            }) => {
+                assert!(pid != emitter || accepted);
+                running.retain(|p| *p != pid);
+            }
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::extrinsics::wasi::WasiExtrinsics;
+use crate::scheduler::{CoreBuilder, CoreRunOutcome, ProcessExitReason};
+use futures::prelude::*;
+
+#[test]
+fn proc_exit() {
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+        (memory (export "memory") 1)
+        (func $_start
+            (call $proc_exit (i32.const 3))
+            unreachable)
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 3 });
+        }
+        _ => panic!(),
+    }
+}
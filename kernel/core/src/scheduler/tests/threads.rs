@@ -0,0 +1,72 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::extrinsics::NoExtrinsics;
+use crate::scheduler::{CoreBuilder, CoreRunOutcome, ProcessExitReason};
+use crate::WasmValue;
+use futures::prelude::*;
+
+#[test]
+fn secondary_thread() {
+    // The main thread waits for the secondary thread to write a value in memory. Both threads
+    // return their own value of the stack pointer.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (memory (export "memory") 1)
+        (global $stack_pointer (mut i32) (i32.const 1024))
+        (table (export "__indirect_function_table") 1 funcref)
+        (elem (i32.const 0) $thread)
+        (func $thread (param $value i32) (result i32)
+            (i32.store (i32.const 0) (local.get $value))
+            (global.get $stack_pointer))
+        (func $_start (result i32)
+            (loop $wait
+                (br_if $wait (i32.eqz (i32.load (i32.const 0)))))
+            (global.get $stack_pointer))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    // Fuel is necessary, otherwise the main thread would loop forever.
+    let core = CoreBuilder::<NoExtrinsics>::with_seed([0; 64])
+        .with_fuel_per_run(1000)
+        .build();
+    let expected_tid = {
+        let (process, _) = core.execute(&module, Default::default()).unwrap();
+        process
+            .start_thread(0, vec![WasmValue::I32(7)], Some(4096))
+            .unwrap()
+    };
+
+    let mut thread_finished = false;
+    let outcome = loop {
+        match core.run().now_or_never().unwrap().or_run() {
+            Some(CoreRunOutcome::ThreadFinished {
+                thread_id, value, ..
+            }) => {
+                assert_eq!(thread_id, expected_tid);
+                assert!(matches!(value, Some(WasmValue::I32(4096))));
+                thread_finished = true;
+            }
+            Some(CoreRunOutcome::ProgramFinished { outcome, .. }) => break outcome,
+            Some(_) => panic!(),
+            None => {}
+        }
+    };
+
+    assert!(thread_finished);
+    assert_eq!(outcome, ProcessExitReason::Exited { code: 1024 });
+}
@@ -0,0 +1,70 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::extrinsics::NoExtrinsics;
+use crate::scheduler::{CoreBuilder, CoreRunOutcome, ProcessExitReason};
+use futures::prelude::*;
+
+#[test]
+fn too_many_pending_messages() {
+    // Emits two messages that need an answer. The second emission must fail with the error code
+    // corresponding to too many pending messages.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i64 i32) (result i32)))
+        (memory $memory 1)
+        (data (i32.const 32) "\40\00\00\00\01\00\00\00")
+        (func $_start
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i64.const 1) (i32.const 128)))
+            (if (i32.ne (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i64.const 1) (i32.const 128)) (i32.const 2))
+                (then (unreachable))))
+        (export "memory" (memory 0))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<NoExtrinsics>::with_seed([0; 64])
+        .with_max_pending_messages(1)
+        .build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    match core.run().now_or_never().unwrap().or_run() {
+        Some(CoreRunOutcome::InterfaceMessage {
+            pid, message_id, ..
+        }) => {
+            assert_eq!(pid, expected_pid);
+            assert!(core.accept_interface_message(message_id).is_some());
+        }
+        _ => panic!(),
+    }
+
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: ProcessExitReason::Exited { .. },
+            ..
+        } => {
+            assert_eq!(pid, expected_pid);
+        }
+        _ => panic!(),
+    }
+}
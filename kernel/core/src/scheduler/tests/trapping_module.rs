@@ -14,7 +14,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use crate::extrinsics::NoExtrinsics;
-use crate::scheduler::{CoreBuilder, CoreRunOutcome};
+use crate::scheduler::{CoreBuilder, CoreRunOutcome, ProcessExitReason, TrapKind};
 use futures::prelude::*;
 
 #[test]
@@ -29,7 +29,7 @@ fn trapping_module() {
     );
 
     let core = CoreBuilder::<NoExtrinsics>::with_seed([0; 64]).build();
-    let expected_pid = core.execute(&module).unwrap().0.pid();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
 
     let event = loop {
         if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
@@ -40,10 +40,11 @@ fn trapping_module() {
     match event {
         CoreRunOutcome::ProgramFinished {
             pid,
-            outcome: Err(_),
+            outcome: ProcessExitReason::Trapped(trap),
             ..
         } => {
             assert_eq!(pid, expected_pid);
+            assert_eq!(*trap.kind(), TrapKind::Unreachable);
         }
         _ => panic!(),
     }
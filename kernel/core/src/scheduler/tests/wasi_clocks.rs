@@ -0,0 +1,270 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::extrinsics::{wasi::WasiExtrinsics, LaunchConfig};
+use crate::scheduler::{Core, CoreBuilder, CoreRunOutcome, ProcessExitReason};
+use crate::{Decode as _, Encode as _, EncodedMessage, InterfaceHash, MessageId};
+use alloc::vec::Vec;
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use futures::prelude::*;
+use redshirt_time_interface::ffi::{self as time_ffi, TimeMessage};
+
+#[test]
+fn cpu_time_clocks() {
+    // Queries the process CPU time, then the thread CPU time. Exits with
+    // `process_seconds * 10 + thread_seconds`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "clock_time_get" (func $clock_time_get (param i32 i64 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+        (memory (export "memory") 1)
+        (func $_start
+            (drop (call $clock_time_get (i32.const 2) (i64.const 1) (i32.const 0)))
+            (drop (call $clock_time_get (i32.const 3) (i64.const 1) (i32.const 8)))
+            (call $proc_exit
+                (i32.add
+                    (i32.mul
+                        (i32.wrap_i64 (i64.div_u (i64.load (i32.const 0)) (i64.const 1000000000)))
+                        (i32.const 10))
+                    (i32.wrap_i64 (i64.div_u (i64.load (i32.const 8)) (i64.const 1000000000)))))
+            unreachable)
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    // Every time the clock is read, it advances by one second. Each call to `run` therefore
+    // accounts exactly one second of CPU time.
+    let clock = AtomicU64::new(0);
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64])
+        .with_monotonic_clock(move || Duration::from_secs(clock.fetch_add(1, Ordering::Relaxed)))
+        .build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 12 });
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn poll_timeout_only() {
+    // Polls a single subscription to the monotonic clock, with a relative timeout of 1ms and a
+    // `userdata` of 2. Exits with
+    // `errno * 100000 + num_events * 10000 + userdata * 100 + type * 10 + nbytes`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "poll_oneoff" (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\02")
+        (data (i32.const 16) "\01")
+        (data (i32.const 24) "\40\42\0f")
+        (func $_start (result i32)
+            (i32.add
+                (i32.add
+                    (i32.mul
+                        (call $poll_oneoff (i32.const 0) (i32.const 128) (i32.const 1) (i32.const 256))
+                        (i32.const 100000))
+                    (i32.mul (i32.load (i32.const 256)) (i32.const 10000)))
+                (i32.add
+                    (i32.add
+                        (i32.mul (i32.wrap_i64 (i64.load (i32.const 128))) (i32.const 100))
+                        (i32.mul (i32.load8_u (i32.const 138)) (i32.const 10)))
+                    (i32.wrap_i64 (i64.load (i32.const 144))))))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    // The current time is queried in order to determine the deadline.
+    let messages = run_until_idle(&core);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].1, time_ffi::INTERFACE);
+    assert!(matches!(
+        TimeMessage::decode(messages[0].2.clone()),
+        Ok(TimeMessage::GetMonotonic)
+    ));
+    core.answer_message(messages[0].0, Ok(10_000_000u128.encode()));
+
+    let messages = run_until_idle(&core);
+    assert_eq!(messages.len(), 1);
+    assert!(matches!(
+        TimeMessage::decode(messages[0].2.clone()),
+        Ok(TimeMessage::WaitMonotonic(11_000_000))
+    ));
+    core.answer_message(messages[0].0, Ok(().encode()));
+
+    // 0 is `EVENTTYPE_CLOCK`.
+    assert_eq!(
+        run_until_end(&core, expected_pid),
+        ProcessExitReason::Exited { code: 10200 }
+    );
+}
+
+#[test]
+fn poll_fd_ready_first() {
+    let (core, expected_pid, read_message, _) = start_poll_console_and_clock();
+
+    let response = redshirt_console_interface::ffi::ReadResponse {
+        data: b"ab".to_vec(),
+    };
+    core.answer_message(read_message, Ok(response.encode()));
+
+    // The timer is never answered. Only the console is reported, as `EVENTTYPE_FD_READ` (1) with
+    // 2 bytes available.
+    assert_eq!(
+        run_until_end(&core, expected_pid),
+        ProcessExitReason::Exited { code: 10112 }
+    );
+}
+
+#[test]
+fn poll_clock_first() {
+    let (core, expected_pid, _, wait_message) = start_poll_console_and_clock();
+
+    core.answer_message(wait_message, Ok(().encode()));
+
+    // The console read is never answered. Only the clock is reported, as `EVENTTYPE_CLOCK` (0).
+    assert_eq!(
+        run_until_end(&core, expected_pid),
+        ProcessExitReason::Exited { code: 10200 }
+    );
+}
+
+/// Starts a process that polls both the console, with a `userdata` of 1, and the monotonic
+/// clock, with a relative timeout of 1ms and a `userdata` of 2. The process exits with
+/// `errno * 100000 + num_events * 10000 + userdata * 100 + type * 10 + nbytes` of the first
+/// event.
+///
+/// Returns the identifiers of the message reading from the console and of the message waiting
+/// for the deadline, once both have been emitted.
+fn start_poll_console_and_clock() -> (Core<WasiExtrinsics>, crate::Pid, MessageId, MessageId) {
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "poll_oneoff" (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\01")
+        (data (i32.const 8) "\01")
+        (data (i32.const 48) "\02")
+        (data (i32.const 64) "\01")
+        (data (i32.const 72) "\40\42\0f")
+        (func $_start (result i32)
+            (i32.add
+                (i32.add
+                    (i32.mul
+                        (call $poll_oneoff (i32.const 0) (i32.const 128) (i32.const 2) (i32.const 256))
+                        (i32.const 100000))
+                    (i32.mul (i32.load (i32.const 256)) (i32.const 10000)))
+                (i32.add
+                    (i32.add
+                        (i32.mul (i32.wrap_i64 (i64.load (i32.const 128))) (i32.const 100))
+                        (i32.mul (i32.load8_u (i32.const 138)) (i32.const 10)))
+                    (i32.wrap_i64 (i64.load (i32.const 144))))))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let launch_config = LaunchConfig {
+        console_stdin: true,
+        ..Default::default()
+    };
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let pid = core
+        .execute_with_launch_config(&module, Default::default(), launch_config)
+        .unwrap()
+        .0
+        .pid();
+
+    let messages = run_until_idle(&core);
+    assert_eq!(messages.len(), 1);
+    assert!(matches!(
+        TimeMessage::decode(messages[0].2.clone()),
+        Ok(TimeMessage::GetMonotonic)
+    ));
+    core.answer_message(messages[0].0, Ok(0u128.encode()));
+
+    let mut read_message = None;
+    let mut wait_message = None;
+    for (message_id, interface, message) in run_until_idle(&core) {
+        if interface == redshirt_console_interface::ffi::INTERFACE {
+            assert!(read_message.is_none());
+            read_message = Some(message_id);
+        } else {
+            assert!(matches!(
+                TimeMessage::decode(message),
+                Ok(TimeMessage::WaitMonotonic(1_000_000))
+            ));
+            assert!(wait_message.is_none());
+            wait_message = Some(message_id);
+        }
+    }
+
+    (core, pid, read_message.unwrap(), wait_message.unwrap())
+}
+
+/// Runs `core` until it is idle, accepting the messages emitted by the process.
+fn run_until_idle(core: &Core<WasiExtrinsics>) -> Vec<(MessageId, InterfaceHash, EncodedMessage)> {
+    let mut messages = Vec::new();
+    while let Some(out) = core.run().now_or_never() {
+        match out.or_run() {
+            Some(CoreRunOutcome::InterfaceMessage {
+                message_id,
+                interface,
+                ..
+            }) => {
+                let (_, message) = core.accept_interface_message(message_id).unwrap();
+                messages.push((message_id, interface, message));
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    }
+    messages
+}
+
+/// Runs `core` until the process with the given [`Pid`](crate::Pid) has ended, and returns how
+/// it has ended.
+fn run_until_end(core: &Core<WasiExtrinsics>, pid: crate::Pid) -> ProcessExitReason {
+    loop {
+        match core.run().now_or_never().unwrap().or_run() {
+            Some(CoreRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome,
+            }) => {
+                assert_eq!(finished, pid);
+                return outcome;
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,321 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::extrinsics::{wasi::WasiExtrinsics, LaunchConfig, Mount};
+use crate::scheduler::{CoreBuilder, CoreRunOutcome, ProcessExitReason};
+use futures::prelude::*;
+use redshirt_files_interface::ffi as files_ffi;
+use redshirt_syscalls::{Decode as _, Encode as _};
+
+#[test]
+fn write_then_read_file() {
+    // Creates a file, writes `hello` to it, seeks back to the start, and reads it back.
+    // Returns `num_bytes_read * 1000 + content[4]`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_seek" (func $fd_seek (param i32 i64 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "a.txt")
+        (data (i32.const 32) "\40\00\00\00\05\00\00\00")
+        (data (i32.const 64) "hello")
+        (data (i32.const 80) "\80\00\00\00\10\00\00\00")
+        (func $_start (result i32)
+            (drop (call $path_open (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 5)
+                (i32.const 1) (i64.const -1) (i64.const -1) (i32.const 0) (i32.const 16)))
+            (drop (call $fd_write (i32.load (i32.const 16)) (i32.const 32) (i32.const 1) (i32.const 48)))
+            (drop (call $fd_seek (i32.load (i32.const 16)) (i64.const 0) (i32.const 0) (i32.const 56)))
+            (drop (call $fd_read (i32.load (i32.const 16)) (i32.const 80) (i32.const 1) (i32.const 96)))
+            (i32.add
+                (i32.mul (i32.load (i32.const 96)) (i32.const 1000))
+                (i32.load8_u (i32.const 132))))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 5111 });
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn create_directory_and_read_it() {
+    // Creates the directory `d` and the file `d/f`, then lists the root directory.
+    // Returns `num_bytes_listed * 1000 + type_of_first_entry`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "path_create_directory" (func $path_create_directory (param i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_readdir" (func $fd_readdir (param i32 i32 i32 i64 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "d")
+        (data (i32.const 8) "d/f")
+        (func $_start (result i32)
+            (drop (call $path_create_directory (i32.const 3) (i32.const 0) (i32.const 1)))
+            (drop (call $path_open (i32.const 3) (i32.const 0) (i32.const 8) (i32.const 3)
+                (i32.const 1) (i64.const -1) (i64.const -1) (i32.const 0) (i32.const 16)))
+            (drop (call $fd_readdir (i32.const 3) (i32.const 256) (i32.const 128) (i64.const 0) (i32.const 24)))
+            (i32.add
+                (i32.mul (i32.load (i32.const 24)) (i32.const 1000))
+                (i32.load8_u (i32.const 276))))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, expected_pid);
+            // The directory entry is 24 bytes plus the length of the name, and its type is
+            // `FILETYPE_DIRECTORY`.
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 25003 });
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parent_directory_and_rename_into_itself() {
+    // Creates the directory `d`, tries to move it to `d/e`, then creates `e` through a path
+    // containing `..`, and tries to create `../e` which is the same directory.
+    // Returns `rename_errno * 10000 + first_create_errno * 100 + second_create_errno`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "path_create_directory" (func $path_create_directory (param i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "path_rename" (func $path_rename (param i32 i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "d")
+        (data (i32.const 8) "d/e")
+        (data (i32.const 16) "d/../d/../e")
+        (data (i32.const 32) "../e")
+        (func $_start (result i32)
+            (drop (call $path_create_directory (i32.const 3) (i32.const 0) (i32.const 1)))
+            (i32.add
+                (i32.mul
+                    (call $path_rename (i32.const 3) (i32.const 0) (i32.const 1)
+                        (i32.const 3) (i32.const 8) (i32.const 3))
+                    (i32.const 10000))
+                (i32.add
+                    (i32.mul
+                        (call $path_create_directory (i32.const 3) (i32.const 16) (i32.const 11))
+                        (i32.const 100))
+                    (call $path_create_directory (i32.const 3) (i32.const 32) (i32.const 4)))))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core.execute(&module, Default::default()).unwrap().0.pid();
+
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, expected_pid);
+            // `ERRNO_INVAL` is 28, and `ERRNO_EXIST` is 20.
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 280020 });
+        }
+        _ => panic!(),
+    }
+}
+
+/// Launch configuration whose only directory is a mount of the volume `vol`. The mount is
+/// therefore file descriptor 3.
+fn mount_launch_config() -> LaunchConfig {
+    LaunchConfig {
+        preopens: Vec::new(),
+        mounts: vec![Mount {
+            path: "/data".into(),
+            volume: "vol".into(),
+        }],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn mounted_file_open_and_read() {
+    // Opens `dir/a.txt` within the mount and reads from it.
+    // Returns `open_errno * 100000 + read_errno * 1000 + num_bytes_read * 10 + content[0] - 'a'`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "dir/a.txt")
+        (data (i32.const 32) "\40\00\00\00\10\00\00\00")
+        (func $_start (result i32)
+            (i32.add
+                (i32.mul
+                    (call $path_open (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 9)
+                        (i32.const 0) (i64.const -1) (i64.const -1) (i32.const 0) (i32.const 16))
+                    (i32.const 100000))
+                (i32.add
+                    (i32.mul
+                        (call $fd_read (i32.load (i32.const 16)) (i32.const 32) (i32.const 1) (i32.const 48))
+                        (i32.const 1000))
+                    (i32.add
+                        (i32.mul (i32.load (i32.const 48)) (i32.const 10))
+                        (i32.sub (i32.load8_u (i32.const 64)) (i32.const 97))))))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core
+        .execute_with_launch_config(&module, Default::default(), mount_launch_config())
+        .unwrap()
+        .0
+        .pid();
+
+    let outcome = loop {
+        match core.run().now_or_never().unwrap().or_run() {
+            Some(CoreRunOutcome::InterfaceMessage {
+                pid,
+                message_id,
+                interface,
+                ..
+            }) => {
+                assert_eq!(pid, expected_pid);
+                assert_eq!(interface, files_ffi::INTERFACE);
+                let (_, message) = core.accept_interface_message(message_id).unwrap();
+                let answer = match files_ffi::FilesMessage::decode(message).unwrap() {
+                    files_ffi::FilesMessage::Open(open) => {
+                        assert_eq!(open.volume, "vol");
+                        assert_eq!(open.path, "dir/a.txt");
+                        assert!(!open.create);
+                        files_ffi::OpenResponse {
+                            result: Ok(files_ffi::OpenedFile {
+                                file_id: 7,
+                                size: 3,
+                            }),
+                        }
+                        .encode()
+                    }
+                    files_ffi::FilesMessage::Read(read) => {
+                        assert_eq!(read.file_id, 7);
+                        assert_eq!(read.offset, 0);
+                        files_ffi::ReadResponse {
+                            result: Ok(b"bcd".to_vec()),
+                        }
+                        .encode()
+                    }
+                    _ => panic!(),
+                };
+                core.answer_message(message_id, Ok(answer));
+            }
+            Some(CoreRunOutcome::ProgramFinished { pid, outcome }) => {
+                assert_eq!(pid, expected_pid);
+                break outcome;
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    };
+
+    assert_eq!(outcome, ProcessExitReason::Exited { code: 31 });
+}
+
+#[test]
+fn mounted_file_errors_mapped_to_errno() {
+    // Opens `dir/a.txt` within the mount twice. Returns `first_errno * 100 + second_errno`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "dir/a.txt")
+        (func $_start (result i32)
+            (i32.add
+                (i32.mul
+                    (call $path_open (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 9)
+                        (i32.const 0) (i64.const -1) (i64.const -1) (i32.const 0) (i32.const 16))
+                    (i32.const 100))
+                (call $path_open (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 9)
+                    (i32.const 0) (i64.const -1) (i64.const -1) (i32.const 0) (i32.const 16))))
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core
+        .execute_with_launch_config(&module, Default::default(), mount_launch_config())
+        .unwrap()
+        .0
+        .pid();
+
+    // The first message is answered with an error of the `files` interface, and the second
+    // with an error answer, as happens when the handler crashes.
+    let mut num_messages = 0;
+    let outcome = loop {
+        match core.run().now_or_never().unwrap().or_run() {
+            Some(CoreRunOutcome::InterfaceMessage {
+                pid, message_id, ..
+            }) => {
+                assert_eq!(pid, expected_pid);
+                core.accept_interface_message(message_id).unwrap();
+                num_messages += 1;
+                if num_messages == 1 {
+                    let response = files_ffi::OpenResponse {
+                        result: Err(files_ffi::FilesError::NotFound),
+                    };
+                    core.answer_message(message_id, Ok(response.encode()));
+                } else {
+                    core.answer_message(message_id, Err(()));
+                }
+            }
+            Some(CoreRunOutcome::ProgramFinished { pid, outcome }) => {
+                assert_eq!(pid, expected_pid);
+                break outcome;
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    };
+
+    // 44 is `ERRNO_NOENT`, and 29 is `ERRNO_IO`.
+    assert_eq!(num_messages, 2);
+    assert_eq!(outcome, ProcessExitReason::Exited { code: 4429 });
+}
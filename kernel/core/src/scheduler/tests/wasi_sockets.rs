@@ -0,0 +1,205 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::extrinsics::{wasi::WasiExtrinsics, LaunchConfig, TcpListener};
+use crate::scheduler::{Core, CoreBuilder, CoreRunOutcome, ProcessExitReason};
+use crate::{Encode as _, MessageId};
+use alloc::vec::Vec;
+use futures::prelude::*;
+use redshirt_tcp_interface::ffi as tcp_ffi;
+
+#[test]
+fn tcp_listener_file_descriptor() {
+    // The listening socket is placed after the pre-opened root directory, as fd 4. Exits with
+    // `filetype * 10000 + errno_of_sock_recv_on_directory * 100 + errno_of_prestat_get`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "fd_fdstat_get" (func $fd_fdstat_get (param i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_prestat_get" (func $fd_prestat_get (param i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "sock_recv" (func $sock_recv (param i32 i32 i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+        (memory (export "memory") 1)
+        (func $_start
+            (drop (call $fd_fdstat_get (i32.const 4) (i32.const 0)))
+            (call $proc_exit
+                (i32.add
+                    (i32.add
+                        (i32.mul (i32.load8_u (i32.const 0)) (i32.const 10000))
+                        (i32.mul
+                            (call $sock_recv (i32.const 3) (i32.const 100) (i32.const 0) (i32.const 0) (i32.const 200) (i32.const 204))
+                            (i32.const 100)))
+                    (call $fd_prestat_get (i32.const 4) (i32.const 300))))
+            unreachable)
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let launch_config = LaunchConfig {
+        tcp_listeners: vec![TcpListener {
+            ip: [0; 8],
+            port: 80,
+        }],
+        ..Default::default()
+    };
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core
+        .execute_with_launch_config(&module, Default::default(), launch_config)
+        .unwrap()
+        .0
+        .pid();
+
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, expected_pid);
+            // 6 is `FILETYPE_SOCKET_STREAM`, 57 is `ERRNO_NOTSOCK`, and 8 is `ERRNO_BADF`.
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 65708 });
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn poll_waits_for_readiness() {
+    // Polls the listening socket, accepts a connection, polls the accepted socket, then reads
+    // from it. Exits with `num_events_of_first_poll * 100000 + accepted_fd * 10000 +
+    // nbytes_of_second_poll * 100 + num_read`.
+    let module = from_wat!(
+        local,
+        r#"(module
+        (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "poll_oneoff" (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "sock_accept" (func $sock_accept (param i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 8) "\01")
+        (data (i32.const 16) "\04")
+        (data (i32.const 56) "\01")
+        (data (i32.const 64) "\05")
+        (data (i32.const 160) "\c8\00\00\00\10\00\00\00")
+        (func $_start (local $num_events i32) (local $nbytes i32)
+            (drop (call $poll_oneoff (i32.const 0) (i32.const 100) (i32.const 1) (i32.const 140)))
+            (local.set $num_events (i32.load (i32.const 140)))
+            (drop (call $sock_accept (i32.const 4) (i32.const 0) (i32.const 150)))
+            (drop (call $poll_oneoff (i32.const 48) (i32.const 100) (i32.const 1) (i32.const 140)))
+            (local.set $nbytes (i32.wrap_i64 (i64.load (i32.const 116))))
+            (drop (call $fd_read (i32.load (i32.const 150)) (i32.const 160) (i32.const 1) (i32.const 170)))
+            (call $proc_exit
+                (i32.add
+                    (i32.add
+                        (i32.mul (local.get $num_events) (i32.const 100000))
+                        (i32.mul (i32.load (i32.const 150)) (i32.const 10000)))
+                    (i32.add
+                        (i32.mul (local.get $nbytes) (i32.const 100))
+                        (i32.load (i32.const 170)))))
+            unreachable)
+        (export "_start" (func $_start)))
+    "#
+    );
+
+    let launch_config = LaunchConfig {
+        tcp_listeners: vec![TcpListener {
+            ip: [0; 8],
+            port: 80,
+        }],
+        ..Default::default()
+    };
+
+    let core = CoreBuilder::<WasiExtrinsics>::with_seed([0; 64]).build();
+    let expected_pid = core
+        .execute_with_launch_config(&module, Default::default(), launch_config)
+        .unwrap()
+        .0
+        .pid();
+
+    // Polling the listening socket opens sockets listening on its address, which stay open
+    // until a remote connects to one of them.
+    let messages = run_until_idle(&core);
+    assert_eq!(messages.len(), 2);
+    for (_, message) in &messages {
+        match message {
+            tcp_ffi::TcpMessage::Open(open) => assert!(open.listen && open.port == 80),
+            _ => panic!(),
+        }
+    }
+
+    let response = tcp_ffi::TcpOpenResponse {
+        result: Ok(tcp_ffi::TcpSocketOpen {
+            socket_id: 7,
+            local_ip: [0; 8],
+            local_port: 80,
+            remote_ip: [0; 8],
+            remote_port: 1234,
+        }),
+    };
+    core.answer_message(messages[0].0, Ok(response.encode()));
+
+    // Accepting the connection doesn't block, but polling the accepted socket waits for some
+    // data to be received.
+    let messages = run_until_idle(&core);
+    assert_eq!(messages.len(), 1);
+    match &messages[0].1 {
+        tcp_ffi::TcpMessage::Read(read) => assert_eq!(read.socket_id, 7),
+        _ => panic!(),
+    }
+
+    let response = tcp_ffi::TcpReadResponse {
+        result: Ok(b"hello".to_vec()),
+    };
+    core.answer_message(messages[0].0, Ok(response.encode()));
+
+    // Reading then returns the data received while polling.
+    let event = loop {
+        if let Some(ev) = core.run().now_or_never().unwrap().or_run() {
+            break ev;
+        }
+    };
+
+    match event {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(outcome, ProcessExitReason::Exited { code: 150505 });
+        }
+        _ => panic!(),
+    }
+}
+
+/// Runs `core` until it is idle, accepting the messages emitted on the `tcp` interface.
+fn run_until_idle(core: &Core<WasiExtrinsics>) -> Vec<(MessageId, tcp_ffi::TcpMessage)> {
+    let mut messages = Vec::new();
+    while let Some(out) = core.run().now_or_never() {
+        match out.or_run() {
+            Some(CoreRunOutcome::InterfaceMessage {
+                message_id,
+                interface,
+                ..
+            }) => {
+                assert_eq!(interface, tcp_ffi::INTERFACE);
+                let (_, message) = core.accept_interface_message(message_id).unwrap();
+                messages.push((message_id, message.decode().unwrap()));
+            }
+            Some(_) => panic!(),
+            None => {}
+        }
+    }
+    messages
+}
@@ -15,20 +15,20 @@
 
 use crate::{module::Module, primitives::Signature, ValueType, WasmValue};
 
-use alloc::{
-    borrow::{Cow, ToOwned as _},
-    boxed::Box,
-    format,
-    vec::Vec,
-};
-use core::{
-    cell::RefCell,
-    convert::{TryFrom as _, TryInto},
-    fmt,
-};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
 use smallvec::SmallVec;
 
-/// WASMI state machine dedicated to a process.
+pub use self::engine::{Engine, EngineInstance, EngineRunOutcome};
+pub use self::wasmi_engine::WasmiEngine;
+
+mod engine;
+mod wasmi_engine;
+
+/// [`Engine`] used by default when none is specified.
+pub type DefaultEngine = WasmiEngine;
+
+/// Virtual machine state machine dedicated to a process.
 ///
 /// # Initialization
 ///
@@ -41,6 +41,13 @@ use smallvec::SmallVec;
 /// by the user. This integer is later passed back to the user of this struct in the situation when
 /// the state machine invokes that external function.
 ///
+/// # Engine
+///
+/// The state machine doesn't execute code by itself, but delegates this to an implementation of
+/// the [`Engine`] trait, passed as the second generic parameter. It defaults to
+/// [`DefaultEngine`]. The state machine takes care of the threads, poisoning and limits, while
+/// the engine only needs to know how to run a function.
+///
 /// # Threads
 ///
 /// This struct is composed of one or multiple threads. When initialized, the VM starts with a
@@ -48,21 +55,34 @@ use smallvec::SmallVec;
 ///
 /// In order to run the VM, grab a thread by calling [`ProcessStateMachine::threads`], then call
 /// [`Thread::run`]. The thread will then run until it either finishes (in which case the thread
-/// is then destroyed), or attempts to call an imported function.
-///
-/// TODO: It is intended that in the future the `run` function stops after a certain period of
-/// time has elapsed, in order to do preemptive multithreading. This requires a lot of changes in
-/// the interpreter, and isn't going to happen any time soon.
+/// is then destroyed), attempts to call an imported function, or runs out of fuel.
 ///
 /// The [`run`](Thread::run) method requires passing a value. The first time you call
 /// [`run`](Thread::run) for any given thread, you must pass the value `None`. If that thread is
 /// then interrupted by a call to an imported function, you must execute the imported function and
 /// pass its return value the next time you call [`run`](Thread::run).
 ///
+/// # Fuel
+///
+/// The [`run`](Thread::run) method optionally accepts an amount of fuel. Each instruction
+/// executed by the thread consumes fuel, and the execution is paused once the fuel is exhausted.
+/// This makes it possible to do preemptive multithreading, as a thread that runs an infinite
+/// loop will eventually be interrupted.
+///
+/// The amount of fuel consumed is an approximation of the number of instructions executed. See
+/// the [`fuel`](crate::module::fuel) module for more information.
+///
 /// The generic parameter of this struct is some userdata that is associated with each thread.
 /// You must pass a value when creating a thread, and can retreive it later by calling
 /// [`user_data`](Thread::user_data) or [`into_user_data`](Thread::into_user_data).
 ///
+/// # Limits
+///
+/// A [`ProcessLimits`] must be passed when creating the state machine. The initial size of the
+/// memory and of the table of the module are checked against these limits, and so is every
+/// attempt to grow the memory. If the memory would grow above the limit, the execution stops
+/// with an error.
+///
 /// # Poisoning
 ///
 /// If the main thread stops, or if any thread encounters an error, then the VM moves into a
@@ -70,56 +90,63 @@ use smallvec::SmallVec;
 /// so that you can examine their state, but attempting to call [`run`](Thread::run) will return
 /// an error.
 ///
+/// # Stacks
+///
+/// All the threads share the same memory and the same globals. In order for each thread to have
+/// its own stack, the value of the stack pointer global (see the
+/// [`stack_pointer`](crate::module::stack_pointer) module) is saved whenever a thread stops
+/// running, and restored when it resumes. The main thread uses the stack set up by the module
+/// itself, while secondary threads are given a stack pointer when they are started.
+///
 /// # Single-threaded-ness
 ///
 /// The [`ProcessStateMachine`] is single-threaded. In other words, the VM can only ever run one
-/// thread simultaneously. This might change in the future.
+/// thread simultaneously. Threads are interleaved, and can be paused at any point when they run
+/// out of fuel.
 ///
-pub struct ProcessStateMachine<T> {
-    /// Original module, with resolved imports.
-    module: wasmi::ModuleRef,
-
-    /// Memory of the module instantiation.
-    ///
-    /// Right now we only support one unique `Memory` object per process. This is it.
-    /// Contains `None` if the process doesn't export any memory object, which means it doesn't use
-    /// any memory.
-    memory: Option<wasmi::MemoryRef>,
-
-    /// Table of the indirect function calls.
-    ///
-    /// In WASM, function pointers are in reality indices in a table called
-    /// `__indirect_function_table`. This is this table, if it exists.
-    indirect_table: Option<wasmi::TableRef>,
+pub struct ProcessStateMachine<T, TEng: Engine = DefaultEngine> {
+    /// Instantiated module.
+    instance: TEng::Instance,
 
     /// List of threads that this process is running.
-    threads: SmallVec<[ThreadState<T>; 4]>,
+    threads: SmallVec<[ThreadState<T, TEng>; 4]>,
 
     /// If true, the state machine is in a poisoned state and cannot run any code anymore.
     is_poisoned: bool,
 }
 
 /// State of a single thread within the VM.
-struct ThreadState<T> {
+struct ThreadState<T, TEng: Engine> {
     /// Execution context of this thread. This notably holds the program counter, state of the
     /// stack, and so on.
-    ///
-    /// This field is an `Option` because we need to be able to temporarily extract it. It must
-    /// always be `Some`.
-    execution: Option<wasmi::FuncInvocation<'static>>,
+    execution: <TEng::Instance as EngineInstance>::Execution,
 
-    /// If false, then one must call `execution.start_execution()` instead of `resume_execution()`.
-    /// This is a particularity of the WASM interpreter that we don't want to expose in our API.
-    interrupted: bool,
+    /// Value of the stack pointer of this thread, saved while the thread isn't running. `None` if
+    /// the module doesn't have a stack pointer, or if the thread has been started without a
+    /// stack of its own.
+    stack_pointer: Option<i32>,
 
     /// Opaque user data associated with the thread.
     user_data: T,
 }
 
+/// Limits to the resources that a process is allowed to use.
+///
+/// The default value doesn't enforce any limit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessLimits {
+    /// Maximum size of the memory of the process, in number of 64kiB pages. `None` if there is
+    /// no limit.
+    pub max_memory_pages: Option<u32>,
+
+    /// Maximum number of elements in the table of the process. `None` if there is no limit.
+    pub max_table_elements: Option<u32>,
+}
+
 /// Access to a thread within the virtual machine.
-pub struct Thread<'a, T> {
+pub struct Thread<'a, T, TEng: Engine = DefaultEngine> {
     /// Reference to the parent object.
-    vm: &'a mut ProcessStateMachine<T>,
+    vm: &'a mut ProcessStateMachine<T, TEng>,
 
     // Index within [`ProcessStateMachine::threads`] of the thread we are referencing.
     index: usize,
@@ -127,7 +154,7 @@ pub struct Thread<'a, T> {
 
 /// Outcome of the [`run`](Thread::run) function.
 #[derive(Debug)]
-pub enum ExecOutcome<'a, T> {
+pub enum ExecOutcome<'a, T, TEng: Engine = DefaultEngine> {
     /// A thread has finished. The thread no longer exists in the list.
     ///
     /// If this was the main thread (i.e. `thread_index` is 0), then the state machine is now in
@@ -160,7 +187,7 @@ pub enum ExecOutcome<'a, T> {
     /// >           [`run`](Thread::run) with a value of the wrong type.
     Interrupted {
         /// Thread that was interrupted.
-        thread: Thread<'a, T>,
+        thread: Thread<'a, T, TEng>,
 
         /// Identifier of the function to call. Corresponds to the value provided at
         /// initialization when resolving imports.
@@ -170,35 +197,165 @@ pub enum ExecOutcome<'a, T> {
         params: Vec<WasmValue>,
     },
 
+    /// The currently-executed thread has been paused because it has consumed all the fuel that
+    /// was passed to [`run`](Thread::run).
+    ///
+    /// When you call [`run`](Thread::run) again, you must pass a value of `None`.
+    Preempted {
+        /// Thread that was preempted.
+        thread: Thread<'a, T, TEng>,
+    },
+
     /// The currently-executed function has finished with an error. The state machine is now in a
     /// poisoned state.
     ///
     /// Calling [`is_poisoned`](ProcessStateMachine::is_poisoned) will return true.
     Errored {
         /// Thread that error'd.
-        thread: Thread<'a, T>,
+        thread: Thread<'a, T, TEng>,
 
         /// Error that happened.
-        // TODO: error type should change here
-        error: wasmi::Trap,
+        error: Trap,
     },
 }
 
+/// Error that happened during the execution of a function, and that stopped it.
+///
+/// Doesn't depend on the [`Engine`] that is in use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trap {
+    kind: TrapKind,
+    backtrace: Vec<u32>,
+}
+
+impl Trap {
+    /// Builds a new [`Trap`].
+    ///
+    /// `backtrace` contains the indices of the functions that were being executed when the trap
+    /// happened, starting with the innermost one.
+    pub fn new(kind: TrapKind, backtrace: Vec<u32>) -> Self {
+        Trap { kind, backtrace }
+    }
+
+    /// Returns what caused the trap.
+    pub fn kind(&self) -> &TrapKind {
+        &self.kind
+    }
+
+    /// Returns the indices of the functions that were being executed when the trap happened,
+    /// starting with the innermost one.
+    ///
+    /// Empty if the [`Engine`] doesn't provide this information, or if the `backtraces` feature
+    /// of this crate is disabled.
+    pub fn backtrace(&self) -> &[u32] {
+        &self.backtrace
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.kind, f)?;
+        for function_index in &self.backtrace {
+            write!(f, "\n    in function #{}", function_index)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reason why a [`Trap`] happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrapKind {
+    /// An `unreachable` instruction has been executed.
+    Unreachable,
+    /// Attempted to load or store outside of the bounds of the memory.
+    MemoryAccessOutOfBounds,
+    /// Attempted to access an element outside of the bounds of the table.
+    TableAccessOutOfBounds,
+    /// Attempted to call an element of the table that hasn't been initialized.
+    ElemUninitialized,
+    /// Integer division or remainder by zero.
+    DivisionByZero,
+    /// Overflow or NaN while converting a floating point value to an integer.
+    InvalidConversionToInt,
+    /// The call stack has exceeded the limit of the engine.
+    StackOverflow,
+    /// The signature of the function called with `call_indirect` doesn't match the expected
+    /// one.
+    UnexpectedSignature,
+    /// Attempted to grow the memory above [`ProcessLimits::max_memory_pages`].
+    MemoryLimitExceeded,
+    /// Other engine-specific error.
+    Other(String),
+}
+
+impl fmt::Display for TrapKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrapKind::Unreachable => write!(f, "Unreachable instruction executed"),
+            TrapKind::MemoryAccessOutOfBounds => write!(f, "Out of bounds memory access"),
+            TrapKind::TableAccessOutOfBounds => write!(f, "Out of bounds table access"),
+            TrapKind::ElemUninitialized => write!(f, "Uninitialized table element"),
+            TrapKind::DivisionByZero => write!(f, "Integer division by zero"),
+            TrapKind::InvalidConversionToInt => write!(f, "Invalid conversion to integer"),
+            TrapKind::StackOverflow => write!(f, "Call stack exhausted"),
+            TrapKind::UnexpectedSignature => write!(f, "Indirect call signature mismatch"),
+            TrapKind::MemoryLimitExceeded => write!(f, "Memory limit exceeded"),
+            TrapKind::Other(msg) => fmt::Display::fmt(msg, f),
+        }
+    }
+}
+
 /// Error that can happen when initializing a VM.
 #[derive(Debug)]
 pub enum NewErr {
-    /// Error in the interpreter.
-    Interpreter(wasmi::Error),
+    /// Error in the engine while instantiating the module.
+    Interpreter(String),
     /// The "start" symbol doesn't exist.
     StartNotFound,
     /// The "start" symbol must be a function.
     StartIsntAFunction,
+    /// The "start" symbol must be a function that doesn't take any parameter.
+    StartBadSignature,
     /// If a "memory" symbol is provided, it must be a memory.
     MemoryIsntMemory,
     /// A memory object has both been imported and exported.
     MultipleMemoriesNotSupported,
     /// If a "__indirect_function_table" symbol is provided, it must be a table.
     IndirectTableIsntTable,
+    /// The initial size of the memory is above the limit.
+    MemoryLimitExceeded,
+    /// The initial size of the table is above the limit.
+    TableLimitExceeded,
+    /// The module imports a function that doesn't exist.
+    UnresolvedFunctionImport {
+        /// Name of the module the function is imported from.
+        module_name: String,
+        /// Name of the function.
+        function: String,
+    },
+    /// The module imports a function that exists, but with a different signature.
+    FunctionImportSignatureMismatch {
+        /// Name of the module the function is imported from.
+        module_name: String,
+        /// Name of the function.
+        function: String,
+        /// Signature of the function that exists.
+        expected: Signature,
+        /// Signature the module imports the function with.
+        obtained: Signature,
+    },
+}
+
+/// Error that can be returned when resolving an import. See [`Engine::instantiate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportErr {
+    /// No function exists with this name.
+    NotFound,
+    /// A function exists with this name, but its signature is different.
+    BadSignature {
+        /// Signature of the function that exists.
+        expected: Signature,
+    },
 }
 
 /// Error that can happen when starting a new thread.
@@ -210,6 +367,8 @@ pub enum StartErr {
     FunctionNotFound,
     /// The requested function has been found in the list of exports, but it is not a function.
     NotAFunction,
+    /// The parameters don't match the signature of the requested function.
+    BadSignature,
 }
 
 /// Error that can happen when resuming the execution of a function.
@@ -227,154 +386,75 @@ pub enum RunErr {
 }
 
 impl<T> ProcessStateMachine<T> {
-    /// Creates a new process state machine from the given module.
+    /// Creates a new process state machine from the given module, using the
+    /// [`DefaultEngine`].
+    ///
+    /// See [`ProcessStateMachine::with_engine`].
+    pub fn new(
+        module: &Module,
+        limits: ProcessLimits,
+        main_thread_user_data: T,
+        symbols: impl FnMut(&str, &str, &Signature) -> Result<usize, ImportErr>,
+    ) -> Result<Self, NewErr> {
+        Self::with_engine(module, limits, main_thread_user_data, symbols)
+    }
+}
+
+impl<T, TEng: Engine> ProcessStateMachine<T, TEng> {
+    /// Creates a new process state machine from the given module, executed by the given
+    /// [`Engine`].
     ///
     /// The closure is called for each import that the module has. It must assign a number to each
     /// import, or return an error if the import can't be resolved. When the VM calls one of these
     /// functions, this number will be returned back in order for the user to know how to handle
-    /// the call.
+    /// the call. If the closure returns an error, the creation fails with
+    /// [`NewErr::UnresolvedFunctionImport`] or [`NewErr::FunctionImportSignatureMismatch`].
     ///
     /// A single main thread (whose user data is passed by parameter) is automatically created and
     /// is paused at the start of the "_start" function of the module.
-    pub fn new(
+    ///
+    /// > **Note**: The closure isn't called for the imports used for fuel metering and memory
+    /// >           growth, which are resolved internally.
+    ///
+    /// Returns an error if the initial size of the memory or of the table of the module is above
+    /// the given limits.
+    pub fn with_engine(
         module: &Module,
+        limits: ProcessLimits,
         main_thread_user_data: T,
-        mut symbols: impl FnMut(&str, &str, &Signature) -> Result<usize, ()>,
+        mut symbols: impl FnMut(&str, &str, &Signature) -> Result<usize, ImportErr>,
     ) -> Result<Self, NewErr> {
-        struct ImportResolve<'a> {
-            func: RefCell<&'a mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ()>>,
-            memory: RefCell<&'a mut Option<wasmi::MemoryRef>>,
-        }
-
-        impl<'a> wasmi::ImportResolver for ImportResolve<'a> {
-            fn resolve_func(
-                &self,
-                module_name: &str,
-                field_name: &str,
-                signature: &wasmi::Signature,
-            ) -> Result<wasmi::FuncRef, wasmi::Error> {
-                let closure = &mut **self.func.borrow_mut();
-                let index = match closure(module_name, field_name, &From::from(signature)) {
-                    Ok(i) => i,
-                    Err(_) => {
-                        return Err(wasmi::Error::Instantiation(format!(
-                            "Couldn't resolve `{}`:`{}`",
-                            module_name, field_name
-                        )))
-                    }
-                };
-
-                Ok(wasmi::FuncInstance::alloc_host(signature.clone(), index))
-            }
-
-            fn resolve_global(
-                &self,
-                _module_name: &str,
-                _field_name: &str,
-                _global_type: &wasmi::GlobalDescriptor,
-            ) -> Result<wasmi::GlobalRef, wasmi::Error> {
-                Err(wasmi::Error::Instantiation(
-                    "Importing globals is not supported yet".to_owned(),
-                ))
-            }
-
-            fn resolve_memory(
-                &self,
-                _module_name: &str,
-                _field_name: &str,
-                memory_type: &wasmi::MemoryDescriptor,
-            ) -> Result<wasmi::MemoryRef, wasmi::Error> {
-                let mut mem = self.memory.borrow_mut();
-                if mem.is_some() {
-                    return Err(wasmi::Error::Instantiation(
-                        "Only one memory object is supported yet".to_owned(),
-                    ));
-                }
-
-                let new_mem = wasmi::MemoryInstance::alloc(
-                    wasmi::memory_units::Pages(usize::try_from(memory_type.initial()).unwrap()),
-                    memory_type
-                        .maximum()
-                        .map(|p| wasmi::memory_units::Pages(usize::try_from(p).unwrap())),
-                )
-                .unwrap();
-                **mem = Some(new_mem.clone());
-                Ok(new_mem)
-            }
-
-            fn resolve_table(
-                &self,
-                _module_name: &str,
-                _field_name: &str,
-                _table_type: &wasmi::TableDescriptor,
-            ) -> Result<wasmi::TableRef, wasmi::Error> {
-                Err(wasmi::Error::Instantiation(
-                    "Importing tables is not supported yet".to_owned(),
-                ))
+        // Check the limits before instantiating the module, as instantiating allocates the
+        // memory and the table.
+        if let (Some(initial), Some(max)) = (module.memory_initial_pages(), limits.max_memory_pages)
+        {
+            if initial > max {
+                return Err(NewErr::MemoryLimitExceeded);
             }
         }
-
-        let (not_started, imported_memory) = {
-            let mut imported_memory = None;
-            let resolve = ImportResolve {
-                func: RefCell::new(&mut symbols),
-                memory: RefCell::new(&mut imported_memory),
-            };
-            let not_started = wasmi::ModuleInstance::new(module.as_ref(), &resolve)
-                .map_err(NewErr::Interpreter)?;
-            (not_started, imported_memory)
-        };
-
-        // TODO: WASM has a special "start" instruction that can be used to designate a function
-        // that must be executed before the module is considered initialized. It is unclear whether
-        // this is intended to be a function that for example initializes global variables, or if
-        // this is an equivalent of "_start". In practice, Rust never seems to generate such as
-        // "start" instruction, so for now we ignore it. The code below panics if there is such
-        // a "start" item, so we will fortunately not blindly run into troubles.
-        let module = not_started.assert_no_start();
-
-        let memory = if let Some(imported_mem) = imported_memory {
-            if module
-                .export_by_name("memory")
-                .map_or(false, |m| m.as_memory().is_some())
-            {
-                return Err(NewErr::MultipleMemoriesNotSupported);
-            }
-            Some(imported_mem)
-        } else if let Some(mem) = module.export_by_name("memory") {
-            if let Some(mem) = mem.as_memory() {
-                Some(mem.clone())
-            } else {
-                return Err(NewErr::MemoryIsntMemory);
+        if let (Some(initial), Some(max)) =
+            (module.table_initial_elements(), limits.max_table_elements)
+        {
+            if initial > max {
+                return Err(NewErr::TableLimitExceeded);
             }
-        } else {
-            None
-        };
+        }
 
-        let indirect_table = if let Some(tbl) = module.export_by_name("__indirect_function_table") {
-            if let Some(tbl) = tbl.as_table() {
-                Some(tbl.clone())
-            } else {
-                return Err(NewErr::IndirectTableIsntTable);
-            }
-        } else {
-            None
-        };
+        let instance = TEng::instantiate(module, &limits, &mut symbols)?;
 
         let mut state_machine = ProcessStateMachine {
-            module,
-            memory,
-            indirect_table,
+            instance,
             is_poisoned: false,
             threads: SmallVec::new(),
         };
 
         // Try to start executing `_start`.
-        match state_machine.start_thread_by_name("_start", &[][..], main_thread_user_data) {
+        match state_machine.start_thread_by_name("_start", &[], main_thread_user_data) {
             Ok(_) => {}
             Err((StartErr::FunctionNotFound, _)) => return Err(NewErr::StartNotFound),
             Err((StartErr::Poisoned, _)) => unreachable!(),
             Err((StartErr::NotAFunction, _)) => return Err(NewErr::StartIsntAFunction),
+            Err((StartErr::BadSignature, _)) => return Err(NewErr::StartBadSignature),
         };
 
         Ok(state_machine)
@@ -390,6 +470,11 @@ impl<T> ProcessStateMachine<T> {
     ///
     /// You should call [`run`](Thread::run) afterwards with a value of `None`.
     ///
+    /// `stack_pointer` is the initial value of the stack pointer of the new thread. It must point
+    /// to the end of a memory area reserved for the stack of that thread. If `None`, the thread
+    /// shares the stack of the thread that has last run, which is only sound if the function
+    /// doesn't use the stack.
+    ///
     /// > **Note**: The "function ID" is the index of the function in the WASM module. WASM
     /// >           doesn't have function pointers. Instead, all the functions are part of a single
     /// >           global array of functions.
@@ -397,33 +482,18 @@ impl<T> ProcessStateMachine<T> {
         &mut self,
         function_id: u32,
         params: impl IntoIterator<Item = WasmValue>,
+        stack_pointer: Option<i32>,
         user_data: T,
-    ) -> Result<Thread<T>, StartErr> {
+    ) -> Result<Thread<T, TEng>, StartErr> {
         if self.is_poisoned {
             return Err(StartErr::Poisoned);
         }
 
-        // Find the function within the process.
-        let function = self
-            .indirect_table
-            .as_ref()
-            .and_then(|t| t.get(function_id).ok())
-            .and_then(|f| f)
-            .ok_or(StartErr::FunctionNotFound)?;
-
-        let params = params
-            .into_iter()
-            .map(wasmi::RuntimeValue::from)
-            .collect::<Vec<_>>();
-
-        let execution = match wasmi::FuncInstance::invoke_resumable(&function, params) {
-            Ok(e) => e,
-            Err(err) => unreachable!("{:?}", err),
-        };
-
+        let params = params.into_iter().collect::<Vec<_>>();
+        let execution = self.instance.start_function_by_id(function_id, &params)?;
         self.threads.push(ThreadState {
-            execution: Some(execution),
-            interrupted: false,
+            execution,
+            stack_pointer,
             user_data,
         });
 
@@ -439,27 +509,20 @@ impl<T> ProcessStateMachine<T> {
     fn start_thread_by_name(
         &mut self,
         symbol_name: &str,
-        params: impl Into<Cow<'static, [wasmi::RuntimeValue]>>,
+        params: &[WasmValue],
         user_data: T,
-    ) -> Result<Thread<T>, (StartErr, T)> {
+    ) -> Result<Thread<T, TEng>, (StartErr, T)> {
         if self.is_poisoned {
             return Err((StartErr::Poisoned, user_data));
         }
 
-        match self.module.export_by_name(symbol_name) {
-            Some(wasmi::ExternVal::Func(f)) => {
-                let execution = match wasmi::FuncInstance::invoke_resumable(&f, params) {
-                    Ok(e) => e,
-                    Err(err) => unreachable!("{:?}", err),
-                };
-                self.threads.push(ThreadState {
-                    execution: Some(execution),
-                    interrupted: false,
-                    user_data,
-                });
-            }
-            None => return Err((StartErr::FunctionNotFound, user_data)),
-            _ => return Err((StartErr::NotAFunction, user_data)),
+        match self.instance.start_function_by_name(symbol_name, params) {
+            Ok(execution) => self.threads.push(ThreadState {
+                execution,
+                stack_pointer: self.instance.stack_pointer(),
+                user_data,
+            }),
+            Err(err) => return Err((err, user_data)),
         }
 
         let thread_id = self.threads.len() - 1;
@@ -483,7 +546,7 @@ impl<T> ProcessStateMachine<T> {
     ///
     /// Returns `None` if the index is superior or equal to what
     /// [`num_threads`](ProcessStateMachine::num_threads) would return.
-    pub fn thread(&mut self, index: usize) -> Option<Thread<T>> {
+    pub fn thread(&mut self, index: usize) -> Option<Thread<T, TEng>> {
         if index < self.threads.len() {
             Some(Thread { vm: self, index })
         } else {
@@ -500,39 +563,18 @@ impl<T> ProcessStateMachine<T> {
     ///
     /// Returns an error if the range is invalid or out of range.
     pub fn read_memory(&self, offset: u32, size: u32) -> Result<Vec<u8>, ()> {
-        let mem = match self.memory.as_ref() {
-            Some(m) => m,
-            None => unreachable!(),
-        };
-
-        mem.get(offset, size.try_into().map_err(|_| ())?)
-            .map_err(|_| ())
+        self.instance.read_memory(offset, size)
     }
 
     /// Write the data at the given memory location.
     ///
     /// Returns an error if the range is invalid or out of range.
     pub fn write_memory(&mut self, offset: u32, value: &[u8]) -> Result<(), ()> {
-        let mem = match self.memory.as_ref() {
-            Some(m) => m,
-            None => unreachable!(),
-        };
-
-        mem.set(offset, value).map_err(|_| ())
+        self.instance.write_memory(offset, value)
     }
 }
 
-// The fields related to `wasmi` do not implement `Send` because they use `std::rc::Rc`. `Rc`
-// does not implement `Send` because incrementing/decrementing the reference counter from
-// multiple threads simultaneously would be racy. It is however perfectly sound to move all the
-// instances of `Rc`s at once between threads, which is what we're doing here.
-//
-// This importantly means that we should never return a `Rc` (even by reference) across the API
-// boundary.
-// TODO: really annoying to have to use unsafe code
-unsafe impl<T: Send> Send for ProcessStateMachine<T> {}
-
-impl<T> fmt::Debug for ProcessStateMachine<T>
+impl<T, TEng: Engine> fmt::Debug for ProcessStateMachine<T, TEng>
 where
     T: fmt::Debug,
 {
@@ -541,7 +583,7 @@ where
     }
 }
 
-impl<T> fmt::Debug for ThreadState<T>
+impl<T, TEng: Engine> fmt::Debug for ThreadState<T, TEng>
 where
     T: fmt::Debug,
 {
@@ -550,74 +592,47 @@ where
     }
 }
 
-impl<'a, T> Thread<'a, T> {
+impl<'a, T, TEng: Engine> Thread<'a, T, TEng> {
     /// Starts or continues execution of this thread.
     ///
     /// If this is the first call you call [`run`](Thread::run) for this thread, then you must pass
     /// a value of `None`.
     /// If, however, you call this function after a previous call to [`run`](Thread::run) that was
     /// interrupted by an external function call, then you must pass back the outcome of that call.
-    pub fn run(mut self, value: Option<WasmValue>) -> Result<ExecOutcome<'a, T>, RunErr> {
-        struct DummyExternals;
-        impl wasmi::Externals for DummyExternals {
-            fn invoke_index(
-                &mut self,
-                index: usize,
-                args: wasmi::RuntimeArgs,
-            ) -> Result<Option<wasmi::RuntimeValue>, wasmi::Trap> {
-                Err(wasmi::TrapKind::Host(Box::new(Interrupt {
-                    index,
-                    args: args.as_ref().to_vec(),
-                }))
-                .into())
-            }
-        }
-
-        #[derive(Debug)]
-        struct Interrupt {
-            index: usize,
-            args: Vec<wasmi::RuntimeValue>,
-        }
-        impl fmt::Display for Interrupt {
-            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                write!(f, "Interrupt")
-            }
-        }
-        impl wasmi::HostError for Interrupt {}
-
+    /// If the previous call was preempted, you must pass `None`.
+    ///
+    /// If `fuel` is `Some`, the execution is paused and [`ExecOutcome::Preempted`] is returned
+    /// once this amount of fuel has been consumed. If `fuel` is `None`, the thread runs for as
+    /// long as necessary.
+    pub fn run(
+        self,
+        value: Option<WasmValue>,
+        fuel: Option<u64>,
+    ) -> Result<ExecOutcome<'a, T, TEng>, RunErr> {
         if self.vm.is_poisoned {
             return Err(RunErr::Poisoned);
         }
 
-        let thread_state = &mut self.vm.threads[self.index];
+        let outcome = {
+            let vm = &mut *self.vm;
+            let thread = &mut vm.threads[self.index];
 
-        let mut execution = match thread_state.execution.take() {
-            Some(e) => e,
-            None => unreachable!(),
-        };
-        let result = if thread_state.interrupted {
-            let expected_ty = execution.resumable_value_type().map(ValueType::from);
-            let obtained_ty = value.as_ref().map(|v| v.ty());
-            if expected_ty != obtained_ty {
-                return Err(RunErr::BadValueTy {
-                    expected: expected_ty,
-                    obtained: obtained_ty,
-                });
+            if let Some(stack_pointer) = thread.stack_pointer {
+                vm.instance.set_stack_pointer(stack_pointer);
             }
-            execution.resume_execution(value.map(From::from), &mut DummyExternals)
-        } else {
-            if value.is_some() {
-                return Err(RunErr::BadValueTy {
-                    expected: None,
-                    obtained: value.as_ref().map(|v| v.ty()),
-                });
+
+            let outcome = vm.instance.run(&mut thread.execution, value, fuel)?;
+
+            // Save the stack pointer in case another thread runs before this one resumes.
+            if thread.stack_pointer.is_some() {
+                thread.stack_pointer = vm.instance.stack_pointer();
             }
-            thread_state.interrupted = true;
-            execution.start_execution(&mut DummyExternals)
+
+            outcome
         };
 
-        match result {
-            Ok(return_value) => {
+        match outcome {
+            EngineRunOutcome::Finished(return_value) => {
                 let user_data = self.vm.threads.remove(self.index).user_data;
                 // If this is the "main" function, the state machine is now poisoned.
                 if self.index == 0 {
@@ -625,32 +640,21 @@ impl<'a, T> Thread<'a, T> {
                 }
                 Ok(ExecOutcome::ThreadFinished {
                     thread_index: self.index,
-                    return_value: return_value.map(From::from),
+                    return_value,
                     user_data,
                 })
             }
-            Err(wasmi::ResumableError::AlreadyStarted) => unreachable!(),
-            Err(wasmi::ResumableError::NotResumable) => unreachable!(),
-            Err(wasmi::ResumableError::Trap(ref trap)) if trap.kind().is_host() => {
-                let interrupt: &Interrupt = match trap.kind() {
-                    wasmi::TrapKind::Host(err) => match err.downcast_ref() {
-                        Some(e) => e,
-                        None => unreachable!(),
-                    },
-                    _ => unreachable!(),
-                };
-                thread_state.execution = Some(execution);
-                Ok(ExecOutcome::Interrupted {
-                    thread: self,
-                    id: interrupt.index,
-                    params: interrupt.args.iter().map(|v| From::from(*v)).collect(),
-                })
-            }
-            Err(wasmi::ResumableError::Trap(trap)) => {
+            EngineRunOutcome::Interrupted { id, params } => Ok(ExecOutcome::Interrupted {
+                thread: self,
+                id,
+                params,
+            }),
+            EngineRunOutcome::Preempted => Ok(ExecOutcome::Preempted { thread: self }),
+            EngineRunOutcome::Errored(error) => {
                 self.vm.is_poisoned = true;
                 Ok(ExecOutcome::Errored {
                     thread: self,
-                    error: trap,
+                    error,
                 })
             }
         }
@@ -675,7 +679,7 @@ impl<'a, T> Thread<'a, T> {
     }
 }
 
-impl<'a, T> fmt::Debug for Thread<'a, T>
+impl<'a, T, TEng: Engine> fmt::Debug for Thread<'a, T, TEng>
 where
     T: fmt::Debug,
 {
@@ -690,6 +694,10 @@ impl fmt::Display for NewErr {
             NewErr::Interpreter(err) => write!(f, "Error in the interpreter: {}", err),
             NewErr::StartNotFound => write!(f, "The \"start\" symbol doesn't exist"),
             NewErr::StartIsntAFunction => write!(f, "The \"start\" symbol must be a function"),
+            NewErr::StartBadSignature => write!(
+                f,
+                "The \"start\" symbol must be a function that doesn't take any parameter"
+            ),
             NewErr::MemoryIsntMemory => {
                 write!(f, "If a \"memory\" symbol is provided, it must be a memory")
             }
@@ -700,6 +708,26 @@ impl fmt::Display for NewErr {
                 f,
                 "If a \"__indirect_function_table\" symbol is provided, it must be a table"
             ),
+            NewErr::MemoryLimitExceeded => {
+                write!(f, "The initial size of the memory is above the limit")
+            }
+            NewErr::TableLimitExceeded => {
+                write!(f, "The initial size of the table is above the limit")
+            }
+            NewErr::UnresolvedFunctionImport {
+                module_name,
+                function,
+            } => write!(f, "Couldn't resolve `{}`:`{}`", module_name, function),
+            NewErr::FunctionImportSignatureMismatch {
+                module_name,
+                function,
+                expected,
+                obtained,
+            } => write!(
+                f,
+                "Signature mismatch for `{}`:`{}`: expected {:?}, obtained {:?}",
+                module_name, function, expected, obtained
+            ),
         }
     }
 }
@@ -710,6 +738,7 @@ impl fmt::Display for StartErr {
             StartErr::Poisoned => write!(f, "State machine is in a poisoned state"),
             StartErr::FunctionNotFound => write!(f, "Function to start was not found"),
             StartErr::NotAFunction => write!(f, "Symbol to start is not a function"),
+            StartErr::BadSignature => write!(f, "Parameters don't match the function signature"),
         }
     }
 }
@@ -729,8 +758,8 @@ impl fmt::Display for RunErr {
 
 #[cfg(test)]
 mod tests {
-    use super::{ExecOutcome, NewErr, ProcessStateMachine};
-    use crate::primitives::WasmValue;
+    use super::{ExecOutcome, ImportErr, NewErr, ProcessLimits, ProcessStateMachine, TrapKind};
+    use crate::{primitives::WasmValue, sig};
 
     #[test]
     fn starts_if_main() {
@@ -744,7 +773,8 @@ mod tests {
         );
 
         let _state_machine =
-            ProcessStateMachine::new(&module, (), |_, _, _| unreachable!()).unwrap();
+            ProcessStateMachine::new(&module, Default::default(), (), |_, _, _| unreachable!())
+                .unwrap();
     }
 
     #[test]
@@ -758,12 +788,86 @@ mod tests {
         "#
         );
 
-        match ProcessStateMachine::new(&module, (), |_, _, _| unreachable!()) {
+        match ProcessStateMachine::new(&module, Default::default(), (), |_, _, _| unreachable!()) {
             Err(NewErr::StartNotFound) => {}
             _ => panic!(),
         }
     }
 
+    #[test]
+    fn error_if_main_takes_parameters() {
+        let module = from_wat!(
+            local,
+            r#"(module
+            (func $_start (param i32))
+            (export "_start" (func $_start)))
+        "#
+        );
+
+        match ProcessStateMachine::new(&module, Default::default(), (), |_, _, _| unreachable!()) {
+            Err(NewErr::StartBadSignature) => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn unresolved_import_reported() {
+        let module = from_wat!(
+            local,
+            r#"(module
+            (import "foo" "bar" (func $bar (param i32)))
+            (func $_start (result i32)
+                i32.const 5)
+            (export "_start" (func $_start)))
+        "#
+        );
+
+        match ProcessStateMachine::new(&module, Default::default(), (), |_, _, _| {
+            Err(ImportErr::NotFound)
+        }) {
+            Err(NewErr::UnresolvedFunctionImport {
+                module_name,
+                function,
+            }) => {
+                assert_eq!(module_name, "foo");
+                assert_eq!(function, "bar");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn import_signature_mismatch_reported() {
+        let module = from_wat!(
+            local,
+            r#"(module
+            (import "foo" "bar" (func $bar (param i32)))
+            (func $_start (result i32)
+                i32.const 5)
+            (export "_start" (func $_start)))
+        "#
+        );
+
+        match ProcessStateMachine::new(&module, Default::default(), (), |_, _, _| {
+            Err(ImportErr::BadSignature {
+                expected: sig!((I64)),
+            })
+        }) {
+            Err(NewErr::FunctionImportSignatureMismatch {
+                module_name,
+                function,
+                expected,
+                obtained,
+            }) => {
+                assert_eq!(module_name, "foo");
+                assert_eq!(function, "bar");
+                assert_eq!(expected, sig!((I64)));
+                assert_eq!(obtained, sig!((I32)));
+            }
+            _ => panic!(),
+        }
+    }
+
     #[test]
     fn main_executes() {
         let module = from_wat!(
@@ -776,8 +880,9 @@ mod tests {
         );
 
         let mut state_machine =
-            ProcessStateMachine::new(&module, (), |_, _, _| unreachable!()).unwrap();
-        match state_machine.thread(0).unwrap().run(None) {
+            ProcessStateMachine::new(&module, Default::default(), (), |_, _, _| unreachable!())
+                .unwrap();
+        match state_machine.thread(0).unwrap().run(None, None) {
             Ok(ExecOutcome::ThreadFinished {
                 return_value: Some(WasmValue::I32(5)),
                 ..
@@ -799,8 +904,9 @@ mod tests {
         "#
         );
 
-        let mut state_machine = ProcessStateMachine::new(&module, (), |_, _, _| Ok(9876)).unwrap();
-        match state_machine.thread(0).unwrap().run(None) {
+        let mut state_machine =
+            ProcessStateMachine::new(&module, Default::default(), (), |_, _, _| Ok(9876)).unwrap();
+        match state_machine.thread(0).unwrap().run(None, None) {
             Ok(ExecOutcome::Interrupted {
                 id: 9876,
                 ref params,
@@ -812,7 +918,7 @@ mod tests {
         match state_machine
             .thread(0)
             .unwrap()
-            .run(Some(WasmValue::I32(2227)))
+            .run(Some(WasmValue::I32(2227)), None)
         {
             Ok(ExecOutcome::ThreadFinished {
                 return_value: Some(WasmValue::I32(2227)),
@@ -835,9 +941,12 @@ mod tests {
         );
 
         let mut state_machine =
-            ProcessStateMachine::new(&module, (), |_, _, _| unreachable!()).unwrap();
-        match state_machine.thread(0).unwrap().run(None) {
-            Ok(ExecOutcome::Errored { .. }) => {}
+            ProcessStateMachine::new(&module, Default::default(), (), |_, _, _| unreachable!())
+                .unwrap();
+        match state_machine.thread(0).unwrap().run(None, None) {
+            Ok(ExecOutcome::Errored { error, .. }) => {
+                assert_eq!(*error.kind(), TrapKind::Unreachable)
+            }
             _ => panic!(),
         }
 
@@ -846,5 +955,175 @@ mod tests {
         // TODO: start running another function and check that `Poisoned` error is returned
     }
 
+    #[test]
+    #[cfg(feature = "backtraces")]
+    fn trap_backtrace() {
+        let module = from_wat!(
+            local,
+            r#"(module
+            (import "" "test" (func $test))
+            (func $_start
+                call $test
+                (drop (call $early_return))
+                call $trapping)
+            (func $early_return (result i32)
+                (block (return (i32.const 5)))
+                i32.const 6)
+            (func $trapping
+                unreachable)
+            (export "_start" (func $_start)))
+        "#
+        );
+
+        let mut state_machine =
+            ProcessStateMachine::new(&module, Default::default(), (), |_, _, _| Ok(1)).unwrap();
+        match state_machine.thread(0).unwrap().run(None, None) {
+            Ok(ExecOutcome::Interrupted { id: 1, .. }) => {}
+            _ => panic!(),
+        }
+
+        match state_machine.thread(0).unwrap().run(None, None) {
+            Ok(ExecOutcome::Errored { error, .. }) => {
+                assert_eq!(*error.kind(), TrapKind::Unreachable);
+                assert_eq!(error.backtrace(), &[3, 1]);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn preempted_then_resumed() {
+        let module = from_wat!(
+            local,
+            r#"(module
+            (func $_start (result i32)
+                (local $i i32)
+                (loop $loop
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $loop (i32.lt_u (local.get $i) (i32.const 10000))))
+                (local.get $i))
+            (export "_start" (func $_start)))
+        "#
+        );
+
+        let mut state_machine =
+            ProcessStateMachine::new(&module, Default::default(), (), |_, _, _| unreachable!())
+                .unwrap();
+
+        let mut num_preempted = 0;
+        loop {
+            match state_machine.thread(0).unwrap().run(None, Some(1000)) {
+                Ok(ExecOutcome::Preempted { .. }) => num_preempted += 1,
+                Ok(ExecOutcome::ThreadFinished {
+                    return_value: Some(WasmValue::I32(10000)),
+                    ..
+                }) => break,
+                _ => panic!(),
+            }
+        }
+
+        assert!(num_preempted >= 10);
+        assert!(state_machine.thread(0).is_none());
+    }
+
+    #[test]
+    fn infinite_loop_preempted() {
+        let module = from_wat!(
+            local,
+            r#"(module
+            (func $_start
+                (loop $loop
+                    (br $loop)))
+            (export "_start" (func $_start)))
+        "#
+        );
+
+        let mut state_machine =
+            ProcessStateMachine::new(&module, Default::default(), (), |_, _, _| unreachable!())
+                .unwrap();
+        for _ in 0..5 {
+            match state_machine.thread(0).unwrap().run(None, Some(10_000)) {
+                Ok(ExecOutcome::Preempted { .. }) => {}
+                _ => panic!(),
+            }
+        }
+        assert!(!state_machine.is_poisoned());
+    }
+
+    #[test]
+    fn memory_initial_size_above_limit() {
+        let module = from_wat!(
+            local,
+            r#"(module
+            (memory (export "memory") 4)
+            (func $_start)
+            (export "_start" (func $_start)))
+        "#
+        );
+
+        let limits = ProcessLimits {
+            max_memory_pages: Some(2),
+            ..Default::default()
+        };
+        match ProcessStateMachine::new(&module, limits, (), |_, _, _| unreachable!()) {
+            Err(NewErr::MemoryLimitExceeded) => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn memory_grow_within_limit() {
+        let module = from_wat!(
+            local,
+            r#"(module
+            (memory (export "memory") 1)
+            (func $_start (result i32)
+                (memory.grow (i32.const 1)))
+            (export "_start" (func $_start)))
+        "#
+        );
+
+        let limits = ProcessLimits {
+            max_memory_pages: Some(2),
+            ..Default::default()
+        };
+        let mut state_machine =
+            ProcessStateMachine::new(&module, limits, (), |_, _, _| unreachable!()).unwrap();
+        match state_machine.thread(0).unwrap().run(None, None) {
+            Ok(ExecOutcome::ThreadFinished {
+                return_value: Some(WasmValue::I32(1)),
+                ..
+            }) => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn memory_grow_above_limit() {
+        let module = from_wat!(
+            local,
+            r#"(module
+            (memory (export "memory") 1)
+            (func $_start (result i32)
+                (memory.grow (i32.const 4)))
+            (export "_start" (func $_start)))
+        "#
+        );
+
+        let limits = ProcessLimits {
+            max_memory_pages: Some(2),
+            ..Default::default()
+        };
+        let mut state_machine =
+            ProcessStateMachine::new(&module, limits, (), |_, _, _| unreachable!()).unwrap();
+        match state_machine.thread(0).unwrap().run(None, None) {
+            Ok(ExecOutcome::Errored { error, .. }) => {
+                assert_eq!(*error.kind(), TrapKind::MemoryLimitExceeded)
+            }
+            _ => panic!(),
+        }
+        assert!(state_machine.is_poisoned());
+    }
+
     // TODO: start mutiple threads
 }
@@ -0,0 +1,142 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Abstraction over the engine that executes WebAssembly code.
+//!
+//! The [`ProcessStateMachine`](super::ProcessStateMachine) doesn't execute code by itself, and
+//! instead relies on an implementation of the [`Engine`] trait. This makes it possible to swap
+//! the interpreter currently in use for a different one, or for an ahead-of-time compiler.
+//!
+//! The [`Core`](crate::scheduler::Core) and the [`System`](crate::System) accept the engine to
+//! use as a generic parameter, which defaults to [`DefaultEngine`](super::DefaultEngine). For
+//! example, `SystemBuilder::<TExtr, WasmiEngine>::new` builds a [`System`](crate::System) that
+//! uses `wasmi`. The `keccak` benchmark is generic over the engine, and
+//! [`WasmiEngine`](super::WasmiEngine) is currently the only engine it measures.
+//!
+//! Engines receive the module after instrumentation, obtained with [`Module::instrumented`].
+//! They can be implemented outside of this crate.
+//!
+//! The engine is responsible for resolving the imports used for fuel metering and memory growth
+//! (see the [`fuel`](crate::module::fuel) and [`memory_grow`](crate::module::memory_grow)
+//! modules), and for enforcing the memory limit when the memory grows. If the `backtraces`
+//! feature is enabled, the imports injected by the `fuel` module also indicate when a function is
+//! entered or left, which lets the engine report a backtrace in the [`Trap`]s it returns.
+
+use super::{ImportErr, NewErr, ProcessLimits, RunErr, StartErr, Trap};
+use crate::{module::Module, primitives::Signature, WasmValue};
+
+use alloc::vec::Vec;
+
+/// Execution engine capable of instantiating a [`Module`].
+pub trait Engine {
+    /// Instantiated module, ready to execute code.
+    type Instance: EngineInstance;
+
+    /// Instantiates the given module.
+    ///
+    /// The closure is called for each import of the module, except for the imports used for
+    /// fuel metering and memory growth. It must assign a number to each import, or return an
+    /// error if the import can't be resolved. This number is later reported in
+    /// [`EngineRunOutcome::Interrupted`].
+    ///
+    /// The memory of the instance must never grow above
+    /// [`ProcessLimits::max_memory_pages`]. Note that the initial sizes of the memory and table
+    /// defined by the module have already been checked by the caller.
+    fn instantiate(
+        module: &Module,
+        limits: &ProcessLimits,
+        symbols: &mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ImportErr>,
+    ) -> Result<Self::Instance, NewErr>;
+}
+
+/// Instance of a module, created by [`Engine::instantiate`].
+pub trait EngineInstance {
+    /// Execution of a function within the instance. Holds the state of the stack and the
+    /// program counter.
+    type Execution;
+
+    /// Prepares the execution of the exported function with the given name. The execution
+    /// doesn't start before [`EngineInstance::run`] is called.
+    fn start_function_by_name(
+        &mut self,
+        symbol_name: &str,
+        params: &[WasmValue],
+    ) -> Result<Self::Execution, StartErr>;
+
+    /// Prepares the execution of the function with the given index within the indirect
+    /// function table. The execution doesn't start before [`EngineInstance::run`] is called.
+    fn start_function_by_id(
+        &mut self,
+        function_id: u32,
+        params: &[WasmValue],
+    ) -> Result<Self::Execution, StartErr>;
+
+    /// Starts or resumes the given execution.
+    ///
+    /// `value` must be `None` when starting the execution or resuming after
+    /// [`EngineRunOutcome::Preempted`], and must be the return value of the external function
+    /// when resuming after [`EngineRunOutcome::Interrupted`].
+    ///
+    /// If `fuel` is `Some`, the execution must be paused and [`EngineRunOutcome::Preempted`]
+    /// returned once this amount of fuel has been consumed.
+    fn run(
+        &mut self,
+        execution: &mut Self::Execution,
+        value: Option<WasmValue>,
+        fuel: Option<u64>,
+    ) -> Result<EngineRunOutcome, RunErr>;
+
+    /// Returns the current value of the stack pointer of the module, or `None` if the module
+    /// doesn't have any.
+    ///
+    /// See the [`stack_pointer`](crate::module::stack_pointer) module.
+    fn stack_pointer(&self) -> Option<i32>;
+
+    /// Sets the value of the stack pointer of the module. Has no effect if the module doesn't
+    /// have any stack pointer.
+    fn set_stack_pointer(&mut self, value: i32);
+
+    /// Copies the given memory range into a `Vec<u8>`.
+    ///
+    /// Returns an error if the range is invalid or out of range.
+    fn read_memory(&self, offset: u32, size: u32) -> Result<Vec<u8>, ()>;
+
+    /// Write the data at the given memory location.
+    ///
+    /// Returns an error if the range is invalid or out of range.
+    fn write_memory(&mut self, offset: u32, value: &[u8]) -> Result<(), ()>;
+}
+
+/// Outcome of [`EngineInstance::run`].
+#[derive(Debug)]
+pub enum EngineRunOutcome {
+    /// The function has returned. The execution can't be resumed anymore.
+    Finished(Option<WasmValue>),
+
+    /// The execution has been paused due to a call to an external function.
+    Interrupted {
+        /// Identifier of the function, as returned by the closure passed to
+        /// [`Engine::instantiate`].
+        id: usize,
+        /// Parameters of the function call.
+        params: Vec<WasmValue>,
+    },
+
+    /// The execution has been paused because it has consumed all the fuel.
+    Preempted,
+
+    /// The execution has stopped because of an error. The execution can't be resumed anymore.
+    Errored(Trap),
+}
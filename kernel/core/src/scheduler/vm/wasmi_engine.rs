@@ -0,0 +1,631 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Implementation of the [`Engine`] trait using the `wasmi` interpreter.
+
+use super::{
+    engine::{Engine, EngineInstance, EngineRunOutcome},
+    ImportErr, NewErr, ProcessLimits, RunErr, StartErr, Trap, TrapKind,
+};
+use crate::{
+    module::{fuel, memory_grow, stack_pointer, Module},
+    primitives::Signature,
+    ValueType, WasmValue,
+};
+
+use alloc::{borrow::ToOwned as _, boxed::Box, string::ToString as _, vec::Vec};
+use core::{
+    cell::RefCell,
+    convert::{TryFrom as _, TryInto},
+    fmt,
+};
+
+/// [`Engine`] that uses the `wasmi` interpreter.
+#[derive(Debug)]
+pub enum WasmiEngine {}
+
+/// Instance of a module within `wasmi`.
+pub struct WasmiInstance {
+    /// Original module, with resolved imports.
+    module: wasmi::ModuleRef,
+
+    /// Memory of the module instantiation.
+    ///
+    /// Right now we only support one unique `Memory` object per process. This is it.
+    /// Contains `None` if the process doesn't export any memory object, which means it doesn't use
+    /// any memory.
+    memory: Option<wasmi::MemoryRef>,
+
+    /// Table of the indirect function calls.
+    ///
+    /// In WASM, function pointers are in reality indices in a table called
+    /// `__indirect_function_table`. This is this table, if it exists.
+    indirect_table: Option<wasmi::TableRef>,
+
+    /// Global containing the stack pointer, if any.
+    stack_pointer: Option<wasmi::GlobalRef>,
+
+    /// Maximum number of pages of the memory.
+    max_memory_pages: Option<u32>,
+}
+
+/// Execution of a function within a [`WasmiInstance`].
+pub struct WasmiExecution {
+    /// Execution context. This notably holds the program counter, state of the stack, and so on.
+    invocation: wasmi::FuncInvocation<'static>,
+
+    /// If false, then one must call `invocation.start_execution()` instead of
+    /// `resume_execution()`. This is a particularity of the WASM interpreter that we don't want
+    /// to expose in our API.
+    interrupted: bool,
+
+    /// Indices of the functions currently being executed, starting with the outermost one.
+    /// Maintained by the calls to the imports injected by the [`fuel`] module.
+    call_stack: Vec<u32>,
+}
+
+// The fields related to `wasmi` do not implement `Send` because they use `std::rc::Rc`. `Rc`
+// does not implement `Send` because incrementing/decrementing the reference counter from
+// multiple threads simultaneously would be racy. It is however perfectly sound to move all the
+// instances of `Rc`s at once between threads, which is what the `ProcessStateMachine` does, as
+// it owns both the instance and all of its executions.
+//
+// This importantly means that we should never return a `Rc` (even by reference) across the API
+// boundary.
+// TODO: really annoying to have to use unsafe code
+unsafe impl Send for WasmiInstance {}
+unsafe impl Send for WasmiExecution {}
+
+impl Engine for WasmiEngine {
+    type Instance = WasmiInstance;
+
+    fn instantiate(
+        module: &Module,
+        limits: &ProcessLimits,
+        symbols: &mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ImportErr>,
+    ) -> Result<WasmiInstance, NewErr> {
+        struct ImportResolve<'a> {
+            func: RefCell<&'a mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ImportErr>>,
+            memory: RefCell<&'a mut Option<wasmi::MemoryRef>>,
+            max_memory_pages: Option<u32>,
+            /// If a function import fails to resolve, the error to return instead of the generic
+            /// error generated by `wasmi`.
+            import_error: RefCell<Option<NewErr>>,
+        }
+
+        impl<'a> wasmi::ImportResolver for ImportResolve<'a> {
+            fn resolve_func(
+                &self,
+                module_name: &str,
+                field_name: &str,
+                signature: &wasmi::Signature,
+            ) -> Result<wasmi::FuncRef, wasmi::Error> {
+                if module_name == fuel::FUEL_IMPORT_MODULE
+                    && field_name == fuel::FUEL_IMPORT_FUNCTION
+                {
+                    return Ok(wasmi::FuncInstance::alloc_host(
+                        signature.clone(),
+                        FUEL_FUNCTION_INDEX,
+                    ));
+                }
+
+                if module_name == fuel::FUEL_IMPORT_MODULE
+                    && field_name == fuel::FUNCTION_ENTER_IMPORT_FUNCTION
+                {
+                    return Ok(wasmi::FuncInstance::alloc_host(
+                        signature.clone(),
+                        FUNCTION_ENTER_FUNCTION_INDEX,
+                    ));
+                }
+
+                if module_name == fuel::FUEL_IMPORT_MODULE
+                    && field_name == fuel::FUNCTION_LEAVE_IMPORT_FUNCTION
+                {
+                    return Ok(wasmi::FuncInstance::alloc_host(
+                        signature.clone(),
+                        FUNCTION_LEAVE_FUNCTION_INDEX,
+                    ));
+                }
+
+                if module_name == memory_grow::MEMORY_GROW_IMPORT_MODULE
+                    && field_name == memory_grow::MEMORY_GROW_IMPORT_FUNCTION
+                {
+                    return Ok(wasmi::FuncInstance::alloc_host(
+                        signature.clone(),
+                        MEMORY_GROW_FUNCTION_INDEX,
+                    ));
+                }
+
+                let closure = &mut **self.func.borrow_mut();
+                let obtained = Signature::from(signature);
+                let index = match closure(module_name, field_name, &obtained) {
+                    Ok(i) => i,
+                    Err(err) => {
+                        let error = match err {
+                            ImportErr::NotFound => NewErr::UnresolvedFunctionImport {
+                                module_name: module_name.to_owned(),
+                                function: field_name.to_owned(),
+                            },
+                            ImportErr::BadSignature { expected } => {
+                                NewErr::FunctionImportSignatureMismatch {
+                                    module_name: module_name.to_owned(),
+                                    function: field_name.to_owned(),
+                                    expected,
+                                    obtained,
+                                }
+                            }
+                        };
+                        let message = error.to_string();
+                        *self.import_error.borrow_mut() = Some(error);
+                        return Err(wasmi::Error::Instantiation(message));
+                    }
+                };
+
+                Ok(wasmi::FuncInstance::alloc_host(signature.clone(), index))
+            }
+
+            fn resolve_global(
+                &self,
+                _module_name: &str,
+                _field_name: &str,
+                _global_type: &wasmi::GlobalDescriptor,
+            ) -> Result<wasmi::GlobalRef, wasmi::Error> {
+                Err(wasmi::Error::Instantiation(
+                    "Importing globals is not supported yet".to_owned(),
+                ))
+            }
+
+            fn resolve_memory(
+                &self,
+                _module_name: &str,
+                _field_name: &str,
+                memory_type: &wasmi::MemoryDescriptor,
+            ) -> Result<wasmi::MemoryRef, wasmi::Error> {
+                let mut mem = self.memory.borrow_mut();
+                if mem.is_some() {
+                    return Err(wasmi::Error::Instantiation(
+                        "Only one memory object is supported yet".to_owned(),
+                    ));
+                }
+
+                if self
+                    .max_memory_pages
+                    .map_or(false, |max| memory_type.initial() > max)
+                {
+                    return Err(wasmi::Error::Instantiation(
+                        "Memory limit exceeded".to_owned(),
+                    ));
+                }
+
+                let new_mem = wasmi::MemoryInstance::alloc(
+                    wasmi::memory_units::Pages(usize::try_from(memory_type.initial()).unwrap()),
+                    memory_type
+                        .maximum()
+                        .map(|p| wasmi::memory_units::Pages(usize::try_from(p).unwrap())),
+                )
+                .unwrap();
+                **mem = Some(new_mem.clone());
+                Ok(new_mem)
+            }
+
+            fn resolve_table(
+                &self,
+                _module_name: &str,
+                _field_name: &str,
+                _table_type: &wasmi::TableDescriptor,
+            ) -> Result<wasmi::TableRef, wasmi::Error> {
+                Err(wasmi::Error::Instantiation(
+                    "Importing tables is not supported yet".to_owned(),
+                ))
+            }
+        }
+
+        let compiled = wasmi::Module::from_parity_wasm_module(module.instrumented().clone())
+            .map_err(|err| NewErr::Interpreter(err.to_string()))?;
+
+        let (not_started, imported_memory) = {
+            let mut imported_memory = None;
+            let resolve = ImportResolve {
+                func: RefCell::new(symbols),
+                memory: RefCell::new(&mut imported_memory),
+                max_memory_pages: limits.max_memory_pages,
+                import_error: RefCell::new(None),
+            };
+            let not_started = wasmi::ModuleInstance::new(&compiled, &resolve).map_err(|err| {
+                resolve
+                    .import_error
+                    .borrow_mut()
+                    .take()
+                    .unwrap_or_else(|| NewErr::Interpreter(err.to_string()))
+            })?;
+            (not_started, imported_memory)
+        };
+
+        // TODO: WASM has a special "start" instruction that can be used to designate a function
+        // that must be executed before the module is considered initialized. It is unclear whether
+        // this is intended to be a function that for example initializes global variables, or if
+        // this is an equivalent of "_start". In practice, Rust never seems to generate such as
+        // "start" instruction, so for now we ignore it. The code below panics if there is such
+        // a "start" item, so we will fortunately not blindly run into troubles.
+        let module = not_started.assert_no_start();
+
+        let memory = if let Some(imported_mem) = imported_memory {
+            if module
+                .export_by_name("memory")
+                .map_or(false, |m| m.as_memory().is_some())
+            {
+                return Err(NewErr::MultipleMemoriesNotSupported);
+            }
+            Some(imported_mem)
+        } else if let Some(mem) = module.export_by_name("memory") {
+            if let Some(mem) = mem.as_memory() {
+                Some(mem.clone())
+            } else {
+                return Err(NewErr::MemoryIsntMemory);
+            }
+        } else {
+            None
+        };
+
+        let indirect_table = if let Some(tbl) = module.export_by_name("__indirect_function_table") {
+            if let Some(tbl) = tbl.as_table() {
+                Some(tbl.clone())
+            } else {
+                return Err(NewErr::IndirectTableIsntTable);
+            }
+        } else {
+            None
+        };
+
+        let stack_pointer = module
+            .export_by_name(stack_pointer::STACK_POINTER_EXPORT)
+            .and_then(|g| g.as_global().cloned());
+
+        Ok(WasmiInstance {
+            module,
+            memory,
+            indirect_table,
+            stack_pointer,
+            max_memory_pages: limits.max_memory_pages,
+        })
+    }
+}
+
+impl EngineInstance for WasmiInstance {
+    type Execution = WasmiExecution;
+
+    fn start_function_by_name(
+        &mut self,
+        symbol_name: &str,
+        params: &[WasmValue],
+    ) -> Result<WasmiExecution, StartErr> {
+        let function = match self.module.export_by_name(symbol_name) {
+            Some(wasmi::ExternVal::Func(f)) => f,
+            None => return Err(StartErr::FunctionNotFound),
+            _ => return Err(StartErr::NotAFunction),
+        };
+
+        start_function(&function, params)
+    }
+
+    fn start_function_by_id(
+        &mut self,
+        function_id: u32,
+        params: &[WasmValue],
+    ) -> Result<WasmiExecution, StartErr> {
+        let function = self
+            .indirect_table
+            .as_ref()
+            .and_then(|t| t.get(function_id).ok())
+            .and_then(|f| f)
+            .ok_or(StartErr::FunctionNotFound)?;
+
+        start_function(&function, params)
+    }
+
+    fn run(
+        &mut self,
+        execution: &mut WasmiExecution,
+        value: Option<WasmValue>,
+        fuel: Option<u64>,
+    ) -> Result<EngineRunOutcome, RunErr> {
+        let mut externals = Externals {
+            remaining_fuel: fuel,
+            memory: self.memory.as_ref(),
+            max_memory_pages: self.max_memory_pages,
+            call_stack: &mut execution.call_stack,
+        };
+
+        let result = if execution.interrupted {
+            let expected_ty = execution
+                .invocation
+                .resumable_value_type()
+                .map(ValueType::from);
+            let obtained_ty = value.as_ref().map(|v| v.ty());
+            if expected_ty != obtained_ty {
+                return Err(RunErr::BadValueTy {
+                    expected: expected_ty,
+                    obtained: obtained_ty,
+                });
+            }
+            execution
+                .invocation
+                .resume_execution(value.map(From::from), &mut externals)
+        } else {
+            if value.is_some() {
+                return Err(RunErr::BadValueTy {
+                    expected: None,
+                    obtained: value.as_ref().map(|v| v.ty()),
+                });
+            }
+            execution.interrupted = true;
+            execution.invocation.start_execution(&mut externals)
+        };
+
+        match result {
+            Ok(return_value) => Ok(EngineRunOutcome::Finished(return_value.map(From::from))),
+            Err(wasmi::ResumableError::AlreadyStarted) => unreachable!(),
+            Err(wasmi::ResumableError::NotResumable) => unreachable!(),
+            Err(wasmi::ResumableError::Trap(ref trap)) if trap.kind().is_host() => {
+                let host_error = match trap.kind() {
+                    wasmi::TrapKind::Host(err) => err,
+                    _ => unreachable!(),
+                };
+
+                if host_error.downcast_ref::<OutOfFuel>().is_some() {
+                    return Ok(EngineRunOutcome::Preempted);
+                }
+
+                if host_error.downcast_ref::<MemoryLimitExceeded>().is_some() {
+                    return Ok(EngineRunOutcome::Errored(Trap::new(
+                        TrapKind::MemoryLimitExceeded,
+                        backtrace(&execution.call_stack),
+                    )));
+                }
+
+                let interrupt: &Interrupt = match host_error.downcast_ref() {
+                    Some(e) => e,
+                    None => unreachable!(),
+                };
+                Ok(EngineRunOutcome::Interrupted {
+                    id: interrupt.index,
+                    params: interrupt.args.iter().map(|v| From::from(*v)).collect(),
+                })
+            }
+            Err(wasmi::ResumableError::Trap(trap)) => {
+                let kind = match trap.kind() {
+                    wasmi::TrapKind::Unreachable => TrapKind::Unreachable,
+                    wasmi::TrapKind::MemoryAccessOutOfBounds => TrapKind::MemoryAccessOutOfBounds,
+                    wasmi::TrapKind::TableAccessOutOfBounds => TrapKind::TableAccessOutOfBounds,
+                    wasmi::TrapKind::ElemUninitialized => TrapKind::ElemUninitialized,
+                    wasmi::TrapKind::DivisionByZero => TrapKind::DivisionByZero,
+                    wasmi::TrapKind::InvalidConversionToInt => TrapKind::InvalidConversionToInt,
+                    wasmi::TrapKind::StackOverflow => TrapKind::StackOverflow,
+                    wasmi::TrapKind::UnexpectedSignature => TrapKind::UnexpectedSignature,
+                    _ => TrapKind::Other(trap.to_string()),
+                };
+
+                // `wasmi` doesn't report the call stack at the time of the trap. We instead use
+                // the one maintained through the imports injected by the `fuel` module.
+                Ok(EngineRunOutcome::Errored(Trap::new(
+                    kind,
+                    backtrace(&execution.call_stack),
+                )))
+            }
+        }
+    }
+
+    fn stack_pointer(&self) -> Option<i32> {
+        match self.stack_pointer.as_ref()?.get() {
+            wasmi::RuntimeValue::I32(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn set_stack_pointer(&mut self, value: i32) {
+        if let Some(global) = self.stack_pointer.as_ref() {
+            // Can only fail if the type doesn't match, which has been checked when the module
+            // was parsed.
+            let _ = global.set(wasmi::RuntimeValue::I32(value));
+        }
+    }
+
+    fn read_memory(&self, offset: u32, size: u32) -> Result<Vec<u8>, ()> {
+        let mem = match self.memory.as_ref() {
+            Some(m) => m,
+            None => unreachable!(),
+        };
+
+        mem.get(offset, size.try_into().map_err(|_| ())?)
+            .map_err(|_| ())
+    }
+
+    fn write_memory(&mut self, offset: u32, value: &[u8]) -> Result<(), ()> {
+        let mem = match self.memory.as_ref() {
+            Some(m) => m,
+            None => unreachable!(),
+        };
+
+        mem.set(offset, value).map_err(|_| ())
+    }
+}
+
+/// Prepares the execution of the given function.
+///
+/// Returns an error if `params` don't match the signature of the function.
+fn start_function(
+    function: &wasmi::FuncRef,
+    params: &[WasmValue],
+) -> Result<WasmiExecution, StartErr> {
+    let params = params
+        .iter()
+        .map(|p| wasmi::RuntimeValue::from(*p))
+        .collect::<Vec<_>>();
+
+    let invocation = match wasmi::FuncInstance::invoke_resumable(function, params) {
+        Ok(e) => e,
+        Err(_) => return Err(StartErr::BadSignature),
+    };
+
+    Ok(WasmiExecution {
+        invocation,
+        interrupted: false,
+        call_stack: Vec::new(),
+    })
+}
+
+/// Turns the call stack of an execution into a backtrace, as expected by [`Trap::new`].
+fn backtrace(call_stack: &[u32]) -> Vec<u32> {
+    call_stack.iter().rev().copied().collect()
+}
+
+/// Implementation of [`wasmi::Externals`] passed to the interpreter.
+struct Externals<'a> {
+    remaining_fuel: Option<u64>,
+    memory: Option<&'a wasmi::MemoryRef>,
+    max_memory_pages: Option<u32>,
+    call_stack: &'a mut Vec<u32>,
+}
+
+impl<'a> wasmi::Externals for Externals<'a> {
+    fn invoke_index(
+        &mut self,
+        index: usize,
+        args: wasmi::RuntimeArgs,
+    ) -> Result<Option<wasmi::RuntimeValue>, wasmi::Trap> {
+        if index == FUEL_FUNCTION_INDEX {
+            let cost: u32 = args.nth_checked(0)?;
+            return self.consume_fuel(cost);
+        }
+
+        if index == FUNCTION_ENTER_FUNCTION_INDEX {
+            let cost: u32 = args.nth_checked(0)?;
+            let function_index: u32 = args.nth_checked(1)?;
+            // The function must be pushed before consuming the fuel, as the execution resumes
+            // after this call in case of preemption.
+            self.call_stack.push(function_index);
+            return self.consume_fuel(cost);
+        }
+
+        if index == FUNCTION_LEAVE_FUNCTION_INDEX {
+            self.call_stack.pop();
+            return Ok(None);
+        }
+
+        if index == MEMORY_GROW_FUNCTION_INDEX {
+            let additional: u32 = args.nth_checked(0)?;
+            // Since the `memory.grow` instructions have been replaced before the validation, a
+            // module without any memory can reach this code.
+            let memory = match self.memory {
+                Some(m) => m,
+                None => return Ok(Some(wasmi::RuntimeValue::I32(-1))),
+            };
+
+            let current = memory.current_size().0;
+            if let Some(max) = self.max_memory_pages {
+                let new_size = u64::try_from(current)
+                    .unwrap()
+                    .saturating_add(u64::from(additional));
+                if new_size > u64::from(max) {
+                    return Err(wasmi::TrapKind::Host(Box::new(MemoryLimitExceeded)).into());
+                }
+            }
+
+            // As defined by the specification, a failure to grow the memory returns `-1` rather
+            // than trapping.
+            let additional = wasmi::memory_units::Pages(
+                usize::try_from(additional).unwrap_or(usize::max_value()),
+            );
+            let result = match memory.grow(additional) {
+                Ok(previous) => i32::try_from(previous.0).unwrap_or(-1),
+                Err(_) => -1,
+            };
+            return Ok(Some(wasmi::RuntimeValue::I32(result)));
+        }
+
+        Err(wasmi::TrapKind::Host(Box::new(Interrupt {
+            index,
+            args: args.as_ref().to_vec(),
+        }))
+        .into())
+    }
+}
+
+impl<'a> Externals<'a> {
+    /// Subtracts `cost` from the remaining fuel, and returns an error if there isn't enough.
+    fn consume_fuel(&mut self, cost: u32) -> Result<Option<wasmi::RuntimeValue>, wasmi::Trap> {
+        let remaining_fuel = match self.remaining_fuel.as_mut() {
+            Some(f) => f,
+            None => return Ok(None),
+        };
+
+        match remaining_fuel.checked_sub(u64::from(cost)) {
+            Some(f) => {
+                *remaining_fuel = f;
+                Ok(None)
+            }
+            None => {
+                *remaining_fuel = 0;
+                Err(wasmi::TrapKind::Host(Box::new(OutOfFuel)).into())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MemoryLimitExceeded;
+impl fmt::Display for MemoryLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Memory limit exceeded")
+    }
+}
+impl wasmi::HostError for MemoryLimitExceeded {}
+
+#[derive(Debug)]
+struct OutOfFuel;
+impl fmt::Display for OutOfFuel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OutOfFuel")
+    }
+}
+impl wasmi::HostError for OutOfFuel {}
+
+#[derive(Debug)]
+struct Interrupt {
+    index: usize,
+    args: Vec<wasmi::RuntimeValue>,
+}
+impl fmt::Display for Interrupt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Interrupt")
+    }
+}
+impl wasmi::HostError for Interrupt {}
+
+/// Index passed to `wasmi` for the function used for fuel metering. Must not conflict with any of
+/// the indices returned by the user when resolving imports.
+const FUEL_FUNCTION_INDEX: usize = usize::max_value();
+
+/// Index passed to `wasmi` for the function that replaces the `memory.grow` instruction. Must not
+/// conflict with any of the indices returned by the user when resolving imports.
+const MEMORY_GROW_FUNCTION_INDEX: usize = usize::max_value() - 1;
+
+/// Index passed to `wasmi` for the function called when entering a function. Must not conflict
+/// with any of the indices returned by the user when resolving imports.
+const FUNCTION_ENTER_FUNCTION_INDEX: usize = usize::max_value() - 2;
+
+/// Index passed to `wasmi` for the function called when leaving a function. Must not conflict
+/// with any of the indices returned by the user when resolving imports.
+const FUNCTION_LEAVE_FUNCTION_INDEX: usize = usize::max_value() - 3;
@@ -21,31 +21,47 @@
 //! TODO: more details
 //!
 //! - `interface`.
+//! - `process`. Programs started through this interface are loaded using the `loader` interface
+//!   when they are designated by their hash.
 //!
 
-use crate::extrinsics;
+use crate::extrinsics::{self, LaunchConfig};
 use crate::module::{Module, ModuleHash};
-use crate::scheduler::{self, Core, CoreBuilder, CoreRunOutcome, NewErr};
-use crate::InterfaceHash;
+use crate::scheduler::{
+    self, Core, CoreBuilder, CoreRunOutcome, DefaultEngine, Engine, NewErr, ProcessExitReason,
+    ProcessLimits,
+};
+use crate::{InterfaceHash, WasmValue};
 
+mod children;
 mod interfaces;
 mod pending_answers;
+mod restarts;
+mod threads;
 
-use alloc::{collections::VecDeque, format, vec::Vec};
-use core::{convert::TryFrom as _, fmt, iter, num::NonZeroU64, sync::atomic::Ordering};
+pub use self::interfaces::{StuckInterface, WaitingEmitter};
+pub use self::restarts::RestartPolicy;
+
+use alloc::{collections::VecDeque, format, string::String, sync::Arc, vec, vec::Vec};
+use core::{
+    convert::TryFrom as _, fmt, iter, num::NonZeroU64, sync::atomic::Ordering, time::Duration,
+};
 use crossbeam_queue::SegQueue;
 use hashbrown::{HashMap, HashSet};
 use nohash_hasher::BuildNoHashHasher;
-use redshirt_syscalls::{Decode, Encode, EncodedMessage, MessageId, Pid};
+use redshirt_syscalls::{Decode, Encode, EncodedMessage, MessageId, Pid, ThreadId};
 use spinning_top::Spinlock;
 
 /// Main struct that handles a system, including the scheduler, program loader,
 /// inter-process communication, and so on.
 ///
 /// See [the module-level documentation](super) for more information.
-pub struct System<TExtr: extrinsics::Extrinsics> {
+///
+/// The code of the programs is executed by the [`Engine`] passed as second generic parameter,
+/// which defaults to [`DefaultEngine`].
+pub struct System<TExtr: extrinsics::Extrinsics, TEng: Engine = DefaultEngine> {
     /// Inner system with inter-process communications.
-    core: Core<TExtr>,
+    core: Core<TExtr, TEng>,
 
     /// For each interface, which program is fulfilling it.
     interfaces: interfaces::Interfaces,
@@ -53,6 +69,15 @@ pub struct System<TExtr: extrinsics::Extrinsics> {
     /// Collection of messages that have been delivered but are waiting to be answered.
     pending_answers: pending_answers::PendingAnswers,
 
+    /// Secondary threads started through the `threads` interface.
+    threads: threads::Threads,
+
+    /// Processes started through the `process` interface.
+    children: children::Children,
+
+    /// Startup processes that must be restarted when they end.
+    restarts: restarts::Restarts,
+
     /// Total number of processes that have been spawned since initialization.
     num_processes_started: atomic::Atomic<u64>,
 
@@ -70,16 +95,28 @@ pub struct System<TExtr: extrinsics::Extrinsics> {
     // TODO: add timeout for loader interface availability?
     loader_registration_id: atomic::Atomic<Option<usize>>,
 
-    /// List of programs to load if the loader interface handler is available.
-    programs_to_load: SegQueue<ModuleHash>,
+    /// List of programs to load if the loader interface handler is available, with the
+    /// configuration to start them with.
+    programs_to_load: SegQueue<(ModuleHash, LaunchConfig)>,
 
     /// "Virtual" pid for the process that sends messages towards the loader.
     load_source_virtual_pid: Pid,
 
-    /// Set of messages that we emitted of requests to load a program from the loader interface.
+    /// Messages that we emitted of requests to load a program from the loader interface, and
+    /// what to do with the program once loaded.
     /// All these messages expect a `redshirt_loader_interface::ffi::LoadResponse` as answer.
     // TODO: call shink_to_fit from time to time
-    loading_programs: Spinlock<HashSet<MessageId, BuildNoHashHasher<u64>>>,
+    loading_programs: Spinlock<HashMap<MessageId, LoadingProgram, BuildNoHashHasher<u64>>>,
+
+    /// Limits applied to processes started without any explicit limit.
+    default_process_limits: ProcessLimits,
+
+    /// Function that returns the current value of the monotonic clock.
+    monotonic_clock: Arc<dyn Fn() -> Duration + Send + Sync>,
+
+    /// If `Some`, [`System::run`] reports emitters that have been waiting for a handler for
+    /// longer than this duration.
+    stuck_emitter_warning_threshold: Option<Duration>,
 }
 
 #[derive(Debug)]
@@ -120,10 +157,21 @@ struct InterfaceRegistration {
     pending_accept: VecDeque<(MessageId, bool)>,
 }
 
+/// Program being loaded through the `loader` interface.
+struct LoadingProgram {
+    /// If the program has been requested through the `process` interface, the process that has
+    /// requested it and that becomes its parent.
+    parent: Option<Pid>,
+    /// Message on the `process` interface to answer once the program has been started, if any.
+    spawn_message_id: Option<MessageId>,
+    /// Launch configuration to pass to the program.
+    launch_config: LaunchConfig,
+}
+
 /// Prototype for a [`System`].
-pub struct SystemBuilder<TExtr: extrinsics::Extrinsics> {
+pub struct SystemBuilder<TExtr: extrinsics::Extrinsics, TEng: Engine = DefaultEngine> {
     /// Builder for the inner core.
-    core: CoreBuilder<TExtr>,
+    core: CoreBuilder<TExtr, TEng>,
 
     /// "Virtual" pid for the process that sends messages towards the loader.
     load_source_virtual_pid: Pid,
@@ -131,33 +179,54 @@ pub struct SystemBuilder<TExtr: extrinsics::Extrinsics> {
     /// Interfaces handled natively.
     native_interfaces: HashSet<InterfaceHash, fnv::FnvBuildHasher>,
 
-    /// List of programs to start executing immediately after construction.
-    startup_processes: Vec<Module>,
+    /// Interfaces on which only the processes that have been granted the right to do so can
+    /// emit messages.
+    restricted_interfaces: HashSet<InterfaceHash, fnv::FnvBuildHasher>,
+
+    /// List of programs to start executing immediately after construction, their limits, their
+    /// launch configuration, what to do when they end, and the interfaces they are allowed to
+    /// emit messages on. If `None`, the default limits are used.
+    startup_processes: Vec<(
+        Module,
+        Option<ProcessLimits>,
+        LaunchConfig,
+        RestartPolicy,
+        Vec<InterfaceHash>,
+    )>,
+
+    /// Same field as [`System::default_process_limits`].
+    default_process_limits: ProcessLimits,
 
     /// Same field as [`System::programs_to_load`].
-    programs_to_load: SegQueue<ModuleHash>,
+    programs_to_load: SegQueue<(ModuleHash, LaunchConfig)>,
+
+    /// Same field as [`System::monotonic_clock`].
+    monotonic_clock: Arc<dyn Fn() -> Duration + Send + Sync>,
+
+    /// Same field as [`System::stuck_emitter_warning_threshold`].
+    stuck_emitter_warning_threshold: Option<Duration>,
 }
 
 /// Event returned by [`System::run`].
-pub enum ExecuteOut<'a, TExtr: extrinsics::Extrinsics> {
+pub enum ExecuteOut<'a, TExtr: extrinsics::Extrinsics, TEng: Engine = DefaultEngine> {
     /// Event directly generated.
-    Direct(SystemRunOutcome<'a, TExtr>),
+    Direct(SystemRunOutcome<'a, TExtr, TEng>),
     /// Ready to execute a bit of a thread.
-    ReadyToRun(ReadyToRun<'a, TExtr>),
+    ReadyToRun(ReadyToRun<'a, TExtr, TEng>),
 }
 
 /// Ready to resume one of the threads of a process.
 #[must_use]
-pub struct ReadyToRun<'a, TExtr: extrinsics::Extrinsics> {
-    system: &'a System<TExtr>,
-    inner: scheduler::ReadyToRun<'a, TExtr>,
+pub struct ReadyToRun<'a, TExtr: extrinsics::Extrinsics, TEng: Engine = DefaultEngine> {
+    system: &'a System<TExtr, TEng>,
+    inner: scheduler::ReadyToRun<'a, TExtr, TEng>,
 }
 
-impl<'a, TExtr: extrinsics::Extrinsics> ReadyToRun<'a, TExtr> {
+impl<'a, TExtr: extrinsics::Extrinsics, TEng: Engine> ReadyToRun<'a, TExtr, TEng> {
     /// Performs the actual execution.
     ///
     /// Returns `None` if the execution doesn't lead to any event in particular.
-    pub fn run(self) -> Option<SystemRunOutcome<'a, TExtr>> {
+    pub fn run(self) -> Option<SystemRunOutcome<'a, TExtr, TEng>> {
         let system = self.system;
         self.inner
             .run()
@@ -167,20 +236,55 @@ impl<'a, TExtr: extrinsics::Extrinsics> ReadyToRun<'a, TExtr> {
 
 /// Outcome of running the [`System`] once.
 #[derive(Debug)]
-pub enum SystemRunOutcome<'a, TExtr: extrinsics::Extrinsics> {
+pub enum SystemRunOutcome<'a, TExtr: extrinsics::Extrinsics, TEng: Engine = DefaultEngine> {
     /// A program has ended, either successfully or after an error.
     ProgramFinished {
         /// Identifier of the process that has stopped.
         pid: Pid,
-        /// Either `Ok(())` if the main thread has ended, or the error that happened in the
-        /// process.
-        // TODO: change error type
-        outcome: Result<(), wasmi::Error>,
+        /// Why the process has stopped.
+        outcome: ProcessExitReason,
+    },
+
+    /// A program started with a [`RestartPolicy`] has ended earlier, and has now been started
+    /// again.
+    ///
+    /// The new process has to register its interfaces again, if any. Messages emitted on these
+    /// interfaces in the meanwhile are delivered to it once it has done so.
+    ProgramRestarted {
+        /// Identifier the process had before it ended. Was earlier reported in a
+        /// [`SystemRunOutcome::ProgramFinished`].
+        previous_pid: Pid,
+        /// Identifier of the new process.
+        pid: Pid,
+    },
+
+    /// A program started with a [`RestartPolicy`] has ended earlier, and starting it again has
+    /// failed. The program is no longer restarted.
+    ProgramRestartFailed {
+        /// Identifier the process had before it ended. Was earlier reported in a
+        /// [`SystemRunOutcome::ProgramFinished`].
+        previous_pid: Pid,
+        /// Why starting the program has failed.
+        error: NewErr,
     },
 
     /// A program has requested metrics from the kernel. Use the [`KernelDebugMetricsRequest`] to
     /// report them.
-    KernelDebugMetricsRequest(KernelDebugMetricsRequest<'a, TExtr>),
+    KernelDebugMetricsRequest(KernelDebugMetricsRequest<'a, TExtr, TEng>),
+
+    /// A program has been waiting for longer than the threshold passed to
+    /// [`SystemBuilder::with_stuck_emitter_warning`] for a handler of an interface to be
+    /// registered.
+    ///
+    /// Each emitted message is reported at most once.
+    StuckEmitter {
+        /// Interface that has no handler.
+        interface: InterfaceHash,
+        /// Identifier of the process that is waiting.
+        pid: Pid,
+        /// How long the process has been waiting for.
+        waiting_for: Duration,
+    },
 
     /// A program has emitted a message on a native interface.
     NativeInterfaceMessage {
@@ -196,17 +300,17 @@ pub enum SystemRunOutcome<'a, TExtr: extrinsics::Extrinsics> {
         message_id: Option<MessageId>,
 
         /// Body of the message. Extractable by calling [`NativeInterfaceMessage::extract`].
-        message: NativeInterfaceMessage<'a, TExtr>,
+        message: NativeInterfaceMessage<'a, TExtr, TEng>,
     },
 }
 
 /// See [`SystemRunOutcome::NativeInterfaceMessage::message`].
-pub struct NativeInterfaceMessage<'a, TExtr: extrinsics::Extrinsics> {
-    system: &'a System<TExtr>,
+pub struct NativeInterfaceMessage<'a, TExtr: extrinsics::Extrinsics, TEng: Engine = DefaultEngine> {
+    system: &'a System<TExtr, TEng>,
     message_id: MessageId,
 }
 
-impl<'a, TExtr: extrinsics::Extrinsics> NativeInterfaceMessage<'a, TExtr> {
+impl<'a, TExtr: extrinsics::Extrinsics, TEng: Engine> NativeInterfaceMessage<'a, TExtr, TEng> {
     /// Extracts the message and resumes the execution of the program.
     ///
     /// > **Note**: Since the program that has emitted the message can now resume when calling
@@ -223,30 +327,126 @@ impl<'a, TExtr: extrinsics::Extrinsics> NativeInterfaceMessage<'a, TExtr> {
     }
 }
 
-impl<'a, TExtr: extrinsics::Extrinsics> fmt::Debug for NativeInterfaceMessage<'a, TExtr> {
+impl<'a, TExtr: extrinsics::Extrinsics, TEng: Engine> fmt::Debug
+    for NativeInterfaceMessage<'a, TExtr, TEng>
+{
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_tuple("NativeInterfaceMessage").finish()
     }
 }
 
-impl<TExtr> System<TExtr>
+impl<TExtr, TEng> System<TExtr, TEng>
 where
     TExtr: extrinsics::Extrinsics,
+    TEng: Engine,
 {
     /// Start executing a program.
+    ///
+    /// The process is subject to the limits passed to
+    /// [`SystemBuilder::with_default_process_limits`].
     pub fn execute(&self, program: &Module) -> Result<Pid, NewErr> {
+        self.execute_with_limits(program, self.default_process_limits.clone())
+    }
+
+    /// Start executing a program, forbidding it from using more resources than the given limits.
+    ///
+    /// Returns an error if the program exceeds these limits right from the start. If the program
+    /// later attempts to go above the limits, it is stopped and a
+    /// [`SystemRunOutcome::ProgramFinished`] containing [`ProcessExitReason::OutOfMemory`] is
+    /// emitted.
+    pub fn execute_with_limits(
+        &self,
+        program: &Module,
+        limits: ProcessLimits,
+    ) -> Result<Pid, NewErr> {
+        self.execute_with_launch_config(program, limits, Default::default())
+    }
+
+    /// Same as [`execute_with_limits`](System::execute_with_limits), but additionally passes
+    /// the given arguments, environment variables, and pre-opened directories to the program.
+    pub fn execute_with_launch_config(
+        &self,
+        program: &Module,
+        limits: ProcessLimits,
+        launch_config: LaunchConfig,
+    ) -> Result<Pid, NewErr> {
+        self.execute_with_setup(program, limits, launch_config, |_| {})
+    }
+
+    /// Same as [`execute_with_launch_config`](System::execute_with_launch_config), but calls
+    /// `setup` with the [`Pid`] of the new process before any of its threads can run.
+    fn execute_with_setup(
+        &self,
+        program: &Module,
+        limits: ProcessLimits,
+        launch_config: LaunchConfig,
+        setup: impl FnOnce(Pid),
+    ) -> Result<Pid, NewErr> {
         self.num_processes_started.fetch_add(1, Ordering::Relaxed);
-        Ok(self.core.execute(program)?.0.pid())
+        Ok(self
+            .core
+            .execute_with_setup(program, limits, launch_config, setup)?
+            .0
+            .pid())
     }
 
     /// Runs the [`System`] once and returns the outcome.
     ///
+    /// The startup processes that are due to be restarted are restarted when this function is
+    /// called. The returned `Future` doesn't wake up by itself once the next restart is due. Use
+    /// [`System::next_restart`] to find out when to stop waiting for it and call this function
+    /// again.
+    ///
     /// > **Note**: For now, it can a long time for this `Future` to be `Ready` because it is also
     /// >           waiting for the native programs to produce events in case there's nothing to
     /// >           do. In other words, this function can be seen more as a generator that whose
     /// >           `Future` becomes `Ready` only when something needs to be notified.
     // TODO: revisit comment
-    pub async fn run<'a>(&'a self) -> ExecuteOut<'a, TExtr> {
+    pub async fn run<'a>(&'a self) -> ExecuteOut<'a, TExtr, TEng> {
+        // Note that this check is only performed when `run` is called, and not when the
+        // threshold is reached.
+        if let Some(threshold) = self.stuck_emitter_warning_threshold {
+            let now = (self.monotonic_clock)();
+            while let Some((interface, emitter)) =
+                self.interfaces.next_stuck_emitter(now, threshold)
+            {
+                // The process might have been killed in the meanwhile.
+                if self.core.process_by_id(emitter.pid).is_some() {
+                    return ExecuteOut::Direct(SystemRunOutcome::StuckEmitter {
+                        interface,
+                        pid: emitter.pid,
+                        waiting_for: emitter.waiting_for,
+                    });
+                }
+            }
+        }
+
+        // Same remark as above, processes are restarted only when `run` is called.
+        let now = (self.monotonic_clock)();
+        if let Some((previous_pid, mut supervised)) = self.restarts.next_due(now) {
+            supervised.started_at = now;
+            let module = supervised.module.clone();
+            let limits = supervised.limits.clone();
+            let launch_config = supervised.launch_config.clone();
+
+            // The grants are applied and the process is registered for restarts before it can
+            // emit any message or end.
+            let result = self.execute_with_setup(&module, limits, launch_config, |pid| {
+                for interface in &supervised.grants {
+                    self.interfaces.grant(interface.clone(), pid);
+                }
+                self.restarts.insert(pid, supervised);
+            });
+
+            return ExecuteOut::Direct(match result {
+                Ok(pid) => SystemRunOutcome::ProgramRestarted { previous_pid, pid },
+                Err(error) => SystemRunOutcome::ProgramRestartFailed {
+                    previous_pid,
+                    error,
+                },
+            });
+        }
+
         loop {
             match self.core.run().await {
                 scheduler::ExecuteOut::Direct(event) => {
@@ -267,26 +467,79 @@ where
     fn inner_event<'a>(
         &'a self,
         event: scheduler::CoreRunOutcome,
-    ) -> Option<SystemRunOutcome<'a, TExtr>> {
+    ) -> Option<SystemRunOutcome<'a, TExtr, TEng>> {
         match event {
             CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
-                // TODO: cancel interface registrations ; update loader_registration_id
-                // TODO: notify interface registrations of process destruction
+                // Release the interfaces that the process was handling, so that another
+                // process (for example a restarted instance of the same program) can register
+                // them again.
+                let unregistered = self.interfaces.unregister_process(pid);
+                if unregistered
+                    .interfaces
+                    .contains(&redshirt_loader_interface::ffi::INTERFACE)
+                {
+                    self.loader_registration_id.store(None, Ordering::Release);
+                }
 
-                for _message_id in self.pending_answers.drain_by_answerer(&pid) {
-                    // TODO: notify emitter of cancellation
+                // The process is dead, and `answer_message` ignores messages whose emitter no
+                // longer exists. We still answer in order to clean up the state in the core.
+                for query_message_id in unregistered.queries {
+                    self.core.answer_message(query_message_id, Err(()));
                 }
 
-                if outcome.is_ok() {
+                // Messages that the process was supposed to answer are answered with an error.
+                for message_id in self.pending_answers.drain_by_answerer(&pid) {
+                    self.answer_delivered_message(message_id, Err(()));
+                }
+
+                // Same as above, the process is dead but we clean up the state in the core.
+                for message_id in self.threads.remove_process(pid) {
+                    self.core.answer_message(message_id, Err(()));
+                }
+
+                self.interfaces.revoke_process(pid);
+
+                // Schedule a restart of the process, if necessary.
+                self.restarts
+                    .process_finished(pid, outcome.is_success(), (self.monotonic_clock)());
+
+                // Answer the processes waiting for this one through the `process` interface.
+                let status = match outcome {
+                    ProcessExitReason::Exited { code } => {
+                        redshirt_process_interface::ffi::ExitStatus::Exited(code)
+                    }
+                    ProcessExitReason::Killed => {
+                        redshirt_process_interface::ffi::ExitStatus::Killed
+                    }
+                    _ => redshirt_process_interface::ffi::ExitStatus::Crashed,
+                };
+                let waiters = self.children.process_finished(pid, status.clone());
+                for message_id in waiters {
+                    self.core
+                        .answer_message(message_id, Ok(Ok::<_, ()>(status.clone()).encode()));
+                }
+
+                if let ProcessExitReason::Exited { .. } = outcome {
                     self.num_processes_finished.fetch_add(1, Ordering::Relaxed);
                 } else {
                     self.num_processes_trap.fetch_add(1, Ordering::Relaxed);
                 }
 
-                return Some(SystemRunOutcome::ProgramFinished {
-                    pid,
-                    outcome: outcome.map(|_| ()).map_err(|err| err.into()),
-                });
+                return Some(SystemRunOutcome::ProgramFinished { pid, outcome });
+            }
+
+            // Messages on the interfaces handled by the kernel and on the native interfaces don't
+            // go through `self.interfaces`, and rights must therefore be checked here.
+            CoreRunOutcome::InterfaceMessage {
+                pid,
+                message_id,
+                interface,
+                ..
+            } if self.is_handled_by_kernel(&interface)
+                && !self.interfaces.is_emission_allowed(&interface, pid) =>
+            {
+                self.core.deny_interface_message(message_id);
+                None
             }
 
             CoreRunOutcome::InterfaceMessage {
@@ -304,10 +557,10 @@ where
 
                 match redshirt_interface_interface::ffi::InterfaceMessage::decode(message) {
                     Ok(redshirt_interface_interface::ffi::InterfaceMessage::Register(
-                        interface_hash,
+                        interface_hashes,
                     )) => {
                         // Set the process as interface handler, if possible.
-                        let result = self.set_interface_handler(&interface_hash, pid);
+                        let result = self.set_interface_handler(interface_hashes, pid);
 
                         let response =
                             redshirt_interface_interface::ffi::InterfaceRegisterResponse {
@@ -361,28 +614,86 @@ where
                             .remove(&answered_message_id, &pid)
                             .is_ok()
                         {
-                            // TODO: must handle emitter is native
-                            self.core.answer_message(
+                            // Messages emitted by the kernel itself, such as requests to
+                            // load programs, are intercepted by `answer_delivered_message`.
+                            // Other messages have been emitted by a process, and the answer,
+                            // even an error, is pushed to its queue of notifications.
+                            self.answer_delivered_message(
                                 answered_message_id,
                                 answer_bytes.map(EncodedMessage),
                             );
                         }
 
                         None
+                    }
+                    Ok(redshirt_interface_interface::ffi::InterfaceMessage::Unregister(
+                        registration_id,
+                    )) => {
+                        let result = self.interfaces.unregister(registration_id.into(), pid);
+                        if let Ok((interfaces, queries)) = &result {
+                            for query_message_id in queries {
+                                self.core.answer_message(*query_message_id, Err(()));
+                            }
 
-                        // TODO: reimplement
-                        /*if self.loading_programs.lock().remove(&message_id) {
-                            let redshirt_loader_interface::ffi::LoadResponse { result } =
-                                Decode::decode(answer.unwrap()).unwrap();
-                            // TODO: don't unwrap
-                            let module = Module::from_bytes(&result.expect("loader returned error"))
-                                .expect("module isn't proper wasm");
-                            self.num_processes_started.fetch_add(1, Ordering::Relaxed);
-                            match self.core.execute(&module) {
-                                Ok(_) => {}
-                                Err(_) => panic!(),
+                            if interfaces.contains(&redshirt_loader_interface::ffi::INTERFACE) {
+                                self.loader_registration_id.store(None, Ordering::Release);
                             }
-                        }*/
+                        }
+
+                        if needs_answer {
+                            let result = result.map(|_| ());
+                            self.core.answer_message(message_id, Ok(result.encode()));
+                        }
+                        None
+                    }
+                    Ok(redshirt_interface_interface::ffi::InterfaceMessage::Handover(
+                        registration_id,
+                        new_handler,
+                    )) => {
+                        let result = self.interfaces.allow_handover(
+                            registration_id.into(),
+                            pid,
+                            new_handler,
+                        );
+
+                        if needs_answer {
+                            self.core.answer_message(message_id, Ok(result.encode()));
+                        }
+                        None
+                    }
+                    Ok(redshirt_interface_interface::ffi::InterfaceMessage::Grant(
+                        registration_id,
+                        interface,
+                        grantee,
+                    )) => {
+                        let result = self.interfaces.grant_from_handler(
+                            registration_id.into(),
+                            pid,
+                            interface,
+                            grantee,
+                        );
+
+                        if needs_answer {
+                            self.core.answer_message(message_id, Ok(result.encode()));
+                        }
+                        None
+                    }
+                    Ok(redshirt_interface_interface::ffi::InterfaceMessage::Revoke(
+                        registration_id,
+                        interface,
+                        grantee,
+                    )) => {
+                        let result = self.interfaces.revoke_from_handler(
+                            registration_id.into(),
+                            pid,
+                            &interface,
+                            grantee,
+                        );
+
+                        if needs_answer {
+                            self.core.answer_message(message_id, Ok(result.encode()));
+                        }
+                        None
                     }
                     Err(_) => {
                         if needs_answer {
@@ -393,6 +704,129 @@ where
                 }
             }
 
+            CoreRunOutcome::InterfaceMessage {
+                pid,
+                needs_answer,
+                immediate: _,
+                message_id,
+                interface,
+            } if interface == redshirt_process_interface::ffi::INTERFACE => {
+                // Handling messages on the `process` interface.
+                let (_, message) = match self.core.accept_interface_message(message_id) {
+                    Some(v) => v,
+                    None => return None,
+                };
+
+                match redshirt_process_interface::ffi::ProcessMessage::decode(message) {
+                    Ok(redshirt_process_interface::ffi::ProcessMessage::Spawn(spawn)) => {
+                        self.spawn(
+                            pid,
+                            if needs_answer { Some(message_id) } else { None },
+                            spawn,
+                        );
+                    }
+                    Ok(redshirt_process_interface::ffi::ProcessMessage::Wait(_))
+                        if !needs_answer => {}
+                    Ok(redshirt_process_interface::ffi::ProcessMessage::Wait(child_pid)) => {
+                        match self.children.wait(child_pid, pid, message_id) {
+                            children::Wait::Finished(status) => {
+                                self.core
+                                    .answer_message(message_id, Ok(Ok::<_, ()>(status).encode()));
+                            }
+                            children::Wait::Pending => {}
+                            children::Wait::Unknown => {
+                                let response =
+                                    Err::<redshirt_process_interface::ffi::ExitStatus, ()>(());
+                                self.core.answer_message(message_id, Ok(response.encode()));
+                            }
+                        }
+                    }
+                    Ok(redshirt_process_interface::ffi::ProcessMessage::Kill(child_pid)) => {
+                        let result = if self.children.is_running_child(child_pid, pid) {
+                            // The process is reported as finished later, and the processes
+                            // waiting for it are answered at this moment.
+                            if let Some(process) = self.core.process_by_id(child_pid) {
+                                process.abort();
+                            }
+                            Ok::<(), ()>(())
+                        } else {
+                            Err(())
+                        };
+
+                        if needs_answer {
+                            self.core.answer_message(message_id, Ok(result.encode()));
+                        }
+                    }
+                    Ok(redshirt_process_interface::ffi::ProcessMessage::List) => {
+                        if needs_answer {
+                            let list = self.children.running_children(pid);
+                            self.core.answer_message(message_id, Ok(list.encode()));
+                        }
+                    }
+                    Err(_) => {
+                        if needs_answer {
+                            self.core.answer_message(message_id, Err(()));
+                        }
+                    }
+                }
+
+                None
+            }
+
+            CoreRunOutcome::ThreadFinished { pid, thread_id, .. } => {
+                for message_id in self.threads.thread_finished(thread_id, pid) {
+                    self.core
+                        .answer_message(message_id, Ok(Ok::<(), ()>(()).encode()));
+                }
+
+                None
+            }
+
+            CoreRunOutcome::InterfaceMessage {
+                pid,
+                needs_answer,
+                immediate: _,
+                message_id,
+                interface,
+            } if interface == redshirt_threads_interface::ffi::INTERFACE => {
+                // Handling messages on the `threads` interface.
+                let (_, message) = match self.core.accept_interface_message(message_id) {
+                    Some(v) => v,
+                    None => return None,
+                };
+
+                match redshirt_threads_interface::ffi::ThreadsMessage::decode(message) {
+                    Ok(redshirt_threads_interface::ffi::ThreadsMessage::New(new_thread)) => {
+                        let result = self.start_thread(pid, &new_thread);
+                        if needs_answer {
+                            self.core.answer_message(message_id, Ok(result.encode()));
+                        }
+                    }
+                    Ok(redshirt_threads_interface::ffi::ThreadsMessage::Join(_))
+                        if !needs_answer => {}
+                    Ok(redshirt_threads_interface::ffi::ThreadsMessage::Join(thread_id)) => {
+                        match self.threads.join(thread_id, pid, message_id) {
+                            threads::Join::Finished => {
+                                self.core
+                                    .answer_message(message_id, Ok(Ok::<(), ()>(()).encode()));
+                            }
+                            threads::Join::Pending => {}
+                            threads::Join::Unknown => {
+                                self.core
+                                    .answer_message(message_id, Ok(Err::<(), ()>(()).encode()));
+                            }
+                        }
+                    }
+                    Err(_) => {
+                        if needs_answer {
+                            self.core.answer_message(message_id, Err(()));
+                        }
+                    }
+                }
+
+                None
+            }
+
             CoreRunOutcome::InterfaceMessage {
                 pid: _,
                 needs_answer,
@@ -414,6 +848,12 @@ where
                                 message_id,
                             },
                         ));
+                    } else if message.0 == redshirt_kernel_debug_interface::STUCK_EMITTERS_REQUEST {
+                        let response = EncodedMessage(self.stuck_emitters_metrics().into_bytes());
+                        self.core.answer_message(message_id, Ok(response));
+                    } else if message.0 == redshirt_kernel_debug_interface::GRANTS_REQUEST {
+                        let response = EncodedMessage(self.grants_metrics().into_bytes());
+                        self.core.answer_message(message_id, Ok(response));
                     } else {
                         self.core.answer_message(message_id, Err(()));
                     }
@@ -447,12 +887,26 @@ where
                 message_id,
                 interface,
             } => {
+                // Messages destined to a handler whose notifications queue is full are put on
+                // hold by the core, which emits them again later.
+                if self.interfaces.is_emission_allowed(&interface, pid) {
+                    if let Some(handler) = self.interfaces.interface_handler(&interface) {
+                        if !self
+                            .core
+                            .check_recipient_queue(message_id, immediate, handler)
+                        {
+                            return None;
+                        }
+                    }
+                }
+
                 match self.interfaces.emit_interface_message(
                     &interface,
                     message_id,
                     pid,
                     needs_answer,
                     immediate,
+                    (self.monotonic_clock)(),
                 ) {
                     interfaces::EmitInterfaceMessage::Deliver(delivery) => {
                         match self.deliver(delivery) {
@@ -467,6 +921,9 @@ where
                     interfaces::EmitInterfaceMessage::Queued => {
                         debug_assert!(!immediate);
                     }
+                    interfaces::EmitInterfaceMessage::Denied => {
+                        self.core.deny_interface_message(message_id);
+                    }
                 }
 
                 None
@@ -474,6 +931,170 @@ where
         }
     }
 
+    /// Returns true if messages on the given interface are handled by the kernel or are native
+    /// interface messages, in which case they don't go through [`interfaces::Interfaces`].
+    fn is_handled_by_kernel(&self, interface: &InterfaceHash) -> bool {
+        *interface == redshirt_interface_interface::ffi::INTERFACE
+            || *interface == redshirt_process_interface::ffi::INTERFACE
+            || *interface == redshirt_threads_interface::ffi::INTERFACE
+            || *interface == redshirt_kernel_debug_interface::INTERFACE
+            || self.native_interfaces.contains(interface)
+    }
+
+    /// Starts a new thread in the given process, following a message on the `threads` interface.
+    fn start_thread(
+        &self,
+        pid: Pid,
+        new_thread: &redshirt_threads_interface::ffi::ThreadNew,
+    ) -> Result<ThreadId, ()> {
+        let process = self.core.process_by_id(pid).ok_or(())?;
+        let thread_id = process
+            .start_thread(
+                new_thread.fn_ptr,
+                vec![WasmValue::I32(new_thread.user_data as i32)],
+                Some(new_thread.stack_pointer as i32),
+            )
+            .map_err(|_| ())?;
+        self.threads.insert(thread_id, pid);
+        Ok(thread_id)
+    }
+
+    /// Returns the list of interfaces that have no handler and that processes are waiting for.
+    ///
+    /// Since no timeout mechanism exists, a process waiting for an interface that never gets
+    /// registered is stuck forever. This method is meant to be used to report these situations
+    /// to the user.
+    ///
+    /// The durations are calculated using the clock passed to
+    /// [`SystemBuilder::with_monotonic_clock`], and are all zero if no clock has been passed.
+    pub fn stuck_interfaces(&self) -> Vec<StuckInterface> {
+        let now = (self.monotonic_clock)();
+        let mut list = self.interfaces.stuck_interfaces(now);
+
+        // Entries of processes that have been killed are still in the list.
+        for interface in &mut list {
+            interface
+                .waiting_emitters
+                .retain(|e| self.core.process_by_id(e.pid).is_some());
+        }
+        list.retain(|i| !i.waiting_emitters.is_empty());
+
+        list
+    }
+
+    /// Builds the Prometheus-compatible response to a
+    /// [`redshirt_kernel_debug_interface::STUCK_EMITTERS_REQUEST`].
+    fn stuck_emitters_metrics(&self) -> String {
+        let mut out = String::new();
+        out.push_str(
+            "# HELP redshirt_stuck_emitter_waiting_seconds Number of seconds during which a \
+            process has been waiting for a handler of an interface.\n",
+        );
+        out.push_str("# TYPE redshirt_stuck_emitter_waiting_seconds gauge\n");
+
+        for stuck in self.stuck_interfaces() {
+            let interface = stuck
+                .interface
+                .as_ref()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>();
+
+            // A process can be waiting multiple times on the same interface. Since the emitters
+            // are ordered by emission time, only the first entry of each process is reported.
+            let mut reported = HashSet::<Pid, fnv::FnvBuildHasher>::default();
+            for emitter in stuck.waiting_emitters {
+                if !reported.insert(emitter.pid) {
+                    continue;
+                }
+
+                out.push_str(&format!(
+                    "redshirt_stuck_emitter_waiting_seconds{{interface=\"0x{}\",pid=\"{}\"}} {}\n",
+                    interface,
+                    u64::from(emitter.pid),
+                    emitter.waiting_for.as_secs_f64()
+                ));
+            }
+        }
+
+        out
+    }
+
+    /// Builds the Prometheus-compatible response to a
+    /// [`redshirt_kernel_debug_interface::GRANTS_REQUEST`].
+    fn grants_metrics(&self) -> String {
+        let grants = self
+            .interfaces
+            .grants()
+            .into_iter()
+            .map(|grants| {
+                let interface = grants
+                    .interface
+                    .as_ref()
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>();
+                (interface, grants.restricted, grants.pids)
+            })
+            .collect::<Vec<_>>();
+
+        // The lines of each metric must be grouped together.
+        let mut out = String::new();
+        out.push_str(
+            "# HELP redshirt_interface_restricted 1 if only the processes that have been granted \
+            the right to do so can emit messages on an interface.\n",
+        );
+        out.push_str("# TYPE redshirt_interface_restricted gauge\n");
+        for (interface, restricted, _) in &grants {
+            out.push_str(&format!(
+                "redshirt_interface_restricted{{interface=\"0x{}\"}} {}\n",
+                interface,
+                if *restricted { 1 } else { 0 }
+            ));
+        }
+
+        out.push_str(
+            "# HELP redshirt_interface_grant Processes that have been granted the right to emit \
+            messages on an interface.\n",
+        );
+        out.push_str("# TYPE redshirt_interface_grant gauge\n");
+        for (interface, _, pids) in &grants {
+            for pid in pids {
+                out.push_str(&format!(
+                    "redshirt_interface_grant{{interface=\"0x{}\",pid=\"{}\"}} 1\n",
+                    interface,
+                    u64::from(*pid)
+                ));
+            }
+        }
+
+        out
+    }
+
+    /// Allows the given process to emit messages on the given interface.
+    ///
+    /// This is meant to be used by the handlers of native interfaces. Programs that handle an
+    /// interface do so by sending a message on the `interface` interface. Only has an effect on
+    /// the interfaces passed to [`SystemBuilder::with_restricted_interface`].
+    pub fn grant_interface(&self, interface: InterfaceHash, pid: Pid) {
+        self.interfaces.grant(interface, pid);
+    }
+
+    /// Cancels a previous call to [`System::grant_interface`].
+    ///
+    /// Messages that the process has already emitted on this interface are still delivered.
+    pub fn revoke_interface(&self, interface: &InterfaceHash, pid: Pid) {
+        self.interfaces.revoke(interface, pid);
+    }
+
+    /// Returns the value of the monotonic clock when the next startup process is due to be
+    /// restarted, or `None` if no process is waiting to be restarted.
+    ///
+    /// See [`System::run`].
+    pub fn next_restart(&self) -> Option<Duration> {
+        self.restarts.next_deadline()
+    }
+
     /// Answers a message previously emitted using [`SystemRunOutcome::NativeInterfaceMessage`].
     ///
     /// > **Note**: The validity of the [`MessageId`] is not checked, for performance reasons.
@@ -484,23 +1105,34 @@ where
 
     fn set_interface_handler(
         &self,
-        interface_hash: &InterfaceHash,
+        interface_hashes: Vec<InterfaceHash>,
         pid: Pid,
     ) -> Result<NonZeroU64, redshirt_interface_interface::ffi::InterfaceRegisterError> {
+        let is_loader = interface_hashes.contains(&redshirt_loader_interface::ffi::INTERFACE);
+
         let result = self
             .interfaces
-            .set_interface_handler(interface_hash.clone(), pid);
+            .set_interface_handler(interface_hashes, pid)
+            .map(|registration| {
+                // If the registration was taken over from a different handler, the messages
+                // that this previous handler was waiting for will never be answered.
+                for query_message_id in registration.replaced_queries {
+                    self.core.answer_message(query_message_id, Err(()));
+                }
 
-        // Special handling if the registered interface is the loader.
-        if *interface_hash == redshirt_loader_interface::ffi::INTERFACE {
+                registration.registration_id
+            });
+
+        // Special handling if the registered interfaces include the loader.
+        if is_loader {
             if let Ok(registration_id) = result {
                 self.loader_registration_id.store(
                     Some(usize::try_from(registration_id.get()).unwrap()),
                     Ordering::Release,
                 );
 
-                while let Some(h) = self.programs_to_load.pop() {
-                    todo!() // TODO:
+                while let Some((hash, launch_config)) = self.programs_to_load.pop() {
+                    self.load_program(hash, None, None, launch_config);
                 }
             }
         }
@@ -513,12 +1145,15 @@ where
     /// Returns `Ok` if the message still exists, or an error if the message to deliver was no
     /// longer valid.
     fn deliver(&self, delivery: interfaces::MessageDelivery) -> Result<(), ()> {
-        let (emitter_pid, message) = match self
-            .core
-            .accept_interface_message(delivery.to_deliver_message_id)
-        {
-            Some(v) => v,
-            None => return Err(()),
+        let (emitter_pid, message) = match delivery.body {
+            Some(body) => (delivery.emitter_pid, body),
+            None => match self
+                .core
+                .accept_interface_message(delivery.to_deliver_message_id)
+            {
+                Some(v) => v,
+                None => return Err(()),
+            },
         };
 
         let notification = redshirt_interface_interface::ffi::build_interface_notification(
@@ -545,16 +1180,185 @@ where
 
         Ok(())
     }
+
+    /// Answers a message that has been delivered to an interface handler, either emitted by a
+    /// process or by the kernel itself.
+    fn answer_delivered_message(
+        &self,
+        message_id: MessageId,
+        response: Result<EncodedMessage, ()>,
+    ) {
+        let loading = self.loading_programs.lock().remove(&message_id);
+        match loading {
+            Some(loading) => self.program_loaded(loading, response),
+            None => self.core.answer_message(message_id, response),
+        }
+    }
+
+    /// Starts a new process following a `Spawn` message on the `process` interface.
+    fn spawn(
+        &self,
+        parent: Pid,
+        message_id: Option<MessageId>,
+        spawn: redshirt_process_interface::ffi::Spawn,
+    ) {
+        let launch_config = LaunchConfig {
+            args: spawn.args,
+            ..Default::default()
+        };
+
+        match spawn.module {
+            redshirt_process_interface::ffi::SpawnModule::Hash(hash) => {
+                self.load_program(hash.into(), Some(parent), message_id, launch_config);
+            }
+            redshirt_process_interface::ffi::SpawnModule::Bytes(bytes) => {
+                let result = self.start_program(Some(parent), &bytes, launch_config);
+                if let Some(message_id) = message_id {
+                    self.core.answer_message(message_id, Ok(result.encode()));
+                }
+            }
+        }
+    }
+
+    /// Emits a message on the `loader` interface requesting the program with the given hash.
+    ///
+    /// The program is started when the answer arrives, and becomes a child of `parent` if any.
+    fn load_program(
+        &self,
+        hash: ModuleHash,
+        parent: Option<Pid>,
+        spawn_message_id: Option<MessageId>,
+        launch_config: LaunchConfig,
+    ) {
+        let message_id = self.core.new_message_id();
+        self.loading_programs.lock().insert(
+            message_id,
+            LoadingProgram {
+                parent,
+                spawn_message_id,
+                launch_config,
+            },
+        );
+
+        let message = redshirt_loader_interface::ffi::LoaderMessage::Load(hash.into()).encode();
+        match self.interfaces.emit_kernel_message(
+            &redshirt_loader_interface::ffi::INTERFACE,
+            message_id,
+            self.load_source_virtual_pid,
+            message,
+            (self.monotonic_clock)(),
+        ) {
+            interfaces::EmitInterfaceMessage::Deliver(delivery) => {
+                // Can't fail, as there's no emitter thread that could have disappeared.
+                let _ = self.deliver(delivery);
+            }
+            interfaces::EmitInterfaceMessage::Queued => {}
+            interfaces::EmitInterfaceMessage::Reject | interfaces::EmitInterfaceMessage::Denied => {
+                unreachable!()
+            }
+        }
+    }
+
+    /// Called when the `loader` interface has answered a message emitted by
+    /// [`System::load_program`].
+    fn program_loaded(&self, loading: LoadingProgram, response: Result<EncodedMessage, ()>) {
+        let module_bytes = response
+            .ok()
+            .and_then(|r| redshirt_loader_interface::ffi::LoadResponse::decode(r).ok())
+            .and_then(|r| r.result.ok());
+
+        let result = match module_bytes {
+            Some(bytes) => self.start_program(loading.parent, &bytes, loading.launch_config),
+            None => Err(redshirt_process_interface::ffi::SpawnError::LoadFailed),
+        };
+
+        // TODO: failures to start the programs passed to `with_main_programs` are silently
+        // ignored, as there is no way to report them at the moment
+        if let Some(message_id) = loading.spawn_message_id {
+            self.core.answer_message(message_id, Ok(result.encode()));
+        }
+    }
+
+    /// Starts a process executing the given module, as a child of `parent` if any.
+    fn start_program(
+        &self,
+        parent: Option<Pid>,
+        module_bytes: &[u8],
+        launch_config: LaunchConfig,
+    ) -> Result<Pid, redshirt_process_interface::ffi::SpawnError> {
+        let module = Module::from_bytes(module_bytes)
+            .map_err(|_| redshirt_process_interface::ffi::SpawnError::InvalidModule)?;
+        let limits = self.default_process_limits.clone();
+
+        match parent {
+            Some(parent) => self.children.spawn(parent, || {
+                self.execute_with_launch_config(&module, limits, launch_config)
+            }),
+            None => self.execute_with_launch_config(&module, limits, launch_config),
+        }
+        .map_err(|err| spawn_error(&err))
+    }
+}
+
+/// Converts an error while starting a process into the error reported on the `process` interface.
+fn spawn_error(error: &NewErr) -> redshirt_process_interface::ffi::SpawnError {
+    use redshirt_process_interface::ffi::SpawnError;
+
+    match error {
+        NewErr::Interpreter(_)
+        | NewErr::MemoryIsntMemory
+        | NewErr::MultipleMemoriesNotSupported
+        | NewErr::IndirectTableIsntTable => SpawnError::InvalidModule,
+        NewErr::StartNotFound | NewErr::StartIsntAFunction | NewErr::StartBadSignature => {
+            SpawnError::BadEntryPoint
+        }
+        NewErr::UnresolvedFunctionImport {
+            module_name,
+            function,
+        }
+        | NewErr::FunctionImportSignatureMismatch {
+            module_name,
+            function,
+            ..
+        } => SpawnError::UnresolvedImport {
+            module_name: module_name.clone(),
+            function: function.clone(),
+        },
+        NewErr::MemoryLimitExceeded | NewErr::TableLimitExceeded => SpawnError::LimitExceeded,
+    }
+}
+
+impl<TInner, TEng> System<extrinsics::log_calls::LogExtrinsics<TInner>, TEng>
+where
+    TInner: extrinsics::Extrinsics,
+    TEng: Engine,
+{
+    /// Enables or disables the tracing of the extrinsics called by the given process.
+    ///
+    /// Returns `false` if there is no process with this [`Pid`].
+    pub fn set_extrinsics_tracing(&self, pid: Pid, enabled: bool) -> bool {
+        match self.core.process_by_id(pid) {
+            Some(process) => {
+                process.extrinsics().set_enabled(enabled);
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 /// Object to use to report kernel metrics to a requesting process.
 #[must_use]
-pub struct KernelDebugMetricsRequest<'a, TExtr: extrinsics::Extrinsics> {
-    system: &'a System<TExtr>,
+pub struct KernelDebugMetricsRequest<
+    'a,
+    TExtr: extrinsics::Extrinsics,
+    TEng: Engine = DefaultEngine,
+> {
+    system: &'a System<TExtr, TEng>,
     message_id: MessageId,
 }
 
-impl<'a, TExtr: extrinsics::Extrinsics> KernelDebugMetricsRequest<'a, TExtr> {
+impl<'a, TExtr: extrinsics::Extrinsics, TEng: Engine> KernelDebugMetricsRequest<'a, TExtr, TEng> {
     /// Indicate the metrics. Must pass a Prometheus-compatible metrics.
     /// See [this document](https://prometheus.io/docs/instrumenting/exposition_formats/#text-format-details)
     /// for more information.
@@ -600,6 +1404,24 @@ impl<'a, TExtr: extrinsics::Extrinsics> KernelDebugMetricsRequest<'a, TExtr> {
         );
         metrics_bytes.extend_from_slice(b"\n");
 
+        // `process_cpu_seconds_total`
+        metrics_bytes.extend_from_slice(
+            b"# HELP redshirt_process_cpu_seconds_total Time spent executing the threads of \
+            each process that is currently alive.\n",
+        );
+        metrics_bytes.extend_from_slice(b"# TYPE redshirt_process_cpu_seconds_total counter\n");
+        for process in self.system.core.processes() {
+            metrics_bytes.extend_from_slice(
+                format!(
+                    "redshirt_process_cpu_seconds_total{{pid=\"{}\"}} {}\n",
+                    u64::from(process.pid()),
+                    process.cpu_time().as_secs_f64()
+                )
+                .as_bytes(),
+            );
+        }
+        metrics_bytes.extend_from_slice(b"\n");
+
         // TODO: add more metrics?
 
         let response = EncodedMessage(metrics_bytes);
@@ -609,15 +1431,18 @@ impl<'a, TExtr: extrinsics::Extrinsics> KernelDebugMetricsRequest<'a, TExtr> {
     }
 }
 
-impl<'a, TExtr: extrinsics::Extrinsics> fmt::Debug for KernelDebugMetricsRequest<'a, TExtr> {
+impl<'a, TExtr: extrinsics::Extrinsics, TEng: Engine> fmt::Debug
+    for KernelDebugMetricsRequest<'a, TExtr, TEng>
+{
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_tuple("KernelDebugMetricsRequest").finish()
     }
 }
 
-impl<TExtr> SystemBuilder<TExtr>
+impl<TExtr, TEng> SystemBuilder<TExtr, TEng>
 where
     TExtr: extrinsics::Extrinsics,
+    TEng: Engine,
 {
     /// Initializes a new builder.
     ///
@@ -630,9 +1455,13 @@ where
         SystemBuilder {
             core,
             startup_processes: Vec::new(),
+            default_process_limits: Default::default(),
             native_interfaces: Default::default(),
+            restricted_interfaces: Default::default(),
             load_source_virtual_pid,
             programs_to_load: SegQueue::new(),
+            monotonic_clock: Arc::new(|| Duration::new(0, 0)),
+            stuck_emitter_warning_threshold: None,
         }
     }
 
@@ -644,6 +1473,36 @@ where
         self
     }
 
+    /// Sets the maximum amount of fuel that a thread can consume before being preempted and
+    /// another thread given the chance to run.
+    ///
+    /// The amount of fuel is approximately equal to the number of Wasm instructions executed.
+    /// By default, threads are never preempted, and a program that runs an infinite loop without
+    /// ever calling an extrinsic blocks the CPU it runs on forever.
+    pub fn with_fuel_per_run(mut self, fuel: u64) -> Self {
+        self.core = self.core.with_fuel_per_run(fuel);
+        self
+    }
+
+    /// Sets the maximum number of messages that a process can have waiting for an answer.
+    /// Emitting a message above this limit fails.
+    ///
+    /// See [`CoreBuilder::with_max_pending_messages`].
+    pub fn with_max_pending_messages(mut self, max: usize) -> Self {
+        self.core = self.core.with_max_pending_messages(max);
+        self
+    }
+
+    /// Sets the maximum number of notifications in the queue of a process. Above this limit,
+    /// emitting a message on the interfaces handled by the process, or emitting from the process
+    /// a message that expects an answer, blocks, or fails if the emitter doesn't allow delays.
+    ///
+    /// See [`CoreBuilder::with_max_queued_notifications`].
+    pub fn with_max_queued_notifications(mut self, max: usize) -> Self {
+        self.core = self.core.with_max_queued_notifications(max);
+        self
+    }
+
     /// Adds a process to the list of processes that the [`System`] must start as part of the
     /// startup process.
     ///
@@ -652,9 +1511,144 @@ where
     ///
     /// By default, the list is empty. Should at least contain a process that handles the `loader`
     /// interface.
+    ///
+    /// The process is subject to the limits passed to
+    /// [`with_default_process_limits`](SystemBuilder::with_default_process_limits).
     pub fn with_startup_process(mut self, process: impl Into<Module>) -> Self {
         let process = process.into();
-        self.startup_processes.push(process);
+        self.startup_processes.push((
+            process,
+            None,
+            Default::default(),
+            RestartPolicy::Never,
+            Vec::new(),
+        ));
+        self
+    }
+
+    /// Same as [`with_startup_process`](SystemBuilder::with_startup_process), but forbids the
+    /// process from using more resources than the given limits.
+    pub fn with_startup_process_and_limits(
+        mut self,
+        process: impl Into<Module>,
+        limits: ProcessLimits,
+    ) -> Self {
+        let process = process.into();
+        self.startup_processes.push((
+            process,
+            Some(limits),
+            Default::default(),
+            RestartPolicy::Never,
+            Vec::new(),
+        ));
+        self
+    }
+
+    /// Same as [`with_startup_process`](SystemBuilder::with_startup_process), but additionally
+    /// passes the given arguments, environment variables, and pre-opened directories to the
+    /// process.
+    pub fn with_startup_process_and_launch_config(
+        mut self,
+        process: impl Into<Module>,
+        launch_config: LaunchConfig,
+    ) -> Self {
+        let process = process.into();
+        self.startup_processes.push((
+            process,
+            None,
+            launch_config,
+            RestartPolicy::Never,
+            Vec::new(),
+        ));
+        self
+    }
+
+    /// Same as [`with_startup_process`](SystemBuilder::with_startup_process), but the process is
+    /// started again, following the given policy, when it ends.
+    ///
+    /// A process that ends repeatedly is restarted after a delay that increases exponentially,
+    /// up to one minute. Restarts are performed when [`System::run`] is called, and are reported
+    /// with [`SystemRunOutcome::ProgramRestarted`] or [`SystemRunOutcome::ProgramRestartFailed`].
+    /// Requires a clock to be passed to
+    /// [`with_monotonic_clock`](SystemBuilder::with_monotonic_clock).
+    pub fn with_startup_process_and_restart_policy(
+        mut self,
+        process: impl Into<Module>,
+        policy: RestartPolicy,
+    ) -> Self {
+        let process = process.into();
+        self.startup_processes
+            .push((process, None, Default::default(), policy, Vec::new()));
+        self
+    }
+
+    /// Same as [`with_startup_process`](SystemBuilder::with_startup_process), but the process is
+    /// allowed to emit messages on the given interfaces.
+    ///
+    /// This is only relevant for the interfaces passed to
+    /// [`with_restricted_interface`](SystemBuilder::with_restricted_interface), as all the
+    /// processes are allowed to emit messages on the other interfaces.
+    pub fn with_startup_process_and_grants(
+        mut self,
+        process: impl Into<Module>,
+        interfaces: impl IntoIterator<Item = InterfaceHash>,
+    ) -> Self {
+        let process = process.into();
+        self.startup_processes.push((
+            process,
+            None,
+            Default::default(),
+            RestartPolicy::Never,
+            interfaces.into_iter().collect(),
+        ));
+        self
+    }
+
+    /// Restricts the given interface. Only the processes that have been granted the right to do
+    /// so can emit messages on it. Other processes fail to emit messages on this interface.
+    ///
+    /// Rights are granted to startup processes with
+    /// [`with_startup_process_and_grants`](SystemBuilder::with_startup_process_and_grants), by
+    /// the handler of the interface, or by calling [`System::grant_interface`].
+    ///
+    /// This also applies to the interfaces handled by the kernel itself, such as `process` or
+    /// `kernel-debug`, and to the native interfaces.
+    pub fn with_restricted_interface(mut self, hash: InterfaceHash) -> Self {
+        self.restricted_interfaces.insert(hash);
+        self
+    }
+
+    /// Sets the limits to the resources that processes can use, for processes started without
+    /// any explicit limit.
+    ///
+    /// By default, no limit is enforced.
+    pub fn with_default_process_limits(mut self, limits: ProcessLimits) -> Self {
+        self.default_process_limits = limits;
+        self
+    }
+
+    /// Sets the function that returns the current value of the monotonic clock.
+    ///
+    /// The clock is used to measure how long processes have been waiting for an interface
+    /// handler, the CPU time of processes and threads, and the delay before restarting startup
+    /// processes. By default, the clock always returns zero, in which case all these durations
+    /// are zero.
+    pub fn with_monotonic_clock(
+        mut self,
+        clock: impl Fn() -> Duration + Send + Sync + 'static,
+    ) -> Self {
+        self.monotonic_clock = Arc::new(clock);
+        self
+    }
+
+    /// Enables the generation of [`SystemRunOutcome::StuckEmitter`] events for processes that
+    /// have been waiting for longer than the given threshold for a handler to be registered.
+    ///
+    /// Requires a clock to be passed to
+    /// [`with_monotonic_clock`](SystemBuilder::with_monotonic_clock). Without one, emitters are
+    /// never considered as waiting, and no event is ever generated unless `threshold` is zero.
+    pub fn with_stuck_emitter_warning(mut self, threshold: Duration) -> Self {
+        self.stuck_emitter_warning_threshold = Some(threshold);
         self
     }
 
@@ -662,7 +1656,7 @@ where
     /// times.
     pub fn with_main_programs(self, hashes: impl IntoIterator<Item = ModuleHash>) -> Self {
         for hash in hashes {
-            self.programs_to_load.push(hash);
+            self.programs_to_load.push((hash, Default::default()));
         }
         self
     }
@@ -681,16 +1675,63 @@ where
         self.with_main_programs(iter::once(hash))
     }
 
+    /// Same as [`with_main_program`](SystemBuilder::with_main_program), but additionally passes
+    /// the given launch configuration to the program.
+    ///
+    /// This is notably how an interactive program, such as a shell, can be given access to the
+    /// console through [`LaunchConfig::console_stdin`].
+    pub fn with_main_program_and_launch_config(
+        self,
+        hash: ModuleHash,
+        launch_config: LaunchConfig,
+    ) -> Self {
+        self.programs_to_load.push((hash, launch_config));
+        self
+    }
+
     /// Builds the [`System`].
     ///
     /// Returns an error if any of the programs passed through
     /// [`SystemBuilder::with_startup_process`] fails to start.
-    pub fn build(mut self) -> Result<System<TExtr>, NewErr> {
-        let core = self.core.build();
+    pub fn build(mut self) -> Result<System<TExtr, TEng>, NewErr> {
+        let core = {
+            let clock = self.monotonic_clock.clone();
+            self.core.with_monotonic_clock(move || clock()).build()
+        };
+
+        let restarts = restarts::Restarts::new();
+        let now = (self.monotonic_clock)();
+
+        let interfaces = interfaces::Interfaces::new();
+        for hash in self.restricted_interfaces {
+            interfaces.restrict(hash);
+        }
 
         let num_processes_started = u64::try_from(self.startup_processes.len()).unwrap();
-        for program in self.startup_processes {
-            core.execute(&program)?;
+        let default_process_limits = &self.default_process_limits;
+        for (program, limits, launch_config, policy, grants) in self.startup_processes {
+            let limits = limits.unwrap_or_else(|| default_process_limits.clone());
+            let pid = core
+                .execute_with_launch_config(&program, limits.clone(), launch_config.clone())?
+                .0
+                .pid();
+            for interface in &grants {
+                interfaces.grant(interface.clone(), pid);
+            }
+
+            if policy != RestartPolicy::Never {
+                restarts.insert(
+                    pid,
+                    restarts::Supervised::new(
+                        Arc::new(program),
+                        limits,
+                        launch_config,
+                        policy,
+                        grants,
+                        now,
+                    ),
+                );
+            }
         }
 
         self.native_interfaces.shrink_to_fit();
@@ -698,8 +1739,11 @@ where
         Ok(System {
             core,
             load_source_virtual_pid: self.load_source_virtual_pid,
-            interfaces: Default::default(),
+            interfaces,
             pending_answers: Default::default(),
+            threads: Default::default(),
+            children: Default::default(),
+            restarts,
             num_processes_started: atomic::Atomic::new(num_processes_started),
             num_processes_finished: atomic::Atomic::new(0),
             num_processes_trap: atomic::Atomic::new(0),
@@ -707,16 +1751,963 @@ where
             loader_registration_id: atomic::Atomic::new(None),
             loading_programs: Spinlock::new(Default::default()),
             programs_to_load: self.programs_to_load,
+            default_process_limits: self.default_process_limits,
+            monotonic_clock: self.monotonic_clock,
+            stuck_emitter_warning_threshold: self.stuck_emitter_warning_threshold,
         })
     }
 }
 #[cfg(test)]
 mod tests {
+    use super::{ExecuteOut, RestartPolicy, System, SystemBuilder, SystemRunOutcome};
     use crate::extrinsics;
+    use crate::scheduler::ProcessExitReason;
+    use alloc::sync::Arc;
+    use core::{
+        convert::TryFrom as _,
+        iter,
+        num::NonZeroU64,
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    };
+    use futures::prelude::*;
+    use redshirt_interface_interface::ffi::{self as interface_ffi, InterfaceMessage};
+    use redshirt_loader_interface::ffi as loader_ffi;
+    use redshirt_process_interface::ffi::{
+        self as process_ffi, ExitStatus, ProcessMessage, Spawn, SpawnError, SpawnModule,
+    };
+    use redshirt_syscalls::{Encode as _, MessageId, Pid};
 
     #[test]
     fn send_sync() {
         fn is_send_sync<T: Send + Sync>() {}
         is_send_sync::<super::System<extrinsics::NoExtrinsics>>()
     }
+
+    /// Message emitted by a process built with [`requests_module`].
+    ///
+    /// The process holds a single 64-bits value, named the register, in order to reuse in a
+    /// message a value that is only known after a previous message has been answered, such as
+    /// a [`Pid`].
+    #[derive(Default)]
+    struct Request {
+        /// Interface to emit the message on.
+        interface: [u8; 32],
+        /// Encoded message.
+        message: Vec<u8>,
+        /// If `Some`, the register is written at this offset in the message before it's emitted.
+        register_in_message: Option<usize>,
+        /// If `Some`, the message needs an answer, and the answer must start with these bytes.
+        /// The process exits with an error otherwise.
+        answer_prefix: Option<Vec<u8>>,
+        /// If `Some`, the answer must contain the register at this offset.
+        register_in_answer: Option<usize>,
+        /// If `Some`, the 64-bits value at this offset in the answer is stored in the register.
+        store_register: Option<usize>,
+        /// If `Some`, emitting the message must fail with this error code.
+        emit_error: Option<i32>,
+    }
+
+    /// Builds a module that emits the given messages one by one, waiting for the answer of each
+    /// message before emitting the next one.
+    ///
+    /// The process exits with code 0 if all the answers are as expected, or with the index
+    /// within `requests` plus one of the first request whose answer isn't as expected.
+    fn requests_module(requests: &[Request]) -> crate::Module {
+        fn escape(bytes: &[u8]) -> String {
+            bytes.iter().map(|b| format!("\\{:02x}", b)).collect()
+        }
+
+        // Memory layout: the register at 0, the emitted message id at 8, the list of messages
+        // to poll at 16, the iovec at 24, the received notification at 64, and the data of each
+        // request starting at 8192.
+        let mut data = String::new();
+        let mut body = String::new();
+        let mut data_ptr = 8192;
+
+        for (index, request) in requests.iter().enumerate() {
+            let error_code = index + 1;
+            let interface_ptr = data_ptr;
+            let message_ptr = interface_ptr + 32;
+            let answer_ptr = message_ptr + request.message.len();
+            let answer_prefix = request.answer_prefix.as_deref().unwrap_or(&[]);
+            data_ptr = answer_ptr + answer_prefix.len();
+
+            data.push_str(&format!(
+                "(data (i32.const {}) \"{}{}{}\")\n",
+                interface_ptr,
+                escape(&request.interface),
+                escape(&request.message),
+                escape(answer_prefix)
+            ));
+
+            if let Some(offset) = request.register_in_message {
+                body.push_str(&format!(
+                    "(i64.store (i32.const {}) (i64.load (i32.const 0)))\n",
+                    message_ptr + offset
+                ));
+            }
+
+            body.push_str(&format!(
+                "(i32.store (i32.const 24) (i32.const {}))
+                (i32.store (i32.const 28) (i32.const {}))
+                (if (i32.ne (call $emit_message (i32.const {}) (i32.const 24) (i32.const 1) (i64.const {}) (i32.const 8)) (i32.const {}))
+                    (then (return (i32.const {}))))\n",
+                message_ptr,
+                request.message.len(),
+                interface_ptr,
+                if request.answer_prefix.is_some() { 3 } else { 2 },
+                request.emit_error.unwrap_or(0),
+                error_code
+            ));
+
+            if request.answer_prefix.is_none() || request.emit_error.is_some() {
+                continue;
+            }
+
+            // The answer starts at offset 78 of the memory.
+            body.push_str(&format!(
+                "(i64.store (i32.const 16) (i64.load (i32.const 8)))
+                (local.set $len (call $next_notification (i32.const 16) (i32.const 1) (i32.const 64) (i32.const 4096) (i64.const 1)))
+                (if (i32.load8_u (i32.const 77))
+                    (then (return (i32.const {code}))))
+                (if (i32.lt_u (i32.sub (local.get $len) (i32.const 14)) (i32.const {len}))
+                    (then (return (i32.const {code}))))
+                (if (i32.eqz (call $memeq (i32.const 78) (i32.const {ptr}) (i32.const {len})))
+                    (then (return (i32.const {code}))))\n",
+                code = error_code,
+                ptr = answer_ptr,
+                len = answer_prefix.len(),
+            ));
+
+            if let Some(offset) = request.register_in_answer {
+                body.push_str(&format!(
+                    "(if (i64.ne (i64.load (i32.const {})) (i64.load (i32.const 0)))
+                        (then (return (i32.const {}))))\n",
+                    78 + offset,
+                    error_code
+                ));
+            }
+
+            if let Some(offset) = request.store_register {
+                body.push_str(&format!(
+                    "(i64.store (i32.const 0) (i64.load (i32.const {})))\n",
+                    78 + offset
+                ));
+            }
+        }
+
+        let wat = format!(
+            r#"(module
+            (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i64 i32) (result i32)))
+            (import "redshirt" "next_notification" (func $next_notification (param i32 i32 i32 i32 i64) (result i32)))
+            (memory $memory 2)
+            {}
+            ;; Returns 1 if the `$len` bytes at `$a` and `$b` are equal, and 0 otherwise.
+            (func $memeq (param $a i32) (param $b i32) (param $len i32) (result i32)
+                (block $done
+                    (loop $next
+                        (br_if $done (i32.eqz (local.get $len)))
+                        (if (i32.ne (i32.load8_u (local.get $a)) (i32.load8_u (local.get $b)))
+                            (then (return (i32.const 0))))
+                        (local.set $a (i32.add (local.get $a) (i32.const 1)))
+                        (local.set $b (i32.add (local.get $b) (i32.const 1)))
+                        (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+                        (br $next)))
+                (i32.const 1))
+            (func $_start (result i32)
+                (local $len i32)
+                {}
+                (i32.const 0))
+            (export "memory" (memory 0))
+            (export "_start" (func $_start)))"#,
+            data, body
+        );
+
+        crate::Module::from_bytes(wat::parse_str(wat).unwrap()).unwrap()
+    }
+
+    /// Runs the system until the process with the given [`Pid`] has ended, and returns how it
+    /// has ended. Panics if the system has nothing more to do.
+    fn run_until_end<TExt: extrinsics::Extrinsics>(
+        system: &System<TExt>,
+        pid: Pid,
+    ) -> ProcessExitReason {
+        loop {
+            let event = match system.run().now_or_never() {
+                Some(ExecuteOut::Direct(event)) => Some(event),
+                Some(ExecuteOut::ReadyToRun(ready_to_run)) => ready_to_run.run(),
+                None => panic!("process {:?} is stuck", pid),
+            };
+
+            match event {
+                Some(SystemRunOutcome::ProgramFinished {
+                    pid: finished,
+                    outcome,
+                }) if finished == pid => return outcome,
+                _ => {}
+            }
+        }
+    }
+
+    /// Module whose `_start` function returns 7.
+    fn exit_7_module() -> Vec<u8> {
+        wat::parse_str(
+            r#"(module
+            (func $_start (result i32)
+                i32.const 7)
+            (export "_start" (func $_start)))"#,
+        )
+        .unwrap()
+    }
+
+    /// Module that never ends.
+    fn sleeping_module() -> Vec<u8> {
+        wat::parse_str(
+            r#"(module
+            (import "redshirt" "next_notification" (func $next_notification (param i32 i32 i32 i32 i64) (result i32)))
+            (memory $memory 1)
+            (func $_start (result i32)
+                (drop (call $next_notification (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0) (i64.const 1)))
+                (i32.const 0))
+            (export "memory" (memory 0))
+            (export "_start" (func $_start)))"#,
+        )
+        .unwrap()
+    }
+
+    fn spawn_message(module: SpawnModule) -> Vec<u8> {
+        ProcessMessage::Spawn(Spawn {
+            module,
+            args: Vec::new(),
+        })
+        .encode()
+        .0
+    }
+
+    #[test]
+    fn spawn_from_bytes_and_wait() {
+        let system = SystemBuilder::<extrinsics::NoExtrinsics>::new([0; 64])
+            .build()
+            .unwrap();
+
+        let parent = requests_module(&[
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: spawn_message(SpawnModule::Bytes(exit_7_module())),
+                // `Ok` followed with the `Pid` of the child.
+                answer_prefix: Some(vec![0]),
+                store_register: Some(1),
+                ..Default::default()
+            },
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: ProcessMessage::Wait(Pid::from(0)).encode().0,
+                register_in_message: Some(1),
+                answer_prefix: Some(Ok::<_, ()>(ExitStatus::Exited(7)).encode().0),
+                ..Default::default()
+            },
+            // A process can only be waited for once.
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: ProcessMessage::Wait(Pid::from(0)).encode().0,
+                register_in_message: Some(1),
+                answer_prefix: Some(Err::<ExitStatus, ()>(()).encode().0),
+                ..Default::default()
+            },
+        ]);
+
+        let parent = system.execute(&parent).unwrap();
+        assert_eq!(
+            run_until_end(&system, parent),
+            ProcessExitReason::Exited { code: 0 }
+        );
+    }
+
+    #[test]
+    fn spawn_errors() {
+        let system = SystemBuilder::<extrinsics::NoExtrinsics>::new([0; 64])
+            .build()
+            .unwrap();
+
+        let no_start = wat::parse_str(r#"(module (func $f) (export "f" (func $f)))"#).unwrap();
+        let bad_import = wat::parse_str(
+            r#"(module
+            (import "foo" "bar" (func $bar))
+            (func $_start (result i32)
+                i32.const 0)
+            (export "_start" (func $_start)))"#,
+        )
+        .unwrap();
+
+        let parent = requests_module(&[
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: spawn_message(SpawnModule::Bytes(b"not a module".to_vec())),
+                answer_prefix: Some(Err::<Pid, _>(SpawnError::InvalidModule).encode().0),
+                ..Default::default()
+            },
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: spawn_message(SpawnModule::Bytes(no_start)),
+                answer_prefix: Some(Err::<Pid, _>(SpawnError::BadEntryPoint).encode().0),
+                ..Default::default()
+            },
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: spawn_message(SpawnModule::Bytes(bad_import)),
+                answer_prefix: Some(
+                    Err::<Pid, _>(SpawnError::UnresolvedImport {
+                        module_name: "foo".into(),
+                        function: "bar".into(),
+                    })
+                    .encode()
+                    .0,
+                ),
+                ..Default::default()
+            },
+            // The process doesn't have any child.
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: ProcessMessage::Wait(Pid::from(12345)).encode().0,
+                answer_prefix: Some(Err::<ExitStatus, ()>(()).encode().0),
+                ..Default::default()
+            },
+        ]);
+
+        let parent = system.execute(&parent).unwrap();
+        assert_eq!(
+            run_until_end(&system, parent),
+            ProcessExitReason::Exited { code: 0 }
+        );
+    }
+
+    #[test]
+    fn restricted_interfaces() {
+        let restricted = [0x42; 32];
+        let system = SystemBuilder::<extrinsics::NoExtrinsics>::new([0; 64])
+            .with_restricted_interface(redshirt_syscalls::InterfaceHash::from_raw_hash(restricted))
+            .with_restricted_interface(process_ffi::INTERFACE)
+            .build()
+            .unwrap();
+
+        // Emitting on a restricted interface fails immediately, even though the emitter allows
+        // delays and no handler is registered, and no matter whether the interface is handled
+        // by a program or by the kernel.
+        let emitter = requests_module(&[
+            Request {
+                interface: restricted,
+                message: vec![1, 2, 3],
+                answer_prefix: Some(Vec::new()),
+                emit_error: Some(4),
+                ..Default::default()
+            },
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: spawn_message(SpawnModule::Bytes(exit_7_module())),
+                answer_prefix: Some(Vec::new()),
+                emit_error: Some(4),
+                ..Default::default()
+            },
+            // Messages on non-restricted interfaces are queued until a handler is registered.
+            Request {
+                interface: [0x43; 32],
+                message: vec![1, 2, 3],
+                ..Default::default()
+            },
+        ]);
+
+        let emitter = system.execute(&emitter).unwrap();
+        let stuck = loop {
+            match system.run().now_or_never() {
+                Some(ExecuteOut::Direct(_)) => {}
+                Some(ExecuteOut::ReadyToRun(ready_to_run)) => {
+                    if let Some(SystemRunOutcome::ProgramFinished { pid, outcome }) =
+                        ready_to_run.run()
+                    {
+                        panic!("{:?} finished: {:?}", pid, outcome);
+                    }
+                }
+                None => break system.stuck_interfaces(),
+            }
+        };
+        assert_eq!(stuck.len(), 1);
+        assert_eq!(stuck[0].interface.as_ref(), &[0x43; 32]);
+        assert_eq!(stuck[0].waiting_emitters.len(), 1);
+        assert_eq!(stuck[0].waiting_emitters[0].pid, emitter);
+    }
+
+    #[test]
+    fn registration_management_answers() {
+        let system = SystemBuilder::<extrinsics::NoExtrinsics>::new([0; 64])
+            .build()
+            .unwrap();
+
+        let interface = redshirt_syscalls::InterfaceHash::from_raw_hash([0x42; 32]);
+        let other_interface = redshirt_syscalls::InterfaceHash::from_raw_hash([0x43; 32]);
+        let placeholder = NonZeroU64::new(1).unwrap();
+        let ok = Ok::<(), ()>(()).encode().0;
+        let err = Err::<(), ()>(()).encode().0;
+
+        // The registration id is stored in the register and written right after the variant
+        // index of the messages.
+        let request = |message: InterfaceMessage, answer: &[u8]| Request {
+            interface: interface_ffi::INTERFACE.into(),
+            message: message.encode().0,
+            register_in_message: Some(1),
+            answer_prefix: Some(answer.to_vec()),
+            ..Default::default()
+        };
+
+        let handler = requests_module(&[
+            Request {
+                interface: interface_ffi::INTERFACE.into(),
+                message: InterfaceMessage::Register(vec![interface.clone()])
+                    .encode()
+                    .0,
+                answer_prefix: Some(vec![0]),
+                store_register: Some(1),
+                ..Default::default()
+            },
+            request(
+                InterfaceMessage::Grant(placeholder, interface.clone(), Pid::from(1234)),
+                &ok,
+            ),
+            request(
+                InterfaceMessage::Grant(placeholder, other_interface.clone(), Pid::from(1234)),
+                &err,
+            ),
+            request(
+                InterfaceMessage::Revoke(placeholder, interface.clone(), Pid::from(1234)),
+                &ok,
+            ),
+            request(
+                InterfaceMessage::Handover(placeholder, Pid::from(1234)),
+                &ok,
+            ),
+            request(InterfaceMessage::Unregister(placeholder), &ok),
+            // The registration no longer exists.
+            request(InterfaceMessage::Unregister(placeholder), &err),
+            request(
+                InterfaceMessage::Grant(placeholder, interface.clone(), Pid::from(1234)),
+                &err,
+            ),
+            request(
+                InterfaceMessage::Handover(placeholder, Pid::from(1234)),
+                &err,
+            ),
+        ]);
+
+        let handler = system.execute(&handler).unwrap();
+        assert_eq!(
+            run_until_end(&system, handler),
+            ProcessExitReason::Exited { code: 0 }
+        );
+    }
+
+    #[test]
+    fn register_multiple_interfaces() {
+        let system = SystemBuilder::<extrinsics::NoExtrinsics>::new([0; 64])
+            .build()
+            .unwrap();
+
+        let first = redshirt_syscalls::InterfaceHash::from_raw_hash([0x42; 32]);
+        let second = redshirt_syscalls::InterfaceHash::from_raw_hash([0x43; 32]);
+
+        // Asks for the next message of the registration, which must have been emitted on the
+        // given interface.
+        let next_message = |interface: &redshirt_syscalls::InterfaceHash| {
+            let mut answer_prefix = vec![0];
+            answer_prefix.extend_from_slice(interface.as_ref());
+            Request {
+                interface: interface_ffi::INTERFACE.into(),
+                message: InterfaceMessage::NextMessage(NonZeroU64::new(1).unwrap())
+                    .encode()
+                    .0,
+                register_in_message: Some(1),
+                answer_prefix: Some(answer_prefix),
+                ..Default::default()
+            }
+        };
+
+        let handler = requests_module(&[
+            Request {
+                interface: interface_ffi::INTERFACE.into(),
+                message: InterfaceMessage::Register(vec![first.clone(), second.clone()])
+                    .encode()
+                    .0,
+                answer_prefix: Some(vec![0]),
+                store_register: Some(1),
+                ..Default::default()
+            },
+            next_message(&first),
+            next_message(&second),
+        ]);
+
+        // Emits one message on each interface. Each emission blocks until the message has been
+        // delivered, guaranteeing the order in which the handler receives them.
+        let emitter = requests_module(&[
+            Request {
+                interface: first.clone().into(),
+                message: vec![1],
+                ..Default::default()
+            },
+            Request {
+                interface: second.clone().into(),
+                message: vec![2],
+                ..Default::default()
+            },
+        ]);
+
+        let handler = system.execute(&handler).unwrap();
+        system.execute(&emitter).unwrap();
+        assert_eq!(
+            run_until_end(&system, handler),
+            ProcessExitReason::Exited { code: 0 }
+        );
+    }
+
+    #[test]
+    fn handler_death() {
+        let system = SystemBuilder::<extrinsics::wasi::WasiExtrinsics>::new([0; 64])
+            .build()
+            .unwrap();
+
+        let register = || Request {
+            interface: interface_ffi::INTERFACE.into(),
+            message: InterfaceMessage::Register(vec![redshirt_time_interface::ffi::INTERFACE])
+                .encode()
+                .0,
+            answer_prefix: Some(vec![0]),
+            store_register: Some(1),
+            ..Default::default()
+        };
+
+        // Reads the monotonic clock through the `time` interface, and exits with the returned
+        // errno.
+        let client = from_wat!(
+            local,
+            r#"(module
+            (import "wasi_snapshot_preview1" "clock_time_get" (func $clock_time_get (param i32 i64 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+            (memory (export "memory") 1)
+            (func $_start
+                (call $proc_exit (call $clock_time_get (i32.const 1) (i64.const 1) (i32.const 0)))
+                unreachable)
+            (export "_start" (func $_start)))
+        "#
+        );
+
+        // Handles the `time` interface, and dies after having received the message of the client
+        // but before answering it.
+        let mut next_message_answer = vec![0];
+        next_message_answer.extend_from_slice(redshirt_time_interface::ffi::INTERFACE.as_ref());
+        let handler = requests_module(&[
+            register(),
+            Request {
+                interface: interface_ffi::INTERFACE.into(),
+                message: InterfaceMessage::NextMessage(NonZeroU64::new(1).unwrap())
+                    .encode()
+                    .0,
+                register_in_message: Some(1),
+                answer_prefix: Some(next_message_answer),
+                ..Default::default()
+            },
+        ]);
+
+        let client = system.execute(&client).unwrap();
+        let handler = system.execute(&handler).unwrap();
+        assert_eq!(
+            run_until_end(&system, handler),
+            ProcessExitReason::Exited { code: 0 }
+        );
+
+        // The message is answered with an error, which is reported as `ERRNO_IO`.
+        assert_eq!(
+            run_until_end(&system, client),
+            ProcessExitReason::Exited { code: 29 }
+        );
+
+        // The registration of the dead handler has been released.
+        let new_handler = system.execute(&requests_module(&[register()])).unwrap();
+        assert_eq!(
+            run_until_end(&system, new_handler),
+            ProcessExitReason::Exited { code: 0 }
+        );
+    }
+
+    #[test]
+    fn list_and_kill() {
+        let system = SystemBuilder::<extrinsics::NoExtrinsics>::new([0; 64])
+            .build()
+            .unwrap();
+
+        let parent = requests_module(&[
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: spawn_message(SpawnModule::Bytes(sleeping_module())),
+                answer_prefix: Some(vec![0]),
+                store_register: Some(1),
+                ..Default::default()
+            },
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: ProcessMessage::List.encode().0,
+                // A list of one element, followed with the `Pid` of the child.
+                answer_prefix: Some(vec![4]),
+                register_in_answer: Some(1),
+                ..Default::default()
+            },
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: ProcessMessage::Kill(Pid::from(0)).encode().0,
+                register_in_message: Some(1),
+                answer_prefix: Some(Ok::<(), ()>(()).encode().0),
+                ..Default::default()
+            },
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: ProcessMessage::Wait(Pid::from(0)).encode().0,
+                register_in_message: Some(1),
+                answer_prefix: Some(Ok::<_, ()>(ExitStatus::Killed).encode().0),
+                ..Default::default()
+            },
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: ProcessMessage::List.encode().0,
+                answer_prefix: Some(Vec::<Pid>::new().encode().0),
+                ..Default::default()
+            },
+            // The child has already ended.
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: ProcessMessage::Kill(Pid::from(0)).encode().0,
+                register_in_message: Some(1),
+                answer_prefix: Some(Err::<(), ()>(()).encode().0),
+                ..Default::default()
+            },
+        ]);
+
+        let parent = system.execute(&parent).unwrap();
+        assert_eq!(
+            run_until_end(&system, parent),
+            ProcessExitReason::Exited { code: 0 }
+        );
+    }
+
+    #[test]
+    fn spawn_from_hash() {
+        let system = SystemBuilder::<extrinsics::NoExtrinsics>::new([0; 64])
+            .build()
+            .unwrap();
+
+        // Registers the `loader` interface, and answers the first message with the content of
+        // `exit_7_module`.
+        let loader = requests_module(&[
+            Request {
+                interface: interface_ffi::INTERFACE.into(),
+                message: InterfaceMessage::Register(vec![loader_ffi::INTERFACE])
+                    .encode()
+                    .0,
+                // `Ok` followed with the registration identifier.
+                answer_prefix: Some(vec![0]),
+                store_register: Some(1),
+                ..Default::default()
+            },
+            Request {
+                interface: interface_ffi::INTERFACE.into(),
+                message: InterfaceMessage::NextMessage(NonZeroU64::new(1).unwrap())
+                    .encode()
+                    .0,
+                register_in_message: Some(1),
+                // Interface notification, followed with the identifier of the message.
+                answer_prefix: Some(
+                    iter::once(0)
+                        .chain(<[u8; 32]>::from(loader_ffi::INTERFACE).iter().copied())
+                        .collect(),
+                ),
+                store_register: Some(33),
+                ..Default::default()
+            },
+            Request {
+                interface: interface_ffi::INTERFACE.into(),
+                message: InterfaceMessage::Answer(
+                    MessageId::try_from(1).unwrap(),
+                    Ok(loader_ffi::LoadResponse {
+                        result: Ok(exit_7_module()),
+                    }
+                    .encode()
+                    .0),
+                )
+                .encode()
+                .0,
+                register_in_message: Some(1),
+                ..Default::default()
+            },
+        ]);
+
+        let parent = requests_module(&[
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: spawn_message(SpawnModule::Hash([1; 32])),
+                answer_prefix: Some(vec![0]),
+                store_register: Some(1),
+                ..Default::default()
+            },
+            Request {
+                interface: process_ffi::INTERFACE.into(),
+                message: ProcessMessage::Wait(Pid::from(0)).encode().0,
+                register_in_message: Some(1),
+                answer_prefix: Some(Ok::<_, ()>(ExitStatus::Exited(7)).encode().0),
+                ..Default::default()
+            },
+        ]);
+
+        let loader = system.execute(&loader).unwrap();
+        let parent = system.execute(&parent).unwrap();
+        assert_eq!(
+            run_until_end(&system, loader),
+            ProcessExitReason::Exited { code: 0 }
+        );
+        assert_eq!(
+            run_until_end(&system, parent),
+            ProcessExitReason::Exited { code: 0 }
+        );
+    }
+
+    #[test]
+    fn threads_new_and_join() {
+        // Emits on the `threads` interface a `New` message whose function has the wrong
+        // signature, then a valid `New` message, then a `Join` for the created thread.
+        // The process exits with a non-zero code if one of the answers isn't the expected one.
+        let interface = <[u8; 32]>::from(redshirt_threads_interface::ffi::INTERFACE)
+            .iter()
+            .map(|byte| format!("\\{:02x}", byte))
+            .collect::<String>();
+        let module = crate::Module::from_bytes(
+            wat::parse_str(format!(
+                r#"(module
+            (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i64 i32) (result i32)))
+            (import "redshirt" "next_notification" (func $next_notification (param i32 i32 i32 i32 i64) (result i32)))
+            (memory $memory 1)
+            (table $table 2 funcref)
+            (elem (i32.const 0) $bad_signature $thread)
+            (data (i32.const 0) "{}")
+            (data (i32.const 32) "\60\00\00\00\0d\00\00\00")
+            (data (i32.const 40) "\70\00\00\00\0d\00\00\00")
+            (data (i32.const 48) "\80\00\00\00\09\00\00\00")
+            (data (i32.const 96) "\00\00\00\00\00\00\00\00\00\00\00\00\00")
+            (data (i32.const 112) "\00\01\00\00\00\2a\00\00\00\00\00\00\00")
+            (data (i32.const 128) "\01")
+            (func $bad_signature (param i32) (param i32))
+            (func $thread (param $user_data i32)
+                (i32.store (i32.const 200) (local.get $user_data)))
+            ;; Emits the message described by the iovec at `$iovec`, waits for the answer, and
+            ;; returns the first byte of the answer, or -1 if there's no answer.
+            (func $request (param $iovec i32) (result i32)
+                (if (i32.ne (call $emit_message (i32.const 0) (local.get $iovec) (i32.const 1) (i64.const 3) (i32.const 64)) (i32.const 0))
+                    (then (return (i32.const -1))))
+                (i64.store (i32.const 72) (i64.load (i32.const 64)))
+                (drop (call $next_notification (i32.const 72) (i32.const 1) (i32.const 256) (i32.const 64) (i64.const 1)))
+                (if (i32.ne (i32.load8_u (i32.const 269)) (i32.const 0))
+                    (then (return (i32.const -1))))
+                (i32.load8_u (i32.const 270)))
+            (func $_start (result i32)
+                (if (i32.ne (call $request (i32.const 32)) (i32.const 1))
+                    (then (return (i32.const 1))))
+                (if (i32.ne (call $request (i32.const 40)) (i32.const 0))
+                    (then (return (i32.const 2))))
+                (i64.store (i32.const 129) (i64.load (i32.const 271)))
+                (if (i32.ne (call $request (i32.const 48)) (i32.const 0))
+                    (then (return (i32.const 3))))
+                (if (i32.ne (i32.load (i32.const 200)) (i32.const 42))
+                    (then (return (i32.const 4))))
+                (i32.const 0))
+            (export "memory" (memory 0))
+            (export "__indirect_function_table" (table 0))
+            (export "_start" (func $_start)))
+        "#,
+                interface
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let system = SystemBuilder::<extrinsics::NoExtrinsics>::new([0; 64])
+            .with_startup_process(module)
+            .build()
+            .unwrap();
+
+        loop {
+            let event = match system.run().now_or_never().unwrap() {
+                ExecuteOut::Direct(event) => Some(event),
+                ExecuteOut::ReadyToRun(ready_to_run) => ready_to_run.run(),
+            };
+
+            match event {
+                Some(SystemRunOutcome::ProgramFinished { outcome, .. }) => {
+                    assert_eq!(outcome, ProcessExitReason::Exited { code: 0 });
+                    break;
+                }
+                Some(_) => panic!(),
+                None => {}
+            }
+        }
+    }
+
+    #[test]
+    fn stuck_emitters() {
+        let now_ms = Arc::new(AtomicU64::new(1_000));
+        let system = SystemBuilder::<extrinsics::NoExtrinsics>::new([0; 64])
+            .with_monotonic_clock({
+                let now_ms = now_ms.clone();
+                move || Duration::from_millis(now_ms.load(Ordering::SeqCst))
+            })
+            .with_stuck_emitter_warning(Duration::from_secs(2))
+            .build()
+            .unwrap();
+
+        // Returns the `StuckEmitter` events generated until there is nothing more to do.
+        fn stuck_events(
+            system: &System<extrinsics::NoExtrinsics>,
+        ) -> Vec<(redshirt_syscalls::InterfaceHash, Pid, Duration)> {
+            let mut events = Vec::new();
+            loop {
+                let event = match system.run().now_or_never() {
+                    Some(ExecuteOut::Direct(event)) => Some(event),
+                    Some(ExecuteOut::ReadyToRun(ready_to_run)) => ready_to_run.run(),
+                    None => return events,
+                };
+
+                match event {
+                    Some(SystemRunOutcome::StuckEmitter {
+                        interface,
+                        pid,
+                        waiting_for,
+                    }) => events.push((interface, pid, waiting_for)),
+                    Some(SystemRunOutcome::ProgramFinished { pid, outcome }) => {
+                        panic!("{:?} finished: {:?}", pid, outcome)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // Emits a message on an interface that nobody handles.
+        let interface = redshirt_syscalls::InterfaceHash::from_raw_hash([0x42; 32]);
+        let emitter = requests_module(&[Request {
+            interface: interface.clone().into(),
+            message: vec![1, 2, 3],
+            answer_prefix: Some(Vec::new()),
+            ..Default::default()
+        }]);
+        let emitter = system.execute(&emitter).unwrap();
+        assert!(stuck_events(&system).is_empty());
+
+        now_ms.store(2_500, Ordering::SeqCst);
+        assert!(stuck_events(&system).is_empty());
+
+        // Each emitter is only reported once.
+        now_ms.store(6_000, Ordering::SeqCst);
+        assert_eq!(
+            stuck_events(&system),
+            vec![(interface.clone(), emitter, Duration::from_secs(5))]
+        );
+        now_ms.store(7_000, Ordering::SeqCst);
+        assert!(stuck_events(&system).is_empty());
+
+        let stuck = system.stuck_interfaces();
+        assert_eq!(stuck.len(), 1);
+        assert_eq!(stuck[0].interface, interface);
+        assert_eq!(
+            stuck[0].waiting_emitters,
+            vec![super::WaitingEmitter {
+                pid: emitter,
+                waiting_for: Duration::from_secs(6),
+            }]
+        );
+
+        // The same list can be obtained through the `kernel-debug` interface.
+        let metrics = system.stuck_emitters_metrics();
+        assert!(metrics.ends_with(&format!(
+            "redshirt_stuck_emitter_waiting_seconds{{interface=\"0x{}\",pid=\"{}\"}} 6\n",
+            "42".repeat(32),
+            u64::from(emitter)
+        )));
+        let debug = requests_module(&[Request {
+            interface: redshirt_kernel_debug_interface::INTERFACE.into(),
+            message: redshirt_kernel_debug_interface::STUCK_EMITTERS_REQUEST.to_vec(),
+            answer_prefix: Some(metrics.into_bytes()),
+            ..Default::default()
+        }]);
+        let debug = system.execute(&debug).unwrap();
+        assert_eq!(
+            run_until_end(&system, debug),
+            ProcessExitReason::Exited { code: 0 }
+        );
+    }
+
+    #[test]
+    fn restart_on_failure() {
+        let module = from_wat!(
+            local,
+            r#"(module
+            (func $_start
+                unreachable)
+            (export "_start" (func $_start)))
+        "#
+        );
+
+        let now_ms = Arc::new(AtomicU64::new(0));
+        let system = SystemBuilder::<extrinsics::NoExtrinsics>::new([0; 64])
+            .with_monotonic_clock({
+                let now_ms = now_ms.clone();
+                move || Duration::from_millis(now_ms.load(Ordering::SeqCst))
+            })
+            .with_startup_process_and_restart_policy(module, RestartPolicy::OnFailure)
+            .build()
+            .unwrap();
+
+        // Returns `None` if there is nothing to do.
+        fn next_event(
+            system: &System<extrinsics::NoExtrinsics>,
+        ) -> Option<SystemRunOutcome<extrinsics::NoExtrinsics>> {
+            loop {
+                let event = match system.run().now_or_never()? {
+                    ExecuteOut::Direct(event) => Some(event),
+                    ExecuteOut::ReadyToRun(ready_to_run) => ready_to_run.run(),
+                };
+
+                if event.is_some() {
+                    return event;
+                }
+            }
+        }
+
+        let first_pid = match next_event(&system) {
+            Some(SystemRunOutcome::ProgramFinished { pid, outcome }) => {
+                assert!(matches!(outcome, ProcessExitReason::Trapped(_)));
+                pid
+            }
+            _ => panic!(),
+        };
+
+        // The process must not be restarted before the back-off delay has elapsed.
+        assert_eq!(system.next_restart(), Some(Duration::from_millis(500)));
+        assert!(next_event(&system).is_none());
+        now_ms.store(10_000, Ordering::SeqCst);
+
+        let second_pid = match next_event(&system) {
+            Some(SystemRunOutcome::ProgramRestarted { previous_pid, pid }) => {
+                assert_eq!(previous_pid, first_pid);
+                pid
+            }
+            _ => panic!(),
+        };
+
+        match next_event(&system) {
+            Some(SystemRunOutcome::ProgramFinished { pid, .. }) => assert_eq!(pid, second_pid),
+            _ => panic!(),
+        }
+    }
 }
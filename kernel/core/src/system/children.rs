@@ -0,0 +1,150 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Processes started through the `process` interface.
+//!
+//! The [`Children`] struct keeps track of which process has started which other process, in
+//! order to answer the messages of processes that want to wait for their children to end, or
+//! kill them.
+
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+use nohash_hasher::BuildNoHashHasher;
+use redshirt_process_interface::ffi::ExitStatus;
+use redshirt_syscalls::{MessageId, Pid};
+
+pub struct Children {
+    // TODO: smarter than a spinloop?
+    inner: spinning_top::Spinlock<Inner>,
+}
+
+struct Inner {
+    // TODO: call shrink_to_fit from time to time?
+    children: HashMap<Pid, Child, BuildNoHashHasher<u64>>,
+}
+
+struct Child {
+    /// Process that has started this process.
+    parent: Pid,
+    /// If `Some`, the process has ended but its parent hasn't waited for it yet.
+    status: Option<ExitStatus>,
+    /// Messages to answer when the process ends.
+    waiters: Vec<MessageId>,
+}
+
+/// Outcome of [`Children::wait`].
+pub enum Wait {
+    /// The process has already ended. The message must be answered immediately.
+    Finished(ExitStatus),
+    /// The message will be returned by [`Children::process_finished`] later.
+    Pending,
+    /// The process doesn't exist, has a different parent, or has already been waited for.
+    Unknown,
+}
+
+impl Children {
+    pub fn new() -> Self {
+        Children {
+            inner: spinning_top::Spinlock::new(Inner {
+                children: Default::default(),
+            }),
+        }
+    }
+
+    /// Calls `spawn` and, on success, registers the returned process as a child of `parent`.
+    ///
+    /// The collection is locked while `spawn` runs, so that the child can't be reported to
+    /// [`Children::process_finished`] before being registered.
+    pub fn spawn<E>(&self, parent: Pid, spawn: impl FnOnce() -> Result<Pid, E>) -> Result<Pid, E> {
+        let mut inner = self.inner.lock();
+        let pid = spawn()?;
+        inner.children.insert(
+            pid,
+            Child {
+                parent,
+                status: None,
+                waiters: Vec::new(),
+            },
+        );
+        Ok(pid)
+    }
+
+    /// Returns true if `pid` is a child of `parent` that hasn't ended yet.
+    pub fn is_running_child(&self, pid: Pid, parent: Pid) -> bool {
+        match self.inner.lock().children.get(&pid) {
+            Some(child) => child.parent == parent && child.status.is_none(),
+            None => false,
+        }
+    }
+
+    /// Returns the list of children of `parent` that haven't ended yet.
+    pub fn running_children(&self, parent: Pid) -> Vec<Pid> {
+        // TODO: O(n) complexity
+        self.inner
+            .lock()
+            .children
+            .iter()
+            .filter(|(_, child)| child.parent == parent && child.status.is_none())
+            .map(|(pid, _)| *pid)
+            .collect()
+    }
+
+    /// Registers a message of the given process that must be answered when the child ends.
+    pub fn wait(&self, pid: Pid, emitter_pid: Pid, message_id: MessageId) -> Wait {
+        let mut inner = self.inner.lock();
+        let child = match inner.children.get_mut(&pid) {
+            Some(c) if c.parent == emitter_pid => c,
+            _ => return Wait::Unknown,
+        };
+
+        if child.status.is_some() {
+            let child = inner.children.remove(&pid).unwrap();
+            Wait::Finished(child.status.unwrap())
+        } else {
+            child.waiters.push(message_id);
+            Wait::Pending
+        }
+    }
+
+    /// Indicates that the given process has ended.
+    ///
+    /// If the process is a child, returns the list of messages to answer. Also forgets about
+    /// the children of this process, as nobody can wait for them anymore.
+    pub fn process_finished(&self, pid: Pid, status: ExitStatus) -> Vec<MessageId> {
+        let mut inner = self.inner.lock();
+
+        // TODO: O(n) complexity
+        inner.children.retain(|_, child| child.parent != pid);
+
+        let child = match inner.children.get_mut(&pid) {
+            Some(c) => c,
+            None => return Vec::new(),
+        };
+        debug_assert!(child.status.is_none());
+
+        if child.waiters.is_empty() {
+            child.status = Some(status);
+            Vec::new()
+        } else {
+            inner.children.remove(&pid).unwrap().waiters
+        }
+    }
+}
+
+impl Default for Children {
+    fn default() -> Self {
+        Children::new()
+    }
+}
@@ -15,10 +15,12 @@
 
 // TODO: doc
 
-use alloc::collections::VecDeque;
-use core::{convert::TryFrom as _, mem, num::NonZeroU64};
-use hashbrown::{hash_map::Entry, HashMap};
-use redshirt_syscalls::{InterfaceHash, MessageId, Pid};
+use alloc::{collections::VecDeque, vec::Vec};
+use core::{convert::TryFrom as _, mem, num::NonZeroU64, time::Duration};
+use hashbrown::{hash_map::Entry, HashMap, HashSet};
+use nohash_hasher::BuildNoHashHasher;
+use redshirt_interface_interface::ffi::InterfaceRegisterError;
+use redshirt_syscalls::{EncodedMessage, InterfaceHash, MessageId, Pid};
 
 pub struct Interfaces {
     // TODO: do something smarter than a spinning lock?
@@ -29,6 +31,19 @@ pub struct Interfaces {
 struct Inner {
     interfaces: HashMap<InterfaceHash, Interface, fnv::FnvBuildHasher>,
     registrations: slab::Slab<InterfaceRegistration>,
+    /// Value of [`PendingAccept::queued_at`] of the oldest entry, within all the
+    /// [`Interface::NotRegistered`], that hasn't been reported by
+    /// [`Interfaces::next_stuck_emitter`] yet. `None` if there is no such entry.
+    ///
+    /// Might be outdated and point to an entry that no longer exists, but is never later than
+    /// the actual oldest entry.
+    oldest_unreported: Option<Duration>,
+    /// Interfaces on which only the processes found in [`Inner::grants`] are allowed to emit
+    /// messages.
+    restricted: HashSet<InterfaceHash, fnv::FnvBuildHasher>,
+    /// For each interface, list of processes that have been granted the right to emit messages
+    /// on it. Entries are only relevant for the interfaces found in [`Inner::restricted`].
+    grants: HashMap<InterfaceHash, HashSet<Pid, BuildNoHashHasher<u64>>, fnv::FnvBuildHasher>,
 }
 
 #[derive(Debug)]
@@ -47,20 +62,41 @@ enum Interface {
         /// corresponds to a thread currently being paused, the total number of entries across
         /// all `pending_accept` fields is bounded by the total number of threads across all
         /// processes.
-        pending_accept: VecDeque<(MessageId, bool)>,
+        pending_accept: VecDeque<PendingAccept>,
     },
 }
 
+/// Message emitted by a program and that hasn't been accepted yet.
 #[derive(Debug)]
-struct InterfaceRegistration {
+struct PendingAccept {
+    /// Interface the message has been emitted on.
     interface: InterfaceHash,
+    message_id: MessageId,
+    needs_answer: bool,
+    emitter_pid: Pid,
+    /// Body of the message if it has been emitted by the kernel itself, in which case there is
+    /// no thread to accept the message from.
+    body: Option<EncodedMessage>,
+    /// Value of the monotonic clock when the message has been emitted.
+    queued_at: Duration,
+    /// True if this entry has already been returned by [`Interfaces::next_stuck_emitter`].
+    reported_stuck: bool,
+}
+
+#[derive(Debug)]
+struct InterfaceRegistration {
+    /// List of interfaces covered by this registration. Never contains duplicates. Empty only
+    /// for the dummy entry at index 0.
+    interfaces: Vec<InterfaceHash>,
     pid: Pid,
     /// Messages of type `NextMessage` sent on the interface interface and that must be answered
     /// with the next interface message.
     queries: VecDeque<MessageId>,
     /// If [`InterfaceRegistration::queries`] is empty, messages emitted by programs and that
     /// haven't been accepted yet are pushed to this field.
-    pending_accept: VecDeque<(MessageId, bool)>,
+    pending_accept: VecDeque<PendingAccept>,
+    /// If `Some`, the given process is allowed to take over this registration.
+    handover_to: Option<Pid>,
 }
 
 impl Interfaces {
@@ -74,19 +110,180 @@ impl Interfaces {
                     // generated registration IDs to never be equal to 0.
                     let mut registrations = slab::Slab::default();
                     let _id = registrations.insert(InterfaceRegistration {
-                        interface: InterfaceHash::from_raw_hash(Default::default()),
+                        interfaces: Vec::new(),
                         pid: 0xdeadbeef.into(), // TODO: ?!
                         queries: VecDeque::new(),
                         pending_accept: VecDeque::new(),
+                        handover_to: None,
                     });
                     assert_eq!(_id, 0);
                     registrations
                 },
+                oldest_unreported: None,
+                restricted: Default::default(),
+                grants: Default::default(),
             }),
         }
     }
 
+    /// Marks the given interface as restricted. Only the processes that have been granted the
+    /// right to do so, using [`Interfaces::grant`] or [`Interfaces::grant_from_handler`], are
+    /// then allowed to emit messages on it.
+    pub fn restrict(&self, interface: InterfaceHash) {
+        self.inner.lock().restricted.insert(interface);
+    }
+
+    /// Returns true if `pid` is allowed to emit messages on the given interface.
+    ///
+    /// [`Interfaces::emit_interface_message`] already performs this check. It must only be
+    /// called explicitly before handling a message on an interface handled by the kernel, as
+    /// these messages don't go through this module.
+    pub fn is_emission_allowed(&self, interface: &InterfaceHash, pid: Pid) -> bool {
+        let inner = self.inner.lock();
+        if !inner.restricted.contains(interface) {
+            return true;
+        }
+
+        inner
+            .grants
+            .get(interface)
+            .map_or(false, |pids| pids.contains(&pid))
+    }
+
+    /// Allows `pid` to emit messages on the given interface.
+    pub fn grant(&self, interface: InterfaceHash, pid: Pid) {
+        self.inner
+            .lock()
+            .grants
+            .entry(interface)
+            .or_default()
+            .insert(pid);
+    }
+
+    /// Cancels a previous call to [`Interfaces::grant`].
+    pub fn revoke(&self, interface: &InterfaceHash, pid: Pid) {
+        let mut inner = self.inner.lock();
+        if let Some(pids) = inner.grants.get_mut(interface) {
+            pids.remove(&pid);
+            if pids.is_empty() {
+                inner.grants.remove(interface);
+            }
+        }
+    }
+
+    /// Same as [`Interfaces::grant`], but on behalf of an interface handler.
+    ///
+    /// Must be passed the [`Pid`] that the registration is expected to belong to. Returns an
+    /// error if the registration doesn't exist, if the ownership doesn't match, or if the
+    /// interface isn't covered by the registration.
+    pub fn grant_from_handler(
+        &self,
+        registration_id: RegistrationId,
+        expected_registerer_pid: Pid,
+        interface: InterfaceHash,
+        pid: Pid,
+    ) -> Result<(), ()> {
+        self.check_handler(registration_id, expected_registerer_pid, &interface)?;
+        self.grant(interface, pid);
+        Ok(())
+    }
+
+    /// Same as [`Interfaces::revoke`], but on behalf of an interface handler.
+    ///
+    /// See [`Interfaces::grant_from_handler`].
+    pub fn revoke_from_handler(
+        &self,
+        registration_id: RegistrationId,
+        expected_registerer_pid: Pid,
+        interface: &InterfaceHash,
+        pid: Pid,
+    ) -> Result<(), ()> {
+        self.check_handler(registration_id, expected_registerer_pid, interface)?;
+        self.revoke(interface, pid);
+        Ok(())
+    }
+
+    /// Checks whether the given registration exists, belongs to `expected_registerer_pid`, and
+    /// covers `interface`.
+    fn check_handler(
+        &self,
+        registration_id: RegistrationId,
+        expected_registerer_pid: Pid,
+        interface: &InterfaceHash,
+    ) -> Result<(), ()> {
+        let registration_id = match usize::try_from(registration_id.0.get()) {
+            Ok(v) => v,
+            Err(_) => return Err(()),
+        };
+
+        let inner = self.inner.lock();
+        match inner.registrations.get(registration_id) {
+            Some(registration)
+                if registration.pid == expected_registerer_pid
+                    && registration.interfaces.contains(interface) =>
+            {
+                Ok(())
+            }
+            _ => Err(()),
+        }
+    }
+
+    /// Removes all the rights granted to the given process, for example because it has
+    /// terminated.
+    pub fn revoke_process(&self, pid: Pid) {
+        let mut inner = self.inner.lock();
+        // TODO: O(n) complexity
+        inner.grants.retain(|_, pids| {
+            pids.remove(&pid);
+            !pids.is_empty()
+        });
+    }
+
+    /// Returns the list of interfaces that are either restricted or on which rights have been
+    /// granted, alongside with the processes that have been granted these rights.
+    pub fn grants(&self) -> Vec<InterfaceGrants> {
+        let inner = self.inner.lock();
+
+        let mut list = inner
+            .restricted
+            .iter()
+            .chain(
+                inner
+                    .grants
+                    .keys()
+                    .filter(|i| !inner.restricted.contains(*i)),
+            )
+            .map(|interface| {
+                let mut pids = inner
+                    .grants
+                    .get(interface)
+                    .map(|pids| pids.iter().copied().collect::<Vec<_>>())
+                    .unwrap_or_default();
+                pids.sort_by_key(|pid| u64::from(*pid));
+                InterfaceGrants {
+                    interface: interface.clone(),
+                    restricted: inner.restricted.contains(interface),
+                    pids,
+                }
+            })
+            .collect::<Vec<_>>();
+        list.sort_by(|a, b| a.interface.as_ref().cmp(b.interface.as_ref()));
+        list
+    }
+
+    /// Returns the process that handles the given interface, if any.
+    pub fn interface_handler(&self, interface: &InterfaceHash) -> Option<Pid> {
+        let inner = self.inner.lock();
+        match inner.interfaces.get(interface)? {
+            Interface::Registered(index) => Some(inner.registrations[*index].pid),
+            Interface::NotRegistered { .. } => None,
+        }
+    }
+
     /// Called when a process requests to deliver a message to an interface handler.
+    ///
+    /// `now` is the current value of the monotonic clock, used to report emitters that have
+    /// been waiting for a long time.
     pub fn emit_interface_message(
         &self,
         interface_hash: &InterfaceHash,
@@ -94,6 +291,53 @@ impl Interfaces {
         emitter_pid: Pid,
         needs_answer: bool,
         immediate: bool,
+        now: Duration,
+    ) -> EmitInterfaceMessage {
+        if !self.is_emission_allowed(interface_hash, emitter_pid) {
+            return EmitInterfaceMessage::Denied;
+        }
+
+        let pending = PendingAccept {
+            interface: interface_hash.clone(),
+            message_id,
+            needs_answer,
+            emitter_pid,
+            body: None,
+            queued_at: now,
+            reported_stuck: false,
+        };
+        self.emit(interface_hash, pending, immediate)
+    }
+
+    /// Same as [`Interfaces::emit_interface_message`], but for a message emitted by the kernel
+    /// itself on behalf of `emitter_pid`, which is typically a "virtual" pid.
+    ///
+    /// The message always expects an answer and is never rejected.
+    pub fn emit_kernel_message(
+        &self,
+        interface_hash: &InterfaceHash,
+        message_id: MessageId,
+        emitter_pid: Pid,
+        body: EncodedMessage,
+        now: Duration,
+    ) -> EmitInterfaceMessage {
+        let pending = PendingAccept {
+            interface: interface_hash.clone(),
+            message_id,
+            needs_answer: true,
+            emitter_pid,
+            body: Some(body),
+            queued_at: now,
+            reported_stuck: false,
+        };
+        self.emit(interface_hash, pending, false)
+    }
+
+    fn emit(
+        &self,
+        interface_hash: &InterfaceHash,
+        pending: PendingAccept,
+        immediate: bool,
     ) -> EmitInterfaceMessage {
         let mut interfaces = self.inner.lock();
         let interfaces = &mut *interfaces; // Avoids borrow errors.
@@ -116,18 +360,18 @@ impl Interfaces {
                 if let Some(query_message_id) = registration.queries.pop_front() {
                     debug_assert!(registration.pending_accept.is_empty());
                     EmitInterfaceMessage::Deliver(MessageDelivery {
-                        to_deliver_message_id: message_id,
-                        interface: registration.interface.clone(),
-                        needs_answer,
+                        to_deliver_message_id: pending.message_id,
+                        interface: pending.interface,
+                        emitter_pid: pending.emitter_pid,
+                        body: pending.body,
+                        needs_answer: pending.needs_answer,
                         query_message_id,
                         recipient_pid: registration.pid,
                     })
                 } else if immediate {
                     EmitInterfaceMessage::Reject
                 } else {
-                    registration
-                        .pending_accept
-                        .push_back((message_id, needs_answer));
+                    registration.pending_accept.push_back(pending);
                     EmitInterfaceMessage::Queued
                 }
             }
@@ -135,8 +379,11 @@ impl Interfaces {
                 if immediate {
                     EmitInterfaceMessage::Reject
                 } else {
+                    if interfaces.oldest_unreported.is_none() {
+                        interfaces.oldest_unreported = Some(pending.queued_at);
+                    }
                     // TODO: is this unbounded queue attackable?
-                    pending_accept.push_back((message_id, needs_answer));
+                    pending_accept.push_back(pending);
                     EmitInterfaceMessage::Queued
                 }
             }
@@ -167,12 +414,14 @@ impl Interfaces {
 
         if let Some(registration) = inner.registrations.get_mut(registration_id) {
             if registration.pid == expected_registerer_pid {
-                if let Some((msg, needs_answer)) = registration.pending_accept.pop_front() {
+                if let Some(pending) = registration.pending_accept.pop_front() {
                     debug_assert!(registration.queries.is_empty());
                     Ok(Some(MessageDelivery {
-                        to_deliver_message_id: msg,
-                        interface: registration.interface.clone(),
-                        needs_answer,
+                        to_deliver_message_id: pending.message_id,
+                        interface: pending.interface,
+                        emitter_pid: pending.emitter_pid,
+                        body: pending.body,
+                        needs_answer: pending.needs_answer,
                         query_message_id,
                         recipient_pid: registration.pid,
                     }))
@@ -188,49 +437,298 @@ impl Interfaces {
         }
     }
 
-    /// Sets the handler of the given interface hash.
+    /// Sets the handler of the given interface hashes.
+    ///
+    /// A single registration covers all the interfaces in `interface_hashes`, and the messages
+    /// emitted on any of them are delivered in the order in which they have been emitted.
+    /// Duplicate entries in the list are ignored.
     ///
     /// On success, returns a [`RegistrationId`] to pass later to refer to that registration.
     ///
-    /// Returns an error if there already exists a handler for this interface.
+    /// Returns an error if the list is empty, or if there already exists a handler for one of
+    /// these interfaces, unless this handler has allowed `pid` to take over its registration with
+    /// [`Interfaces::allow_handover`]. In that case, the previous registration is removed, and its
+    /// messages that haven't been delivered yet are transferred to the new registration if they
+    /// concern one of the interfaces of `interface_hashes`. On error, nothing is modified.
     pub fn set_interface_handler(
         &self,
-        interface_hash: InterfaceHash,
+        interface_hashes: Vec<InterfaceHash>,
         pid: Pid,
-    ) -> Result<NonZeroU64, redshirt_interface_interface::ffi::InterfaceRegisterError> {
+    ) -> Result<NewRegistration, InterfaceRegisterError> {
         let mut interfaces = self.inner.lock();
         let interfaces = &mut *interfaces;
 
-        match interfaces.interfaces.entry(interface_hash) {
-            Entry::Occupied(mut entry) => {
-                let interface = entry.key().clone();
-                match entry.get_mut() {
-                    Interface::Registered(_) =>
-                        Err(redshirt_interface_interface::ffi::InterfaceRegisterError::AlreadyRegistered),
-                    Interface::NotRegistered { pending_accept } => {
-                        let id = interfaces.registrations.insert(InterfaceRegistration {
-                            pid,
-                            interface,
-                            queries: VecDeque::with_capacity(16),  // TODO: be less magic with capacity
-                            pending_accept: mem::take(pending_accept),
-                        });
-                        entry.insert(Interface::Registered(id));
-                        Ok(NonZeroU64::new(u64::try_from(id).unwrap()).unwrap())
-                    }
+        let mut hashes = Vec::with_capacity(interface_hashes.len());
+        for hash in interface_hashes {
+            if !hashes.contains(&hash) {
+                hashes.push(hash);
+            }
+        }
+
+        if hashes.is_empty() {
+            return Err(InterfaceRegisterError::EmptyList);
+        }
+
+        // Check whether the registration is possible before modifying anything.
+        let mut replaced_registrations = Vec::new();
+        for hash in &hashes {
+            if let Some(Interface::Registered(registration_id)) = interfaces.interfaces.get(hash) {
+                if interfaces.registrations[*registration_id].handover_to != Some(pid) {
+                    return Err(InterfaceRegisterError::AlreadyRegistered);
+                }
+                if !replaced_registrations.contains(registration_id) {
+                    replaced_registrations.push(*registration_id);
                 }
             }
-            Entry::Vacant(entry) => {
-                let id = interfaces.registrations.insert(InterfaceRegistration {
-                    pid,
-                    interface: entry.key().clone(),
-                    queries: VecDeque::with_capacity(16), // TODO: be less magic with capacity
-                    pending_accept: VecDeque::with_capacity(16), // TODO: be less magic with capacity
-                });
-                entry.insert(Interface::Registered(id));
-                Ok(NonZeroU64::new(u64::try_from(id).unwrap()).unwrap())
+        }
+
+        // Removing the registrations that are taken over moves back their pending messages to
+        // their respective interfaces, from which they are then picked below.
+        let mut replaced_queries = Vec::new();
+        for registration_id in replaced_registrations {
+            let previous = interfaces.remove_registration(registration_id);
+            replaced_queries.extend(previous.queries);
+        }
+
+        let mut pending_accept = Vec::new();
+        for hash in &hashes {
+            match interfaces.interfaces.remove(hash) {
+                None => {}
+                Some(Interface::NotRegistered { pending_accept: p }) => pending_accept.extend(p),
+                Some(Interface::Registered(_)) => unreachable!(),
+            }
+        }
+        // The sort is stable, and messages of the same interface thus stay in the same order.
+        pending_accept.sort_by_key(|p| p.queued_at);
+
+        let id = interfaces.registrations.insert(InterfaceRegistration {
+            pid,
+            interfaces: hashes.clone(),
+            queries: VecDeque::with_capacity(16), // TODO: be less magic with capacity
+            pending_accept: pending_accept.into(),
+            handover_to: None,
+        });
+        for hash in hashes {
+            interfaces
+                .interfaces
+                .insert(hash, Interface::Registered(id));
+        }
+
+        Ok(NewRegistration {
+            registration_id: NonZeroU64::new(u64::try_from(id).unwrap()).unwrap(),
+            replaced_queries,
+        })
+    }
+
+    /// Allows the process `new_handler` to take over the given registration when it calls
+    /// [`Interfaces::set_interface_handler`].
+    ///
+    /// Must be passed the [`Pid`] that the registration is expected to belong to. Returns an
+    /// error if the registration doesn't exist or the ownership doesn't match.
+    pub fn allow_handover(
+        &self,
+        registration_id: RegistrationId,
+        expected_registerer_pid: Pid,
+        new_handler: Pid,
+    ) -> Result<(), ()> {
+        let registration_id = match usize::try_from(registration_id.0.get()) {
+            Ok(v) => v,
+            Err(_) => return Err(()),
+        };
+
+        let mut inner = self.inner.lock();
+        match inner.registrations.get_mut(registration_id) {
+            Some(registration) if registration.pid == expected_registerer_pid => {
+                registration.handover_to = Some(new_handler);
+                Ok(())
             }
+            _ => Err(()),
         }
     }
+
+    /// Removes the given registration.
+    ///
+    /// Must be passed the [`Pid`] that the registration is expected to belong to. Returns an
+    /// error if the registration doesn't exist or the ownership doesn't match.
+    ///
+    /// On success, returns the interfaces that were registered, and the list of messages of type
+    /// `NextMessage` that were waiting for an interface message and that should be answered with
+    /// an error.
+    ///
+    /// The interfaces become available for registration again. Messages that were waiting for
+    /// the handler to accept them are kept, and will be delivered to the next handler of their
+    /// interface.
+    pub fn unregister(
+        &self,
+        registration_id: RegistrationId,
+        expected_registerer_pid: Pid,
+    ) -> Result<(Vec<InterfaceHash>, Vec<MessageId>), ()> {
+        let registration_id = match usize::try_from(registration_id.0.get()) {
+            Ok(v) => v,
+            Err(_) => return Err(()),
+        };
+
+        let mut inner = self.inner.lock();
+        match inner.registrations.get(registration_id) {
+            Some(registration) if registration.pid == expected_registerer_pid => {}
+            _ => return Err(()),
+        }
+
+        let registration = inner.remove_registration(registration_id);
+        Ok((
+            registration.interfaces,
+            registration.queries.into_iter().collect(),
+        ))
+    }
+
+    /// Returns the list of interfaces that have no handler and that messages are waiting for.
+    ///
+    /// `now` is the current value of the monotonic clock, and is used to calculate how long
+    /// emitters have been waiting.
+    pub fn stuck_interfaces(&self, now: Duration) -> Vec<StuckInterface> {
+        let inner = self.inner.lock();
+        inner
+            .interfaces
+            .iter()
+            .filter_map(|(hash, interface)| match interface {
+                Interface::NotRegistered { pending_accept } if !pending_accept.is_empty() => {
+                    Some(StuckInterface {
+                        interface: hash.clone(),
+                        waiting_emitters: pending_accept
+                            .iter()
+                            .map(|p| WaitingEmitter {
+                                pid: p.emitter_pid,
+                                waiting_for: now.saturating_sub(p.queued_at),
+                            })
+                            .collect(),
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns an emitter that has been waiting for at least `threshold` for a handler to be
+    /// registered, and that hasn't been returned by this method before.
+    ///
+    /// `now` is the current value of the monotonic clock.
+    pub fn next_stuck_emitter(
+        &self,
+        now: Duration,
+        threshold: Duration,
+    ) -> Option<(InterfaceHash, WaitingEmitter)> {
+        let mut inner = self.inner.lock();
+        let inner = &mut *inner;
+
+        // Fast path, in order to avoid iterating over all the interfaces every time.
+        match inner.oldest_unreported {
+            Some(oldest) if now.saturating_sub(oldest) >= threshold => {}
+            _ => return None,
+        }
+
+        // TODO: O(n) complexity
+        let mut found = None;
+        let mut oldest_unreported = None;
+        for (hash, interface) in inner.interfaces.iter_mut() {
+            let pending_accept = match interface {
+                Interface::NotRegistered { pending_accept } => pending_accept,
+                Interface::Registered(_) => continue,
+            };
+
+            for pending in pending_accept.iter_mut().filter(|p| !p.reported_stuck) {
+                if found.is_none() && now.saturating_sub(pending.queued_at) >= threshold {
+                    pending.reported_stuck = true;
+                    found = Some((
+                        hash.clone(),
+                        WaitingEmitter {
+                            pid: pending.emitter_pid,
+                            waiting_for: now.saturating_sub(pending.queued_at),
+                        },
+                    ));
+                    continue;
+                }
+
+                oldest_unreported = match oldest_unreported {
+                    Some(o) if o <= pending.queued_at => Some(o),
+                    _ => Some(pending.queued_at),
+                };
+            }
+        }
+
+        inner.oldest_unreported = oldest_unreported;
+        found
+    }
+
+    /// Removes all the registrations belonging to the given process, for example because it has
+    /// terminated.
+    ///
+    /// See also [`Interfaces::unregister`].
+    pub fn unregister_process(&self, pid: Pid) -> UnregisteredProcess {
+        let mut inner = self.inner.lock();
+
+        let mut outcome = UnregisteredProcess {
+            interfaces: Vec::new(),
+            queries: Vec::new(),
+        };
+
+        // TODO: O(n) complexity
+        let registration_ids = inner
+            .registrations
+            .iter()
+            .skip(1) // Skip the dummy entry at index 0.
+            .filter(|(_, r)| r.pid == pid)
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+
+        for registration_id in registration_ids {
+            let registration = inner.remove_registration(registration_id);
+            outcome.queries.extend(registration.queries);
+            outcome.interfaces.extend(registration.interfaces);
+        }
+
+        outcome
+    }
+}
+
+impl Inner {
+    /// Removes the given registration and marks its interfaces as not registered.
+    ///
+    /// Messages that were waiting for the handler to accept them are moved back to their
+    /// interface, while the `queries` of the registration are returned as part of the removed
+    /// registration.
+    fn remove_registration(&mut self, registration_id: usize) -> InterfaceRegistration {
+        debug_assert_ne!(registration_id, 0);
+        let mut registration = self.registrations.remove(registration_id);
+
+        for interface in &registration.interfaces {
+            let _removed = self.interfaces.remove(interface);
+            debug_assert!(
+                matches!(_removed, Some(Interface::Registered(id)) if id == registration_id)
+            );
+        }
+
+        for pending in mem::take(&mut registration.pending_accept) {
+            // Keep `oldest_unreported` accurate, as these entries might be older.
+            if !pending.reported_stuck {
+                self.oldest_unreported = match self.oldest_unreported {
+                    Some(o) if o <= pending.queued_at => Some(o),
+                    _ => Some(pending.queued_at),
+                };
+            }
+
+            match self
+                .interfaces
+                .entry(pending.interface.clone())
+                .or_insert_with(|| Interface::NotRegistered {
+                    pending_accept: VecDeque::new(),
+                }) {
+                Interface::NotRegistered { pending_accept } => pending_accept.push_back(pending),
+                Interface::Registered(_) => unreachable!(),
+            }
+        }
+
+        registration
+    }
 }
 
 impl Default for Interfaces {
@@ -243,9 +741,13 @@ impl Default for Interfaces {
 pub struct MessageDelivery {
     /// Identifier of the message to be delivered.
     pub to_deliver_message_id: MessageId,
-    /// Registered interface the message concerns.
-    // TODO: is this needed? programs should be able to deduce this from the message id
+    /// Interface the message has been emitted on. A registration can cover multiple interfaces.
     pub interface: InterfaceHash,
+    /// Process that has emitted the message.
+    pub emitter_pid: Pid,
+    /// Body of the message, if it has been emitted with [`Interfaces::emit_kernel_message`].
+    /// If `None`, the message must be accepted from the thread that has emitted it.
+    pub body: Option<EncodedMessage>,
     /// True if the message in `to_deliver_message_id` expects an answer.
     pub needs_answer: bool,
     pub query_message_id: MessageId,
@@ -262,6 +764,67 @@ pub enum EmitInterfaceMessage {
     /// Message has been queued and might later be delivered when
     /// [`Interfaces::emit_message_query`] is called. Can only happen if `immediate` is `false`.
     Queued,
+    /// The emitter isn't allowed to emit messages on this interface. See
+    /// [`Interfaces::restrict`]. Never returned by [`Interfaces::emit_kernel_message`].
+    Denied,
+}
+
+/// Successful outcome of [`Interfaces::set_interface_handler`].
+#[must_use]
+pub struct NewRegistration {
+    /// Identifier of the new registration.
+    pub registration_id: NonZeroU64,
+    /// If the registration has taken over a previous registration, messages of type
+    /// `NextMessage` emitted by the previous handler that were waiting for an interface message.
+    /// They should be answered with an error, which the previous handler interprets as the end
+    /// of its registration.
+    pub replaced_queries: Vec<MessageId>,
+}
+
+/// Interface without any handler, and that messages are waiting for.
+///
+/// See [`System::stuck_interfaces`](super::System::stuck_interfaces).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StuckInterface {
+    /// Hash of the interface.
+    pub interface: InterfaceHash,
+    /// List of emitters waiting for a handler, in the order in which they have emitted their
+    /// message. A process can appear multiple times.
+    pub waiting_emitters: Vec<WaitingEmitter>,
+}
+
+/// Process waiting for an interface handler to be registered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaitingEmitter {
+    /// Process that has emitted the message.
+    pub pid: Pid,
+    /// How long the process has been waiting for.
+    pub waiting_for: Duration,
+}
+
+/// Interface and the processes allowed to emit messages on it.
+///
+/// See [`Interfaces::grants`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceGrants {
+    /// Hash of the interface.
+    pub interface: InterfaceHash,
+    /// True if only the processes in [`InterfaceGrants::pids`] can emit messages on this
+    /// interface.
+    pub restricted: bool,
+    /// Processes that have been granted the right to emit messages on this interface, ordered
+    /// by [`Pid`].
+    pub pids: Vec<Pid>,
+}
+
+/// Outcome of [`Interfaces::unregister_process`].
+#[must_use]
+pub struct UnregisteredProcess {
+    /// List of interfaces that the process was handling and that are now unregistered.
+    pub interfaces: Vec<InterfaceHash>,
+    /// Messages of type `NextMessage` emitted by the process that were waiting for an interface
+    /// message. They should be answered with an error.
+    pub queries: Vec<MessageId>,
 }
 
 /// Identifier of an interface registration.
@@ -281,3 +844,285 @@ impl From<RegistrationId> for NonZeroU64 {
         v.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{EmitInterfaceMessage, InterfaceGrants, Interfaces};
+    use core::{convert::TryFrom as _, time::Duration};
+    use redshirt_syscalls::{InterfaceHash, MessageId, Pid};
+
+    #[test]
+    fn multiple_interfaces_per_registration() {
+        let interfaces = Interfaces::new();
+        let old = InterfaceHash::from_raw_hash([1; 32]);
+        let new = InterfaceHash::from_raw_hash([2; 32]);
+        let handler = Pid::from(1);
+        let emitter = Pid::from(2);
+        let message_id = |n: u64| MessageId::try_from(n).unwrap();
+
+        // Emitted before the registration, and delivered afterwards.
+        let outcome = interfaces.emit_interface_message(
+            &new,
+            message_id(10),
+            emitter,
+            true,
+            false,
+            Duration::ZERO,
+        );
+        assert!(matches!(outcome, EmitInterfaceMessage::Queued));
+
+        let registration = interfaces
+            .set_interface_handler(vec![old.clone(), new.clone(), old.clone()], handler)
+            .unwrap();
+        assert!(interfaces
+            .set_interface_handler(vec![new.clone()], Pid::from(3))
+            .is_err());
+        assert!(interfaces
+            .set_interface_handler(Vec::new(), handler)
+            .is_err());
+
+        let delivery = interfaces
+            .emit_message_query(registration.registration_id.into(), message_id(20), handler)
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.interface, new);
+        assert_eq!(delivery.to_deliver_message_id, message_id(10));
+
+        let outcome = interfaces.emit_interface_message(
+            &old,
+            message_id(11),
+            emitter,
+            true,
+            false,
+            Duration::from_secs(1),
+        );
+        assert!(matches!(outcome, EmitInterfaceMessage::Queued));
+        let delivery = interfaces
+            .emit_message_query(registration.registration_id.into(), message_id(21), handler)
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.interface, old);
+
+        let (unregistered, _) = interfaces
+            .unregister(registration.registration_id.into(), handler)
+            .unwrap();
+        assert_eq!(unregistered, vec![old, new]);
+    }
+
+    #[test]
+    fn handover_with_outstanding_query() {
+        let interfaces = Interfaces::new();
+        let interface = InterfaceHash::from_raw_hash([1; 32]);
+        let old_handler = Pid::from(1);
+        let new_handler = Pid::from(2);
+        let emitter = Pid::from(3);
+        let message_id = |n: u64| MessageId::try_from(n).unwrap();
+
+        let old_registration = interfaces
+            .set_interface_handler(vec![interface.clone()], old_handler)
+            .unwrap();
+
+        // The old handler waits for a message when the handover happens.
+        assert!(interfaces
+            .emit_message_query(
+                old_registration.registration_id.into(),
+                message_id(20),
+                old_handler
+            )
+            .unwrap()
+            .is_none());
+
+        interfaces
+            .allow_handover(
+                old_registration.registration_id.into(),
+                old_handler,
+                new_handler,
+            )
+            .unwrap();
+        let new_registration = interfaces
+            .set_interface_handler(vec![interface.clone()], new_handler)
+            .unwrap();
+        assert_eq!(new_registration.replaced_queries, vec![message_id(20)]);
+
+        // The outstanding query of the old handler no longer receives messages.
+        let outcome = interfaces.emit_interface_message(
+            &interface,
+            message_id(10),
+            emitter,
+            true,
+            false,
+            Duration::ZERO,
+        );
+        assert!(matches!(outcome, EmitInterfaceMessage::Queued));
+        assert!(interfaces
+            .emit_message_query(
+                old_registration.registration_id.into(),
+                message_id(21),
+                old_handler
+            )
+            .is_err());
+
+        let delivery = interfaces
+            .emit_message_query(
+                new_registration.registration_id.into(),
+                message_id(22),
+                new_handler,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.to_deliver_message_id, message_id(10));
+        assert_eq!(delivery.query_message_id, message_id(22));
+        assert_eq!(delivery.recipient_pid, new_handler);
+    }
+
+    #[test]
+    fn unregister_process() {
+        let interfaces = Interfaces::new();
+        let first = InterfaceHash::from_raw_hash([1; 32]);
+        let second = InterfaceHash::from_raw_hash([2; 32]);
+        let handler = Pid::from(1);
+        let emitter = Pid::from(2);
+        let new_handler = Pid::from(3);
+        let message_id = |n: u64| MessageId::try_from(n).unwrap();
+
+        let first_registration = interfaces
+            .set_interface_handler(vec![first.clone()], handler)
+            .unwrap();
+        let second_registration = interfaces
+            .set_interface_handler(vec![second.clone()], handler)
+            .unwrap();
+
+        // The handler dies while it waits for a message on the first interface, and before it
+        // has accepted a message emitted on the second interface.
+        assert!(interfaces
+            .emit_message_query(
+                first_registration.registration_id.into(),
+                message_id(20),
+                handler
+            )
+            .unwrap()
+            .is_none());
+        let outcome = interfaces.emit_interface_message(
+            &second,
+            message_id(10),
+            emitter,
+            true,
+            false,
+            Duration::ZERO,
+        );
+        assert!(matches!(outcome, EmitInterfaceMessage::Queued));
+
+        let unregistered = interfaces.unregister_process(handler);
+        assert_eq!(unregistered.interfaces, vec![first.clone(), second.clone()]);
+        assert_eq!(unregistered.queries, vec![message_id(20)]);
+        assert!(interfaces
+            .emit_message_query(
+                second_registration.registration_id.into(),
+                message_id(21),
+                handler
+            )
+            .is_err());
+
+        // The interfaces can be registered again, and the message that hadn't been accepted is
+        // delivered to the new handler.
+        let new_registration = interfaces
+            .set_interface_handler(vec![first, second.clone()], new_handler)
+            .unwrap();
+        let delivery = interfaces
+            .emit_message_query(
+                new_registration.registration_id.into(),
+                message_id(22),
+                new_handler,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.interface, second);
+        assert_eq!(delivery.to_deliver_message_id, message_id(10));
+        assert_eq!(delivery.emitter_pid, emitter);
+    }
+
+    #[test]
+    fn grants() {
+        let interfaces = Interfaces::new();
+        let restricted = InterfaceHash::from_raw_hash([1; 32]);
+        let open = InterfaceHash::from_raw_hash([2; 32]);
+        let handler = Pid::from(1);
+        let emitter = Pid::from(2);
+
+        interfaces.restrict(restricted.clone());
+        assert!(!interfaces.is_emission_allowed(&restricted, emitter));
+        assert!(interfaces.is_emission_allowed(&open, emitter));
+
+        let message_id = |n: u64| MessageId::try_from(n).unwrap();
+        let outcome = interfaces.emit_interface_message(
+            &restricted,
+            message_id(10),
+            emitter,
+            true,
+            false,
+            Duration::ZERO,
+        );
+        assert!(matches!(outcome, EmitInterfaceMessage::Denied));
+        let outcome = interfaces.emit_interface_message(
+            &open,
+            message_id(11),
+            emitter,
+            true,
+            false,
+            Duration::ZERO,
+        );
+        assert!(matches!(outcome, EmitInterfaceMessage::Queued));
+
+        // Only the handler of the interface can grant rights on it.
+        let registration = interfaces
+            .set_interface_handler(vec![restricted.clone()], handler)
+            .unwrap();
+        assert!(interfaces
+            .grant_from_handler(
+                registration.registration_id.into(),
+                emitter,
+                restricted.clone(),
+                emitter
+            )
+            .is_err());
+        assert!(interfaces
+            .grant_from_handler(
+                registration.registration_id.into(),
+                handler,
+                open.clone(),
+                emitter
+            )
+            .is_err());
+        interfaces
+            .grant_from_handler(
+                registration.registration_id.into(),
+                handler,
+                restricted.clone(),
+                emitter,
+            )
+            .unwrap();
+        assert!(interfaces.is_emission_allowed(&restricted, emitter));
+        assert!(!interfaces.is_emission_allowed(&restricted, Pid::from(3)));
+        let outcome = interfaces.emit_interface_message(
+            &restricted,
+            message_id(12),
+            emitter,
+            true,
+            false,
+            Duration::ZERO,
+        );
+        assert!(matches!(outcome, EmitInterfaceMessage::Queued));
+
+        assert_eq!(
+            interfaces.grants(),
+            vec![InterfaceGrants {
+                interface: restricted.clone(),
+                restricted: true,
+                pids: vec![emitter],
+            }]
+        );
+
+        interfaces.revoke_process(emitter);
+        assert!(!interfaces.is_emission_allowed(&restricted, emitter));
+    }
+}
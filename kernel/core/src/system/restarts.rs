@@ -0,0 +1,163 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Restarting the startup processes that have ended.
+//!
+//! The [`Restarts`] struct keeps track of the processes that have been started with a
+//! [`RestartPolicy`] other than [`RestartPolicy::Never`], and of the processes that have ended
+//! and are waiting to be restarted.
+//!
+//! Processes that crash repeatedly are restarted after a delay that doubles after each restart,
+//! in order to not waste resources restarting a program that can't work. This delay is reset
+//! once a process has been running for long enough. See [`Backoff`].
+
+use crate::extrinsics::LaunchConfig;
+use crate::module::Module;
+use crate::scheduler::ProcessLimits;
+
+use alloc::{sync::Arc, vec::Vec};
+use core::time::Duration;
+use hashbrown::HashMap;
+use nohash_hasher::BuildNoHashHasher;
+use redshirt_process_interface::restart::Backoff;
+use redshirt_syscalls::{InterfaceHash, Pid};
+
+/// What to do when a startup process ends.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Never restart the process.
+    Never,
+    /// Restart the process only if it hasn't exited with a code of 0.
+    OnFailure,
+    /// Always restart the process, no matter how it has ended.
+    Always,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy::Never
+    }
+}
+
+pub struct Restarts {
+    // TODO: smarter than a spinloop?
+    inner: spinning_top::Spinlock<Inner>,
+}
+
+struct Inner {
+    /// Processes that are running and must be restarted when they end.
+    running: HashMap<Pid, Supervised, BuildNoHashHasher<u64>>,
+    /// Processes that have ended and that must be restarted at the given time.
+    // TODO: O(n) complexity everywhere; use a binary heap?
+    pending: Vec<(Duration, Pid, Supervised)>,
+}
+
+/// Everything needed to start a process again.
+#[derive(Clone)]
+pub struct Supervised {
+    pub module: Arc<Module>,
+    pub limits: ProcessLimits,
+    pub launch_config: LaunchConfig,
+    pub policy: RestartPolicy,
+    /// Interfaces the process is allowed to emit messages on.
+    pub grants: Vec<InterfaceHash>,
+    /// Value of the monotonic clock when the process has last been started.
+    pub started_at: Duration,
+    /// Delay to wait before the next restart.
+    backoff: Backoff,
+}
+
+impl Supervised {
+    pub fn new(
+        module: Arc<Module>,
+        limits: ProcessLimits,
+        launch_config: LaunchConfig,
+        policy: RestartPolicy,
+        grants: Vec<InterfaceHash>,
+        started_at: Duration,
+    ) -> Self {
+        Supervised {
+            module,
+            limits,
+            launch_config,
+            policy,
+            grants,
+            started_at,
+            backoff: Backoff::new(),
+        }
+    }
+}
+
+impl Restarts {
+    pub fn new() -> Self {
+        Restarts {
+            inner: spinning_top::Spinlock::new(Inner {
+                running: Default::default(),
+                pending: Vec::new(),
+            }),
+        }
+    }
+
+    /// Registers a process that has just been started.
+    ///
+    /// Must be called before any of the threads of the process can run, as a process that ends
+    /// before being registered isn't restarted.
+    pub fn insert(&self, pid: Pid, supervised: Supervised) {
+        debug_assert_ne!(supervised.policy, RestartPolicy::Never);
+        self.inner.lock().running.insert(pid, supervised);
+    }
+
+    /// Indicates that the given process has ended. `now` is the current value of the monotonic
+    /// clock.
+    ///
+    /// Returns the time when the process must be restarted, or `None` if the process isn't
+    /// supervised or must not be restarted.
+    pub fn process_finished(&self, pid: Pid, success: bool, now: Duration) -> Option<Duration> {
+        let mut inner = self.inner.lock();
+        let mut supervised = inner.running.remove(&pid)?;
+
+        match supervised.policy {
+            RestartPolicy::Never => return None,
+            RestartPolicy::OnFailure if success => return None,
+            RestartPolicy::OnFailure | RestartPolicy::Always => {}
+        }
+
+        let ran_for = now.checked_sub(supervised.started_at).unwrap_or_default();
+        let restart_at = now + supervised.backoff.next_delay(ran_for);
+        inner.pending.push((restart_at, pid, supervised));
+        Some(restart_at)
+    }
+
+    /// Returns the earliest time when a process must be restarted, or `None` if no process is
+    /// waiting to be restarted.
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.inner.lock().pending.iter().map(|(at, _, _)| *at).min()
+    }
+
+    /// Returns a process whose restart time is before or equal to `now`, alongside with the
+    /// [`Pid`] it had before ending.
+    pub fn next_due(&self, now: Duration) -> Option<(Pid, Supervised)> {
+        let mut inner = self.inner.lock();
+        let pos = inner.pending.iter().position(|(at, _, _)| *at <= now)?;
+        let (_, previous_pid, supervised) = inner.pending.remove(pos);
+        Some((previous_pid, supervised))
+    }
+}
+
+impl Default for Restarts {
+    fn default() -> Self {
+        Restarts::new()
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Threads started through the `threads` interface.
+//!
+//! The [`Threads`] struct keeps track of the secondary threads that processes have started, in
+//! order to answer the messages of processes that want to wait for them to finish.
+
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+use nohash_hasher::BuildNoHashHasher;
+use redshirt_syscalls::{MessageId, Pid, ThreadId};
+
+pub struct Threads {
+    // TODO: smarter than a spinloop?
+    inner: spinning_top::Spinlock<Inner>,
+}
+
+struct Inner {
+    // TODO: call shrink_to_fit from time to time?
+    threads: HashMap<ThreadId, Thread, BuildNoHashHasher<u64>>,
+}
+
+struct Thread {
+    /// Process the thread belongs to.
+    pid: Pid,
+    /// True if the thread has finished but nobody has joined it yet.
+    finished: bool,
+    /// Messages to answer when the thread finishes.
+    joiners: Vec<MessageId>,
+}
+
+/// Outcome of [`Threads::join`].
+pub enum Join {
+    /// The thread has already finished. The message must be answered immediately.
+    Finished,
+    /// The message will be returned by [`Threads::thread_finished`] later.
+    Pending,
+    /// The thread doesn't exist, belongs to a different process, or has already been joined.
+    Unknown,
+}
+
+impl Threads {
+    pub fn new() -> Self {
+        Threads {
+            inner: spinning_top::Spinlock::new(Inner {
+                threads: Default::default(),
+            }),
+        }
+    }
+
+    /// Registers a thread that has just been started.
+    pub fn insert(&self, thread_id: ThreadId, pid: Pid) {
+        // The thread might already have finished and been reported through
+        // `thread_finished`, in which case we keep the existing entry.
+        self.inner
+            .lock()
+            .threads
+            .entry(thread_id)
+            .or_insert_with(|| Thread {
+                pid,
+                finished: false,
+                joiners: Vec::new(),
+            });
+    }
+
+    /// Indicates that the given thread has finished. Returns the list of messages to answer.
+    pub fn thread_finished(&self, thread_id: ThreadId, pid: Pid) -> Vec<MessageId> {
+        let mut inner = self.inner.lock();
+        let thread = inner.threads.entry(thread_id).or_insert_with(|| Thread {
+            pid,
+            finished: false,
+            joiners: Vec::new(),
+        });
+        debug_assert_eq!(thread.pid, pid);
+        debug_assert!(!thread.finished);
+
+        if thread.joiners.is_empty() {
+            thread.finished = true;
+            Vec::new()
+        } else {
+            inner.threads.remove(&thread_id).unwrap().joiners
+        }
+    }
+
+    /// Registers a message of the given process that must be answered when the thread finishes.
+    pub fn join(&self, thread_id: ThreadId, emitter_pid: Pid, message_id: MessageId) -> Join {
+        let mut inner = self.inner.lock();
+        let thread = match inner.threads.get_mut(&thread_id) {
+            Some(t) if t.pid == emitter_pid => t,
+            _ => return Join::Unknown,
+        };
+
+        if thread.finished {
+            inner.threads.remove(&thread_id);
+            Join::Finished
+        } else {
+            thread.joiners.push(message_id);
+            Join::Pending
+        }
+    }
+
+    /// Removes from the collection all the threads of the given process. Returns the list of
+    /// messages that were waiting for these threads.
+    pub fn remove_process(&self, pid: Pid) -> Vec<MessageId> {
+        // TODO: O(n) complexity
+        let mut inner = self.inner.lock();
+        let mut joiners = Vec::new();
+        inner.threads.retain(|_, thread| {
+            if thread.pid != pid {
+                return true;
+            }
+            joiners.append(&mut thread.joiners);
+            false
+        });
+        joiners
+    }
+}
+
+impl Default for Threads {
+    fn default() -> Self {
+        Threads::new()
+    }
+}
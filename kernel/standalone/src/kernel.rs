@@ -28,12 +28,12 @@ use crate::{
 };
 
 use alloc::{format, string::String, sync::Arc, vec::Vec};
-use core::{pin::Pin, sync::atomic::Ordering};
+use core::{convert::TryFrom as _, pin::Pin, sync::atomic::Ordering, time::Duration};
 use futures::prelude::*;
 use redshirt_core::{
     build_wasm_module,
     extrinsics::wasi::WasiExtrinsics,
-    system::{KernelDebugMetricsRequest, SystemRunOutcome},
+    system::{KernelDebugMetricsRequest, RestartPolicy, SystemRunOutcome},
     System,
 };
 
@@ -79,11 +79,21 @@ impl Kernel {
             .with_native_interface_handler(redshirt_random_interface::ffi::INTERFACE)
             .with_native_interface_handler(redshirt_pci_interface::ffi::INTERFACE)
             .with_native_interface_handler(redshirt_kernel_log_interface::ffi::INTERFACE)
+            .with_fuel_per_run(1_000_000)
+            .with_monotonic_clock({
+                let platform_specific = platform_specific.clone();
+                move || {
+                    let nanos = platform_specific.as_ref().monotonic_clock();
+                    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::max_value()))
+                }
+            })
+            .with_stuck_emitter_warning(Duration::from_secs(10))
             .with_startup_process(build_wasm_module!(
                 "../../../programs/p2p-loader",
                 "programs-loader"
             ))
             .with_startup_process(build_wasm_module!("../../../programs/compositor"))
+            .with_startup_process(build_wasm_module!("../../../programs/console"))
             .with_startup_process(build_wasm_module!("../../../programs/pci-printer"))
             // TODO: actually implement system-time and remove this dummy; https://github.com/tomaka/redshirt/issues/542
             .with_startup_process(build_wasm_module!("../../../programs/dummy-system-time"))
@@ -93,8 +103,15 @@ impl Kernel {
                 "../../../programs/diagnostics-http-server"
             ))
             .with_startup_process(build_wasm_module!("../../../programs/hello-world"))
-            .with_startup_process(build_wasm_module!("../../../programs/network-manager"))
-            .with_startup_process(build_wasm_module!("../../../programs/e1000"));
+            .with_startup_process_and_restart_policy(
+                build_wasm_module!("../../../programs/network-manager"),
+                RestartPolicy::OnFailure,
+            )
+            .with_startup_process(build_wasm_module!("../../../programs/files-manager"))
+            .with_startup_process_and_restart_policy(
+                build_wasm_module!("../../../programs/e1000"),
+                RestartPolicy::OnFailure,
+            );
 
         // TODO: remove the cfg guards once rpi-framebuffer is capable of auto-detecting whether
         // it should enable itself
@@ -103,8 +120,14 @@ impl Kernel {
             .with_startup_process(build_wasm_module!("../../../programs/rpi-framebuffer"));
 
         // TODO: temporary; uncomment to test
-        /*system_builder = system_builder.with_main_program(
+        // The main program is the one the user interacts with, and is the only one whose standard
+        // input is read from the console.
+        /*system_builder = system_builder.with_main_program_and_launch_config(
             ModuleHash::from_base58("FWMwRMQCKdWVDdKyx6ogQ8sXuoeDLNzZxniRMyD5S71").unwrap(),
+            LaunchConfig {
+                console_stdin: true,
+                ..Default::default()
+            },
         );*/
 
         let cpu_counters = (0..platform_specific.as_ref().num_cpus().get())
@@ -157,11 +180,22 @@ impl Kernel {
                 continue;
             }
 
+            // The core only restarts the processes that are due when `run` is called. We stop
+            // waiting for it once the next restart is due, so that it is called again.
+            let next_restart = match self.system.next_restart() {
+                Some(at) => {
+                    future::Either::Left(self.platform_specific.as_ref().timer(at.as_nanos()))
+                }
+                None => future::Either::Right(future::pending()),
+            };
+
             // Ask the core for the next event, or the next process execution to perform.
             let core_work = self.system.run();
-            futures::pin_mut!(core_work);
-            let core_event = match future::select(interface_handlers, core_work).await {
-                future::Either::Right((event, _)) => event,
+            futures::pin_mut!(next_restart, core_work);
+            let core_or_restart = future::select(core_work, next_restart);
+            let core_event = match future::select(interface_handlers, core_or_restart).await {
+                future::Either::Right((future::Either::Left((event, _)), _)) => event,
+                future::Either::Right((future::Either::Right(((), _)), _)) => continue,
                 future::Either::Left(((message_id, response), _)) => {
                     self.system.answer_message(message_id, Ok(response));
                     continue;
@@ -213,12 +247,41 @@ impl Kernel {
         monotonic_clock_value: u128,
     ) {
         match core_event {
-            SystemRunOutcome::ProgramFinished { pid, .. } => {
+            SystemRunOutcome::ProgramFinished { pid, outcome } => {
+                if !outcome.is_success() {
+                    self.platform_specific
+                        .write_log(&format!("Process {:?} has stopped: {}\n", pid, outcome));
+                }
                 self.hardware.process_destroyed(pid);
             }
+            SystemRunOutcome::ProgramRestarted { previous_pid, pid } => {
+                self.platform_specific.write_log(&format!(
+                    "Process {:?} has been restarted as {:?}\n",
+                    previous_pid, pid
+                ));
+            }
+            SystemRunOutcome::ProgramRestartFailed {
+                previous_pid,
+                error,
+            } => {
+                self.platform_specific.write_log(&format!(
+                    "Failed to restart process {:?}: {:?}\n",
+                    previous_pid, error
+                ));
+            }
             SystemRunOutcome::KernelDebugMetricsRequest(report) => {
                 self.report_kernel_metrics(report, monotonic_clock_value);
             }
+            SystemRunOutcome::StuckEmitter {
+                interface,
+                pid,
+                waiting_for,
+            } => {
+                self.platform_specific.write_log(&format!(
+                    "Process {:?} has been waiting for {:?} for a handler of {:?}\n",
+                    pid, waiting_for, interface
+                ));
+            }
 
             // Time handling.
             SystemRunOutcome::NativeInterfaceMessage {
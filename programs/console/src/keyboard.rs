@@ -0,0 +1,117 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Translation of keyboard events into lines of text.
+//!
+//! Assumes a US QWERTY layout.
+
+mod tests;
+
+/// Accumulates keyboard events into a line of text, and reports the line once Enter has been
+/// pressed.
+#[derive(Debug, Default)]
+pub struct LineEditor {
+    left_shift: bool,
+    right_shift: bool,
+    caps_lock: bool,
+    /// Line being typed.
+    current_line: String,
+}
+
+impl LineEditor {
+    /// Creates a new `LineEditor` with an empty line.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the line being typed, not including the final `\n`.
+    pub fn current_line(&self) -> &str {
+        &self.current_line
+    }
+
+    /// Updates the state with a key press or release. `scancode` is as defined in the USB HID
+    /// Usage tables.
+    ///
+    /// Returns the line that has been typed, including the final `\n`, if Enter has been
+    /// pressed.
+    pub fn on_key(&mut self, scancode: u16, pressed: bool) -> Option<String> {
+        match scancode {
+            0xe1 => self.left_shift = pressed,
+            0xe5 => self.right_shift = pressed,
+            0x39 if pressed => self.caps_lock = !self.caps_lock,
+            _ if !pressed => {}
+            // Enter and keypad Enter.
+            0x28 | 0x58 => {
+                self.current_line.push('\n');
+                return Some(std::mem::take(&mut self.current_line));
+            }
+            // Backspace.
+            0x2a => {
+                self.current_line.pop();
+            }
+            _ => {
+                if let Some(c) = self.translate(scancode) {
+                    self.current_line.push(c);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the character corresponding to the given key, taking the modifiers into account.
+    fn translate(&self, scancode: u16) -> Option<char> {
+        let shift = self.left_shift || self.right_shift;
+
+        // Letters.
+        if (0x04..=0x1d).contains(&scancode) {
+            let c = char::from(b'a' + (scancode - 0x04) as u8);
+            return Some(if shift != self.caps_lock {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            });
+        }
+
+        let (normal, shifted) = match scancode {
+            0x1e => ('1', '!'),
+            0x1f => ('2', '@'),
+            0x20 => ('3', '#'),
+            0x21 => ('4', '$'),
+            0x22 => ('5', '%'),
+            0x23 => ('6', '^'),
+            0x24 => ('7', '&'),
+            0x25 => ('8', '*'),
+            0x26 => ('9', '('),
+            0x27 => ('0', ')'),
+            0x2b => ('\t', '\t'),
+            0x2c => (' ', ' '),
+            0x2d => ('-', '_'),
+            0x2e => ('=', '+'),
+            0x2f => ('[', '{'),
+            0x30 => (']', '}'),
+            0x31 => ('\\', '|'),
+            0x33 => (';', ':'),
+            0x34 => ('\'', '"'),
+            0x35 => ('`', '~'),
+            0x36 => (',', '<'),
+            0x37 => ('.', '>'),
+            0x38 => ('/', '?'),
+            _ => return None,
+        };
+
+        Some(if shift { shifted } else { normal })
+    }
+}
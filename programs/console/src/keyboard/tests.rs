@@ -0,0 +1,100 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+#![cfg(test)]
+
+use super::LineEditor;
+
+/// Presses and releases the given key.
+fn type_key(editor: &mut LineEditor, scancode: u16) -> Option<String> {
+    let line = editor.on_key(scancode, true);
+    assert!(editor.on_key(scancode, false).is_none());
+    line
+}
+
+#[test]
+fn line_reported_on_enter() {
+    let mut editor = LineEditor::new();
+    // `h`, `i`, `1`, space.
+    for scancode in &[0x0b, 0x0c, 0x1e, 0x2c] {
+        assert!(type_key(&mut editor, *scancode).is_none());
+    }
+    assert_eq!(editor.current_line(), "hi1 ");
+
+    assert_eq!(type_key(&mut editor, 0x28).as_deref(), Some("hi1 \n"));
+    assert_eq!(editor.current_line(), "");
+}
+
+#[test]
+fn keypad_enter() {
+    let mut editor = LineEditor::new();
+    assert!(type_key(&mut editor, 0x04).is_none());
+    assert_eq!(type_key(&mut editor, 0x58).as_deref(), Some("a\n"));
+}
+
+#[test]
+fn shift_and_caps_lock() {
+    let mut editor = LineEditor::new();
+
+    // Left shift held while typing `a` and `1`.
+    assert!(editor.on_key(0xe1, true).is_none());
+    type_key(&mut editor, 0x04);
+    type_key(&mut editor, 0x1e);
+    assert!(editor.on_key(0xe1, false).is_none());
+    type_key(&mut editor, 0x04);
+
+    // Caps lock only affects letters, and is cancelled by shift.
+    type_key(&mut editor, 0x39);
+    type_key(&mut editor, 0x04);
+    type_key(&mut editor, 0x1e);
+    assert!(editor.on_key(0xe5, true).is_none());
+    type_key(&mut editor, 0x04);
+    assert!(editor.on_key(0xe5, false).is_none());
+    type_key(&mut editor, 0x39);
+    type_key(&mut editor, 0x04);
+
+    assert_eq!(editor.current_line(), "A!aA1aa");
+}
+
+#[test]
+fn backspace() {
+    let mut editor = LineEditor::new();
+    type_key(&mut editor, 0x04);
+    type_key(&mut editor, 0x05);
+    type_key(&mut editor, 0x2a);
+    assert_eq!(editor.current_line(), "a");
+
+    // Backspace on an empty line does nothing.
+    type_key(&mut editor, 0x2a);
+    type_key(&mut editor, 0x2a);
+    assert_eq!(editor.current_line(), "");
+}
+
+#[test]
+fn unknown_keys_ignored() {
+    let mut editor = LineEditor::new();
+    // F1 and right arrow.
+    type_key(&mut editor, 0x3a);
+    type_key(&mut editor, 0x4f);
+    assert_eq!(editor.current_line(), "");
+}
+
+#[test]
+fn nothing_typed_on_release() {
+    let mut editor = LineEditor::new();
+    assert!(editor.on_key(0x04, false).is_none());
+    assert!(editor.on_key(0x28, false).is_none());
+    assert_eq!(editor.current_line(), "");
+}
@@ -0,0 +1,116 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Console.
+//!
+//! Registers the `console` interface, and feeds it with the text typed on the keyboard.
+//! Keyboard events are received through a framebuffer created with the `framebuffer` interface,
+//! on which the text being typed is drawn.
+//!
+//! Text is made available one line at a time, once Enter has been pressed.
+
+use futures::prelude::*;
+use redshirt_console_interface::ffi as console_ffi;
+use redshirt_framebuffer_interface::ffi as fb_ffi;
+use redshirt_interface_interface::DecodedInterfaceOrDestroyed;
+use redshirt_syscalls::{Decode as _, MessageId, Pid};
+use std::{cmp, collections::VecDeque, convert::TryFrom as _};
+
+mod keyboard;
+mod screen;
+
+fn main() {
+    redshirt_log_interface::init();
+    redshirt_syscalls::block_on(async_main())
+}
+
+async fn async_main() {
+    let mut registration = redshirt_interface_interface::register_interface(console_ffi::INTERFACE)
+        .await
+        .unwrap();
+
+    let mut framebuffer = redshirt_framebuffer_interface::Framebuffer::new(true, 640, 480).await;
+    let mut screen = screen::Screen::new(640, 480);
+    let mut line_editor = keyboard::LineEditor::new();
+    framebuffer.set_data(&screen.render(""));
+
+    // Text that has been typed and validated, but not delivered yet.
+    let mut available = VecDeque::<u8>::new();
+    // `Read` messages waiting for some text to be available, with their emitter and the
+    // maximum length requested.
+    let mut pending_reads = VecDeque::<(Pid, MessageId, u32)>::new();
+
+    loop {
+        futures::select! {
+            interface_event = registration.next_message_raw().fuse() => {
+                match interface_event {
+                    DecodedInterfaceOrDestroyed::Interface(msg) => {
+                        let message_id = match msg.message_id {
+                            Some(m) => m,
+                            None => continue,
+                        };
+
+                        match console_ffi::ConsoleMessage::decode(msg.actual_data) {
+                            Ok(console_ffi::ConsoleMessage::Read { max_len: 0 }) => {
+                                redshirt_interface_interface::emit_answer(
+                                    message_id,
+                                    &console_ffi::ReadResponse { data: Vec::new() },
+                                );
+                            }
+                            Ok(console_ffi::ConsoleMessage::Read { max_len }) => {
+                                pending_reads.push_back((msg.emitter_pid, message_id, max_len));
+                            }
+                            Err(_) => redshirt_interface_interface::emit_message_error(message_id),
+                        }
+                    }
+                    DecodedInterfaceOrDestroyed::ProcessDestroyed(msg) => {
+                        pending_reads.retain(|(pid, _, _)| *pid != msg.pid);
+                    }
+                }
+            }
+            event = framebuffer.next_event().fuse() => {
+                if let fb_ffi::Event::KeyboardChange { scancode, new_state } = event {
+                    let pressed = matches!(new_state, fb_ffi::ElementState::Pressed);
+                    if let Some(line) = line_editor.on_key(scancode, pressed) {
+                        screen.push_line(line.trim_end_matches('\n'));
+                        available.extend(line.into_bytes());
+                    }
+                    // Releasing a key never changes the text.
+                    if pressed {
+                        framebuffer.set_data(&screen.render(line_editor.current_line()));
+                    }
+                }
+            }
+        }
+
+        // Answer the pending reads for as long as text is available.
+        while !available.is_empty() {
+            let (_, message_id, max_len) = match pending_reads.pop_front() {
+                Some(r) => r,
+                None => break,
+            };
+
+            let len = cmp::min(
+                available.len(),
+                usize::try_from(max_len).unwrap_or(usize::max_value()),
+            );
+            let data = available.drain(..len).collect::<Vec<_>>();
+            redshirt_interface_interface::emit_answer(
+                message_id,
+                &console_ffi::ReadResponse { data },
+            );
+        }
+    }
+}
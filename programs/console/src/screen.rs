@@ -0,0 +1,124 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Drawing of the text typed on the console.
+
+use std::{collections::VecDeque, convert::TryFrom as _};
+
+mod tests;
+
+/// Width and height in pixels of a character.
+const CHARACTER_SIZE: usize = 8;
+
+/// Lines of text that have been typed, drawn from top to bottom.
+///
+/// Lines too long to fit the width of the screen are wrapped. Once the screen is full, the
+/// oldest lines are scrolled out.
+#[derive(Debug)]
+pub struct Screen {
+    width: usize,
+    height: usize,
+    /// Lines that have been validated, oldest first. Never contains more lines than can be
+    /// displayed.
+    lines: VecDeque<String>,
+}
+
+impl Screen {
+    /// Creates a new empty `Screen` of the given size in pixels.
+    pub fn new(width: u32, height: u32) -> Self {
+        Screen {
+            width: usize::try_from(width).unwrap(),
+            height: usize::try_from(height).unwrap(),
+            lines: VecDeque::new(),
+        }
+    }
+
+    /// Adds a line that has been validated. `line` must not contain the final `\n`.
+    pub fn push_line(&mut self, line: &str) {
+        self.lines.push_back(line.to_owned());
+        while self.lines.len() > self.num_rows() {
+            self.lines.pop_front();
+        }
+    }
+
+    /// Returns the content of the screen, in the format expected by the `framebuffer` interface,
+    /// followed with `current_line`, the line being typed.
+    pub fn render(&self, current_line: &str) -> Vec<u8> {
+        let mut out = vec![0; self.width * self.height * 3];
+
+        let num_columns = self.width / CHARACTER_SIZE;
+        if num_columns == 0 {
+            return out;
+        }
+
+        let rows = self
+            .lines
+            .iter()
+            .map(|l| &l[..])
+            .chain(Some(current_line))
+            .flat_map(|line| {
+                let chars = line.chars().collect::<Vec<_>>();
+                if chars.is_empty() {
+                    vec![Vec::new()]
+                } else {
+                    chars.chunks(num_columns).map(|c| c.to_vec()).collect()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let first_row = rows.len().saturating_sub(self.num_rows());
+        for (row_index, row) in rows[first_row..].iter().enumerate() {
+            for (column_index, chr) in row.iter().enumerate() {
+                self.draw_character(
+                    &mut out,
+                    column_index * CHARACTER_SIZE,
+                    row_index * CHARACTER_SIZE,
+                    *chr,
+                );
+            }
+        }
+
+        out
+    }
+
+    /// Number of rows of characters that fit the screen.
+    fn num_rows(&self) -> usize {
+        self.height / CHARACTER_SIZE
+    }
+
+    /// Draws a white character at the given position in pixels. Characters outside of the ASCII
+    /// range are drawn as `?`.
+    fn draw_character(&self, out: &mut [u8], x: usize, y: usize, chr: char) {
+        let glyph = glyph(if chr.is_ascii() { chr } else { '?' });
+
+        for glyph_y in 0..CHARACTER_SIZE {
+            for glyph_x in 0..CHARACTER_SIZE {
+                let opacity = glyph[glyph_y * CHARACTER_SIZE + glyph_x];
+                let pixel = ((y + glyph_y) * self.width + x + glyph_x) * 3;
+                out[pixel..pixel + 3].copy_from_slice(&[opacity; 3]);
+            }
+        }
+    }
+}
+
+/// Returns the opacity of each of the pixels of the given ASCII character, row by row.
+fn glyph(chr: char) -> &'static [u8] {
+    debug_assert!(chr.is_ascii());
+    let index = usize::from(chr as u8) * CHARACTER_SIZE * CHARACTER_SIZE;
+    &FONT_DATA[index..index + CHARACTER_SIZE * CHARACTER_SIZE]
+}
+
+/// Sprite sheet of the 128 ASCII characters, generated by the build script.
+const FONT_DATA: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/font.bin"));
@@ -0,0 +1,92 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+#![cfg(test)]
+
+use super::{glyph, Screen, CHARACTER_SIZE};
+
+/// Returns the pixels of the character at the given row and column of a rendered screen, in the
+/// same format as [`glyph`].
+fn cell(rendered: &[u8], width: usize, column: usize, row: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    for y in 0..CHARACTER_SIZE {
+        for x in 0..CHARACTER_SIZE {
+            let pixel = ((row * CHARACTER_SIZE + y) * width + column * CHARACTER_SIZE + x) * 3;
+            assert_eq!(rendered[pixel], rendered[pixel + 1]);
+            assert_eq!(rendered[pixel], rendered[pixel + 2]);
+            out.push(rendered[pixel]);
+        }
+    }
+    out
+}
+
+#[test]
+fn empty_screen() {
+    let screen = Screen::new(32, 16);
+    let rendered = screen.render("");
+    assert_eq!(rendered.len(), 32 * 16 * 3);
+    assert!(rendered.iter().all(|b| *b == 0));
+}
+
+#[test]
+fn current_line_drawn_after_lines() {
+    // Makes sure that the comparisons below are meaningful.
+    assert_ne!(glyph('a'), glyph(' '));
+    assert_ne!(glyph('a'), glyph('b'));
+
+    let mut screen = Screen::new(32, 24);
+    screen.push_line("ab");
+    let rendered = screen.render("c");
+
+    assert_eq!(cell(&rendered, 32, 0, 0), glyph('a'));
+    assert_eq!(cell(&rendered, 32, 1, 0), glyph('b'));
+    assert_eq!(cell(&rendered, 32, 0, 1), glyph('c'));
+    assert_eq!(cell(&rendered, 32, 1, 1), glyph(' '));
+    assert_eq!(cell(&rendered, 32, 0, 2), glyph(' '));
+}
+
+#[test]
+fn long_lines_wrapped() {
+    // Four columns.
+    let screen = Screen::new(32, 16);
+    let rendered = screen.render("abcdef");
+    assert_eq!(cell(&rendered, 32, 3, 0), glyph('d'));
+    assert_eq!(cell(&rendered, 32, 0, 1), glyph('e'));
+    assert_eq!(cell(&rendered, 32, 1, 1), glyph('f'));
+}
+
+#[test]
+fn oldest_lines_scrolled_out() {
+    // Two rows.
+    let mut screen = Screen::new(32, 16);
+    screen.push_line("a");
+    screen.push_line("b");
+    screen.push_line("c");
+    let rendered = screen.render("d");
+    assert_eq!(cell(&rendered, 32, 0, 0), glyph('c'));
+    assert_eq!(cell(&rendered, 32, 0, 1), glyph('d'));
+
+    // An empty line being typed still occupies a row.
+    let rendered = screen.render("");
+    assert_eq!(cell(&rendered, 32, 0, 0), glyph('c'));
+    assert_eq!(cell(&rendered, 32, 0, 1), glyph(' '));
+}
+
+#[test]
+fn non_ascii_drawn_as_question_mark() {
+    let screen = Screen::new(32, 16);
+    let rendered = screen.render("é");
+    assert_eq!(cell(&rendered, 32, 0, 0), glyph('?'));
+}
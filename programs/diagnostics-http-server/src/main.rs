@@ -54,6 +54,19 @@ fn main() {
                         .status(hyper::StatusCode::OK)
                         .header("Content-Type", "text/plain; version=0.0.4")
                         .body(hyper::Body::from(metrics))
+                } else if req.uri().path() == "/stuck-emitters" {
+                    let metrics =
+                        redshirt_kernel_debug_interface::get_stuck_emitters_metrics().await;
+                    hyper::Response::builder()
+                        .status(hyper::StatusCode::OK)
+                        .header("Content-Type", "text/plain; version=0.0.4")
+                        .body(hyper::Body::from(metrics))
+                } else if req.uri().path() == "/grants" {
+                    let metrics = redshirt_kernel_debug_interface::get_grants_metrics().await;
+                    hyper::Response::builder()
+                        .status(hyper::StatusCode::OK)
+                        .header("Content-Type", "text/plain; version=0.0.4")
+                        .body(hyper::Body::from(metrics))
                 } else {
                     hyper::Response::builder()
                         .status(hyper::StatusCode::NOT_FOUND)
@@ -0,0 +1,157 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! In-memory copy of the content of a disk.
+
+use std::{cell::RefCell, cmp, collections::BTreeSet, convert::TryFrom as _, io, mem, rc::Rc};
+
+/// Content of a disk, kept in memory.
+///
+/// Implements the `Read`, `Write` and `Seek` traits so that it can be passed to `fatfs`. The
+/// content is shared between all the clones of a `DiskImage`, which makes it possible to find
+/// out which sectors have been modified after `fatfs` has taken ownership of one of them.
+#[derive(Clone)]
+pub struct DiskImage {
+    inner: Rc<RefCell<Inner>>,
+    /// Position of the cursor. Not shared between clones.
+    position: u64,
+}
+
+struct Inner {
+    /// Content of the disk.
+    data: Vec<u8>,
+    /// Size in bytes of a sector of the disk.
+    sector_size: u32,
+    /// Sectors modified since the last call to [`DiskImage::take_dirty_sectors`].
+    dirty_sectors: BTreeSet<u64>,
+}
+
+impl DiskImage {
+    /// Creates a new image whose content is full of zeroes.
+    pub fn new(sector_size: u32, num_sectors: u32) -> Self {
+        let len = usize::try_from(u64::from(sector_size) * u64::from(num_sectors)).unwrap();
+        DiskImage {
+            inner: Rc::new(RefCell::new(Inner {
+                data: vec![0; len],
+                sector_size,
+                dirty_sectors: BTreeSet::new(),
+            })),
+            position: 0,
+        }
+    }
+
+    /// Copies data read from the disk to the image, without marking the sectors as dirty.
+    ///
+    /// Data that would go past the end of the disk is ignored.
+    pub fn fill(&self, sector_lba: u64, data: &[u8]) {
+        let mut inner = self.inner.borrow_mut();
+        let start = usize::try_from(sector_lba * u64::from(inner.sector_size))
+            .unwrap_or(usize::max_value());
+        let start = cmp::min(start, inner.data.len());
+        let len = cmp::min(data.len(), inner.data.len() - start);
+        inner.data[start..start + len].copy_from_slice(&data[..len]);
+    }
+
+    /// Returns the sectors that have been modified since the last call, grouped by ranges of
+    /// consecutive sectors. Each element contains the first sector of the range and the content
+    /// of the range.
+    pub fn take_dirty_sectors(&self) -> Vec<(u64, Vec<u8>)> {
+        let mut inner = self.inner.borrow_mut();
+        let dirty_sectors = mem::take(&mut inner.dirty_sectors);
+        let sector_size = u64::from(inner.sector_size);
+
+        let mut out: Vec<(u64, Vec<u8>)> = Vec::new();
+        for sector in dirty_sectors {
+            let start = usize::try_from(sector * sector_size).unwrap();
+            let sector_data = &inner.data[start..start + inner.sector_size as usize];
+            match out.last_mut() {
+                Some((first, data)) if *first + data.len() as u64 / sector_size == sector => {
+                    data.extend_from_slice(sector_data)
+                }
+                _ => out.push((sector, sector_data.to_vec())),
+            }
+        }
+        out
+    }
+}
+
+impl io::Read for DiskImage {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let inner = self.inner.borrow();
+        let start = usize::try_from(self.position).unwrap_or(usize::max_value());
+        let start = cmp::min(start, inner.data.len());
+        let len = cmp::min(buf.len(), inner.data.len() - start);
+        buf[..len].copy_from_slice(&inner.data[start..start + len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl io::Write for DiskImage {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = self.inner.borrow_mut();
+        // Writing past the end of the disk isn't possible. Returning `Ok(0)` makes `write_all`
+        // fail with `WriteZero`.
+        let start = usize::try_from(self.position).unwrap_or(usize::max_value());
+        let start = cmp::min(start, inner.data.len());
+        let len = cmp::min(buf.len(), inner.data.len() - start);
+        if len == 0 {
+            return Ok(0);
+        }
+
+        inner.data[start..start + len].copy_from_slice(&buf[..len]);
+        let sector_size = u64::from(inner.sector_size);
+        let first_sector = start as u64 / sector_size;
+        let last_sector = (start + len - 1) as u64 / sector_size;
+        inner.dirty_sectors.extend(first_sector..=last_sector);
+
+        self.position += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl io::Seek for DiskImage {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let len = self.inner.borrow().data.len() as u64;
+        let new_position = match pos {
+            io::SeekFrom::Start(n) => Some(n),
+            io::SeekFrom::End(n) => add_signed(len, n),
+            io::SeekFrom::Current(n) => add_signed(self.position, n),
+        };
+
+        match new_position {
+            Some(n) => {
+                self.position = n;
+                Ok(n)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+fn add_signed(base: u64, offset: i64) -> Option<u64> {
+    if offset >= 0 {
+        base.checked_add(offset as u64)
+    } else {
+        base.checked_sub(offset.unsigned_abs())
+    }
+}
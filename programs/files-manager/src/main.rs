@@ -0,0 +1,560 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Exposes the FAT file systems found on disks through the `files` interface.
+//!
+//! Registers the `disk` interface. Each disk that gets registered is read entirely into memory,
+//! then mounted if it contains a FAT file system, and exposed as a volume named `disk0`,
+//! `disk1`, and so on in the order of registration.
+//!
+//! Writes are applied to the in-memory copy of the disk, then written back to the disk in the
+//! background.
+
+use futures::prelude::*;
+use redshirt_disk_interface::ffi as disk_ffi;
+use redshirt_files_interface::ffi as files_ffi;
+use redshirt_interface_interface::DecodedInterfaceOrDestroyed;
+use redshirt_syscalls::{Decode as _, MessageId, Pid};
+use std::{
+    cmp,
+    collections::{HashMap, VecDeque},
+    io::{self, Read as _, Seek as _, Write as _},
+    mem,
+};
+
+mod disk_image;
+
+/// Maximum number of sectors requested in a single read command.
+const SECTORS_PER_READ: u32 = 128;
+
+fn main() {
+    redshirt_log_interface::init();
+    redshirt_syscalls::block_on(async_main())
+}
+
+async fn async_main() {
+    let mut disk_registration =
+        redshirt_interface_interface::register_interface(disk_ffi::INTERFACE)
+            .await
+            .unwrap();
+    let mut files_registration =
+        redshirt_interface_interface::register_interface(files_ffi::INTERFACE)
+            .await
+            .unwrap();
+
+    let mut state = State::default();
+
+    loop {
+        futures::select! {
+            interface_event = disk_registration.next_message_raw().fuse() => {
+                match interface_event {
+                    DecodedInterfaceOrDestroyed::Interface(msg) => {
+                        match disk_ffi::DiskMessage::decode(msg.actual_data) {
+                            Ok(msg_data) => state.on_disk_message(msg.emitter_pid, msg.message_id, msg_data),
+                            Err(_) => {
+                                if let Some(message_id) = msg.message_id {
+                                    redshirt_interface_interface::emit_message_error(message_id);
+                                }
+                            }
+                        }
+                    }
+                    DecodedInterfaceOrDestroyed::ProcessDestroyed(msg) => {
+                        state.on_process_destroyed(msg.pid);
+                    }
+                }
+            }
+            interface_event = files_registration.next_message_raw().fuse() => {
+                match interface_event {
+                    DecodedInterfaceOrDestroyed::Interface(msg) => {
+                        match files_ffi::FilesMessage::decode(msg.actual_data) {
+                            Ok(msg_data) => state.on_files_message(msg.emitter_pid, msg.message_id, msg_data),
+                            Err(_) => {
+                                if let Some(message_id) = msg.message_id {
+                                    redshirt_interface_interface::emit_message_error(message_id);
+                                }
+                            }
+                        }
+                    }
+                    DecodedInterfaceOrDestroyed::ProcessDestroyed(msg) => {
+                        state.on_process_destroyed(msg.pid);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Disks that have been registered. Indexed by the process that has registered the disk and
+    /// the identifier it has chosen.
+    disks: HashMap<(Pid, u64), Disk>,
+    /// Number of disks registered since the start. Used to name volumes.
+    num_disks_registered: u64,
+    /// Reads that haven't finished yet. Contains the disk and the first sector of the read.
+    reads: HashMap<disk_ffi::ReadId, ((Pid, u64), u64)>,
+    /// Identifier to use for the next read command.
+    next_read_id: u64,
+    /// Identifier to use for the next write command.
+    next_write_id: u64,
+    /// Files that are currently open. Indexed by the identifier reported through the `files`
+    /// interface.
+    files: HashMap<u64, OpenFile>,
+    /// Identifier to assign to the next opened file.
+    next_file_id: u64,
+}
+
+struct Disk {
+    /// Name of the volume reported through the `files` interface.
+    volume: String,
+    /// True if writing to this disk is allowed.
+    allow_write: bool,
+    /// Copy of the content of the disk.
+    image: disk_image::DiskImage,
+    /// Number of reads that haven't finished yet. The disk is mounted once this reaches 0.
+    pending_reads: usize,
+    /// Mounted file system. `None` if the disk is still being read, or if mounting has failed.
+    fs: Option<fatfs::FileSystem<disk_image::DiskImage>>,
+    /// Commands that must be sent to the driver of the disk.
+    commands: VecDeque<disk_ffi::DiskCommand>,
+    /// `DiskNextCommand` messages waiting for a command to be available.
+    next_command_messages: VecDeque<MessageId>,
+    /// `Open` messages received while the disk is still being read.
+    pending_opens: Vec<(Pid, MessageId, files_ffi::Open)>,
+}
+
+struct OpenFile {
+    /// Process that has opened the file. Only this process is allowed to access it.
+    owner: Pid,
+    /// Key of the disk in [`State::disks`].
+    disk: (Pid, u64),
+    /// Path of the file within the volume.
+    path: String,
+}
+
+impl State {
+    fn on_disk_message(
+        &mut self,
+        emitter_pid: Pid,
+        message_id: Option<MessageId>,
+        message: disk_ffi::DiskMessage,
+    ) {
+        match message {
+            disk_ffi::DiskMessage::RegisterDisk {
+                id,
+                allow_write,
+                sector_size,
+                num_sectors,
+            } => {
+                let key = (emitter_pid, id);
+                if sector_size == 0 || self.disks.contains_key(&key) {
+                    log::warn!("Ignoring invalid disk registration");
+                    return;
+                }
+
+                let volume = format!("disk{}", self.num_disks_registered);
+                self.num_disks_registered += 1;
+                log::info!(
+                    "Registered {} ({} sectors of {} bytes)",
+                    volume,
+                    num_sectors,
+                    sector_size
+                );
+
+                // TODO: the entire disk is loaded in memory, which doesn't scale to large disks
+                let mut disk = Disk {
+                    volume,
+                    allow_write,
+                    image: disk_image::DiskImage::new(sector_size, num_sectors),
+                    pending_reads: 0,
+                    fs: None,
+                    commands: VecDeque::new(),
+                    next_command_messages: VecDeque::new(),
+                    pending_opens: Vec::new(),
+                };
+
+                for first_sector in (0..num_sectors).step_by(SECTORS_PER_READ as usize) {
+                    let read_id = disk_ffi::ReadId(self.next_read_id);
+                    self.next_read_id += 1;
+                    disk.commands.push_back(disk_ffi::DiskCommand::StartRead {
+                        id: read_id,
+                        sector_lba: u64::from(first_sector),
+                        num_sectors: cmp::min(SECTORS_PER_READ, num_sectors - first_sector),
+                    });
+                    self.reads.insert(read_id, (key, u64::from(first_sector)));
+                    disk.pending_reads += 1;
+                }
+
+                self.disks.insert(key, disk);
+                if self.disks[&key].pending_reads == 0 {
+                    self.mount(key);
+                }
+            }
+            disk_ffi::DiskMessage::UnregisterDisk(id) => {
+                self.unregister_disk((emitter_pid, id));
+            }
+            disk_ffi::DiskMessage::DiskNextCommand(id) => {
+                let message_id = match message_id {
+                    Some(m) => m,
+                    None => return,
+                };
+
+                if let Some(disk) = self.disks.get_mut(&(emitter_pid, id)) {
+                    disk.next_command_messages.push_back(message_id);
+                    disk.dispatch_commands();
+                } else {
+                    redshirt_interface_interface::emit_message_error(message_id);
+                }
+            }
+            disk_ffi::DiskMessage::ReadFinished(read_id, data) => {
+                // Only the process that has registered the disk is allowed to report reads.
+                let (key, sector_lba) = match self.reads.get(&read_id) {
+                    Some(&(key, sector_lba)) if key.0 == emitter_pid => (key, sector_lba),
+                    _ => return,
+                };
+                self.reads.remove(&read_id);
+
+                let disk = self.disks.get_mut(&key).unwrap();
+                disk.image.fill(sector_lba, &data);
+                disk.pending_reads -= 1;
+                if disk.pending_reads == 0 {
+                    self.mount(key);
+                }
+            }
+            disk_ffi::DiskMessage::WriteFinished(_) => {
+                // TODO: writes are considered successful as soon as they are issued
+            }
+        }
+    }
+
+    fn on_files_message(
+        &mut self,
+        emitter_pid: Pid,
+        message_id: Option<MessageId>,
+        message: files_ffi::FilesMessage,
+    ) {
+        match message {
+            files_ffi::FilesMessage::Open(open) => {
+                let message_id = match message_id {
+                    Some(m) => m,
+                    None => return,
+                };
+
+                let disk = self
+                    .disks
+                    .iter_mut()
+                    .find(|(_, disk)| disk.volume == open.volume);
+                let key = match disk {
+                    Some((_, disk)) if disk.pending_reads != 0 => {
+                        disk.pending_opens.push((emitter_pid, message_id, open));
+                        return;
+                    }
+                    Some((key, _)) => *key,
+                    None => {
+                        redshirt_interface_interface::emit_answer(
+                            message_id,
+                            &files_ffi::OpenResponse {
+                                result: Err(files_ffi::FilesError::NoSuchVolume),
+                            },
+                        );
+                        return;
+                    }
+                };
+
+                let result = self.open(emitter_pid, key, open);
+                redshirt_interface_interface::emit_answer(
+                    message_id,
+                    &files_ffi::OpenResponse { result },
+                );
+            }
+            files_ffi::FilesMessage::Read(read) => {
+                let message_id = match message_id {
+                    Some(m) => m,
+                    None => return,
+                };
+
+                let result = self.read(emitter_pid, read);
+                redshirt_interface_interface::emit_answer(
+                    message_id,
+                    &files_ffi::ReadResponse { result },
+                );
+            }
+            files_ffi::FilesMessage::Write(write) => {
+                let result = self.write(emitter_pid, write);
+                if let Some(message_id) = message_id {
+                    redshirt_interface_interface::emit_answer(
+                        message_id,
+                        &files_ffi::WriteResponse { result },
+                    );
+                }
+            }
+            files_ffi::FilesMessage::Close(file_id) => {
+                if self
+                    .files
+                    .get(&file_id)
+                    .map_or(false, |f| f.owner == emitter_pid)
+                {
+                    self.files.remove(&file_id);
+                }
+            }
+        }
+    }
+
+    fn on_process_destroyed(&mut self, pid: Pid) {
+        self.files.retain(|_, file| file.owner != pid);
+        for disk in self.disks.values_mut() {
+            disk.pending_opens.retain(|(owner, _, _)| *owner != pid);
+        }
+
+        let disks = self
+            .disks
+            .keys()
+            .filter(|(owner, _)| *owner == pid)
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in disks {
+            self.unregister_disk(key);
+        }
+    }
+
+    /// Removes the given disk, if it exists. Files opened on this disk become invalid.
+    fn unregister_disk(&mut self, key: (Pid, u64)) {
+        let disk = match self.disks.remove(&key) {
+            Some(d) => d,
+            None => return,
+        };
+
+        log::info!("Unregistered {}", disk.volume);
+        for (_, message_id, _) in disk.pending_opens {
+            redshirt_interface_interface::emit_answer(
+                message_id,
+                &files_ffi::OpenResponse {
+                    result: Err(files_ffi::FilesError::NoSuchVolume),
+                },
+            );
+        }
+
+        self.reads.retain(|_, (disk, _)| *disk != key);
+        self.files.retain(|_, file| file.disk != key);
+    }
+
+    /// Called once the entire content of a disk has been read. Tries to mount the file system,
+    /// then answers the `Open` messages that were waiting for this to happen.
+    fn mount(&mut self, key: (Pid, u64)) {
+        let disk = self.disks.get_mut(&key).unwrap();
+        debug_assert!(disk.fs.is_none());
+        debug_assert_eq!(disk.pending_reads, 0);
+
+        match fatfs::FileSystem::new(disk.image.clone(), fatfs::FsOptions::new()) {
+            Ok(fs) => {
+                log::info!("Mounted {}", disk.volume);
+                disk.fs = Some(fs);
+            }
+            Err(err) => log::warn!("Failed to mount {}: {}", disk.volume, err),
+        }
+
+        disk.flush(&mut self.next_write_id);
+
+        for (owner, message_id, open) in mem::take(&mut disk.pending_opens) {
+            let result = self.open(owner, key, open);
+            redshirt_interface_interface::emit_answer(
+                message_id,
+                &files_ffi::OpenResponse { result },
+            );
+        }
+    }
+
+    fn open(
+        &mut self,
+        owner: Pid,
+        disk_key: (Pid, u64),
+        open: files_ffi::Open,
+    ) -> Result<files_ffi::OpenedFile, files_ffi::FilesError> {
+        let disk = self
+            .disks
+            .get_mut(&disk_key)
+            .ok_or(files_ffi::FilesError::NoSuchVolume)?;
+
+        let size = {
+            let fs = disk.fs.as_ref().ok_or(files_ffi::FilesError::Io)?;
+            let root = fs.root_dir();
+
+            let mut file = match root.open_file(&open.path) {
+                Ok(_) if open.create && open.exclusive => {
+                    return Err(files_ffi::FilesError::AlreadyExists)
+                }
+                Ok(file) => file,
+                Err(err) if err.kind() == io::ErrorKind::NotFound && open.create => {
+                    if !disk.allow_write {
+                        return Err(files_ffi::FilesError::ReadOnly);
+                    }
+                    root.create_file(&open.path).map_err(io_error_to_files)?
+                }
+                Err(_) if root.open_dir(&open.path).is_ok() => {
+                    return Err(files_ffi::FilesError::IsDirectory)
+                }
+                Err(err) => return Err(io_error_to_files(err)),
+            };
+
+            if open.truncate {
+                if !disk.allow_write {
+                    return Err(files_ffi::FilesError::ReadOnly);
+                }
+                file.truncate().map_err(io_error_to_files)?;
+            }
+
+            file.seek(io::SeekFrom::End(0)).map_err(io_error_to_files)?
+        };
+
+        disk.flush(&mut self.next_write_id);
+
+        let file_id = self.next_file_id;
+        self.next_file_id += 1;
+        self.files.insert(
+            file_id,
+            OpenFile {
+                owner,
+                disk: disk_key,
+                path: open.path,
+            },
+        );
+
+        Ok(files_ffi::OpenedFile { file_id, size })
+    }
+
+    fn read(
+        &mut self,
+        emitter_pid: Pid,
+        read: files_ffi::Read,
+    ) -> Result<Vec<u8>, files_ffi::FilesError> {
+        let file = self
+            .files
+            .get(&read.file_id)
+            .filter(|f| f.owner == emitter_pid)
+            .ok_or(files_ffi::FilesError::InvalidFile)?;
+        let disk = self
+            .disks
+            .get(&file.disk)
+            .ok_or(files_ffi::FilesError::NoSuchVolume)?;
+        let fs = disk.fs.as_ref().ok_or(files_ffi::FilesError::Io)?;
+
+        // TODO: the file is opened again for every operation
+        let mut fs_file = fs
+            .root_dir()
+            .open_file(&file.path)
+            .map_err(io_error_to_files)?;
+        let size = fs_file
+            .seek(io::SeekFrom::End(0))
+            .map_err(io_error_to_files)?;
+        if read.offset >= size {
+            return Ok(Vec::new());
+        }
+
+        fs_file
+            .seek(io::SeekFrom::Start(read.offset))
+            .map_err(io_error_to_files)?;
+        let mut data = Vec::new();
+        fs_file
+            .take(u64::from(read.len))
+            .read_to_end(&mut data)
+            .map_err(io_error_to_files)?;
+        Ok(data)
+    }
+
+    fn write(
+        &mut self,
+        emitter_pid: Pid,
+        write: files_ffi::Write,
+    ) -> Result<(), files_ffi::FilesError> {
+        let file = self
+            .files
+            .get(&write.file_id)
+            .filter(|f| f.owner == emitter_pid)
+            .ok_or(files_ffi::FilesError::InvalidFile)?;
+        let disk = self
+            .disks
+            .get_mut(&file.disk)
+            .ok_or(files_ffi::FilesError::NoSuchVolume)?;
+        if !disk.allow_write {
+            return Err(files_ffi::FilesError::ReadOnly);
+        }
+
+        {
+            let fs = disk.fs.as_ref().ok_or(files_ffi::FilesError::Io)?;
+            // TODO: the file is opened again for every operation
+            let mut fs_file = fs
+                .root_dir()
+                .open_file(&file.path)
+                .map_err(io_error_to_files)?;
+            let size = fs_file
+                .seek(io::SeekFrom::End(0))
+                .map_err(io_error_to_files)?;
+            if write.offset > size {
+                // Fill the gap with zeroes. The cursor is already at the end of the file.
+                io::copy(&mut io::repeat(0).take(write.offset - size), &mut fs_file)
+                    .map_err(io_error_to_files)?;
+            } else {
+                fs_file
+                    .seek(io::SeekFrom::Start(write.offset))
+                    .map_err(io_error_to_files)?;
+            }
+            fs_file.write_all(&write.data).map_err(io_error_to_files)?;
+            fs_file.flush().map_err(io_error_to_files)?;
+        }
+
+        // The write is reported as successful as soon as the in-memory copy has been updated.
+        disk.flush(&mut self.next_write_id);
+        Ok(())
+    }
+}
+
+impl Disk {
+    /// Answers the pending `DiskNextCommand` messages for as long as commands are available.
+    fn dispatch_commands(&mut self) {
+        while !self.commands.is_empty() && !self.next_command_messages.is_empty() {
+            let command = self.commands.pop_front().unwrap();
+            let message_id = self.next_command_messages.pop_front().unwrap();
+            redshirt_interface_interface::emit_answer(message_id, &command);
+        }
+    }
+
+    /// Queues write commands for the sectors of the image modified since the last call.
+    fn flush(&mut self, next_write_id: &mut u64) {
+        let dirty_sectors = self.image.take_dirty_sectors();
+        if !self.allow_write {
+            return;
+        }
+
+        for (sector_lba, data) in dirty_sectors {
+            let id = disk_ffi::WriteId(*next_write_id);
+            *next_write_id += 1;
+            self.commands.push_back(disk_ffi::DiskCommand::StartWrite {
+                id,
+                sector_lba,
+                data,
+            });
+        }
+
+        self.dispatch_commands();
+    }
+}
+
+fn io_error_to_files(err: io::Error) -> files_ffi::FilesError {
+    match err.kind() {
+        io::ErrorKind::NotFound => files_ffi::FilesError::NotFound,
+        io::ErrorKind::AlreadyExists => files_ffi::FilesError::AlreadyExists,
+        _ => files_ffi::FilesError::Io,
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright (C) 2019-2021  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Starts programs and restarts them when they end.
+//!
+//! Each argument passed to this program designates a program to start, in the format
+//! `<policy>:<hash>`, where `<hash>` is the base58-encoded hash of the program, to be loaded
+//! through the `loader` interface, and `<policy>` is one of:
+//!
+//! - `always`: the program is restarted no matter how it ends.
+//! - `on-failure`: the program is restarted unless it exits with a code of 0.
+//! - `never`: the program is started only once.
+//!
+//! Programs that end repeatedly are restarted after a delay that doubles after each restart,
+//! in order to not waste resources restarting a program that can't work. This delay is the
+//! same as the one the kernel applies to its startup processes.
+
+use futures::prelude::*;
+use redshirt_process_interface::{restart::Backoff, ExitStatus, SpawnModule};
+use std::{convert::TryFrom as _, env, time::Duration};
+
+/// What to do when a program ends.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RestartPolicy {
+    Always,
+    OnFailure,
+    Never,
+}
+
+/// Program to start and supervise.
+struct Service {
+    /// Argument this service has been parsed from. Used for logging purposes.
+    name: String,
+    hash: [u8; 32],
+    policy: RestartPolicy,
+}
+
+fn main() {
+    redshirt_log_interface::init();
+    redshirt_syscalls::block_on(async_main());
+}
+
+async fn async_main() {
+    let services = env::args()
+        .skip(1)
+        .filter_map(|arg| match parse_service(&arg) {
+            Some(service) => Some(service),
+            None => {
+                log::error!("Invalid service: {}", arg);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    future::join_all(services.into_iter().map(supervise)).await;
+}
+
+/// Parses an argument in the `<policy>:<hash>` format.
+fn parse_service(arg: &str) -> Option<Service> {
+    let mut split = arg.splitn(2, ':');
+    let policy = match split.next()? {
+        "always" => RestartPolicy::Always,
+        "on-failure" => RestartPolicy::OnFailure,
+        "never" => RestartPolicy::Never,
+        _ => return None,
+    };
+
+    let mut hash = [0; 32];
+    let len = bs58::decode(split.next()?).into(&mut hash).ok()?;
+    if len != hash.len() {
+        return None;
+    }
+
+    Some(Service {
+        name: arg.to_owned(),
+        hash,
+        policy,
+    })
+}
+
+/// Starts the given service, and restarts it according to its policy.
+async fn supervise(service: Service) {
+    let mut backoff = Backoff::new();
+
+    loop {
+        let pid = match redshirt_process_interface::spawn(
+            SpawnModule::Hash(service.hash),
+            vec![service.name.clone().into_bytes()],
+        )
+        .await
+        {
+            Ok(pid) => pid,
+            Err(err) => {
+                // The program itself is invalid or can't be loaded, and restarting it would
+                // most likely lead to the same outcome.
+                log::error!("Failed to start {}: {:?}", service.name, err);
+                return;
+            }
+        };
+
+        let started_at = redshirt_time_interface::monotonic_clock().await;
+        let status = redshirt_process_interface::wait(pid).await;
+        let ended_at = redshirt_time_interface::monotonic_clock().await;
+
+        let restart = match (service.policy, &status) {
+            (RestartPolicy::Never, _) => false,
+            (RestartPolicy::OnFailure, Ok(ExitStatus::Exited(0))) => false,
+            (RestartPolicy::OnFailure, _) | (RestartPolicy::Always, _) => true,
+        };
+
+        if !restart {
+            log::info!("{} ({:?}) has ended: {:?}", service.name, pid, status);
+            return;
+        }
+
+        log::warn!(
+            "{} ({:?}) has ended: {:?}; restarting",
+            service.name,
+            pid,
+            status
+        );
+
+        let ran_for =
+            u64::try_from(ended_at.saturating_sub(started_at)).unwrap_or(u64::max_value());
+        redshirt_time_interface::monotonic_wait(backoff.next_delay(Duration::from_nanos(ran_for)))
+            .await;
+    }
+}